@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use primeclue::data::data_set::DataSet;
+use primeclue::error::PrimeclueErr;
+use primeclue::exec::classifier::Classifier;
+use primeclue::export::codegen::{generate, Language};
+use primeclue::serialization::{Deserializable, Serializator};
+use primeclue::value::{Precision, Value};
+use std::env;
+use std::path::{Path, PathBuf};
+
+// Prints standalone code of a classifier, with tests on first points of data.
+// Usage:
+// cargo run --release --example generate_code </path/to/classifier.ssd> </path/to/imported/data> <rust|c|python|sql>
+fn main() -> Result<(), PrimeclueErr> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.len() != 3 {
+        return PrimeclueErr::result(
+            "Need paths to classifier and data, and language as args".to_string(),
+        );
+    }
+    let classifier =
+        Classifier::deserialize(&mut Serializator::load(&PathBuf::from(&args[0]))?)?;
+    let language = match args[2].as_str() {
+        "rust" => Language::Rust,
+        "c" => Language::C,
+        "python" => Language::Python,
+        "sql" => Language::Sql,
+        other => return PrimeclueErr::result(format!("Unknown language {}", other)),
+    };
+    let path = PathBuf::from(&args[1]);
+    let code = match classifier.precision() {
+        Precision::Single => code::<f32>(&classifier, language, &path)?,
+        Precision::Double => code::<f64>(&classifier, language, &path)?,
+    };
+    println!("{}", code);
+    Ok(())
+}
+
+fn code<T: Value>(
+    classifier: &Classifier,
+    language: Language,
+    path: &Path,
+) -> Result<String, PrimeclueErr> {
+    let data = DataSet::<T>::read_from_disk(path)?;
+    let mut samples = DataSet::new(classifier.get_classes().clone());
+    for point in data.iter().take(20) {
+        samples.add_data_point(point.clone())?;
+    }
+    generate(classifier, language, &samples.into_view())
+}
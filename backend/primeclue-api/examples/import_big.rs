@@ -17,7 +17,7 @@ fn main() -> Result<(), PrimeclueErr> {
         .map_err(|e| format!("Error converting file content: {:?}", e))?;
     let class_request = ClassRequest::simple_csv_request(name, content, false);
     println!("Building data set");
-    let data_set = build_data_set(&class_request)?;
+    let (data_set, _, _, _) = build_data_set::<f32>(&class_request)?;
     let path = Settings::new()?.data_dir().join(name);
     println!("Saving to {:?}", path);
     data_set.save_to_disk(&path, |p| {
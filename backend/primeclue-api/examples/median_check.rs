@@ -64,7 +64,7 @@ fn main() -> Result<(), PrimeclueErr> {
 
 fn check_once(path: &str, seconds: usize) -> Option<f32> {
     // Read data from disk. Data must be in Primeclue's format, i.e. imported to `data.ssd` file.
-    let data = DataSet::read_from_disk(&PathBuf::from(path)).unwrap();
+    let data: DataSet = DataSet::read_from_disk(&PathBuf::from(path)).unwrap();
 
     // Split data into random parts. Only training and verification sets are used for training,
     // testing set in used to display result to the user.
@@ -80,7 +80,7 @@ fn check_once(path: &str, seconds: usize) -> Option<f32> {
 
     // Actual training happens here
     while Instant::now().lt(&end_time) {
-        training.next_generation();
+        training.next_generation().ok()?;
     }
 
     // Get classifier after training has finished. It will fail if there is no classifier for any of the classes
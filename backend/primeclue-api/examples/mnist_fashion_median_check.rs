@@ -35,7 +35,7 @@ fn check_once() -> Option<f32> {
     // Read data from disk. Data must be in Primeclue's format, i.e. imported to `data.ssd` file.
     // Get training data
     let (training_data, verification_data) =
-        DataSet::read_from_disk(&data_path.join("mnist_fashion_training"))
+        DataSet::<f32>::read_from_disk(&data_path.join("mnist_fashion_training"))
             .unwrap()
             .shuffle()
             .into_2_views_split();
@@ -50,14 +50,15 @@ fn check_once() -> Option<f32> {
 
     // Actual training happens here
     while Instant::now().lt(&end_time) {
-        training.next_generation();
+        training.next_generation().ok()?;
     }
     // Get classifier after training has finished. It will fail if there is no classifier for any of the classes
     let classifier = training.classifier().ok()?;
 
     // Get testing data
-    let testing_data =
-        DataSet::read_from_disk(&data_path.join("mnist_fashion_testing")).unwrap().into_view();
+    let testing_data = DataSet::<f32>::read_from_disk(&data_path.join("mnist_fashion_testing"))
+        .unwrap()
+        .into_view();
 
     // Get classifier's score on unseen data
     Some(classifier.score(&testing_data)?.accuracy)
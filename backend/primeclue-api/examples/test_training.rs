@@ -87,7 +87,7 @@ fn training_success(attempt: usize) -> f32 {
     )
     .unwrap();
     loop {
-        training.next_generation();
+        training.next_generation().unwrap();
         if let Some(stats) = training.stats() {
             if stats.generation > 10_000 {
                 println!("Training failed! Unable to learn after 10k generations");
@@ -18,16 +18,30 @@
 */
 
 use crate::executor::{Status, StatusCallback, Termination};
-use primeclue::data::data_set::{DataSet, DataView, Rewards};
-use primeclue::data::importer::{build_numbers_row, get_header_row, split_to_vec};
+use primeclue::data::chunked::{ChunkedView, CHUNKS_DIR};
+use primeclue::data::csv::CsvFormat;
+use primeclue::data::data_set::{load_serialized, stored_precision, DataSet, DataView, Rewards};
+use primeclue::data::dictionary::Dictionary;
+use primeclue::data::features::{Feature, Features};
+use primeclue::data::importer::{
+    as_str_rows, build_numbers_row, get_header_row, Grouping, MissingValues,
+};
+use primeclue::data::imputation::Imputation;
+use primeclue::data::metadata::{DataVersion, Metadata};
+use primeclue::data::outcome::Class;
+use primeclue::data::scaling::{Scaler, Scaling};
 use primeclue::data::{Input, InputShape, Outcome, Point};
 use primeclue::error::PrimeclueErr;
-use primeclue::exec::classifier::{Classifier, ClassifierScore};
+use primeclue::exec::class_training::Sampling;
+use primeclue::exec::classifier::{Classifier, ClassifierScore, TreeDescription, TreeStructure};
 use primeclue::exec::score::Objective;
 use primeclue::exec::training_group::{Stats, TrainingGroup};
+use primeclue::exec::tree::Tree;
+use primeclue::export::dot::classifier_to_dot;
 use primeclue::serialization::serializator::SERIALIZED_FILE_EXT;
 use primeclue::serialization::{Deserializable, Serializable, Serializator};
 use primeclue::user::{read_files, Settings, CLASSIFIERS_DIR};
+use primeclue::value::{Precision, Value};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::fs::read_dir;
@@ -50,6 +64,25 @@ pub(crate) struct CreateRequest {
     forbidden_columns: String,
     shuffle_data: bool,
     keep_unseen_data: bool,
+    #[serde(default)]
+    sampling: Sampling,
+    #[serde(default)]
+    seeds: Vec<Seed>,
+    /// Scalers fitted on training data and stored with the classifier, none by default
+    #[serde(default)]
+    scaling: Vec<Scaler>,
+    /// Trains on chunks stored at import, scoring fresh trees on random samples of this
+    /// many data points, for data that doesn't fit in memory. See
+    /// [`start_streamed_training`].
+    #[serde(default)]
+    stream_sample_size: Option<usize>,
+}
+
+/// Hand written rule to start training from, see [`Tree::from_formula`]
+#[derive(Deserialize, Debug)]
+struct Seed {
+    class: String,
+    formula: String,
 }
 
 pub(crate) fn create(
@@ -57,17 +90,89 @@ pub(crate) fn create(
     status_callback: &StatusCallback,
     terminator: &Receiver<Termination>,
 ) -> Result<String, PrimeclueErr> {
-    let data_set = read_data(request)?;
-    let result = start_training(request, data_set, status_callback, terminator)?;
-    Ok(result)
+    let settings = Settings::new()?;
+    let data_path = settings.data_dir().join(&request.data_name);
+    let precision = stored_precision(&data_path)?;
+    let dictionary = Dictionary::read_from_disk(&data_path)?;
+    let features = Features::read_from_disk(&data_path)?;
+    let imputation = Imputation::read_from_disk(&data_path)?;
+    let grouping = Grouping::read_from_disk(&data_path)?;
+    let version = Metadata::read_from_disk(&data_path)?.data_version(&request.data_name);
+    let imported = Imported {
+        dictionary,
+        features,
+        imputation,
+        grouping,
+        version,
+        header: vec![],
+        scaling: None,
+    };
+    if let Some(sample_size) = request.stream_sample_size {
+        let chunks = data_path.join(CHUNKS_DIR);
+        return match precision {
+            Precision::Single => start_streamed_training::<f32>(
+                request,
+                &chunks,
+                sample_size,
+                imported,
+                status_callback,
+                terminator,
+            ),
+            Precision::Double => start_streamed_training::<f64>(
+                request,
+                &chunks,
+                sample_size,
+                imported,
+                status_callback,
+                terminator,
+            ),
+        };
+    }
+    let mut s = load_serialized(&data_path)?;
+    match precision {
+        Precision::Single => {
+            let data_set = read_data::<f32>(request, &mut s, &data_path)?;
+            start_training(request, data_set, imported, status_callback, terminator)
+        }
+        Precision::Double => {
+            let data_set = read_data::<f64>(request, &mut s, &data_path)?;
+            start_training(request, data_set, imported, status_callback, terminator)
+        }
+    }
+}
+
+/// How data to train on was imported and scaled, needed to prepare data to classify the
+/// same way
+struct Imported {
+    dictionary: Option<Dictionary>,
+    features: Option<Features>,
+    imputation: Option<Imputation>,
+    grouping: Option<Grouping>,
+    version: Option<DataVersion>,
+    header: Vec<String>,
+    scaling: Option<Scaling>,
 }
 
-fn parse_forbidden_columns(text: &str) -> Result<Vec<usize>, PrimeclueErr> {
+/// Parses space separated forbidden columns, given as 1-based indexes or names. A name
+/// of a one-hot encoded column forbids all of its categories.
+fn parse_forbidden_columns(text: &str, names: &[String]) -> Result<Vec<usize>, PrimeclueErr> {
     let chunks = text.split(' ').filter(|&s| !s.is_empty()).collect::<Vec<_>>();
     let mut columns = Vec::new();
     for chunk in chunks {
-        let column = chunk.trim().parse::<usize>().map_err(|e| {
-            PrimeclueErr::from(format!("Unable to parse '{}' to column index: {}", chunk, e))
+        let chunk = chunk.trim();
+        let prefix = format!("{}=", chunk);
+        let named = names
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| *name == chunk || name.starts_with(&prefix))
+            .map(|(column, _)| column)
+            .collect::<Vec<_>>();
+        if !named.is_empty() {
+            columns.extend(named);
+            continue;
+        }
+        let column = chunk.parse::<usize>().map_err(|e| {
+            PrimeclueErr::from(format!("Unable to find column '{}': {}", chunk, e))
         })?;
         if column < 1 {
             return PrimeclueErr::result("Column indexing starts from 1".to_owned());
@@ -77,7 +182,7 @@ fn parse_forbidden_columns(text: &str) -> Result<Vec<usize>, PrimeclueErr> {
     Ok(columns)
 }
 
-fn print_cost_range(data1: &DataView, data2: &DataView) {
+fn print_cost_range<T: Value>(data1: &DataView<T>, data2: &DataView<T>) {
     let (max, min) = data1.cost_range();
     println!("Cost range for training data {} {}", min, max);
     let (max, min) = data2.cost_range();
@@ -85,20 +190,35 @@ fn print_cost_range(data1: &DataView, data2: &DataView) {
     println!("Cost range for test data {} {}, guessing randomly: {}", min, max, random_guess);
 }
 
-fn start_training(
+fn start_training<T: Value>(
     request: &CreateRequest,
-    mut data_set: DataSet,
+    mut data_set: DataSet<T>,
+    mut imported: Imported,
     status_callback: &StatusCallback,
     terminator: &Receiver<Termination>,
 ) -> Result<String, PrimeclueErr> {
     if request.shuffle_data {
         data_set = data_set.shuffle();
     }
-    let (training_data, verification_data, test_data) =
+    let header = data_set.header().to_vec();
+    let column_names = match &imported.dictionary {
+        Some(dictionary) => dictionary.column_names(&header),
+        None => header.clone(),
+    };
+    let (mut training_data, mut verification_data, mut test_data) =
         split_into_sets(data_set, request.keep_unseen_data);
+    let scaling = if request.scaling.is_empty() {
+        None
+    } else {
+        let scaling = Scaling::fit(&request.scaling, &training_data)?;
+        scaling.apply(&mut training_data);
+        scaling.apply(&mut verification_data);
+        scaling.apply(&mut test_data);
+        Some(scaling)
+    };
     print_cost_range(&training_data, &test_data);
-    let forbidden_cols = parse_forbidden_columns(&request.forbidden_columns)?;
-    let dst_dir = create_classifier_dir(&request)?;
+    let forbidden_cols = parse_forbidden_columns(&request.forbidden_columns, &column_names)?;
+    let seeds = parse_seeds(&request.seeds, &training_data, &column_names)?;
     let mut training = TrainingGroup::new(
         training_data,
         verification_data,
@@ -106,19 +226,95 @@ fn start_training(
         request.size,
         &forbidden_cols,
     )?;
+    training.set_sampling(request.sampling)?;
+    for (class, tree) in seeds {
+        training.seed(&class, tree)?;
+    }
+    imported.header = header;
+    imported.scaling = scaling;
+    train(request, training, &test_data, imported, status_callback, terminator)
+}
+
+/// Trains on chunks stored in directory `path` at import, see
+/// [`TrainingGroup::from_chunks`]. A third of data points, but no more than fit in a
+/// chunk, is drawn from all chunks and held out for verification, and split in two for
+/// testing if `keep_unseen_data` is set. Rewards can't be overridden and data can't be
+/// scaled.
+fn start_streamed_training<T: Value>(
+    request: &CreateRequest,
+    path: &Path,
+    sample_size: usize,
+    mut imported: Imported,
+    status_callback: &StatusCallback,
+    terminator: &Receiver<Termination>,
+) -> Result<String, PrimeclueErr> {
+    if !path.exists() {
+        return PrimeclueErr::result("Data wasn't stored in chunks at import".to_owned());
+    }
+    if request.override_rewards {
+        return PrimeclueErr::result("Unable to override rewards of chunked data".to_owned());
+    }
+    if !request.scaling.is_empty() {
+        return PrimeclueErr::result("Unable to scale chunked data".to_owned());
+    }
+    let chunks = ChunkedView::<T>::open(path)?;
+    let held_out_size = (chunks.len() / 3).min(chunks.chunk_size());
+    let (chunks, held_out) = chunks.split_sampled(held_out_size)?;
+    let (verification_data, test_data) = if request.keep_unseen_data {
+        held_out.into_2_views_split()
+    } else {
+        let verification_data = held_out.into_view();
+        (verification_data.clone(), verification_data)
+    };
+    let header = chunks.header().to_vec();
+    let column_names = match &imported.dictionary {
+        Some(dictionary) => dictionary.column_names(&header),
+        None => header.clone(),
+    };
+    let forbidden_cols = parse_forbidden_columns(&request.forbidden_columns, &column_names)?;
+    let seeds = parse_seeds(&request.seeds, &verification_data, &column_names)?;
+    let mut training = TrainingGroup::from_chunks(
+        chunks,
+        verification_data,
+        request.training_objective,
+        request.size,
+        &forbidden_cols,
+        sample_size,
+    )?;
+    if request.sampling != Sampling::Full {
+        training.set_sampling(request.sampling)?;
+    }
+    for (class, tree) in seeds {
+        training.seed(&class, tree)?;
+    }
+    imported.header = header;
+    train(request, training, &test_data, imported, status_callback, terminator)
+}
+
+/// Trains until request's timeout, reporting score on `test_data`, and saves the classifier
+fn train<T: Value>(
+    request: &CreateRequest,
+    mut training: TrainingGroup<T>,
+    test_data: &DataView<T>,
+    imported: Imported,
+    status_callback: &StatusCallback,
+    terminator: &Receiver<Termination>,
+) -> Result<String, PrimeclueErr> {
+    let dst_dir = create_classifier_dir(&request)?;
     let start_time = std::time::Instant::now();
     let end_time = start_time.add(Duration::from_secs(60 * request.timeout));
     while std::time::Instant::now().lt(&end_time) {
         if terminator.try_recv().is_ok() {
-            if let Err(e) = fs::remove_dir_all(&dst_dir) {
-                println!("Unable to remove classifier directory {:?}, error: {:?}", dst_dir, e);
-            }
+            remove_classifier_dir(&dst_dir);
             return Ok("Terminating training for user request".to_string());
         }
-        training.next_generation();
+        if let Err(e) = training.next_generation() {
+            remove_classifier_dir(&dst_dir);
+            return Err(e);
+        }
         if let Some(stats) = training.stats() {
             if let Ok(classifier) = training.classifier() {
-                if let Some(classifier_score) = classifier.score(&test_data) {
+                if let Some(classifier_score) = classifier.score(test_data) {
                     let status = TrainingStatus { stats, classifier_score };
                     status_callback(Status::Progress(
                         0.0,
@@ -128,30 +324,69 @@ fn start_training(
             }
         }
     }
-    save(&dst_dir, &mut training)?;
+    save(&dst_dir, &mut training, imported)?;
     Ok(format!(
         "Training finished with average score: {:?}",
         training.classifier()?.average_score()
     ))
 }
 
+fn remove_classifier_dir(dst_dir: &Path) {
+    if let Err(e) = fs::remove_dir_all(dst_dir) {
+        println!("Unable to remove classifier directory {:?}, error: {:?}", dst_dir, e);
+    }
+}
+
+fn parse_seeds<T: Value>(
+    seeds: &[Seed],
+    data: &DataView<T>,
+    column_names: &[String],
+) -> Result<Vec<(Class, Tree)>, PrimeclueErr> {
+    let mut result = Vec::with_capacity(seeds.len());
+    for seed in seeds {
+        let class = data
+            .class_map()
+            .iter()
+            .find(|(_, label)| **label == seed.class)
+            .map(|(class, _)| *class)
+            .ok_or_else(|| PrimeclueErr::from(format!("Unknown seed class: {}", seed.class)))?;
+        let tree = Tree::from_formula(&seed.formula, data.input_shape(), Some(column_names))?;
+        result.push((class, tree));
+    }
+    Ok(result)
+}
+
 #[derive(Serialize)]
 struct TrainingStatus {
     stats: Stats,
     classifier_score: ClassifierScore,
 }
 
-fn save(dst_dir: &Path, training: &mut TrainingGroup) -> Result<usize, PrimeclueErr> {
-    let classifier = training.classifier()?;
+fn save<T: Value>(
+    dst_dir: &Path,
+    training: &mut TrainingGroup<T>,
+    imported: Imported,
+) -> Result<usize, PrimeclueErr> {
+    let mut classifier = training.classifier()?;
+    classifier.set_dictionary(imported.dictionary);
+    classifier.set_features(imported.features);
+    classifier.set_imputation(imported.imputation);
+    classifier.set_grouping(imported.grouping);
+    classifier.set_data_version(imported.version);
+    classifier.set_header(imported.header);
+    classifier.set_scaling(imported.scaling);
     let mut s = Serializator::new();
     classifier.serialize(&mut s);
     s.save(&dst_dir, CLASSIFIER_FILE_NAME).map_err(PrimeclueErr::from)
 }
 
-fn read_data(request: &CreateRequest) -> Result<DataSet, PrimeclueErr> {
-    let settings = Settings::new()?;
-    let src_data_dir = settings.data_dir().join(&request.data_name);
-    let mut dsr = DataSet::read_from_disk(&src_data_dir)?;
+fn read_data<T: Value>(
+    request: &CreateRequest,
+    s: &mut Serializator,
+    path: &Path,
+) -> Result<DataSet<T>, PrimeclueErr> {
+    let mut dsr = DataSet::from_serialized(s)?;
+    dsr.read_segments(path)?;
     if request.override_rewards {
         dsr.apply_rewards(&request.rewards);
     }
@@ -191,80 +426,278 @@ pub(crate) struct ClassifyRequest {
     separator: String,
     ignore_first_row: bool,
     data_columns: Vec<bool>,
+    #[serde(default)]
+    csv: CsvFormat,
+    /// Classify points with categories not seen at import as unknown instead of failing
+    #[serde(default)]
+    unseen_as_unknown: bool,
 }
 
 impl ClassifyRequest {
     pub(crate) fn classify(&self) -> Result<String, PrimeclueErr> {
-        let (classifiers, names) = self.read_classifiers()?;
-        let header_row =
-            get_header_row(&self.content, &self.separator, self.ignore_first_row, names);
-        let mut data_raw = split_to_vec(&self.content, &self.separator, self.ignore_first_row);
-        let numbers = parse_data(&data_raw, &self.data_columns)?;
-        ClassifyRequest::validate_input_shape(&classifiers, &numbers)?;
-        let responses_list = build_responses_list(&classifiers, &numbers);
+        let (classifiers, names) = read_classifiers(&self.classifier_name)?;
+        let (content, separator, csv) = (&self.content, &self.separator, &self.csv);
+        let header_row = get_header_row(content, separator, self.ignore_first_row, names, csv)?;
+        let (mut records, mut lines) = csv.split_lines(content, separator)?;
+        if self.ignore_first_row && !records.is_empty() {
+            records.remove(0);
+            lines.remove(0);
+        }
+        let mut data_raw = as_str_rows(&records);
+        let content_header = if self.ignore_first_row {
+            Some(get_header_row(content, separator, true, vec![], csv)?)
+        } else {
+            None
+        };
+        let responses_list = build_responses_list(
+            &classifiers,
+            &data_raw,
+            &lines,
+            &self.data_columns,
+            content_header.as_deref(),
+            self.unseen_as_unknown,
+        )?;
         let mut classification = Vec::with_capacity(data_raw.len());
-        classification.push(header_row.join(&self.separator));
+        classification.push(csv.join(&header_row, separator));
         for r in 0..data_raw.len() {
             let row = &mut data_raw[r];
             for responses in &responses_list {
                 let response = responses[r];
                 row.push(response);
             }
-            let line = row.join(self.separator.as_str());
+            let line = csv.join(row, separator);
             classification.push(line);
         }
         Ok(classification.join("\r\n"))
     }
+}
 
-    fn validate_input_shape(
-        classifiers: &[Classifier],
-        numbers: &[Vec<f32>],
-    ) -> Result<(), PrimeclueErr> {
-        for classifier in classifiers {
-            check_size(&numbers, classifier.input_shape())?;
+fn read_classifiers(name: &str) -> Result<(Vec<Classifier>, Vec<String>), PrimeclueErr> {
+    let settings = Settings::new()?;
+    let mut classifiers = vec![];
+    let path = settings.base_dir().join(CLASSIFIERS_DIR).join(name);
+    let mut names = vec![];
+    for entry in read_dir(&path)? {
+        let entry = entry?;
+        let file_name = entry.file_name().into_string().unwrap();
+        if file_name.ends_with(SERIALIZED_FILE_EXT) {
+            names.push(file_name);
         }
-        Ok(())
     }
-
-    fn read_classifiers(&self) -> Result<(Vec<Classifier>, Vec<String>), PrimeclueErr> {
-        let settings = Settings::new()?;
-        let mut classifiers = vec![];
-        let path = settings.base_dir().join(CLASSIFIERS_DIR).join(&self.classifier_name);
-        let mut names = vec![];
-        for entry in read_dir(&path)? {
-            let entry = entry?;
-            let file_name = entry.file_name().into_string().unwrap();
-            if file_name.ends_with(SERIALIZED_FILE_EXT) {
-                names.push(file_name);
-            }
-        }
-        names.sort();
-        for name in &names {
-            let path = path.join(&name);
-            let classifier = Classifier::deserialize(&mut Serializator::load(&path)?)?;
-            classifiers.push(classifier);
-        }
-        if classifiers.is_empty() {
-            PrimeclueErr::result(format!("Unable to find serialized object in {:?}", path))
-        } else {
-            Ok((classifiers, names))
-        }
+    names.sort();
+    for name in &names {
+        let path = path.join(&name);
+        let classifier = Classifier::deserialize(&mut Serializator::load(&path)?)?;
+        classifiers.push(classifier);
     }
+    if classifiers.is_empty() {
+        PrimeclueErr::result(format!("Unable to find serialized object in {:?}", path))
+    } else {
+        Ok((classifiers, names))
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct ClassifierDescription {
+    file: String,
+    /// Version of data set the classifier was trained on, if known
+    data: Option<DataVersion>,
+    /// Derived columns and scaling applied before trees, see [`Classifier::preprocessing`]
+    preprocessing: Vec<String>,
+    trees: Vec<TreeDescription>,
+}
+
+pub(crate) fn describe(name: &str) -> Result<Vec<ClassifierDescription>, PrimeclueErr> {
+    let (classifiers, files) = read_classifiers(name)?;
+    Ok(classifiers
+        .iter()
+        .zip(files)
+        .map(|(classifier, file)| ClassifierDescription {
+            file,
+            data: classifier.data_version().cloned(),
+            preprocessing: classifier.preprocessing(),
+            trees: classifier.describe(classifier.column_names().as_deref()),
+        })
+        .collect())
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct ClassifierStructure {
+    file: String,
+    /// Names of columns trees refer to by index, if known
+    columns: Option<Vec<String>>,
+    /// Derived columns added to data before encoding
+    features: Vec<Feature>,
+    /// Scaling of columns trees refer to, trees compare scaled values
+    scaling: Option<Scaling>,
+    trees: Vec<TreeStructure>,
+}
+
+pub(crate) fn structure(name: &str) -> Result<Vec<ClassifierStructure>, PrimeclueErr> {
+    let (classifiers, files) = read_classifiers(name)?;
+    Ok(classifiers
+        .iter()
+        .zip(files)
+        .map(|(classifier, file)| ClassifierStructure {
+            file,
+            columns: classifier.column_names(),
+            features: classifier.features().map_or_else(Vec::new, |f| f.features().to_vec()),
+            scaling: classifier.scaling().cloned(),
+            trees: classifier.structure(),
+        })
+        .collect())
 }
 
+/// Graphviz DOT digraphs of all classifiers saved under `name`, one after another.
+pub(crate) fn dot(name: &str) -> Result<String, PrimeclueErr> {
+    let (classifiers, files) = read_classifiers(name)?;
+    Ok(classifiers
+        .iter()
+        .zip(files)
+        .map(|(classifier, file)| {
+            let names = classifier.column_names();
+            format!("// {}\n{}", file, classifier_to_dot(classifier, names.as_deref()))
+        })
+        .collect())
+}
+
+/// Classifies data with each classifier. Columns are matched by name if both the
+/// classifier and data have a header, otherwise `use_columns` are used in order.
+/// Classifiers of data grouped by entity and classifying windows of rows need rows of
+/// one entity, see [`Grouping::check_entity`].
+/// Derived columns of a classifier are added to data first, after putting data's columns
+/// in order of content they were fitted on when they are matched by name. `lines` are
+/// lines of content rows start at, for errors.
 fn build_responses_list<'a>(
     classifiers: &'a [Classifier],
-    numbers: &[Vec<f32>],
-) -> Vec<Vec<&'a str>> {
+    raw: &[Vec<&str>],
+    lines: &[usize],
+    use_columns: &[bool],
+    header: Option<&[String]>,
+    unseen_as_unknown: bool,
+) -> Result<Vec<Vec<&'a str>>, PrimeclueErr> {
     let mut responses_list = vec![];
     for classifier in classifiers {
-        let responses = classify_all(&numbers, &classifier);
+        if let Some(grouping) = classifier.grouping() {
+            if classifier.input_shape().rows() > 1 {
+                grouping.check_entity(raw)?;
+            }
+        }
+        let by_name = has_names(classifier.header());
+        let derived = match classifier.features() {
+            Some(features) => Some(Derived::new(features, raw, use_columns, header, by_name)?),
+            None => None,
+        };
+        let derived_rows = derived.as_ref().map(|derived| as_str_rows(&derived.rows));
+        let (raw, use_columns, header) = match (&derived, &derived_rows) {
+            (Some(derived), Some(rows)) => {
+                (rows.as_slice(), derived.use_columns.as_slice(), derived.header.as_deref())
+            }
+            _ => (raw, use_columns, header),
+        };
+        let matched = match header {
+            Some(header) if by_name => Some(match_columns(raw, header, classifier.header())?),
+            _ => None,
+        };
+        let (raw, use_columns) = match &matched {
+            Some(matched) => (matched.as_slice(), vec![true; classifier.header().len()]),
+            None => (raw, use_columns.to_vec()),
+        };
+        let responses = match classifier.precision() {
+            Precision::Single => {
+                classify_raw::<f32>(raw, lines, &use_columns, classifier, unseen_as_unknown)?
+            }
+            Precision::Double => {
+                classify_raw::<f64>(raw, lines, &use_columns, classifier, unseen_as_unknown)?
+            }
+        };
         responses_list.push(responses);
     }
-    responses_list
+    Ok(responses_list)
+}
+
+/// Data to classify with derived columns added, as they were at import. Derived columns
+/// refer to content's columns by position, so data with a header to be matched by name
+/// is first put in order of [`Features::header`].
+struct Derived {
+    rows: Vec<Vec<String>>,
+    use_columns: Vec<bool>,
+    header: Option<Vec<String>>,
+}
+
+impl Derived {
+    fn new(
+        features: &Features,
+        raw: &[Vec<&str>],
+        use_columns: &[bool],
+        header: Option<&[String]>,
+        by_name: bool,
+    ) -> Result<Self, PrimeclueErr> {
+        let (raw, use_columns, header) = match header {
+            Some(header) if by_name => {
+                if features.header().is_empty() {
+                    return PrimeclueErr::result(
+                        "Classifier doesn't know column names its derived columns were \
+                         calculated from, data must have no header"
+                            .to_owned(),
+                    );
+                }
+                let ordered = match_columns(raw, header, features.header())?;
+                (ordered, vec![true; features.header().len()], Some(features.header()))
+            }
+            _ => (raw.to_vec(), use_columns.to_vec(), header),
+        };
+        let mut rows: Vec<Vec<String>> =
+            raw.iter().map(|row| row.iter().map(|&s| s.to_owned()).collect()).collect();
+        features.apply(&mut rows)?;
+        Ok(Derived {
+            rows,
+            use_columns: features.import_columns(&use_columns),
+            header: header.map(|header| [header, &features.names()].concat()),
+        })
+    }
+}
+
+fn has_names(header: &[String]) -> bool {
+    !header.is_empty() && header.iter().all(|name| !name.is_empty())
 }
 
-fn split_into_sets(data: DataSet, keep_unseen: bool) -> (DataView, DataView, DataView) {
+/// Picks columns of `raw` named as `names`, in their order
+fn match_columns<'a>(
+    raw: &[Vec<&'a str>],
+    header: &[String],
+    names: &[String],
+) -> Result<Vec<Vec<&'a str>>, PrimeclueErr> {
+    let mut columns = Vec::with_capacity(names.len());
+    for name in names {
+        let column = header.iter().position(|h| h.trim() == name).ok_or_else(|| {
+            PrimeclueErr::from(format!("Column '{}' not found in data header", name))
+        })?;
+        columns.push(column);
+    }
+    Ok(raw
+        .iter()
+        .map(|row| columns.iter().filter_map(|&c| row.get(c).copied()).collect())
+        .collect())
+}
+
+/// Parses data in classifier's precision and classifies it
+fn classify_raw<'a, T: Value>(
+    raw: &[Vec<&str>],
+    lines: &[usize],
+    use_columns: &[bool],
+    classifier: &'a Classifier,
+    unseen_as_unknown: bool,
+) -> Result<Vec<&'a str>, PrimeclueErr> {
+    let numbers = parse_data::<T>(raw, lines, use_columns, classifier, unseen_as_unknown)?;
+    check_size(&numbers, classifier.input_shape())?;
+    Ok(classify_all(&numbers, classifier))
+}
+
+fn split_into_sets<T: Value>(
+    data: DataSet<T>,
+    keep_unseen: bool,
+) -> (DataView<T>, DataView<T>, DataView<T>) {
     if keep_unseen {
         data.into_3_views_split()
     } else {
@@ -274,14 +707,46 @@ fn split_into_sets(data: DataSet, keep_unseen: bool) -> (DataView, DataView, Dat
     }
 }
 
-fn parse_data(raw: &[Vec<&str>], use_columns: &[bool]) -> Result<Vec<Vec<f32>>, PrimeclueErr> {
-    let mut values: Vec<Vec<f32>> = Vec::with_capacity(raw.len());
-    for (row_num, row) in raw.iter().enumerate() {
-        let values_row = build_numbers_row(use_columns, row_num, row)?;
+/// Parses data, encoding categorical columns with classifier's dictionary and replacing
+/// missing values the way they were replaced at import. Categories not seen at import are
+/// an error listing all of them, unless `unseen_as_unknown` is set. Then they, as well as
+/// missing values import didn't replace, are classified as unknown.
+fn parse_data<T: Value>(
+    raw: &[Vec<&str>],
+    lines: &[usize],
+    use_columns: &[bool],
+    classifier: &Classifier,
+    unseen_as_unknown: bool,
+) -> Result<Vec<Vec<T>>, PrimeclueErr> {
+    let dictionary = classifier.dictionary();
+    if let Some(dictionary) = dictionary {
+        let unseen = dictionary.unseen(use_columns, raw);
+        if !unseen_as_unknown && !unseen.is_empty() {
+            let unseen = unseen
+                .iter()
+                .map(|(column, value)| format!("column {}: '{}'", column + 1, value))
+                .collect::<Vec<_>>();
+            return PrimeclueErr::result(format!(
+                "Unseen categories in data: {}",
+                unseen.join(", ")
+            ));
+        }
+    }
+    let mut values: Vec<Vec<T>> = Vec::with_capacity(raw.len());
+    for (row, &line) in raw.iter().zip(lines) {
+        let mut values_row = match dictionary {
+            Some(dictionary) => {
+                dictionary.encode(use_columns, line, row, MissingValues::Keep)?
+            }
+            None => build_numbers_row(use_columns, line, row, MissingValues::Keep)?,
+        };
+        if let Some(imputation) = classifier.imputation() {
+            imputation.apply_row(&mut values_row);
+        }
         if !values.is_empty() && values[0].len() != values_row.len() {
             return PrimeclueErr::result(format!(
-                "Invalid {}'nth row length: found {}, expected {}",
-                row_num,
+                "Invalid row length: line {}: found {}, expected {}",
+                line,
                 values_row.len(),
                 values[0].len()
             ));
@@ -291,7 +756,7 @@ fn parse_data(raw: &[Vec<&str>], use_columns: &[bool]) -> Result<Vec<Vec<f32>>,
     Ok(values)
 }
 
-fn check_size(data: &[Vec<f32>], input_shape: &InputShape) -> Result<(), PrimeclueErr> {
+fn check_size<T>(data: &[Vec<T>], input_shape: &InputShape) -> Result<(), PrimeclueErr> {
     if data.len() < input_shape.rows() {
         PrimeclueErr::result(format!(
             "Invalid data size: not enough rows: is: {}, must be at least: {}",
@@ -309,7 +774,7 @@ fn check_size(data: &[Vec<f32>], input_shape: &InputShape) -> Result<(), Primecl
     }
 }
 
-fn classify_all<'a>(numbers: &[Vec<f32>], classifier: &'a Classifier) -> Vec<&'a str> {
+fn classify_all<'a, T: Value>(numbers: &[Vec<T>], classifier: &'a Classifier) -> Vec<&'a str> {
     let mut data_set = DataSet::new(classifier.get_classes().clone());
     for row in 0..=(numbers.len() - classifier.input_shape().rows()) {
         let input_data = build_input_data(numbers, row, classifier.input_shape());
@@ -319,7 +784,11 @@ fn classify_all<'a>(numbers: &[Vec<f32>], classifier: &'a Classifier) -> Vec<&'a
     classifier.classify(&view)
 }
 
-fn build_input_data(numbers: &[Vec<f32>], row: usize, input_shape: &InputShape) -> Input {
+fn build_input_data<T: Value>(
+    numbers: &[Vec<T>],
+    row: usize,
+    input_shape: &InputShape,
+) -> Input<T> {
     let mut data = vec![];
     let start_column = numbers[0].len() - input_shape.columns();
     for r in 0..input_shape.rows() {
@@ -18,13 +18,26 @@
 */
 
 use crate::executor::{Status, StatusCallback};
-use primeclue::data::data_set::DataSet;
+use primeclue::data::chunked::{ChunkWriter, ChunkedView, CHUNKS_DIR};
+use primeclue::data::data_set::{load_serialized, stored_precision, DataSet, DATA_FILE_NAME};
+use primeclue::data::dictionary::Dictionary;
+use primeclue::data::features::Features;
+use primeclue::data::file_import::FileImport;
+use primeclue::data::images::{read_idx, read_pgm_directory};
 use primeclue::data::importer::{
-    build_data_set, class_producer, split_to_vec, ClassRequest, ClassResponse,
+    as_str_rows, build_appended_data_set, build_data_set, class_producer, read_table,
+    ClassRequest, ClassResponse,
 };
+use primeclue::data::metadata::{ImportSettings, Metadata, Operation, Source};
+use primeclue::data::preview::{read_page, Page};
+use primeclue::data::profile::Profile;
 use primeclue::error::PrimeclueErr;
-use primeclue::user::{read_files, Settings, DATA_DIR, DELETE_IN_PROGRESS};
-use std::path::Path;
+use primeclue::serialization::Serializator;
+use primeclue::user::{
+    read_files, Settings, DATA_DIR, DELETE_IN_PROGRESS, REIMPORT_IN_PROGRESS,
+};
+use primeclue::value::{Precision, Value};
+use std::path::{Path, PathBuf};
 use std::{fs, thread};
 
 pub(crate) fn remove(name: &str) -> Result<(), PrimeclueErr> {
@@ -55,11 +68,113 @@ pub(crate) fn list() -> Result<Vec<String>, PrimeclueErr> {
     read_files(rd)
 }
 
+/// Profile of data set, calculated once and then read from data directory
+pub(crate) fn profile(name: &str) -> Result<Profile, PrimeclueErr> {
+    let settings = Settings::new()?;
+    let path = settings.data_dir().join(name);
+    if let Some(profile) = Profile::read_from_disk(&path)? {
+        return Ok(profile);
+    }
+    let mut s = load_serialized(&path)?;
+    let profile = match Precision::peek(&s) {
+        Precision::Single => read_data::<f32>(&mut s, &path)?.profile(),
+        Precision::Double => read_data::<f64>(&mut s, &path)?.profile(),
+    };
+    profile.save_to_disk(&path)?;
+    Ok(profile)
+}
+
+/// Versions of data set, see [`Metadata`]
+pub(crate) fn metadata(name: &str) -> Result<Metadata, PrimeclueErr> {
+    let settings = Settings::new()?;
+    let path = settings.data_dir().join(name);
+    check_exists(&path, name)?;
+    Metadata::read_from_disk(&path)
+}
+
+/// Fails unless data directory `path` of data set `name` holds data
+fn check_exists(path: &Path, name: &str) -> Result<(), PrimeclueErr> {
+    if path.join(DATA_FILE_NAME).exists() {
+        Ok(())
+    } else {
+        PrimeclueErr::result(format!("Data set {} not found", name))
+    }
+}
+
+/// Which points of a data set to show, see [`rows`]
+#[derive(serde::Deserialize)]
+pub(crate) struct RowsQuery {
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_limit")]
+    limit: usize,
+    class: Option<String>,
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+/// Page of points stored in data set `name`, read without loading the whole data set
+pub(crate) fn rows(name: &str, query: &RowsQuery) -> Result<Page, PrimeclueErr> {
+    let settings = Settings::new()?;
+    let path = settings.data_dir().join(name);
+    let class = query.class.as_deref();
+    match stored_precision(&path)? {
+        Precision::Single => read_page::<f32>(&path, query.offset, query.limit, class),
+        Precision::Double => read_page::<f64>(&path, query.offset, query.limit, class),
+    }
+}
+
+/// Data set as CSV with categories it was imported with, see [`DataSet::export_csv`]
+pub(crate) fn export(name: &str) -> Result<String, PrimeclueErr> {
+    let settings = Settings::new()?;
+    let path = settings.data_dir().join(name);
+    let mut s = load_serialized(&path)?;
+    let dictionary = Dictionary::read_from_disk(&path)?;
+    match Precision::peek(&s) {
+        Precision::Single => read_data::<f32>(&mut s, &path)?.export_csv(dictionary.as_ref()),
+        Precision::Double => read_data::<f64>(&mut s, &path)?.export_csv(dictionary.as_ref()),
+    }
+}
+
+/// Data set with all its segments, from data directory loaded into `s`
+fn read_data<T: Value>(s: &mut Serializator, path: &Path) -> Result<DataSet<T>, PrimeclueErr> {
+    let mut data = DataSet::<T>::from_serialized(s)?;
+    data.read_segments(path)?;
+    Ok(data)
+}
+
 pub(crate) fn import(
     r: ClassRequest,
     status_callback: &StatusCallback,
 ) -> Result<String, PrimeclueErr> {
-    let data = build_data_set(&r)?;
+    match r.precision {
+        Precision::Single => import_as::<f32>(r, Operation::Import, status_callback),
+        Precision::Double => import_as::<f64>(r, Operation::Import, status_callback),
+    }
+}
+
+/// Replaces data of an existing data set with a new version, the previous one is kept
+/// in data directory, see [`Metadata::replace`].
+pub(crate) fn reimport(
+    r: ClassRequest,
+    status_callback: &StatusCallback,
+) -> Result<String, PrimeclueErr> {
+    let settings = Settings::new()?;
+    check_exists(&settings.data_dir().join(&r.data_name), &r.data_name)?;
+    match r.precision {
+        Precision::Single => import_as::<f32>(r, Operation::Reimport, status_callback),
+        Precision::Double => import_as::<f64>(r, Operation::Reimport, status_callback),
+    }
+}
+
+fn import_as<T: Value>(
+    r: ClassRequest,
+    operation: Operation,
+    status_callback: &StatusCallback,
+) -> Result<String, PrimeclueErr> {
+    let (data, dictionary, features, imputation) = build_data_set::<T>(&r)?;
     let settings = Settings::new()?;
     let total = data.len();
     let callback = |count| {
@@ -69,15 +184,262 @@ pub(crate) fn import(
         ));
         Ok(())
     };
+    let path = Path::new(settings.base_dir()).join(DATA_DIR).join(&r.data_name);
+    let mut metadata = Metadata::read_from_disk(&path)?;
+    // re-imported data is written next to the data it replaces, then swapped with it
+    let target = if operation == Operation::Reimport {
+        let target =
+            settings.data_dir().join(format!("{}_{}", REIMPORT_IN_PROGRESS, r.data_name));
+        if target.exists() {
+            fs::remove_dir_all(&target)
+                .map_err(|e| format!("Unable to remove {:?}: {}", target, e))?;
+        }
+        target
+    } else {
+        path.clone()
+    };
+    data.save_to_disk(&target, callback)?;
+    if !dictionary.is_empty() {
+        dictionary.save_to_disk(&target)?;
+    }
+    if !features.is_empty() {
+        features.save_to_disk(&target)?;
+    }
+    if !imputation.is_empty() {
+        imputation.save_to_disk(&target)?;
+    }
+    if let Some(grouping) = &r.group {
+        grouping.save_to_disk(&target)?;
+    }
+    write_chunks::<T>(&target, r.chunk_size, status_callback)?;
+    let source = Source::of_request(&r);
+    let replaced = metadata.latest().map_or(0, |latest| latest.number);
+    let version = metadata.record::<T>(&target, operation, Some(source), Some((&r).into()))?;
+    if operation == Operation::Reimport {
+        Metadata::replace(&path, &target, replaced)?;
+    }
+    Ok(format!("Done, version {}", version.number))
+}
+
+/// Stores data of data directory `path` in chunks of `chunk_size` data points as well, for
+/// training on data that doesn't fit in memory, see [`ChunkWriter::write_stored`]. Chunks
+/// stored before are outdated once data changes, so they are rewritten with their chunk
+/// size unless another one is given.
+fn write_chunks<T: Value>(
+    path: &Path,
+    chunk_size: Option<usize>,
+    status_callback: &StatusCallback,
+) -> Result<(), PrimeclueErr> {
+    let chunks = path.join(CHUNKS_DIR);
+    let chunk_size = match chunk_size {
+        Some(chunk_size) => chunk_size,
+        None if chunks.exists() => ChunkedView::<T>::open(&chunks)?.chunk_size(),
+        None => return Ok(()),
+    };
+    status_callback(Status::Progress(0.0, "Writing chunks".to_owned()));
+    if chunks.exists() {
+        fs::remove_dir_all(&chunks)
+            .map_err(|e| format!("Unable to remove outdated chunks: {}", e))?;
+    }
+    ChunkWriter::<T>::write_stored(path, chunk_size)?;
+    Ok(())
+}
+
+/// Import of a file, or a directory of files, from the import directory on the server
+#[derive(serde::Deserialize)]
+pub(crate) struct FileImportRequest {
+    file: String,
+    #[serde(flatten)]
+    request: ClassRequest,
+}
+
+/// Names of files and directories that can be imported with [`import_file`]
+pub(crate) fn import_files() -> Result<Vec<String>, PrimeclueErr> {
+    let settings = Settings::new()?;
+    let mut names = vec![];
+    for entry in fs::read_dir(settings.import_dir())? {
+        if let Some(name) = entry?.file_name().to_str() {
+            names.push(name.to_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+pub(crate) fn import_file(
+    r: FileImportRequest,
+    status_callback: &StatusCallback,
+) -> Result<String, PrimeclueErr> {
+    match r.request.precision {
+        Precision::Single => import_file_as::<f32>(r, status_callback),
+        Precision::Double => import_file_as::<f64>(r, status_callback),
+    }
+}
+
+fn import_file_as<T: Value>(
+    r: FileImportRequest,
+    status_callback: &StatusCallback,
+) -> Result<String, PrimeclueErr> {
+    let settings = Settings::new()?;
+    let source = import_path(&settings, &r.file)?;
+    status_callback(Status::Progress(0.0, format!("Reading {}", r.file)));
+    let import = FileImport::scan(&r.request, &source)?;
+    let total = import.rows();
+    let callback = |count| {
+        status_callback(Status::Progress(
+            count as f64 / total as f64,
+            format!("Imported rows {}", count),
+        ));
+        Ok(())
+    };
+    let path = Path::new(settings.base_dir()).join(DATA_DIR).join(&r.request.data_name);
+    let saved = import.save_to_disk::<T, _>(&path, callback)?;
+    let write = || -> Result<(), PrimeclueErr> {
+        if !import.dictionary().is_empty() {
+            import.dictionary().save_to_disk(&path)?;
+        }
+        if !import.imputation().is_empty() {
+            import.imputation().save_to_disk(&path)?;
+        }
+        write_chunks::<T>(&path, r.request.chunk_size, status_callback)?;
+        let source = Source::of_files(&r.file, &[source])?;
+        let settings = ImportSettings::from(&r.request);
+        Metadata::default().record::<T>(
+            &path,
+            Operation::Import,
+            Some(source),
+            Some(settings),
+        )?;
+        Ok(())
+    };
+    // without its dictionary or metadata saved data can't be used
+    if let Err(e) = write() {
+        fs::remove_dir_all(&path).map_err(|e| format!("Unable to remove {:?}: {}", path, e))?;
+        return Err(e);
+    }
+    Ok(format!("Imported {} data points", saved))
+}
+
+/// Import of images from the import directory on the server
+#[derive(serde::Deserialize)]
+pub(crate) struct ImageImportRequest {
+    data_name: String,
+    #[serde(default)]
+    precision: Precision,
+    source: ImageSource,
+}
+
+/// Image files, with paths relative to the import directory
+#[derive(serde::Deserialize)]
+pub(crate) enum ImageSource {
+    /// IDX files of images and their labels
+    Idx { images: String, labels: String },
+    /// Directory of PGM images in subdirectories named after classes
+    PgmDirectory { directory: String },
+}
+
+pub(crate) fn import_images(
+    r: ImageImportRequest,
+    status_callback: &StatusCallback,
+) -> Result<String, PrimeclueErr> {
+    match r.precision {
+        Precision::Single => import_images_as::<f32>(r, status_callback),
+        Precision::Double => import_images_as::<f64>(r, status_callback),
+    }
+}
+
+fn import_images_as<T: Value>(
+    r: ImageImportRequest,
+    status_callback: &StatusCallback,
+) -> Result<String, PrimeclueErr> {
+    let settings = Settings::new()?;
+    status_callback(Status::Progress(0.0, "Reading images".to_owned()));
+    let (data, source) = match &r.source {
+        ImageSource::Idx { images, labels } => {
+            let files = [import_path(&settings, images)?, import_path(&settings, labels)?];
+            let name = format!("{} {}", images, labels);
+            (read_idx::<T>(&files[0], &files[1])?, Source::of_files(&name, &files)?)
+        }
+        ImageSource::PgmDirectory { directory } => {
+            let path = import_path(&settings, directory)?;
+            (read_pgm_directory::<T>(&path)?, Source::of_files(directory, &[path])?)
+        }
+    };
+    let total = data.len();
+    let callback = |count| {
+        status_callback(Status::Progress(
+            count as f64 / total as f64,
+            format!("Saved data set {}", count),
+        ));
+        Ok(())
+    };
+    let path = settings.data_dir().join(&r.data_name);
     save_data(r.data_name, &data, &settings, callback)?;
-    Ok("Done".to_string())
+    Metadata::default().record::<T>(&path, Operation::Import, Some(source), None)?;
+    Ok(format!("Imported {} images", total))
+}
+
+/// Path of `file` in import directory. Paths leading outside of it are an error.
+fn import_path(settings: &Settings, file: &str) -> Result<PathBuf, PrimeclueErr> {
+    let dir = settings.import_dir().canonicalize()?;
+    let path = dir
+        .join(file)
+        .canonicalize()
+        .map_err(|e| format!("Unable to find {} in import directory: {}", file, e))?;
+    if path.starts_with(&dir) {
+        Ok(path)
+    } else {
+        PrimeclueErr::result(format!("{} is outside of import directory", file))
+    }
+}
+
+/// Appends data to an existing data set, in the precision it was imported with.
+pub(crate) fn append(
+    r: ClassRequest,
+    status_callback: &StatusCallback,
+) -> Result<String, PrimeclueErr> {
+    let settings = Settings::new()?;
+    let path = Path::new(settings.base_dir()).join(DATA_DIR).join(&r.data_name);
+    match Precision::peek(&load_serialized(&path)?) {
+        Precision::Single => append_as::<f32>(r, &path, status_callback),
+        Precision::Double => append_as::<f64>(r, &path, status_callback),
+    }
+}
+
+fn append_as<T: Value>(
+    r: ClassRequest,
+    path: &Path,
+    status_callback: &StatusCallback,
+) -> Result<String, PrimeclueErr> {
+    let dictionary = Dictionary::read_from_disk(path)?;
+    let features = Features::read_from_disk(path)?;
+    let data = build_appended_data_set::<T>(&r, dictionary.as_ref(), features.as_ref())?;
+    let total = data.len();
+    let callback = |count| {
+        status_callback(Status::Progress(
+            count as f64 / total as f64,
+            format!("Saved data set {}", count),
+        ));
+        Ok(())
+    };
+    let segment = data.append_to_disk(path, callback)?;
+    write_chunks::<T>(path, r.chunk_size, status_callback)?;
+    let source = Source::of_request(&r);
+    let mut metadata = Metadata::read_from_disk(path)?;
+    let version =
+        metadata.record::<T>(path, Operation::Append, Some(source), Some((&r).into()))?;
+    Ok(format!(
+        "Appended {} data points as segment {}, version {}",
+        total, segment, version.number
+    ))
 }
 
 pub(crate) fn classes(r: &ClassRequest) -> Result<ClassResponse, PrimeclueErr> {
-    let data = split_to_vec(&r.content, &r.separator, r.ignore_first_row);
+    let (table, _) = read_table(r, None)?;
+    let data = as_str_rows(&table.rows);
     let mut classes = Vec::with_capacity(data.len());
     let class_producer = class_producer(r, &data)?;
-    if r.ignore_first_row {
+    if table.header.is_some() {
         classes.push(String::new());
     }
     for row in 0..data.len() {
@@ -87,9 +449,9 @@ pub(crate) fn classes(r: &ClassRequest) -> Result<ClassResponse, PrimeclueErr> {
     Ok(ClassResponse::new(classes))
 }
 
-fn save_data<F>(
+fn save_data<T: Value, F>(
     name: String,
-    data: &DataSet,
+    data: &DataSet<T>,
     settings: &Settings,
     callback: F,
 ) -> Result<(), PrimeclueErr>
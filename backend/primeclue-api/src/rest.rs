@@ -18,7 +18,10 @@
 */
 
 use crate::classifier::{create, ClassifyRequest, CreateRequest};
-use crate::data::{classes, import};
+use crate::data::{
+    append, classes, import, import_file, import_images, reimport, FileImportRequest,
+    ImageImportRequest, RowsQuery,
+};
 use crate::executor::{Executor, Job, JobId, Status, Termination};
 use crate::{classifier, data};
 use actix_cors::Cors;
@@ -72,6 +75,42 @@ fn data_remove_handler(path: web::Path<String>) -> HttpResponse {
     }
 }
 
+fn data_export_handler(path: web::Path<String>) -> HttpResponse {
+    let name = path.into_inner();
+    match data::export(&name) {
+        Ok(csv) => HttpResponse::Ok()
+            .content_type("text/csv")
+            .header("Content-Disposition", format!("attachment; filename=\"{}.csv\"", name))
+            .body(csv),
+        Err(error) => HttpResponse::InternalServerError().body(format!("Error: {}", error)),
+    }
+}
+
+fn data_profile_handler(path: web::Path<String>) -> HttpResponse {
+    let name = path.into_inner();
+    match data::profile(&name) {
+        Ok(profile) => HttpResponse::Ok().json(profile),
+        Err(error) => HttpResponse::InternalServerError().body(format!("Error: {}", error)),
+    }
+}
+
+fn data_metadata_handler(path: web::Path<String>) -> HttpResponse {
+    let name = path.into_inner();
+    match data::metadata(&name) {
+        Ok(metadata) => HttpResponse::Ok().json(metadata),
+        Err(error) => HttpResponse::InternalServerError().body(format!("Error: {}", error)),
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn data_rows_handler(path: web::Path<String>, query: web::Query<RowsQuery>) -> HttpResponse {
+    let name = path.into_inner();
+    match data::rows(&name, &query) {
+        Ok(page) => HttpResponse::Ok().json(page),
+        Err(error) => HttpResponse::InternalServerError().body(format!("Error: {}", error)),
+    }
+}
+
 fn data_list_handler() -> HttpResponse {
     match data::list() {
         Ok(list) => HttpResponse::Ok().json(list),
@@ -86,6 +125,30 @@ fn classifier_list_handler() -> HttpResponse {
     }
 }
 
+fn classifier_describe_handler(path: web::Path<String>) -> HttpResponse {
+    let name = path.into_inner();
+    match classifier::describe(&name) {
+        Ok(description) => HttpResponse::Ok().json(description),
+        Err(error) => HttpResponse::InternalServerError().body(format!("Error: {}", error)),
+    }
+}
+
+fn classifier_trees_handler(path: web::Path<String>) -> HttpResponse {
+    let name = path.into_inner();
+    match classifier::structure(&name) {
+        Ok(structure) => HttpResponse::Ok().json(structure),
+        Err(error) => HttpResponse::InternalServerError().body(format!("Error: {}", error)),
+    }
+}
+
+fn classifier_dot_handler(path: web::Path<String>) -> HttpResponse {
+    let name = path.into_inner();
+    match classifier::dot(&name) {
+        Ok(dot) => HttpResponse::Ok().content_type("text/vnd.graphviz").body(dot),
+        Err(error) => HttpResponse::InternalServerError().body(format!("Error: {}", error)),
+    }
+}
+
 fn classifier_remove_handler(path: web::Path<String>) -> HttpResponse {
     let name = path.into_inner();
     match classifier::remove(&name) {
@@ -113,6 +176,61 @@ fn data_import_handler(
     id_ok_response(id)
 }
 
+#[allow(clippy::needless_pass_by_value)]
+fn data_reimport_handler(
+    r: web::Json<ClassRequest>,
+    data: web::Data<Mutex<Executor>>,
+) -> HttpResponse {
+    let mut executor = data.lock().unwrap();
+    let (id, callback) = executor.prepare_new_job();
+    let job = Job::new(id, Box::new(move || reimport(r.into_inner(), &callback)));
+    executor.submit(job, None);
+    id_ok_response(id)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn data_import_file_handler(
+    r: web::Json<FileImportRequest>,
+    data: web::Data<Mutex<Executor>>,
+) -> HttpResponse {
+    let mut executor = data.lock().unwrap();
+    let (id, callback) = executor.prepare_new_job();
+    let job = Job::new(id, Box::new(move || import_file(r.into_inner(), &callback)));
+    executor.submit(job, None);
+    id_ok_response(id)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn data_import_images_handler(
+    r: web::Json<ImageImportRequest>,
+    data: web::Data<Mutex<Executor>>,
+) -> HttpResponse {
+    let mut executor = data.lock().unwrap();
+    let (id, callback) = executor.prepare_new_job();
+    let job = Job::new(id, Box::new(move || import_images(r.into_inner(), &callback)));
+    executor.submit(job, None);
+    id_ok_response(id)
+}
+
+fn data_import_files_handler() -> HttpResponse {
+    match data::import_files() {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(error) => HttpResponse::InternalServerError().body(format!("Error: {}", error)),
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn data_append_handler(
+    r: web::Json<ClassRequest>,
+    data: web::Data<Mutex<Executor>>,
+) -> HttpResponse {
+    let mut executor = data.lock().unwrap();
+    let (id, callback) = executor.prepare_new_job();
+    let job = Job::new(id, Box::new(move || append(r.into_inner(), &callback)));
+    executor.submit(job, None);
+    id_ok_response(id)
+}
+
 fn id_ok_response(id: u64) -> HttpResponse {
     HttpResponse::Ok().body(format!("{}", id))
 }
@@ -145,7 +263,31 @@ pub(crate) fn start_web() -> std::io::Result<()> {
             .register_data(web::Data::new(Mutex::new(Executor::create())))
             .route("/data/classes", web::to(data_classes_handler).method(http::Method::POST))
             .route("/data/import", web::to(data_import_handler).method(http::Method::POST))
+            .route("/data/append", web::to(data_append_handler).method(http::Method::POST))
+            .route("/data/reimport", web::to(data_reimport_handler).method(http::Method::POST))
+            .route(
+                "/data/import_file",
+                web::to(data_import_file_handler).method(http::Method::POST),
+            )
+            .route(
+                "/data/import_images",
+                web::to(data_import_images_handler).method(http::Method::POST),
+            )
+            .route(
+                "/data/import_files",
+                web::to(data_import_files_handler).method(http::Method::GET),
+            )
             .route("/data/list", web::to(data_list_handler).method(http::Method::GET))
+            .route("/data/{name}/export", web::to(data_export_handler).method(http::Method::GET))
+            .route(
+                "/data/{name}/profile",
+                web::to(data_profile_handler).method(http::Method::GET),
+            )
+            .route("/data/{name}/rows", web::to(data_rows_handler).method(http::Method::GET))
+            .route(
+                "/data/{name}/metadata",
+                web::to(data_metadata_handler).method(http::Method::GET),
+            )
             .route(
                 "/data/remove/{name}",
                 web::to(data_remove_handler).method(http::Method::POST),
@@ -162,6 +304,18 @@ pub(crate) fn start_web() -> std::io::Result<()> {
                 "/classifier/list",
                 web::to(classifier_list_handler).method(http::Method::GET),
             )
+            .route(
+                "/classifier/{name}/describe",
+                web::to(classifier_describe_handler).method(http::Method::GET),
+            )
+            .route(
+                "/classifier/{name}/trees",
+                web::to(classifier_trees_handler).method(http::Method::GET),
+            )
+            .route(
+                "/classifier/{name}/dot",
+                web::to(classifier_dot_handler).method(http::Method::GET),
+            )
             .route(
                 "/classifier/remove/{name}",
                 web::to(classifier_remove_handler).method(http::Method::POST),
@@ -22,6 +22,7 @@ use primeclue::data::data_set::DataSet;
 use primeclue::data::outcome::Class;
 use primeclue::data::{Input, InputShape, Outcome, Point};
 use primeclue::exec::class_training::ClassTraining;
+use primeclue::exec::compiled_tree::{CompiledTree, Registers};
 use primeclue::exec::score::Objective::{Auc, Cost};
 use primeclue::exec::training_group::TrainingGroup;
 use primeclue::exec::tree::Tree;
@@ -91,7 +92,7 @@ fn training_group_generation_bench(c: &mut Criterion) {
         TrainingGroup::new(training_data, verification_data, Auc, 10, &[]).unwrap();
     c.bench_function("training_group_generation_bench", |b| {
         b.iter(|| {
-            training_group.next_generation();
+            training_group.next_generation().unwrap();
             black_box(training_group.generation());
         })
     });
@@ -122,7 +123,9 @@ fn next_generation_bench(c: &mut Criterion) {
     let mut class_training = ClassTraining::new(10, vec![], Auc, Class::new(0));
     c.bench_function("next_generation", |b| {
         b.iter(|| {
-            class_training.next_generation(black_box(&training_data), &verification_data);
+            class_training
+                .next_generation(black_box(&training_data), &verification_data)
+                .unwrap();
         })
     });
 }
@@ -148,6 +151,56 @@ fn execute_tree_bench(c: &mut Criterion) {
     });
 }
 
+fn execute_compiled_tree_bench(c: &mut Criterion) {
+    let data = create_sample_data(1_000).into_view();
+    let mut rng = GET_RNG();
+    let mut trees = vec![];
+    for _ in 0..100 {
+        let max_depth = rng.gen_range(2, 10);
+        let data_prob = rng.gen_range(0.01, 0.99);
+        let branch_prob = rng.gen_range(0.01, 0.99);
+        let tree = Tree::new(data.input_shape(), max_depth, &[], branch_prob, data_prob);
+        trees.push(CompiledTree::new(&tree))
+    }
+
+    let mut registers = Registers::new();
+    c.bench_function("execute_compiled_tree", |b| {
+        b.iter(|| {
+            for tree in &trees {
+                let _ =
+                    tree.execute_for_score(black_box(&data), Class::new(0), Auc, &mut registers);
+            }
+        })
+    });
+}
+
+fn compile_and_execute_tree_bench(c: &mut Criterion) {
+    let data = create_sample_data(1_000).into_view();
+    let mut rng = GET_RNG();
+    let mut trees = vec![];
+    for _ in 0..100 {
+        let max_depth = rng.gen_range(2, 10);
+        let data_prob = rng.gen_range(0.01, 0.99);
+        let branch_prob = rng.gen_range(0.01, 0.99);
+        let tree = Tree::new(data.input_shape(), max_depth, &[], branch_prob, data_prob);
+        trees.push(tree)
+    }
+
+    let mut registers = Registers::new();
+    c.bench_function("compile_and_execute_tree", |b| {
+        b.iter(|| {
+            for tree in &trees {
+                let _ = CompiledTree::new(tree).execute_for_score(
+                    black_box(&data),
+                    Class::new(0),
+                    Auc,
+                    &mut registers,
+                );
+            }
+        })
+    });
+}
+
 fn create_tree_bench(c: &mut Criterion) {
     let forbidden_cols = vec![1, 2, 3];
     c.bench_function("create_tree", |b| {
@@ -172,6 +225,8 @@ fn create_tree_bench(c: &mut Criterion) {
 criterion_group!(
     benches,
     execute_tree_bench,
+    execute_compiled_tree_bench,
+    compile_and_execute_tree_bench,
     create_tree_bench,
     next_generation_bench,
     training_group_generation_bench,
@@ -0,0 +1,490 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::data_set::{read_stored_points, DataSet, DataView};
+use crate::data::outcome::Class;
+use crate::data::{InputShape, Point};
+use crate::error::PrimeclueErr;
+use crate::rand::GET_RNG;
+use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::{Precision, Value};
+use rand::seq::{index, SliceRandom};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Directory inside data directory in which [`ChunkWriter::write_stored`] puts chunks
+pub const CHUNKS_DIR: &str = "chunks";
+/// Minimum number of data points [`ChunkedView::split_sampled`] holds out
+pub const MIN_HELD_OUT: usize = 10;
+const LAYOUT_FILE_NAME: &str = "layout.ssd";
+
+fn chunk_file_name(chunk: usize) -> String {
+    format!("chunk_{}.ssd", chunk)
+}
+
+/// Writes data points into a chunked data directory, one chunk of at most `chunk_size`
+/// points at a time, so that only one chunk is kept in memory.
+/// [`ChunkWriter::finish`] must be called to make the chunks readable.
+#[derive(Debug)]
+pub struct ChunkWriter<T: Value = f32> {
+    path: PathBuf,
+    chunk_size: usize,
+    classes: HashMap<Class, String>,
+    header: Vec<String>,
+    chunk: DataSet<T>,
+    chunks: usize,
+    len: usize,
+    class_count: HashMap<Class, usize>,
+    input_shape: Option<InputShape>,
+}
+
+impl<T: Value> ChunkWriter<T> {
+    /// Creates directory `path` for chunks of data with given class map
+    pub fn create(
+        path: &Path,
+        classes: HashMap<Class, String>,
+        chunk_size: usize,
+    ) -> Result<Self, PrimeclueErr> {
+        if chunk_size == 0 {
+            return PrimeclueErr::result("Chunk size must be at least 1".to_owned());
+        }
+        if path.exists() {
+            return PrimeclueErr::result(format!("Directory {:?} already exists", path));
+        }
+        fs::create_dir(path)
+            .map_err(|e| format!("Unable to create directory {:?}, error: {}", path, e))?;
+        Ok(ChunkWriter {
+            path: path.to_path_buf(),
+            chunk_size,
+            chunk: DataSet::new(classes.clone()),
+            classes,
+            header: vec![],
+            chunks: 0,
+            len: 0,
+            class_count: HashMap::new(),
+            input_shape: None,
+        })
+    }
+
+    /// Converts data set stored in data directory `path`, including its appended
+    /// segments, into chunks in its `chunks` subdirectory. Files are streamed token by
+    /// token, so the whole data set never has to fit in memory.
+    pub fn write_stored(path: &Path, chunk_size: usize) -> Result<ChunkedView<T>, PrimeclueErr> {
+        let mut writer: Option<ChunkWriter<T>> = None;
+        let header = read_stored_points(path, |classes, point| {
+            if writer.is_none() {
+                let chunks = path.join(CHUNKS_DIR);
+                writer = Some(ChunkWriter::create(&chunks, classes.clone(), chunk_size)?);
+            }
+            writer.as_mut().unwrap().add(point)
+        })?;
+        let mut writer =
+            writer.ok_or_else(|| PrimeclueErr::from("No data points to convert".to_owned()))?;
+        writer.header = header;
+        writer.finish()
+    }
+
+    /// Sets names of columns, see [`DataSet::header`]
+    pub fn set_header(&mut self, header: Vec<String>) {
+        self.header = header;
+    }
+
+    pub fn add(&mut self, point: Point<T>) -> Result<(), PrimeclueErr> {
+        let (input, outcome) = point.data();
+        match &self.input_shape {
+            Some(input_shape) if input_shape != input.input_shape() => {
+                return PrimeclueErr::result(format!(
+                    "Invalid input size, expecting {}, got: {}",
+                    input_shape,
+                    input.input_shape()
+                ));
+            }
+            Some(_) => {}
+            None => self.input_shape = Some(*input.input_shape()),
+        }
+        if !self.classes.contains_key(&outcome.class()) {
+            return PrimeclueErr::result(format!(
+                "Class {:?} not found in class map",
+                outcome.class()
+            ));
+        }
+        *self.class_count.entry(outcome.class()).or_insert(0) += 1;
+        self.chunk.add_data_point(point)?;
+        self.len += 1;
+        if self.chunk.len() == self.chunk_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), PrimeclueErr> {
+        let chunk = std::mem::replace(&mut self.chunk, DataSet::new(self.classes.clone()));
+        self.chunks += 1;
+        let mut s = Serializator::new();
+        s.add(&chunk);
+        s.save(&self.path, &chunk_file_name(self.chunks))?;
+        Ok(())
+    }
+
+    /// Saves the last chunk and layout of all chunks
+    pub fn finish(mut self) -> Result<ChunkedView<T>, PrimeclueErr> {
+        let input_shape = match self.input_shape {
+            Some(input_shape) => input_shape,
+            None => return PrimeclueErr::result("No data points to save".to_owned()),
+        };
+        if !self.chunk.is_empty() {
+            self.flush()?;
+        }
+        let view = ChunkedView {
+            path: self.path,
+            classes: self.classes,
+            header: self.header,
+            class_count: self.class_count,
+            input_shape,
+            chunk_size: self.chunk_size,
+            chunks: self.chunks,
+            len: self.len,
+            held_out: Arc::new(HashSet::new()),
+            _precision: std::marker::PhantomData,
+        };
+        let mut s = Serializator::new();
+        s.add(&view);
+        s.save(&view.path, LAYOUT_FILE_NAME)?;
+        Ok(view)
+    }
+}
+
+/// Data stored in chunks on disk, for data sets that don't fit in memory. Chunks are
+/// read one at a time as [`DataView`]s, see [`ChunkedView::chunks`].
+///
+/// Standard deviation nodes standardize values within each chunk, like they do within
+/// a subsample, so chunks should be big enough to represent the whole data.
+#[derive(Debug, Clone)]
+pub struct ChunkedView<T: Value = f32> {
+    path: PathBuf,
+    classes: HashMap<Class, String>,
+    header: Vec<String>,
+    class_count: HashMap<Class, usize>,
+    input_shape: InputShape,
+    chunk_size: usize,
+    chunks: usize,
+    len: usize,
+    /// Indices of data points, counted across all chunks, that were split off with
+    /// [`ChunkedView::split_sampled`] and are skipped when reading chunks
+    held_out: Arc<HashSet<usize>>,
+    _precision: std::marker::PhantomData<T>,
+}
+
+impl<T: Value> Serializable for ChunkedView<T> {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_items(&[
+            &T::PRECISION,
+            &self.classes,
+            &self.header,
+            &self.class_count,
+            &self.input_shape,
+            &self.chunk_size,
+            &self.chunks,
+            &self.len,
+        ]);
+    }
+}
+
+impl<T: Value> ChunkedView<T> {
+    /// Opens chunks written by [`ChunkWriter`] in directory `path`
+    pub fn open(path: &Path) -> Result<Self, PrimeclueErr> {
+        let mut s = Serializator::load(&path.join(LAYOUT_FILE_NAME))?;
+        let view = ChunkedView::read_layout(path, &mut s)
+            .map_err(|e| format!("Unable to read chunk layout: {}", e))?;
+        for chunk in 1..=view.chunks {
+            let file = path.join(chunk_file_name(chunk));
+            if !file.exists() {
+                return PrimeclueErr::result(format!("Chunk {:?} is missing", file));
+            }
+        }
+        Ok(view)
+    }
+
+    fn read_layout(path: &Path, s: &mut Serializator) -> Result<Self, String> {
+        Precision::deserialize_expecting::<T>(s)?;
+        Ok(ChunkedView {
+            path: path.to_path_buf(),
+            classes: HashMap::deserialize(s)?,
+            header: Vec::deserialize(s)?,
+            class_count: HashMap::deserialize(s)?,
+            input_shape: InputShape::deserialize(s)?,
+            chunk_size: usize::deserialize(s)?,
+            chunks: usize::deserialize(s)?,
+            len: usize::deserialize(s)?,
+            held_out: Arc::new(HashSet::new()),
+            _precision: std::marker::PhantomData,
+        })
+    }
+
+    /// Directory in which chunks are stored
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of data points in all chunks
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn chunk_count(&self) -> usize {
+        self.chunks
+    }
+
+    /// Maximum number of data points in a chunk
+    #[must_use]
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    #[must_use]
+    pub fn class_count(&self) -> usize {
+        self.class_count.len()
+    }
+
+    #[must_use]
+    pub fn class_map(&self) -> &HashMap<Class, String> {
+        &self.classes
+    }
+
+    #[must_use]
+    pub fn header(&self) -> &[String] {
+        &self.header
+    }
+
+    #[must_use]
+    pub fn input_shape(&self) -> &InputShape {
+        &self.input_shape
+    }
+
+    fn read_chunk(&self, chunk: usize) -> Result<DataSet<T>, PrimeclueErr> {
+        let file = self.path.join(chunk_file_name(chunk));
+        let data = DataSet::from_serialized(&mut Serializator::load(&file)?)?;
+        if self.held_out.is_empty() {
+            return Ok(data);
+        }
+        // all chunks but the last are full
+        let offset = (chunk - 1) * self.chunk_size;
+        let mut kept = DataSet::new(self.classes.clone());
+        for (index, point) in data.iter().enumerate() {
+            if !self.held_out.contains(&(offset + index)) {
+                kept.add_data_point(point.clone())?;
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Reads chunks from disk one by one, in order. Only the chunk being iterated over
+    /// is kept in memory. Chunks whose data points were all held out are skipped.
+    pub fn chunks(&self) -> impl Iterator<Item = Result<DataView<T>, PrimeclueErr>> + '_ {
+        (1..=self.chunks)
+            .map(move |chunk| self.read_chunk(chunk))
+            .filter(|chunk| !matches!(chunk, Ok(data) if data.is_empty()))
+            .map(|chunk| chunk.map(DataSet::into_view))
+    }
+
+    /// Splits `size` data points, drawn at random from all chunks and read into memory,
+    /// off the rest, e.g. to hold them out for verification and testing. The returned
+    /// view skips held out data points. At least [`MIN_HELD_OUT`] data points must be
+    /// held out and at least as many must remain.
+    pub fn split_sampled(
+        &self,
+        size: usize,
+    ) -> Result<(ChunkedView<T>, DataSet<T>), PrimeclueErr> {
+        if size < MIN_HELD_OUT || self.len < size + MIN_HELD_OUT {
+            return PrimeclueErr::result(format!(
+                "Unable to hold out {} of {} data points, at least {} must be held out and \
+                 remain",
+                size, self.len, MIN_HELD_OUT
+            ));
+        }
+        // ranks of chosen data points among the ones not held out yet
+        let chosen =
+            index::sample(&mut GET_RNG(), self.len, size).into_iter().collect::<HashSet<_>>();
+        let mut held_out = DataSet::new(self.classes.clone());
+        held_out.set_header(self.header.clone());
+        let mut sampled = (*self.held_out).clone();
+        let mut rest = self.clone();
+        let mut rank = 0;
+        for chunk in 1..=self.chunks {
+            let offset = (chunk - 1) * self.chunk_size;
+            let file = self.path.join(chunk_file_name(chunk));
+            let data = DataSet::<T>::from_serialized(&mut Serializator::load(&file)?)?;
+            for (index, point) in data.iter().enumerate() {
+                if self.held_out.contains(&(offset + index)) {
+                    continue;
+                }
+                if chosen.contains(&rank) {
+                    sampled.insert(offset + index);
+                    if let Some(count) = rest.class_count.get_mut(&point.data().1.class()) {
+                        *count -= 1;
+                    }
+                    held_out.add_data_point(point.clone())?;
+                }
+                rank += 1;
+            }
+        }
+        rest.class_count.retain(|_, count| *count > 0);
+        rest.len -= size;
+        rest.held_out = Arc::new(sampled);
+        Ok((rest, held_out))
+    }
+
+    /// Draws `size` random data points from all chunks into memory, in random order.
+    /// Returns all data points if there are fewer. All chunks are read from disk, so
+    /// a sample costs as much I/O as a pass over the whole data.
+    pub fn sample(&self, size: usize) -> Result<DataView<T>, PrimeclueErr> {
+        let mut rng = GET_RNG();
+        let mut reservoir = Vec::with_capacity(size.min(self.len));
+        let mut seen = 0;
+        for chunk in 1..=self.chunks {
+            for point in self.read_chunk(chunk)?.iter() {
+                seen += 1;
+                if reservoir.len() < size {
+                    reservoir.push(point.clone());
+                } else {
+                    let index = rng.gen_range(0, seen);
+                    if index < size {
+                        reservoir[index] = point.clone();
+                    }
+                }
+            }
+        }
+        reservoir.shuffle(&mut rng);
+        let mut sample = DataSet::new(self.classes.clone());
+        sample.set_header(self.header.clone());
+        for point in reservoir {
+            sample.add_data_point(point)?;
+        }
+        Ok(sample.into_view())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::data::chunked::{
+        chunk_file_name, ChunkWriter, ChunkedView, CHUNKS_DIR, MIN_HELD_OUT,
+    };
+    use crate::data::data_set::test::create_simple_data;
+    use crate::data::data_set::DataSet;
+    use crate::data::test::temp_path;
+    use crate::data::Outcome;
+    use crate::value::Value;
+    use std::fs;
+
+    /// Writes `data` into chunks in a temporary directory, which should be removed with
+    /// `fs::remove_dir_all` after use
+    pub(crate) fn write_chunks<T: Value>(
+        data: &DataSet<T>,
+        chunk_size: usize,
+    ) -> ChunkedView<T> {
+        let mut writer =
+            ChunkWriter::create(&temp_path("chunks"), data.class_map().clone(), chunk_size)
+                .unwrap();
+        for point in data.iter() {
+            writer.add(point.clone()).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn write_stored() {
+        let path = temp_path("chunks");
+        let mut data = create_simple_data(25);
+        data.set_header(vec!["first".to_owned(), "second".to_owned()]);
+        data.save_to_disk(&path, |_| Ok(())).unwrap();
+        let appended = create_simple_data(5);
+        appended.append_to_disk(&path, |_| Ok(())).unwrap();
+
+        let written = ChunkWriter::<f32>::write_stored(&path, 10).unwrap();
+        let chunks = ChunkedView::<f32>::open(&path.join(CHUNKS_DIR)).unwrap();
+        assert_eq!(chunks.len(), 30);
+        assert_eq!(chunks.len(), written.len());
+        assert_eq!(chunks.chunk_count(), 3);
+        assert_eq!(chunks.chunk_size(), 10);
+        assert_eq!(chunks.class_count(), 2);
+        assert_eq!(chunks.header(), data.header());
+        let outcomes = chunks
+            .chunks()
+            .map(|chunk| chunk.unwrap().outcomes().clone())
+            .collect::<Vec<Vec<Outcome>>>();
+        assert!(outcomes.iter().all(|o| o.len() == 10));
+        let expected = data.iter().chain(appended.iter()).map(|p| *p.data().1);
+        assert!(outcomes.concat().into_iter().eq(expected));
+
+        assert!(ChunkWriter::<f32>::write_stored(&path, 10).is_err());
+        assert!(ChunkWriter::<f64>::write_stored(&path, 10).is_err());
+        fs::remove_file(path.join(CHUNKS_DIR).join(chunk_file_name(2))).unwrap();
+        assert!(ChunkedView::<f32>::open(&path.join(CHUNKS_DIR)).is_err());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn split_sampled() {
+        let data = create_simple_data(40);
+        let chunks = write_chunks(&data, 10);
+        let (rest, held_out) = chunks.split_sampled(12).unwrap();
+        assert_eq!(rest.len(), 28);
+        assert_eq!(rest.chunk_count(), 4);
+        assert_eq!(held_out.len(), 12);
+        assert_eq!(rest.class_count(), 2);
+        let (first, second) = rest.split_sampled(10).unwrap();
+        assert_eq!(first.len(), 18);
+        let mut points = first
+            .chunks()
+            .flat_map(|chunk| chunk.unwrap().outcomes().clone())
+            .chain(held_out.iter().chain(second.iter()).map(|p| *p.data().1))
+            .collect::<Vec<Outcome>>();
+        assert_eq!(points.len(), 40);
+        points.sort_by_key(|o| o.class());
+        let mut expected = data.iter().map(|p| *p.data().1).collect::<Vec<_>>();
+        expected.sort_by_key(|o| o.class());
+        assert_eq!(points, expected);
+        assert_eq!(first.sample(100).unwrap().len(), 18);
+        assert!(first.split_sampled(10).is_err());
+        assert!(chunks.split_sampled(MIN_HELD_OUT - 1).is_err());
+        fs::remove_dir_all(chunks.path()).unwrap();
+    }
+
+    #[test]
+    fn sample() {
+        let chunks = write_chunks(&create_simple_data(30), 7);
+        assert_eq!(chunks.chunk_count(), 5);
+        let sample = chunks.sample(12).unwrap();
+        assert_eq!(sample.len(), 12);
+        assert_eq!(sample.input_shape(), chunks.input_shape());
+        assert_eq!(chunks.sample(100).unwrap().len(), 30);
+        fs::remove_dir_all(chunks.path()).unwrap();
+    }
+}
@@ -58,6 +58,10 @@ impl<T> Data<T> {
         &self.data[row * self.input_shape.columns + column]
     }
 
+    pub fn get_mut(&mut self, row: usize, column: usize) -> &mut T {
+        &mut self.data[row * self.input_shape.columns + column]
+    }
+
     pub fn add_row(&mut self, row: Vec<T>) -> Result<usize, PrimeclueErr> {
         if !self.data.is_empty() && self.input_shape.columns() != row.len() {
             PrimeclueErr::result(format!(
@@ -90,7 +94,7 @@ impl<T> Data<T> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
 pub struct InputShape {
     rows: usize,
     columns: usize,
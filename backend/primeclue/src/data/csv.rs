@@ -0,0 +1,376 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::PrimeclueErr;
+
+/// Which whitespace is removed from unquoted fields. Whitespace around quoted fields
+/// is always removed.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Copy, Clone, PartialEq, Default)]
+pub enum Trim {
+    /// Keep all whitespace
+    None,
+    /// Remove whitespace at the start and end of each record
+    #[default]
+    Lines,
+    /// Remove whitespace around each field
+    Fields,
+}
+
+/// How delimited text (CSV, TSV and such) is split into records and fields, following
+/// RFC 4180: fields may be quoted, quoted fields may contain separators, line breaks
+/// and doubled quotes. Field separator is given separately, e.g. by
+/// [`crate::data::importer::ClassRequest`].
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct CsvFormat {
+    /// Quote character, `None` if fields can't be quoted
+    pub quote: Option<char>,
+    /// Character that makes the next character literal, in addition to doubling the
+    /// quote in quoted fields. In unquoted fields it escapes separators, quotes and
+    /// itself, but not line breaks
+    pub escape: Option<char>,
+    pub trim: Trim,
+    /// Lines starting with this prefix are skipped
+    pub comment: Option<String>,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        CsvFormat { quote: Some('"'), escape: None, trim: Trim::default(), comment: None }
+    }
+}
+
+impl CsvFormat {
+    /// Splits `content` into records, skipping empty and comment lines
+    pub fn split(
+        &self,
+        content: &str,
+        separator: &str,
+    ) -> Result<Vec<Vec<String>>, PrimeclueErr> {
+        Ok(self.split_lines(content, separator)?.0)
+    }
+
+    /// Like [`CsvFormat::split`], also returns the line each record starts at, from 1
+    pub fn split_lines(
+        &self,
+        content: &str,
+        separator: &str,
+    ) -> Result<(Vec<Vec<String>>, Vec<usize>), PrimeclueErr> {
+        let mut tokenizer = Tokenizer::new(self, separator)?;
+        let mut records = Vec::new();
+        let mut lines = Vec::new();
+        for line in content.split('\n') {
+            if let Some(record) = tokenizer.line(line)? {
+                records.push(record);
+                lines.push(tokenizer.record_line());
+            }
+        }
+        tokenizer.finish()?;
+        Ok((records, lines))
+    }
+
+    /// First record of `content`, without reading the rest
+    pub fn first_record(
+        &self,
+        content: &str,
+        separator: &str,
+    ) -> Result<Option<Vec<String>>, PrimeclueErr> {
+        let mut tokenizer = Tokenizer::new(self, separator)?;
+        for line in content.split('\n') {
+            if let Some(record) = tokenizer.line(line)? {
+                return Ok(Some(record));
+            }
+        }
+        tokenizer.finish()?;
+        Ok(None)
+    }
+
+    /// Joins fields into a record, quoting fields that need it so that [`CsvFormat::split`]
+    /// reads them back
+    pub fn join<S: AsRef<str>>(&self, fields: &[S], separator: &str) -> String {
+        let fields = fields.iter().map(|field| {
+            let field = field.as_ref();
+            let field = match self.escape {
+                Some(escape) => field.replace(escape, &format!("{}{}", escape, escape)),
+                None => field.to_owned(),
+            };
+            match self.quote {
+                Some(quote) if needs_quotes(&field, separator, quote) => {
+                    let doubled = format!("{}{}", quote, quote);
+                    format!("{}{}{}", quote, field.replace(quote, &doubled), quote)
+                }
+                _ => field,
+            }
+        });
+        fields.collect::<Vec<_>>().join(separator)
+    }
+}
+
+fn needs_quotes(field: &str, separator: &str, quote: char) -> bool {
+    field.contains(separator)
+        || field.contains(quote)
+        || field.contains('\n')
+        || field.contains('\r')
+        || field.starts_with(char::is_whitespace)
+        || field.ends_with(char::is_whitespace)
+}
+
+/// Splits lines into records. Fed with lines one by one, it returns a record once all
+/// of its lines, more than one if a quoted field contains a line break, were given.
+#[derive(Debug)]
+pub struct Tokenizer<'a> {
+    format: &'a CsvFormat,
+    separator: &'a str,
+    line: usize,
+    record_line: usize,
+    record: Vec<String>,
+    quoted: Vec<bool>,
+    field: String,
+    /// Line and column where an open quoted field starts
+    open_quote: Option<(usize, usize)>,
+    after_quote: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(format: &'a CsvFormat, separator: &'a str) -> Result<Self, PrimeclueErr> {
+        if separator.is_empty() {
+            return PrimeclueErr::result("Field separator can't be empty".to_owned());
+        }
+        if format.quote.is_some_and(|q| separator.contains(q)) {
+            return PrimeclueErr::result("Quote can't be a part of separator".to_owned());
+        }
+        Ok(Tokenizer {
+            format,
+            separator,
+            line: 0,
+            record_line: 0,
+            record: vec![],
+            quoted: vec![],
+            field: String::new(),
+            open_quote: None,
+            after_quote: false,
+        })
+    }
+
+    /// Reads the next line, without line break. Returns a record if the line ends one.
+    pub fn line(&mut self, line: &str) -> Result<Option<Vec<String>>, PrimeclueErr> {
+        self.line += 1;
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if self.open_quote.is_none() {
+            let start = line.trim_start();
+            let comment = self.format.comment.as_deref().filter(|c| !c.is_empty());
+            if start.is_empty() || comment.is_some_and(|c| start.starts_with(c)) {
+                return Ok(None);
+            }
+            self.record_line = self.line;
+        }
+        let mut chars = line.char_indices().peekable();
+        let mut column = 0;
+        while let Some((index, c)) = chars.next() {
+            column += 1;
+            if self.open_quote.is_some() {
+                if Some(c) == self.format.quote {
+                    if chars.peek().map(|&(_, next)| next) == Some(c) {
+                        chars.next();
+                        column += 1;
+                        self.field.push(c);
+                    } else {
+                        self.open_quote = None;
+                        self.after_quote = true;
+                    }
+                } else if Some(c) == self.format.escape {
+                    // escaped line break is added with the next line
+                    if let Some((_, next)) = chars.next() {
+                        column += 1;
+                        self.field.push(next);
+                    }
+                } else {
+                    self.field.push(c);
+                }
+            } else if line[index..].starts_with(self.separator) {
+                for _ in 1..self.separator.chars().count() {
+                    chars.next();
+                    column += 1;
+                }
+                self.end_field();
+            } else if self.after_quote {
+                if !c.is_whitespace() {
+                    return PrimeclueErr::result(format!(
+                        "Unexpected '{}' after closing quote: line {}, column {}",
+                        c, self.line, column
+                    ));
+                }
+            } else if Some(c) == self.format.escape {
+                match chars.next() {
+                    Some((_, next)) => {
+                        column += 1;
+                        self.field.push(next);
+                    }
+                    None => {
+                        return PrimeclueErr::result(format!(
+                            "Escape at the end of unquoted field: line {}, column {}",
+                            self.line, column
+                        ))
+                    }
+                }
+            } else if Some(c) == self.format.quote && self.field.trim().is_empty() {
+                self.field.clear();
+                self.open_quote = Some((self.line, column));
+            } else {
+                self.field.push(c);
+            }
+        }
+        if self.open_quote.is_some() {
+            self.field.push('\n');
+            Ok(None)
+        } else {
+            self.end_field();
+            Ok(Some(self.end_record()))
+        }
+    }
+
+    /// Line the last record returned by [`Tokenizer::line`] starts at, from 1
+    pub fn record_line(&self) -> usize {
+        self.record_line
+    }
+
+    /// Checks that the last record is complete
+    pub fn finish(self) -> Result<(), PrimeclueErr> {
+        match self.open_quote {
+            Some((line, column)) => PrimeclueErr::result(format!(
+                "Quoted field is not closed: line {}, column {}",
+                line, column
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn end_field(&mut self) {
+        let mut field = std::mem::take(&mut self.field);
+        let quoted = self.after_quote;
+        if !quoted && self.format.trim == Trim::Fields {
+            field = field.trim().to_owned();
+        }
+        self.record.push(field);
+        self.quoted.push(quoted);
+        self.after_quote = false;
+    }
+
+    fn end_record(&mut self) -> Vec<String> {
+        let mut record = std::mem::take(&mut self.record);
+        let quoted = std::mem::take(&mut self.quoted);
+        if self.format.trim == Trim::Lines {
+            if !quoted[0] {
+                record[0] = record[0].trim_start().to_owned();
+            }
+            let last = record.len() - 1;
+            if !quoted[last] {
+                record[last] = record[last].trim_end().to_owned();
+            }
+        }
+        record
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::csv::{CsvFormat, Trim};
+
+    fn split(format: &CsvFormat, content: &str) -> Vec<Vec<String>> {
+        format.split(content, ",").unwrap()
+    }
+
+    #[test]
+    fn plain() {
+        let format = CsvFormat::default();
+        assert_eq!(
+            split(&format, " 1, a ,b \r\n\n  \n2,,c\n"),
+            vec![vec!["1", " a ", "b"], vec!["2", "", "c"]]
+        );
+        let fields = CsvFormat { trim: Trim::Fields, ..CsvFormat::default() };
+        assert_eq!(split(&fields, " 1, a ,b \n"), vec![vec!["1", "a", "b"]]);
+        let none = CsvFormat { trim: Trim::None, ..CsvFormat::default() };
+        assert_eq!(split(&none, " 1, a ,b \r\n"), vec![vec![" 1", " a ", "b "]]);
+        assert_eq!(format.split("1::2::3", "::").unwrap(), vec![vec!["1", "2", "3"]]);
+        assert_eq!(format.split("1\t2", "\t").unwrap(), vec![vec!["1", "2"]]);
+    }
+
+    #[test]
+    fn quoted() {
+        let format = CsvFormat::default();
+        let content = "\"a,b\", \"say \"\"hi\"\"\" ,\"line\r\nbreak\"\n\"\",x\n";
+        assert_eq!(
+            split(&format, content),
+            vec![vec!["a,b", "say \"hi\"", "line\nbreak"], vec!["", "x"]]
+        );
+        let escaped = CsvFormat { escape: Some('\\'), ..CsvFormat::default() };
+        assert_eq!(split(&escaped, "\"a\\\"b\\\\\",c"), vec![vec!["a\"b\\", "c"]]);
+        assert_eq!(split(&escaped, "\"a\\\nb\""), vec![vec!["a\nb"]]);
+        assert_eq!(split(&escaped, "a\\,b\\\\,\\\"c"), vec![vec!["a,b\\", "\"c"]]);
+        let unquoted = CsvFormat { quote: None, ..CsvFormat::default() };
+        assert_eq!(split(&unquoted, "\"a,b\""), vec![vec!["\"a", "b\""]]);
+        let single = CsvFormat { quote: Some('\''), ..CsvFormat::default() };
+        assert_eq!(split(&single, "'a,b',\"c\""), vec![vec!["a,b", "\"c\""]]);
+        // quote inside an unquoted field is a normal character
+        assert_eq!(split(&format, "5\"2,x"), vec![vec!["5\"2", "x"]]);
+    }
+
+    #[test]
+    fn comments() {
+        let format = CsvFormat { comment: Some("#".to_owned()), ..CsvFormat::default() };
+        assert_eq!(
+            split(&format, "# header\n1,2\n  # indented\n\"#3\",4"),
+            vec![vec!["1", "2"], vec!["#3", "4"]]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let format = CsvFormat::default();
+        let error = format.split("1,2\n3,\"open\n\n", ",").unwrap_err().to_string();
+        assert!(error.contains("line 2, column 3"), "{}", error);
+        let error = format.split("1,\"a\"b,2", ",").unwrap_err().to_string();
+        assert!(error.contains("'b'") && error.contains("line 1, column 6"), "{}", error);
+        let escaped = CsvFormat { escape: Some('\\'), ..CsvFormat::default() };
+        let error = escaped.split("1,2\\\n3", ",").unwrap_err().to_string();
+        assert!(error.contains("line 1, column 4"), "{}", error);
+        assert!(format.split("1,2", "").is_err());
+        assert!(format.split("1,2", "\"").is_err());
+    }
+
+    #[test]
+    fn lines() {
+        let format = CsvFormat::default();
+        let (records, lines) = format.split_lines("\n1,\"a\nb\"\n\n2,c", ",").unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(lines, vec![2, 5]);
+    }
+
+    #[test]
+    fn join() {
+        let format = CsvFormat::default();
+        let fields = ["1", "a,b", "say \"hi\"", "line\nbreak", " x", ""];
+        let joined = format.join(&fields, ",");
+        assert_eq!(joined, "1,\"a,b\",\"say \"\"hi\"\"\",\"line\nbreak\",\" x\",");
+        assert_eq!(split(&format, &joined), vec![fields.to_vec()]);
+        let escaped = CsvFormat { escape: Some('\\'), ..CsvFormat::default() };
+        let fields = ["a\\b", "c\\,d"];
+        assert_eq!(split(&escaped, &escaped.join(&fields, ",")), vec![fields.to_vec()]);
+    }
+}
@@ -17,21 +17,32 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::data::csv::CsvFormat;
+use crate::data::dictionary::Dictionary;
 use crate::data::outcome::Class;
+use crate::data::profile::{Profile, PROFILE_FILE_NAME};
 use crate::data::{Data, Input, InputShape, Outcome};
 use crate::error::PrimeclueErr;
 use crate::rand::GET_RNG;
 use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::{Precision, Value};
 use rand::seq::SliceRandom;
 use rand::Rng;
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::slice::Iter;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_VIEW_ID: AtomicU64 = AtomicU64::new(0);
+
+fn next_view_id() -> u64 {
+    NEXT_VIEW_ID.fetch_add(1, Ordering::Relaxed)
+}
 
 #[derive(Clone, PartialEq, Debug)]
-pub struct Point {
-    input: Input,
+pub struct Point<T: Value = f32> {
+    input: Input<T>,
     outcome: Outcome,
 }
 
@@ -41,28 +52,146 @@ pub struct Rewards {
     penalty: f32,
 }
 
-const DATA_FILE_NAME: &str = "data.ssd";
+/// Data file in data directory, its existence tells the directory holds data
+pub const DATA_FILE_NAME: &str = "data.ssd";
+const INDEX_FILE_NAME: &str = "index.ssd";
+
+/// Data points appended to a data set are saved in numbered segments next to
+/// `data.ssd`: `data_1.ssd`, `data_2.ssd` and so on.
+pub(crate) fn segment_file_name(segment: usize) -> String {
+    format!("data_{}.ssd", segment)
+}
+
+/// Loads serialized data in Primeclue format from disk, without deserializing it.
+/// [`Precision::peek`] tells which [`DataSet`] type it can be deserialized into.
+///
+/// # Arguments
+/// * `path` - A [`Path`] pointing to a data directory inside which `data.ssd` file must exist.
+pub fn load_serialized(path: &Path) -> Result<Serializator, PrimeclueErr> {
+    Ok(Serializator::load(&path.join(DATA_FILE_NAME))?)
+}
+
+/// Precision of data stored in data directory `path`, read from the start of `data.ssd`
+/// only. Fails if there's no data.
+pub fn stored_precision(path: &Path) -> Result<Precision, PrimeclueErr> {
+    Ok(Precision::peek(&Serializator::open(&path.join(DATA_FILE_NAME))?))
+}
+
+/// Data file and segment files of data stored in data directory `path`, in order
+pub(crate) fn stored_files(path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![path.join(DATA_FILE_NAME)];
+    files
+        .extend((1..).map(|s| path.join(segment_file_name(s))).take_while(|file| file.exists()));
+    files
+}
+
+/// Opens stored data or segment `file` and reads it up to its first point. Returns its
+/// class map and number of points, which `s` reads next.
+pub(crate) fn open_stored<T: Value>(
+    file: &Path,
+) -> Result<(Serializator, HashMap<Class, String>, usize), PrimeclueErr> {
+    let error = |e: String| format!("Unable to read {:?}: {}", file, e);
+    let mut s = Serializator::open(file)?;
+    Precision::deserialize_expecting::<T>(&mut s).map_err(error)?;
+    let classes = HashMap::deserialize(&mut s).map_err(error)?;
+    let len = usize::deserialize(&mut s).map_err(error)?;
+    Ok((s, classes, len))
+}
+
+/// Header of stored data and number of points of each class in its data file and every
+/// segment, so that a part of data can be read without reading all of it. Saved next to
+/// data and updated on append. Data saved before it was introduced has none.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StoredIndex {
+    pub(crate) header: Vec<String>,
+    /// Class counts of each file, in order of [`stored_files`]
+    pub(crate) counts: Vec<Vec<(Class, usize)>>,
+}
+
+impl StoredIndex {
+    /// Index of data in `path`, `None` if there's none or it doesn't count all files
+    pub(crate) fn read(path: &Path) -> Result<Option<Self>, PrimeclueErr> {
+        let file = path.join(INDEX_FILE_NAME);
+        if !file.exists() {
+            return Ok(None);
+        }
+        let mut s = Serializator::load(&file)?;
+        let index =
+            StoredIndex { header: Vec::deserialize(&mut s)?, counts: Vec::deserialize(&mut s)? };
+        Ok(Some(index).filter(|index| index.counts.len() == stored_files(path).len()))
+    }
+
+    /// Number of points of each class in all files
+    pub(crate) fn total_counts(&self) -> Vec<(Class, usize)> {
+        let mut total: Vec<(Class, usize)> = Vec::new();
+        for &(class, count) in self.counts.iter().flatten() {
+            match total.iter_mut().find(|(c, _)| *c == class) {
+                Some((_, n)) => *n += count,
+                None => total.push((class, count)),
+            }
+        }
+        total
+    }
+
+    fn save(&self, path: &Path) -> Result<usize, PrimeclueErr> {
+        let mut s = Serializator::new();
+        s.add_items(&[&self.header, &self.counts]);
+        s.save(path, INDEX_FILE_NAME)
+    }
+}
+
+/// Reads data stored in data directory `path`, including its appended segments, token
+/// by token and calls `f` with data's class map and each point. Neither files nor points
+/// are kept, so the whole data set never has to be in memory. Returns data's header.
+pub fn read_stored_points<T: Value, F>(
+    path: &Path,
+    mut f: F,
+) -> Result<Vec<String>, PrimeclueErr>
+where
+    F: FnMut(&HashMap<Class, String>, Point<T>) -> Result<(), PrimeclueErr>,
+{
+    let mut data_classes = None;
+    let mut header = None;
+    for file in stored_files(path) {
+        let (mut s, file_classes, len) = open_stored::<T>(&file)?;
+        let classes = data_classes.get_or_insert_with(|| file_classes.clone());
+        if *classes != file_classes {
+            return PrimeclueErr::result(format!("Class map of segment {:?} differs", file));
+        }
+        for _ in 0..len {
+            let point = Point::deserialize(&mut s)
+                .map_err(|e| format!("Invalid point in {:?}: {}", file, e))?;
+            f(classes, point)?;
+        }
+        if header.is_none() {
+            header =
+                Some(if s.peek_token().is_some() { Vec::deserialize(&mut s)? } else { vec![] });
+        }
+        s.finish().map_err(|e| format!("Unable to read {:?}: {}", file, e))?;
+    }
+    Ok(header.unwrap_or_default())
+}
 
-impl Point {
+impl<T: Value> Point<T> {
     #[must_use]
-    pub fn new(input: Input, outcome: Outcome) -> Point {
+    pub fn new(input: Input<T>, outcome: Outcome) -> Point<T> {
         Point { input, outcome }
     }
 
     #[must_use]
-    pub fn data(&self) -> (&Input, &Outcome) {
+    pub fn data(&self) -> (&Input<T>, &Outcome) {
         (&self.input, &self.outcome)
     }
 }
 
-impl Serializable for Point {
+impl<T: Value> Serializable for Point<T> {
     fn serialize(&self, s: &mut Serializator) {
         s.add_items(&[&self.input, &self.outcome])
     }
 }
 
-impl Deserializable for Point {
-    fn deserialize(s: &mut Serializator) -> Result<Point, String> {
+impl<T: Value> Deserializable for Point<T> {
+    fn deserialize(s: &mut Serializator) -> Result<Point<T>, String> {
         let input = Input::deserialize(s)?;
         let outcome = Outcome::deserialize(s)?;
         Ok(Point { input, outcome })
@@ -74,14 +203,15 @@ impl Deserializable for Point {
 /// This is done mainly for performance (CPU cache and such) as dealing with vectors
 /// is usually faster than individual cells
 #[derive(Debug, Clone)]
-pub struct DataView {
-    cells: Data<Vec<f32>>,
+pub struct DataView<T: Value = f32> {
+    id: u64,
+    cells: Data<Vec<T>>,
     outcomes: Vec<Outcome>,
     class_count: HashMap<Class, usize>,
     class_map: HashMap<Class, String>,
 }
 
-impl DataView {
+impl<T: Value> DataView<T> {
     pub fn random_guess_cost(&self) -> f32 {
         let count = 1_000;
         let mut sum = 0.0;
@@ -125,10 +255,21 @@ impl DataView {
         self.cells.input_shape()
     }
 
-    pub fn cells(&self) -> &Data<Vec<f32>> {
+    pub fn cells(&self) -> &Data<Vec<T>> {
         &self.cells
     }
 
+    pub(crate) fn cells_mut(&mut self) -> &mut Data<Vec<T>> {
+        self.id = next_view_id();
+        &mut self.cells
+    }
+
+    /// Identity of view's data, it changes whenever data does. Clones share it, as they
+    /// have the same data.
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
     pub fn outcomes(&self) -> &Vec<Outcome> {
         &self.outcomes
     }
@@ -136,18 +277,74 @@ impl DataView {
     pub fn class_map(&self) -> &HashMap<Class, String> {
         &self.class_map
     }
+
+    /// Number of data points in this view
+    pub fn len(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outcomes.is_empty()
+    }
+
+    /// Creates a new [`DataView`] containing only data points with given indexes, in
+    /// given order. Class map is left untouched.
+    pub fn select(&self, points: &[usize]) -> DataView<T> {
+        let input_shape = self.input_shape();
+        let mut cells = Data::new();
+        for row in 0..input_shape.rows() {
+            let mut row_data = Vec::with_capacity(input_shape.columns());
+            for column in 0..input_shape.columns() {
+                let values = self.cells.get(row, column);
+                row_data.push(points.iter().map(|&p| values[p]).collect());
+            }
+            cells.add_row(row_data).unwrap();
+        }
+        let mut outcomes = Vec::with_capacity(points.len());
+        let mut class_count = HashMap::new();
+        for &p in points {
+            let outcome = self.outcomes[p];
+            outcomes.push(outcome);
+            *class_count.entry(outcome.class()).or_insert(0) += 1;
+        }
+        DataView {
+            id: next_view_id(),
+            cells,
+            outcomes,
+            class_count,
+            class_map: self.class_map.clone(),
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Default, Clone)]
-pub struct DataSet {
-    points: Vec<Point>,
+pub struct DataSet<T: Value = f32> {
+    points: Vec<Point<T>>,
     classes: HashMap<Class, String>,
+    header: Vec<String>,
 }
 
-impl DataSet {
+impl<T: Value> DataSet<T> {
+    #[must_use]
+    pub fn new(classes: HashMap<Class, String>) -> DataSet<T> {
+        DataSet { points: vec![], classes, header: vec![] }
+    }
+
+    /// Names of imported columns, before categorical columns were encoded (see
+    /// [`crate::data::dictionary::Dictionary::column_names`]). Empty if data was
+    /// imported without a header.
+    #[must_use]
+    pub fn header(&self) -> &[String] {
+        &self.header
+    }
+
+    pub fn set_header(&mut self, header: Vec<String>) {
+        self.header = header;
+    }
+
     #[must_use]
-    pub fn new(classes: HashMap<Class, String>) -> DataSet {
-        DataSet { points: vec![], classes }
+    pub fn class_map(&self) -> &HashMap<Class, String> {
+        &self.classes
     }
 
     #[must_use]
@@ -167,9 +364,10 @@ impl DataSet {
     #[must_use]
     pub fn filter<F>(self, predicate: F) -> Self
     where
-        F: Fn(&Point) -> bool,
+        F: Fn(&Point<T>) -> bool,
     {
         let mut new = DataSet::new(self.classes.clone()); // TODO consider checking if all classes are present in `new`
+        new.header = self.header;
         for point in self.points {
             if predicate(&point) {
                 new.add_data_point(point).unwrap();
@@ -179,7 +377,7 @@ impl DataSet {
     }
 
     #[must_use]
-    pub fn into_view(self) -> DataView {
+    pub fn into_view(self) -> DataView<T> {
         // TODO change to Option, None if empty
         let mut cells = Data::new();
         let input_shape = self.input_shape();
@@ -203,10 +401,16 @@ impl DataSet {
             let count = class_count.remove(&outcome.class()).unwrap_or(0);
             class_count.insert(outcome.class(), count + 1);
         }
-        DataView { outcomes, cells, class_count, class_map: self.classes.clone() }
+        DataView {
+            id: next_view_id(),
+            outcomes,
+            cells,
+            class_count,
+            class_map: self.classes.clone(),
+        }
     }
 
-    pub fn add_data_point(&mut self, point: Point) -> Result<(), String> {
+    pub fn add_data_point(&mut self, point: Point<T>) -> Result<(), String> {
         let (input, _) = point.data();
         if !self.points.is_empty() {
             if input.input_shape() != self.input_shape() {
@@ -243,21 +447,30 @@ impl DataSet {
         self.points[0].input.input_shape() // TODO check for empty points
     }
 
+    /// Precision in which data points are stored and trees will be executed
+    #[must_use]
+    pub fn precision(&self) -> Precision {
+        T::PRECISION
+    }
+
     #[must_use]
-    pub fn iter(&self) -> Iter<'_, Point> {
+    pub fn iter(&self) -> Iter<'_, Point<T>> {
         self.points.iter()
     }
 
     /// Splits [`DataSet`] into three equal [`DataView`].
     /// No attempt is made to ensure equal class count in each [`DataView`]
-    pub fn into_3_views_split(self) -> (DataView, DataView, DataView) {
+    pub fn into_3_views_split(self) -> (DataView<T>, DataView<T>, DataView<T>) {
         let (s1, s2, s3) = self.split3();
         (s1.into_view(), s2.into_view(), s3.into_view())
     }
 
-    pub fn split_with_test_data_marker<P>(self, predicate: P) -> (DataView, DataView, DataView)
+    pub fn split_with_test_data_marker<P>(
+        self,
+        predicate: P,
+    ) -> (DataView<T>, DataView<T>, DataView<T>)
     where
-        P: Fn(&Point) -> bool,
+        P: Fn(&Point<T>) -> bool,
     {
         let mut training_set = DataSet::new(self.classes.clone());
         let mut verification_set = DataSet::new(self.classes.clone());
@@ -277,7 +490,7 @@ impl DataSet {
 
     /// Splits [`DataSet`] into two equal [`DataView`].
     /// No attempt is made to ensure equal class count in each [`DataView`]
-    pub fn into_2_views_split(self) -> (DataView, DataView) {
+    pub fn into_2_views_split(self) -> (DataView<T>, DataView<T>) {
         let (s1, s2) = self.split2();
         (s1.into_view(), s2.into_view())
     }
@@ -288,7 +501,7 @@ impl DataSet {
         self
     }
 
-    fn split3(mut self) -> (DataSet, DataSet, DataSet) {
+    fn split3(mut self) -> (DataSet<T>, DataSet<T>, DataSet<T>) {
         let mut training_set = DataSet::new(self.classes.clone());
         let mut verification_set = DataSet::new(self.classes.clone());
         let mut testing_set = DataSet::new(self.classes.clone());
@@ -307,7 +520,7 @@ impl DataSet {
         (training_set, verification_set, testing_set)
     }
 
-    fn split2(mut self) -> (DataSet, DataSet) {
+    fn split2(mut self) -> (DataSet<T>, DataSet<T>) {
         let mut training_set = DataSet::new(self.classes.clone());
         let mut verification_set = DataSet::new(self.classes.clone());
         let verification_points = self.points.split_off(self.points.len() / 2);
@@ -325,31 +538,145 @@ impl DataSet {
     ///
     /// # Arguments
     /// * `path` - A [`Path`] pointing to a data directory inside which `data.ssd` file must exist.
-    pub fn read_from_disk(path: &Path) -> Result<DataSet, PrimeclueErr> {
-        let mut s = Serializator::load(&path.join(DATA_FILE_NAME))?;
-        DataSet::deserialize(&mut s)
+    pub fn read_from_disk(path: &Path) -> Result<DataSet<T>, PrimeclueErr> {
+        let mut data = DataSet::from_serialized(&mut load_serialized(path)?)?;
+        data.read_segments(path)?;
+        Ok(data)
+    }
+
+    /// Adds data points from segments saved with [`DataSet::append_to_disk`] in data
+    /// directory `path`. [`DataSet::read_from_disk`] does it already, this is for data
+    /// deserialized from [`load_serialized`].
+    pub fn read_segments(&mut self, path: &Path) -> Result<(), PrimeclueErr> {
+        for segment in 1.. {
+            let file = path.join(segment_file_name(segment));
+            if !file.exists() {
+                break;
+            }
+            let data = DataSet::<T>::from_serialized(&mut Serializator::load(&file)?)?;
+            if data.classes != self.classes {
+                return PrimeclueErr::result(format!("Class map of segment {:?} differs", file));
+            }
+            for point in data.points {
+                self.add_data_point(point)
+                    .map_err(|e| format!("Invalid point in segment {:?}: {}", file, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves data points as a new segment of data set stored in `path`. Points must
+    /// have the same precision and [`InputShape`] as stored ones and their classes
+    /// must be in stored class map, labels are matched to stored classes.
+    /// Returns number of the segment.
+    pub fn append_to_disk<F>(&self, path: &Path, mut callback: F) -> Result<usize, PrimeclueErr>
+    where
+        F: FnMut(usize) -> Result<(), PrimeclueErr>,
+    {
+        if self.is_empty() {
+            return PrimeclueErr::result("No data points to append".to_owned());
+        }
+        let StoredLayout { classes, input_shape, header } =
+            StoredLayout::read::<T>(&mut load_serialized(path)?)
+                .map_err(|e| format!("Unable to read stored data: {}", e))?;
+        if let Some(input_shape) = input_shape {
+            if &input_shape != self.input_shape() {
+                return PrimeclueErr::result(format!(
+                    "Invalid input size, expecting {}, got: {}",
+                    input_shape,
+                    self.input_shape()
+                ));
+            }
+        }
+        if !header.is_empty() && !self.header.is_empty() {
+            let mismatch =
+                header.iter().zip(&self.header).position(|(stored, new)| stored != new);
+            if let Some(column) = mismatch {
+                return PrimeclueErr::result(format!(
+                    "Column {} is named '{}', expecting '{}'",
+                    column + 1,
+                    self.header[column],
+                    header[column]
+                ));
+            }
+        }
+        let mut segment_data = DataSet::new(classes);
+        segment_data.header = self.header.clone();
+        for point in &self.points {
+            let label = self.classes.get(&point.outcome.class()).map_or("", String::as_str);
+            let class = segment_data
+                .classes
+                .iter()
+                .find(|(_, stored)| *stored == label)
+                .map(|(class, _)| *class)
+                .ok_or_else(|| format!("Class '{}' not found in stored data", label))?;
+            let mut point = point.clone();
+            point.outcome = Outcome::new(class, point.outcome.reward(), point.outcome.penalty());
+            segment_data.add_data_point(point)?;
+        }
+        let index = StoredIndex::read(path)?;
+        let segment = (1..).find(|&s| !path.join(segment_file_name(s)).exists()).unwrap();
+        let mut serializator = Serializator::new();
+        segment_data.add_to_serializator(&mut callback, &mut serializator)?;
+        serializator.save(path, &segment_file_name(segment))?;
+        if let Some(mut index) = index {
+            index.counts.push(segment_data.class_counts());
+            index.save(path)?;
+        }
+        let profile = path.join(PROFILE_FILE_NAME);
+        if profile.exists() {
+            fs::remove_file(&profile)
+                .map_err(|e| format!("Unable to remove outdated profile: {}", e))?;
+        }
+        Ok(segment)
+    }
+
+    /// Deserializes data loaded with [`load_serialized`]. Fails if data was stored with
+    /// a different precision than `T`.
+    pub fn from_serialized(s: &mut Serializator) -> Result<DataSet<T>, PrimeclueErr> {
+        DataSet::deserialize(s)
             .map_err(|e| PrimeclueErr::from(format!("Unable to deserialize data: {}", e)))
     }
 
+    /// Saves data into data directory `path`, creating it if needed. Fails if the
+    /// directory already holds data, see [`crate::data::metadata::Metadata::archive`].
     pub fn save_to_disk<F>(&self, path: &Path, mut callback: F) -> Result<(), PrimeclueErr>
     where
         F: FnMut(usize) -> Result<(), PrimeclueErr>,
     {
-        if path.exists() {
+        if path.join(DATA_FILE_NAME).exists() {
             return PrimeclueErr::result(format!(
                 "Directory {} already exists",
                 path.to_str().unwrap()
             ));
         }
-        fs::create_dir(path).map_err(|e| {
-            format!("Unable to create directory {}, error: {}", path.to_str().unwrap(), e)
-        })?;
+        if !path.exists() {
+            fs::create_dir(path).map_err(|e| {
+                format!("Unable to create directory {}, error: {}", path.to_str().unwrap(), e)
+            })?;
+        }
         let mut serializator = Serializator::new();
         self.add_to_serializator(&mut callback, &mut serializator)?;
         serializator.save(&PathBuf::from(path), DATA_FILE_NAME)?;
+        StoredIndex { header: self.header.clone(), counts: vec![self.class_counts()] }
+            .save(path)?;
         Ok(())
     }
 
+    /// Number of points of each class, by class
+    pub(crate) fn class_counts(&self) -> Vec<(Class, usize)> {
+        let mut counts = self.classes.keys().map(|&class| (class, 0)).collect::<Vec<_>>();
+        counts.sort();
+        for point in &self.points {
+            if let Some((_, count)) =
+                counts.iter_mut().find(|(c, _)| *c == point.outcome.class())
+            {
+                *count += 1;
+            }
+        }
+        counts
+    }
+
     fn add_to_serializator<F>(
         &self,
         callback: &mut F,
@@ -358,12 +685,14 @@ impl DataSet {
     where
         F: FnMut(usize) -> Result<(), PrimeclueErr>,
     {
+        serializator.add(&T::PRECISION);
         serializator.add(&self.classes);
         serializator.add(&self.points.len());
         for (i, ds) in self.points.iter().enumerate() {
             serializator.add(ds);
             callback(i)?;
         }
+        serializator.add(&self.header);
         Ok(())
     }
 
@@ -372,17 +701,102 @@ impl DataSet {
             ds.outcome.set_reward_penalty(rewards.reward, rewards.penalty);
         });
     }
+
+    /// Statistics of input columns, classes and rewards
+    #[must_use]
+    pub fn profile(&self) -> Profile {
+        Profile::new(self)
+    }
+
+    /// Writes data points as comma separated text, one line per point: input values row
+    /// after row, then class name, reward and penalty. With `dictionary` the data was
+    /// imported with, categorical columns are written as their categories. First line
+    /// is the header, with `class`, `reward` and `penalty` added, if data has one or
+    /// inputs have more rows. Cells of later rows are named `name[row]`, as in formulas.
+    /// Import of single row data with class names in order of their classes (see
+    /// [`crate::data::importer::ClassRequest::class_names`]) gives the same data back.
+    pub fn export_csv(&self, dictionary: Option<&Dictionary>) -> Result<String, PrimeclueErr> {
+        let format = CsvFormat::default();
+        let mut lines = Vec::with_capacity(self.len() + 1);
+        let shape =
+            self.points.first().map_or(InputShape::new(1, 0), |p| *p.input.input_shape());
+        if let Some(dictionary) = dictionary {
+            if dictionary.encoded_columns() != shape.columns() {
+                return PrimeclueErr::result(format!(
+                    "Dictionary has {} columns, data has {}",
+                    dictionary.encoded_columns(),
+                    shape.columns()
+                ));
+            }
+        }
+        let columns = dictionary.map_or(shape.columns(), Dictionary::imported_columns);
+        if !self.header.is_empty() && self.header.len() != columns {
+            return PrimeclueErr::result(format!(
+                "Header has {} columns, data has {}: categorical columns need their dictionary",
+                self.header.len(),
+                columns
+            ));
+        }
+        let decode = |row: &[T]| match dictionary {
+            Some(dictionary) => dictionary.decode(row),
+            None => row.iter().map(ToString::to_string).collect(),
+        };
+        if !self.header.is_empty() || shape.rows() > 1 {
+            let mut names = if self.header.is_empty() {
+                vec![String::new(); columns]
+            } else {
+                self.header.clone()
+            };
+            for (column, name) in names.iter_mut().enumerate() {
+                if name.trim().is_empty() {
+                    *name = format!("col{}", column);
+                }
+            }
+            let mut header = Vec::with_capacity(shape.rows() * names.len() + 3);
+            for row in 0..shape.rows() {
+                header.extend(names.iter().map(|name| match row {
+                    0 => name.clone(),
+                    row => format!("{}[{}]", name, row),
+                }));
+            }
+            for name in &["class", "reward", "penalty"] {
+                let mut unique = (*name).to_owned();
+                let mut suffix = 1;
+                while header.contains(&unique) {
+                    suffix += 1;
+                    unique = format!("{}_{}", name, suffix);
+                }
+                header.push(unique);
+            }
+            lines.push(format.join(&header, ","));
+        }
+        for point in &self.points {
+            let shape = point.input.input_shape();
+            let mut cells = Vec::with_capacity(shape.rows() * shape.columns() + 3);
+            for row in 0..shape.rows() {
+                cells.extend(decode(&point.input.row(row)));
+            }
+            let class = point.outcome.class();
+            cells.push(self.classes.get(&class).cloned().unwrap_or_else(|| class.to_string()));
+            cells.push(point.outcome.reward().to_string());
+            cells.push(point.outcome.penalty().to_string());
+            lines.push(format.join(&cells, ","));
+        }
+        lines.push(String::new());
+        Ok(lines.join("\n"))
+    }
 }
 
-impl Serializable for DataSet {
+impl<T: Value> Serializable for DataSet<T> {
     fn serialize(&self, s: &mut Serializator) {
         let mut callback = |_| Ok(());
         self.add_to_serializator(&mut callback, s).unwrap();
     }
 }
 
-impl Deserializable for DataSet {
-    fn deserialize(s: &mut Serializator) -> Result<DataSet, String> {
+impl<T: Value> Deserializable for DataSet<T> {
+    fn deserialize(s: &mut Serializator) -> Result<DataSet<T>, String> {
+        Precision::deserialize_expecting::<T>(s)?;
         let classes = HashMap::deserialize(s)?;
         let len = usize::deserialize(s)?;
         let mut data = DataSet::new(classes);
@@ -390,19 +804,52 @@ impl Deserializable for DataSet {
             let point = Point::deserialize(s)?;
             data.add_data_point(point)?;
         }
+        // data saved before headers were introduced ends here
+        if s.peek_token().is_some() {
+            data.header = Vec::deserialize(s)?;
+        }
         Ok(data)
     }
 }
 
+/// Class map, input shape and header of serialized data.
+struct StoredLayout {
+    classes: HashMap<Class, String>,
+    input_shape: Option<InputShape>,
+    header: Vec<String>,
+}
+
+impl StoredLayout {
+    /// Reads layout of serialized data, without collecting its points.
+    fn read<T: Value>(s: &mut Serializator) -> Result<Self, String> {
+        Precision::deserialize_expecting::<T>(s)?;
+        let classes = HashMap::deserialize(s)?;
+        let len = usize::deserialize(s)?;
+        let mut input_shape = None;
+        for _ in 0..len {
+            let point = Point::<T>::deserialize(s)?;
+            input_shape.get_or_insert(*point.input.input_shape());
+        }
+        let header = if s.peek_token().is_some() { Vec::deserialize(s)? } else { vec![] };
+        Ok(StoredLayout { classes, input_shape, header })
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod test {
     use crate::data::data_set::{DataSet, Rewards};
+    use crate::data::dictionary::{CategoryEncoding, Dictionary};
+    use crate::data::importer::{build_data_set, ClassRequest, MissingValues};
     use crate::data::outcome::Class;
+    use crate::data::test::temp_path;
     use crate::data::{Input, Outcome, Point};
     use crate::rand::GET_RNG;
     use crate::serialization::serializator::test::test_serialization;
+    use crate::serialization::{Deserializable, Serializator};
+    use crate::value::{Precision, Value};
     use rand::Rng;
     use std::collections::HashMap;
+    use std::fs;
 
     #[test]
     fn serialize() {
@@ -410,6 +857,77 @@ pub(crate) mod test {
         test_serialization(data);
     }
 
+    #[test]
+    fn serialize_header() {
+        let mut data = create_simple_data(10);
+        data.set_header(vec!["first column".to_owned(), "".to_owned()]);
+        test_serialization(data.clone());
+
+        let mut s = Serializator::new();
+        s.add_items(&[&Precision::Single, &data.classes, &data.points]);
+        let old = DataSet::<f32>::deserialize(&mut s).unwrap();
+        assert!(old.header().is_empty());
+        assert_eq!(old.points, data.points);
+    }
+
+    #[test]
+    fn append_segments() {
+        let path = temp_path("append");
+        let mut data = create_simple_data(10);
+        data.set_header(vec!["a".to_owned(), "b".to_owned()]);
+        data.save_to_disk(&path, |_| Ok(())).unwrap();
+
+        // same labels numbered the other way round
+        let mut classes = HashMap::new();
+        classes.insert(Class::new(0), "TRUE".to_owned());
+        classes.insert(Class::new(1), "FALSE".to_owned());
+        let mut appended: DataSet = DataSet::new(classes.clone());
+        appended
+            .add_data_point(Point::new(
+                Input::from_vector(vec![vec![100.0, 1.0]]).unwrap(),
+                Outcome::new(Class::new(1), 1.0, -1.0),
+            ))
+            .unwrap();
+        assert_eq!(appended.append_to_disk(&path, |_| Ok(())).unwrap(), 1);
+        assert_eq!(appended.append_to_disk(&path, |_| Ok(())).unwrap(), 2);
+
+        let mut wrong_shape: DataSet = DataSet::new(classes.clone());
+        wrong_shape
+            .add_data_point(Point::new(
+                Input::from_vector(vec![vec![1.0]]).unwrap(),
+                Outcome::new(Class::new(1), 1.0, -1.0),
+            ))
+            .unwrap();
+        assert!(wrong_shape.append_to_disk(&path, |_| Ok(())).is_err());
+        classes.insert(Class::new(1), "MAYBE".to_owned());
+        let unknown_class = DataSet { classes, ..appended.clone() };
+        assert!(unknown_class.append_to_disk(&path, |_| Ok(())).is_err());
+        let mut renamed = appended.clone();
+        renamed.set_header(vec!["a".to_owned(), "c".to_owned()]);
+        assert!(renamed.append_to_disk(&path, |_| Ok(())).is_err());
+        renamed.set_header(vec!["a".to_owned(), "b".to_owned()]);
+        assert_eq!(renamed.append_to_disk(&path, |_| Ok(())).unwrap(), 3);
+
+        let read = DataSet::<f32>::read_from_disk(&path).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+        assert_eq!(read.len(), 13);
+        assert_eq!(read.points[..10], data.points[..]);
+        assert_eq!(read.points[11].input.get(0, 0), 100.0);
+        assert_eq!(read.points[11].outcome.class(), Class::new(0));
+    }
+
+    #[test]
+    fn serialize_double() {
+        let data = create_simple_data_as::<f64>(100);
+        assert_eq!(data.precision(), Precision::Double);
+        test_serialization(data.clone());
+
+        let mut s = Serializator::new();
+        s.add(&data);
+        assert_eq!(Precision::peek(&s), Precision::Double);
+        assert!(DataSet::<f32>::deserialize(&mut s).is_err());
+    }
+
     #[test]
     fn test_add_input_shape() {
         let mut classes = HashMap::new();
@@ -430,7 +948,7 @@ pub(crate) mod test {
 
     #[test]
     fn test_empty() {
-        let data = DataSet::new(HashMap::new());
+        let data: DataSet = DataSet::new(HashMap::new());
         assert_eq!(data.is_empty(), true);
     }
 
@@ -452,6 +970,18 @@ pub(crate) mod test {
         assert_eq!(view.cells.get(1, 2), &expected);
     }
 
+    #[test]
+    fn test_select() {
+        let view = create_multiclass_data().into_view();
+        let selected = view.select(&[8, 0, 3]);
+        assert_eq!(selected.len(), 3);
+        assert_eq!(selected.cells.get(0, 0), &vec![60.0, 1.0, 7.0]);
+        assert_eq!(selected.cells.get(1, 2), &vec![8.0, 6.0, 12.0]);
+        assert_eq!(selected.outcomes[0].class(), Class::new(2));
+        assert_eq!(selected.class_count(), 3);
+        assert_eq!(selected.class_map(), view.class_map());
+    }
+
     #[test]
     fn test_split_with_marker() {
         let data = create_simple_data(1_000);
@@ -556,14 +1086,18 @@ pub(crate) mod test {
     }
 
     pub(crate) fn create_simple_data(count: usize) -> DataSet {
+        create_simple_data_as(count)
+    }
+
+    pub(crate) fn create_simple_data_as<T: Value>(count: usize) -> DataSet<T> {
         let mut classes = HashMap::new();
         classes.insert(Class::new(0), "FALSE".to_owned());
         classes.insert(Class::new(1), "TRUE".to_owned());
         let mut data = DataSet::new(classes);
         let mut rng = GET_RNG();
         for i in 0..count {
-            let a = i as f32;
-            let b = rng.gen_range(0.0, count as f32);
+            let a = T::from_f64(i as f64);
+            let b = T::from_f64(rng.gen_range(0.0, count as f64));
             data.add_data_point(Point::new(
                 Input::from_vector(vec![vec![a, b]]).unwrap(),
                 if a > b {
@@ -657,4 +1191,54 @@ pub(crate) mod test {
         .unwrap();
         data
     }
+
+    #[test]
+    fn export_csv() {
+        let content =
+            "x,\"y, z\",label,r,p\n0.1,-2,\"b, c\",2,-1.5\n1e-7,3,a,1,-1\n5,6,a,1,-1\n";
+        let mut request = ClassRequest::simple_csv_request("export", content.to_owned(), true);
+        request.import_columns = vec![true, true, false, false, false];
+        request.class_column = 3;
+        request.custom_reward_penalty_columns = true;
+        request.reward_column = 4;
+        request.penalty_column = 5;
+        let (data, _, _, _) = build_data_set::<f32>(&request).unwrap();
+
+        let exported = data.export_csv(None).unwrap();
+        assert!(
+            exported.starts_with("x,\"y, z\",class,reward,penalty\n0.1,-2,\"b, c\",2,-1.5\n")
+        );
+        request.content = exported;
+        request.class_names = vec!["b, c".to_owned()];
+        let (imported, _, _, _) = build_data_set::<f32>(&request).unwrap();
+        assert_eq!(imported.header(), data.header());
+        assert_eq!(imported.class_map(), data.class_map());
+        assert!(imported.iter().eq(data.iter()));
+
+        let data = create_multiclass_data();
+        let exported = data.export_csv(None).unwrap();
+        assert_eq!(exported.lines().count(), data.len() + 1);
+        assert!(exported.starts_with(
+            "col0,col1,col2,col0[1],col1[1],col2[1],class,reward,penalty\n1,2,3,4,5,6,0,1,-1\n"
+        ));
+    }
+
+    #[test]
+    fn export_categories() {
+        let content = "colour,class,size\nred,a,1\ngreen,b,2\n,a,3\n";
+        let mut request = ClassRequest::simple_csv_request("export", content.to_owned(), true);
+        request.import_columns = vec![true, true, true];
+        request.class_column = 2;
+        request.categories = CategoryEncoding::OneHot;
+        request.missing_values = MissingValues::Keep;
+        let (data, dictionary, _, _) = build_data_set::<f32>(&request).unwrap();
+        assert!(data.export_csv(None).is_err());
+        let exported = data.export_csv(Some(&dictionary)).unwrap();
+        assert_eq!(
+            exported,
+            "colour,class,size,class_2,reward,penalty\nred,a,1,a,1,-1\ngreen,b,2,b,1,-1\n,a,3,a,1,-1\n"
+        );
+        let other = Dictionary::build(CategoryEncoding::Ordinal, &[vec!["x"]], &[true]);
+        assert!(data.export_csv(Some(&other)).is_err());
+    }
 }
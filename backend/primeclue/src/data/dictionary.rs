@@ -0,0 +1,342 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::importer::{parse_cell, MissingValues};
+use crate::error::PrimeclueErr;
+use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::Value;
+use std::path::Path;
+
+const DICTIONARY_FILE_NAME: &str = "dictionary.ssd";
+
+/// How imported columns with non-numeric (categorical) values are encoded.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Copy, Clone, PartialEq, Default)]
+pub enum CategoryEncoding {
+    /// Fail import
+    #[default]
+    Reject,
+    /// Replace category with its index in the dictionary
+    Ordinal,
+    /// One column per category, 1 for the value's category and 0 for others
+    OneHot,
+}
+
+impl Serializable for CategoryEncoding {
+    fn serialize(&self, s: &mut Serializator) {
+        match self {
+            CategoryEncoding::Reject => s.add_str("Reject"),
+            CategoryEncoding::Ordinal => s.add_str("Ordinal"),
+            CategoryEncoding::OneHot => s.add_str("OneHot"),
+        }
+    }
+}
+
+impl Deserializable for CategoryEncoding {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        let v = s.next_token()?;
+        match v.as_str() {
+            "Reject" => Ok(CategoryEncoding::Reject),
+            "Ordinal" => Ok(CategoryEncoding::Ordinal),
+            "OneHot" => Ok(CategoryEncoding::OneHot),
+            _ => Err(format!("Invalid category encoding: {}", v)),
+        }
+    }
+}
+
+/// Categories of imported columns, used to encode them into numbers the same way
+/// at import and at classification.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dictionary {
+    encoding: CategoryEncoding,
+    /// Categories in order of appearance for each imported column, `None` for numeric
+    /// columns
+    columns: Vec<Option<Vec<String>>>,
+}
+
+impl Dictionary {
+    /// Builds dictionary for imported columns of `data`. A column is categorical if any
+    /// of its non-empty cells is not a number. With [`CategoryEncoding::Reject`] all
+    /// columns are left numeric.
+    pub fn build(encoding: CategoryEncoding, data: &[Vec<&str>], use_columns: &[bool]) -> Self {
+        let imported = use_columns
+            .iter()
+            .enumerate()
+            .filter_map(|(column, &keep)| if keep { Some(column) } else { None })
+            .collect::<Vec<_>>();
+        let columns = imported
+            .into_iter()
+            .map(|column| match encoding {
+                CategoryEncoding::Reject => None,
+                _ => categories(data, column),
+            })
+            .collect();
+        Dictionary { encoding, columns }
+    }
+
+    /// Dictionary with given categories of each imported column, `None` for numeric ones
+    pub(crate) fn from_columns(
+        encoding: CategoryEncoding,
+        columns: Vec<Option<Vec<String>>>,
+    ) -> Self {
+        Dictionary { encoding, columns }
+    }
+
+    /// True if there are no categorical columns
+    pub fn is_empty(&self) -> bool {
+        self.columns.iter().all(Option::is_none)
+    }
+
+    /// Number of values an encoded row has
+    pub fn encoded_columns(&self) -> usize {
+        self.columns
+            .iter()
+            .map(|c| match (c, self.encoding) {
+                (Some(categories), CategoryEncoding::OneHot) => categories.len(),
+                _ => 1,
+            })
+            .sum()
+    }
+
+    /// Names of encoded columns for names of imported columns. One-hot encoded
+    /// categories are named `column=category`.
+    pub fn column_names(&self, header: &[String]) -> Vec<String> {
+        let mut names = Vec::with_capacity(self.encoded_columns());
+        for (column, categories) in self.columns.iter().enumerate() {
+            let name = header.get(column).map_or("", String::as_str);
+            match (categories, self.encoding) {
+                (Some(categories), CategoryEncoding::OneHot) => {
+                    names.extend(categories.iter().map(|c| format!("{}={}", name, c)))
+                }
+                _ => names.push(name.to_owned()),
+            }
+        }
+        names
+    }
+
+    /// Number of imported columns
+    pub fn imported_columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Decodes a row encoded by [`Dictionary::encode`] into cells of imported columns.
+    /// Categories not in the dictionary and missing ones are empty cells.
+    pub fn decode<T: Value>(&self, row: &[T]) -> Vec<String> {
+        let mut cells = Vec::with_capacity(self.columns.len());
+        let mut values = row.iter().copied();
+        for categories in &self.columns {
+            let cell = match (categories, self.encoding) {
+                (None, _) => values.next().map(|v| v.to_string()),
+                (Some(categories), CategoryEncoding::OneHot) => {
+                    let group = values.by_ref().take(categories.len()).collect::<Vec<_>>();
+                    let index = group.iter().position(|&v| v == T::one());
+                    index.and_then(|index| categories.get(index)).cloned()
+                }
+                (Some(categories), _) => values
+                    .next()
+                    .filter(|v| !v.is_nan())
+                    .and_then(|v| categories.get(v.as_f64() as usize))
+                    .cloned(),
+            };
+            cells.push(cell.unwrap_or_default());
+        }
+        cells
+    }
+
+    /// Encodes imported columns of a row that starts at `line` of content. Numeric
+    /// columns are parsed as in [`crate::data::importer::build_numbers_row`]. Categories
+    /// missing from the dictionary are encoded as NaN, so classifiers report them as
+    /// unknown.
+    pub fn encode<T: Value>(
+        &self,
+        use_columns: &[bool],
+        line: usize,
+        row: &[&str],
+        missing: MissingValues,
+    ) -> Result<Vec<T>, PrimeclueErr> {
+        let to_import = use_columns.iter().zip(row).enumerate().filter(|(_, (&k, _))| k);
+        let mut num_row = Vec::with_capacity(self.encoded_columns());
+        for ((column, (_, value)), categories) in to_import.zip(&self.columns) {
+            match categories {
+                None => num_row.push(parse_cell(value, line, column + 1, missing)?),
+                Some(categories) => {
+                    let value = value.trim();
+                    if value.is_empty() && missing == MissingValues::Reject {
+                        return PrimeclueErr::result(format!(
+                            "Missing category: line {}, column {}",
+                            line,
+                            column + 1
+                        ));
+                    }
+                    let index = categories.iter().position(|c| c == value);
+                    self.encode_category(index, categories.len(), &mut num_row);
+                }
+            }
+        }
+        Ok(num_row)
+    }
+
+    fn encode_category<T: Value>(&self, index: Option<usize>, count: usize, row: &mut Vec<T>) {
+        match (self.encoding, index) {
+            (CategoryEncoding::OneHot, Some(index)) => {
+                row.extend((0..count).map(|i| if i == index { T::one() } else { T::zero() }))
+            }
+            (CategoryEncoding::OneHot, None) => row.extend((0..count).map(|_| T::nan())),
+            (_, Some(index)) => row.push(T::from_f64(index as f64)),
+            (_, None) => row.push(T::nan()),
+        }
+    }
+
+    /// Non-empty values of categorical columns that are missing from the dictionary,
+    /// each reported once as `(imported column, value)`.
+    pub fn unseen<'a>(
+        &self,
+        use_columns: &[bool],
+        data: &[Vec<&'a str>],
+    ) -> Vec<(usize, &'a str)> {
+        let mut unseen = Vec::new();
+        for row in data {
+            let to_import =
+                use_columns
+                    .iter()
+                    .zip(row)
+                    .filter_map(|(&keep, &s)| if keep { Some(s.trim()) } else { None });
+            for (column, (value, categories)) in to_import.zip(&self.columns).enumerate() {
+                if let Some(categories) = categories {
+                    if !value.is_empty()
+                        && !categories.iter().any(|c| c == value)
+                        && !unseen.contains(&(column, value))
+                    {
+                        unseen.push((column, value));
+                    }
+                }
+            }
+        }
+        unseen
+    }
+
+    /// Saves dictionary into data directory, next to data file.
+    pub fn save_to_disk(&self, path: &Path) -> Result<usize, PrimeclueErr> {
+        let mut s = Serializator::new();
+        s.add(self);
+        s.save(path, DICTIONARY_FILE_NAME)
+    }
+
+    /// Reads dictionary from data directory. Data imported without categorical columns
+    /// has none.
+    pub fn read_from_disk(path: &Path) -> Result<Option<Self>, PrimeclueErr> {
+        let path = path.join(DICTIONARY_FILE_NAME);
+        if path.exists() {
+            let dictionary = Dictionary::deserialize(&mut Serializator::load(&path)?)?;
+            Ok(Some(dictionary))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+fn categories(data: &[Vec<&str>], column: usize) -> Option<Vec<String>> {
+    let values = data.iter().filter_map(|row| row.get(column)).map(|v| v.trim());
+    let mut categories = Vec::new();
+    let mut numeric = true;
+    for value in values.filter(|v| !v.is_empty()) {
+        numeric &= value.parse::<f64>().is_ok();
+        if !categories.iter().any(|c| c == value) {
+            categories.push(value.to_owned());
+        }
+    }
+    if numeric {
+        None
+    } else {
+        Some(categories)
+    }
+}
+
+impl Serializable for Dictionary {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_items(&[&self.encoding, &self.columns]);
+    }
+}
+
+impl Deserializable for Dictionary {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        let encoding = CategoryEncoding::deserialize(s)?;
+        let columns = Vec::deserialize(s)?;
+        Ok(Dictionary { encoding, columns })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::dictionary::{CategoryEncoding, Dictionary};
+    use crate::data::importer::MissingValues;
+    use crate::serialization::serializator::test::test_serialization;
+
+    fn data() -> Vec<Vec<&'static str>> {
+        vec![vec!["1.5", "red", "x"], vec!["2.0", "green", "y"], vec!["", " red", "z"]]
+    }
+
+    #[test]
+    fn ordinal() {
+        let dictionary =
+            Dictionary::build(CategoryEncoding::Ordinal, &data(), &[true, true, false]);
+        assert!(!dictionary.is_empty());
+        assert_eq!(dictionary.encoded_columns(), 2);
+        let keep = MissingValues::Keep;
+        let row = dictionary.encode::<f32>(&[true, true, false], 0, &data()[1], keep).unwrap();
+        assert_eq!(row, vec![2.0, 1.0]);
+        let row =
+            dictionary.encode::<f32>(&[true, true, false], 0, &["3", "blue"], keep).unwrap();
+        assert_eq!(row[0], 3.0);
+        assert!(row[1].is_nan());
+        let error = dictionary
+            .encode::<f32>(&[true, true], 4, &["3", ""], MissingValues::Reject)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 4, column 2"), "{}", error);
+        test_serialization(dictionary);
+    }
+
+    #[test]
+    fn one_hot() {
+        let use_columns = [false, true, true];
+        let dictionary = Dictionary::build(CategoryEncoding::OneHot, &data(), &use_columns);
+        assert_eq!(dictionary.encoded_columns(), 5);
+        let row = dictionary.encode::<f64>(&use_columns, 2, &data()[2], MissingValues::Keep);
+        assert_eq!(row.unwrap(), vec![1.0, 0.0, 0.0, 0.0, 1.0]);
+        let unseen =
+            dictionary.unseen(&use_columns, &[vec!["", "blue", "x"], vec!["", "blue", "w"]]);
+        assert_eq!(unseen, vec![(0, "blue"), (1, "w")]);
+        let header = vec!["color".to_owned(), "letter".to_owned()];
+        assert_eq!(
+            dictionary.column_names(&header)[..3],
+            ["color=red", "color=green", "letter=x"]
+        );
+        test_serialization(dictionary);
+    }
+
+    #[test]
+    fn reject() {
+        let dictionary =
+            Dictionary::build(CategoryEncoding::Reject, &data(), &[true, true, true]);
+        assert!(dictionary.is_empty());
+        assert!(dictionary
+            .encode::<f32>(&[true, true, true], 0, &data()[0], MissingValues::Keep)
+            .is_err());
+    }
+}
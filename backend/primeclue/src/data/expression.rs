@@ -25,33 +25,104 @@ type Expression = Vec<String>;
 type BoolProducer = Box<dyn Fn(f64, f64) -> bool>;
 type Data<'a> = [Vec<&'a str>];
 type ValueProducer = Box<dyn Fn(&Data<'_>, usize) -> Result<Option<f64>, String>>;
+type ConditionProducer = Box<dyn Fn(&Data<'_>, usize) -> Result<Option<bool>, String>>;
 type PreProcessor = fn(&mut Expression, data: &Data<'_>) -> Result<(), String>;
 
+/// Statistics of whole columns, replaced by their values before parsing
+const STATISTICS: [&str; 6] = ["AVERAGE", "MEDIAN", "MIN", "MAX", "STD", "PERCENTILE"];
+
+/// Parses expression giving class of a row. It's either a condition, e.g.
+/// `column 1 > number 2 and not ( cell -1 2 + column 2 < average 2 )`, giving `false`
+/// and `true` classes, or a bucketing of a value, e.g. `bucket ( column 3 , [ 10 , 20 ] )`,
+/// giving a class for each bucket, see [`OutcomeProducer::labels`]. Column statistics,
+/// like `average 2` or `percentile 90 2`, are calculated on `data`.
 pub fn parse(text: &str, data: &Data<'_>) -> Result<OutcomeProducer, String> {
     let input = text.to_owned();
-    let mut expression: Expression = text
-        .split(' ')
-        .map(str::trim)
-        .filter_map(|s| if s.is_empty() { None } else { Some(s.to_string()) })
-        .collect();
+    let mut expression = tokens(text);
     pre_process_expression(&mut expression, data)?;
-    if expression.len() <= 4 {
-        return Err(format!("Not enough tokens in expression: {:?}", expression));
+    check_empty_expression(&expression)?;
+    let row_offsets = cell_row_offsets(&expression);
+    let outcome = if expression[0].to_uppercase() == "BUCKET" {
+        bucket(&mut expression)?
+    } else {
+        Outcome::Condition(condition_producer(&mut expression)?)
+    };
+    if !expression.is_empty() {
+        return Err(format!("Not all text parsed {:?}", expression));
     }
-    let lhs = value_producer(&mut expression)?;
-    let bool_producer = bool_producer(&mut expression)?;
-    let rhs = value_producer(&mut expression)?;
+    Ok(OutcomeProducer { input, outcome, row_offsets })
+}
+
+/// Parses expression giving a number for a row, e.g. `column 1 / column 2`. Column
+/// statistics are calculated on `data`, see [`ValueExpression::text`].
+pub fn parse_value(text: &str, data: &Data<'_>) -> Result<ValueExpression, String> {
+    let mut expression = tokens(text);
+    pre_process_expression(&mut expression, data)?;
+    check_empty_expression(&expression)?;
+    let text = expression.join(" ");
+    let row_offsets = cell_row_offsets(&expression);
+    let producer = value_producer(&mut expression)?;
     if !expression.is_empty() {
         return Err(format!("Not all text parsed {:?}", expression));
     }
-    Ok(OutcomeProducer { lhs, rhs, bool_producer, input })
+    Ok(ValueExpression { text, producer, row_offsets })
+}
+
+/// Whether expression has statistics of whole columns, which need all data to be
+/// calculated
+pub fn uses_all_data(text: &str) -> bool {
+    tokens(text).iter().any(|token| STATISTICS.contains(&token.to_uppercase().as_str()))
+}
+
+/// Splits text at whitespace, brackets and commas are tokens on their own
+fn tokens(text: &str) -> Expression {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() || "()[],".contains(c) {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            token.push(c);
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Lowest and highest row offset of `cell`, `lag` and `mean` sources, 0 if there are
+/// none
+fn cell_row_offsets(expression: &Expression) -> (i32, i32) {
+    expression
+        .windows(2)
+        .filter_map(|pair| {
+            let offset = pair[1].parse::<i32>().ok()?;
+            match pair[0].to_uppercase().as_str() {
+                "CELL" => Some(offset),
+                "LAG" => Some(-offset),
+                "MEAN" => Some(1 - offset),
+                _ => None,
+            }
+        })
+        .fold((0, 0), |(min, max), offset| (min.min(offset), max.max(offset)))
 }
 
 pub struct OutcomeProducer {
     input: String,
-    lhs: ValueProducer,
-    rhs: ValueProducer,
-    bool_producer: BoolProducer,
+    outcome: Outcome,
+    row_offsets: (i32, i32),
+}
+
+enum Outcome {
+    Condition(ConditionProducer),
+    /// Value and bounds between buckets, ascending
+    Bucket(ValueProducer, Vec<f64>),
 }
 
 impl Debug for OutcomeProducer {
@@ -60,6 +131,144 @@ impl Debug for OutcomeProducer {
     }
 }
 
+/// Expression giving a number for a row, see [`parse_value`]
+pub struct ValueExpression {
+    text: String,
+    producer: ValueProducer,
+    row_offsets: (i32, i32),
+}
+
+impl Debug for ValueExpression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl ValueExpression {
+    /// Expression with averages and medians replaced by their numbers, so that it gives
+    /// the same values for other data
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Lowest and highest offset of rows, relative to the one the value is for, that
+    /// the expression looks at
+    pub fn row_offsets(&self) -> (i32, i32) {
+        self.row_offsets
+    }
+
+    /// Value for `row` of `data`, `None` if it's missing, e.g. refers to an empty cell
+    /// or a row before the first one
+    pub fn value(&self, data: &Data<'_>, row: usize) -> Result<Option<f64>, String> {
+        (self.producer)(data, row)
+    }
+}
+
+// 'a or b or ...'
+fn condition_producer(expression: &mut Expression) -> Result<ConditionProducer, String> {
+    let mut producer = conjunction_producer(expression)?;
+    while next_is(expression, "OR") {
+        expression.remove(0);
+        let lhs = producer;
+        let rhs = conjunction_producer(expression)?;
+        producer = Box::new(move |data, row| match lhs(data, row)? {
+            Some(true) => Ok(Some(true)),
+            left => match rhs(data, row)? {
+                Some(true) => Ok(Some(true)),
+                Some(false) => Ok(left),
+                None => Ok(None),
+            },
+        });
+    }
+    Ok(producer)
+}
+
+// 'a and b and ...'
+fn conjunction_producer(expression: &mut Expression) -> Result<ConditionProducer, String> {
+    let mut producer = negation_producer(expression)?;
+    while next_is(expression, "AND") {
+        expression.remove(0);
+        let lhs = producer;
+        let rhs = negation_producer(expression)?;
+        producer = Box::new(move |data, row| match lhs(data, row)? {
+            Some(false) => Ok(Some(false)),
+            left => match rhs(data, row)? {
+                Some(false) => Ok(Some(false)),
+                Some(true) => Ok(left),
+                None => Ok(None),
+            },
+        });
+    }
+    Ok(producer)
+}
+
+// 'not a', '( condition )' or 'value comparator value'
+fn negation_producer(expression: &mut Expression) -> Result<ConditionProducer, String> {
+    check_empty_expression(expression)?;
+    if next_is(expression, "NOT") {
+        expression.remove(0);
+        let producer = negation_producer(expression)?;
+        return Ok(Box::new(move |data, row| Ok(producer(data, row)?.map(|b| !b))));
+    }
+    if next_is(expression, "(") {
+        // brackets may also be a part of the first value, e.g. '( column 1 + number 2 ) > ...'
+        let mut inner = expression[1..].to_vec();
+        if let Ok(producer) = condition_producer(&mut inner) {
+            if next_is(&inner, ")") {
+                inner.remove(0);
+                *expression = inner;
+                return Ok(producer);
+            }
+        }
+    }
+    comparison_producer(expression)
+}
+
+fn comparison_producer(expression: &mut Expression) -> Result<ConditionProducer, String> {
+    let lhs = value_producer(expression)?;
+    check_empty_expression(expression)?;
+    let bool_producer = bool_producer(expression)?;
+    check_empty_expression(expression)?;
+    let rhs = value_producer(expression)?;
+    Ok(Box::new(move |data, row| match (lhs(data, row)?, rhs(data, row)?) {
+        (Some(left), Some(right)) => Ok(Some(bool_producer(left, right))),
+        _ => Ok(None),
+    }))
+}
+
+// 'bucket ( value , [ bound , bound ... ] )'
+fn bucket(expression: &mut Expression) -> Result<Outcome, String> {
+    expression.remove(0);
+    expect(expression, "(")?;
+    let value = value_producer(expression)?;
+    expect(expression, ",")?;
+    expect(expression, "[")?;
+    let mut bounds = vec![first_as_f64(expression)?];
+    while next_is(expression, ",") {
+        expression.remove(0);
+        bounds.push(first_as_f64(expression)?);
+    }
+    expect(expression, "]")?;
+    expect(expression, ")")?;
+    if bounds.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(format!("Bucket bounds must be ascending: {:?}", bounds));
+    }
+    Ok(Outcome::Bucket(value, bounds))
+}
+
+fn next_is(expression: &[String], token: &str) -> bool {
+    expression.first().is_some_and(|next| next.to_uppercase() == token)
+}
+
+fn expect(expression: &mut Expression, token: &str) -> Result<(), String> {
+    if next_is(expression, token) {
+        expression.remove(0);
+        Ok(())
+    } else {
+        Err(format!("Expected '{}', found: {:?}", token, expression.first()))
+    }
+}
+
 fn bool_producer(expression: &mut Expression) -> Result<BoolProducer, String> {
     match expression.remove(0).as_str() {
         "<=" => Ok(Box::new(|left, right| left <= right)),
@@ -110,10 +319,7 @@ fn number_value_producer(expression: &mut Expression) -> Result<ValueProducer, S
 fn column_value_producer(expression: &mut Expression) -> Result<ValueProducer, String> {
     let column = first_as_index(expression)?;
     column_check(column)?;
-    Ok(Box::new(move |data, row| {
-        let v = from_data(data, row, column as usize)?;
-        Ok(Some(v))
-    }))
+    Ok(Box::new(move |data, row| cell_value(data, row, column as usize)))
 }
 
 fn cell_value_producer(expression: &mut Expression) -> Result<ValueProducer, String> {
@@ -125,12 +331,143 @@ fn cell_value_producer(expression: &mut Expression) -> Result<ValueProducer, Str
         if actual_row < 0 || actual_row >= data.len() as i32 {
             Ok(None)
         } else {
-            let v = from_data(data, actual_row as usize, column as usize)?;
-            Ok(Some(v))
+            cell_value(data, actual_row as usize, column as usize)
+        }
+    }))
+}
+
+// 'lag 2 5' is the same as 'cell -2 5'
+fn lag_value_producer(expression: &mut Expression) -> Result<ValueProducer, String> {
+    let lag = first_as_index(expression)?;
+    if lag < 0 {
+        return Err(format!("Lag can't be negative, is: {}", lag));
+    }
+    expression.insert(0, format!("{}", -lag));
+    cell_value_producer(expression)
+}
+
+// mean of column in the row and rows before it
+fn mean_value_producer(expression: &mut Expression) -> Result<ValueProducer, String> {
+    let rows = first_as_index(expression)?;
+    if rows <= 0 {
+        return Err(format!("Number of rows for mean must be greater than 0, is: {}", rows));
+    }
+    let column = first_as_index(expression)?;
+    column_check(column)?;
+    let rows = rows as usize;
+    Ok(Box::new(move |data, row| {
+        if row + 1 < rows {
+            return Ok(None);
+        }
+        let mut sum = 0.0;
+        for r in row + 1 - rows..=row {
+            match cell_value(data, r, column as usize)? {
+                Some(v) => sum += v,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(sum / rows as f64))
+    }))
+}
+
+type Operation = fn(f64, f64) -> f64;
+
+fn operation_value_producer(
+    expression: &mut Expression,
+    operation: Operation,
+) -> Result<ValueProducer, String> {
+    let lhs = factor_producer(expression)?;
+    let rhs = factor_producer(expression)?;
+    Ok(combined(lhs, rhs, operation))
+}
+
+/// Result of `operation` on values of two producers, missing if any of them is missing
+/// or the result isn't finite
+fn combined(lhs: ValueProducer, rhs: ValueProducer, operation: Operation) -> ValueProducer {
+    Box::new(move |data, row| match (lhs(data, row)?, rhs(data, row)?) {
+        (Some(left), Some(right)) => Ok(Some(operation(left, right)).filter(|v| v.is_finite())),
+        _ => Ok(None),
+    })
+}
+
+fn date_value_producer(
+    expression: &mut Expression,
+    part: DatePart,
+) -> Result<ValueProducer, String> {
+    let column = first_as_index(expression)?;
+    column_check(column)?;
+    Ok(Box::new(move |data, row| {
+        let text = raw_cell(data, row, column as usize)?.trim();
+        if text.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(part.of(text)? as f64))
         }
     }))
 }
 
+#[derive(Copy, Clone)]
+enum DatePart {
+    Year,
+    Month,
+    Day,
+    Weekday,
+    Hour,
+    Minute,
+}
+
+impl DatePart {
+    /// Part of a date written as `YYYY-MM-DD` or `YYYY/MM/DD`, optionally followed by
+    /// space or `T` and `HH:MM`, with anything after minutes ignored
+    fn of(self, text: &str) -> Result<i64, String> {
+        let error = || format!("Unable to parse '{}' to a date", text);
+        let (date, time) = match text.find([' ', 'T']) {
+            Some(index) => (&text[..index], Some(text[index + 1..].trim())),
+            None => (text, None),
+        };
+        let date = date
+            .split(['-', '/'])
+            .map(|part| part.parse::<i64>().map_err(|_| error()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if date.len() != 3 || !(1..=12).contains(&date[1]) || !(1..=31).contains(&date[2]) {
+            return Err(error());
+        }
+        let (year, month, day) = (date[0], date[1], date[2]);
+        let time = match time {
+            Some(time) => {
+                let mut parts = time.splitn(3, ':');
+                let hour = parts.next().and_then(|p| p.parse::<i64>().ok());
+                let minute = parts.next().and_then(|p| p.get(..2)?.parse::<i64>().ok());
+                match (hour, minute) {
+                    (Some(hour), Some(minute)) if hour < 24 && minute < 60 => (hour, minute),
+                    _ => return Err(error()),
+                }
+            }
+            None => (0, 0),
+        };
+        Ok(match self {
+            DatePart::Year => year,
+            DatePart::Month => month,
+            DatePart::Day => day,
+            DatePart::Weekday => weekday(year, month, day),
+            DatePart::Hour => time.0,
+            DatePart::Minute => time.1,
+        })
+    }
+}
+
+/// Day of week, 1 for Monday to 7 for Sunday, of a date in proleptic Gregorian calendar
+fn weekday(year: i64, month: i64, day: i64) -> i64 {
+    // days since 1970-01-01, which was Thursday
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    (days + 3).rem_euclid(7) + 1
+}
+
 fn column_check(column: i32) -> Result<(), String> {
     if column <= 0 {
         Err(format!("Column must be greater than 0, is: {}", column))
@@ -139,32 +476,141 @@ fn column_check(column: i32) -> Result<(), String> {
     }
 }
 
-fn from_data(data: &Data<'_>, row: usize, column: usize) -> Result<f64, String> {
+/// Values of non-empty cells of a column
+fn known_values(data: &Data<'_>, column: usize) -> Result<Vec<f64>, String> {
+    let mut values = Vec::with_capacity(data.len());
+    for row in 0..data.len() {
+        if let Some(value) = cell_value(data, row, column)? {
+            values.push(value);
+        }
+    }
+    Ok(values)
+}
+
+/// Value of a cell, `None` if it's empty
+fn cell_value(data: &Data<'_>, row: usize, column: usize) -> Result<Option<f64>, String> {
+    let text = raw_cell(data, row, column)?;
+    if text.trim().is_empty() {
+        Ok(None)
+    } else {
+        parse_f64(text).map(Some)
+    }
+}
+
+fn raw_cell<'a>(data: &Data<'a>, row: usize, column: usize) -> Result<&'a str, String> {
     let actual_column = column - 1;
     if actual_column >= data[row].len() {
         Err(format!("Column {} does not exist", column))
     } else {
-        parse_f64(data[row][actual_column])
+        Ok(data[row][actual_column])
     }
 }
 
+// 'term + term - ...'
 fn value_producer(expression: &mut Expression) -> Result<ValueProducer, String> {
+    let mut producer = term_producer(expression)?;
+    loop {
+        let operation: Operation = match expression.first().map(String::as_str) {
+            Some("+") => |a, b| a + b,
+            Some("-") => |a, b| a - b,
+            _ => return Ok(producer),
+        };
+        expression.remove(0);
+        producer = combined(producer, term_producer(expression)?, operation);
+    }
+}
+
+// 'factor * factor / ...'
+fn term_producer(expression: &mut Expression) -> Result<ValueProducer, String> {
+    let mut producer = factor_producer(expression)?;
+    loop {
+        let operation: Operation = match expression.first().map(String::as_str) {
+            Some("*") => |a, b| a * b,
+            Some("/") => |a, b| a / b,
+            _ => return Ok(producer),
+        };
+        expression.remove(0);
+        producer = combined(producer, factor_producer(expression)?, operation);
+    }
+}
+
+// '( value )' or a source
+fn factor_producer(expression: &mut Expression) -> Result<ValueProducer, String> {
+    check_empty_expression(expression)?;
+    if next_is(expression, "(") {
+        expression.remove(0);
+        let producer = value_producer(expression)?;
+        expect(expression, ")")?;
+        Ok(producer)
+    } else {
+        source_producer(expression)
+    }
+}
+
+fn source_producer(expression: &mut Expression) -> Result<ValueProducer, String> {
     let name = expression.remove(0).to_uppercase();
     match name.as_str() {
         "CELL" => cell_value_producer(expression),
         "COLUMN" => column_value_producer(expression),
         "NUMBER" => number_value_producer(expression),
+        "LAG" => lag_value_producer(expression),
+        "MEAN" => mean_value_producer(expression),
+        "ADD" => operation_value_producer(expression, |a, b| a + b),
+        "SUB" => operation_value_producer(expression, |a, b| a - b),
+        "MUL" => operation_value_producer(expression, |a, b| a * b),
+        "DIV" => operation_value_producer(expression, |a, b| a / b),
+        "YEAR" => date_value_producer(expression, DatePart::Year),
+        "MONTH" => date_value_producer(expression, DatePart::Month),
+        "DAY" => date_value_producer(expression, DatePart::Day),
+        "WEEKDAY" => date_value_producer(expression, DatePart::Weekday),
+        "HOUR" => date_value_producer(expression, DatePart::Hour),
+        "MINUTE" => date_value_producer(expression, DatePart::Minute),
         _ => Err(format!("Invalid source type: {}", name)),
     }
 }
 
 impl OutcomeProducer {
+    /// Lowest and highest offset of rows, relative to the classified one, that the
+    /// expression looks at
+    pub fn row_offsets(&self) -> (i32, i32) {
+        self.row_offsets
+    }
+
+    /// Names of classes, `false` and `true` for conditions, ranges of values for buckets
+    pub fn labels(&self) -> Vec<String> {
+        match &self.outcome {
+            Outcome::Condition(_) => vec!["false".to_owned(), "true".to_owned()],
+            Outcome::Bucket(_, bounds) => {
+                let mut labels = vec![format!("<{}", bounds[0])];
+                for pair in bounds.windows(2) {
+                    labels.push(format!("{}..{}", pair[0], pair[1]));
+                }
+                labels.push(format!(">={}", bounds[bounds.len() - 1]));
+                labels
+            }
+        }
+    }
+
+    /// Whether the expression buckets a value rather than being a condition
+    pub fn is_bucket(&self) -> bool {
+        matches!(self.outcome, Outcome::Bucket(_, _))
+    }
+
+    /// Index of row's class in [`OutcomeProducer::labels`], `None` if it can't be told,
+    /// e.g. due to missing values
+    pub fn label(&self, data: &Data<'_>, row: usize) -> Result<Option<usize>, String> {
+        match &self.outcome {
+            Outcome::Condition(producer) => Ok(producer(data, row)?.map(usize::from)),
+            Outcome::Bucket(producer, bounds) => Ok(producer(data, row)?
+                .map(|value| bounds.iter().take_while(|&&bound| bound <= value).count())),
+        }
+    }
+
+    /// Value of a condition for a row. Bucketing has no boolean value.
     pub fn classify(&self, data: &Data<'_>, row: usize) -> Result<Option<bool>, String> {
-        let left = (self.lhs)(data, row)?;
-        let right = (self.rhs)(data, row)?;
-        match (left, right) {
-            (Some(left), Some(right)) => Ok(Some((self.bool_producer)(left, right))),
-            _ => Ok(None),
+        match &self.outcome {
+            Outcome::Condition(producer) => producer(data, row),
+            Outcome::Bucket(_, _) => Err(format!("'{}' is not a condition", self.input)),
         }
     }
 }
@@ -186,29 +632,70 @@ fn column_as_bool_preprocessor(expression: &mut Expression, _: &Data<'_>) -> Res
     Ok(())
 }
 
-// calculates average of column
-// changes '... average 5... ' in expression into 'number <average of column 5>'
-fn column_average_preprocessor(
+// calculates statistics of columns
+// changes '... average 5 ...' in expression into '... number <average of column 5> ...',
+// same for 'median', 'min', 'max', 'std' and 'percentile <0 to 100>'
+fn column_statistics_preprocessor(
     expression: &mut Expression,
     data: &Data<'_>,
 ) -> Result<(), String> {
-    for (av_index, token) in expression.iter().enumerate() {
-        if token.to_uppercase() == "AVERAGE" {
-            let val_index = next_token_index(expression, av_index)?;
+    let mut index = 0;
+    while index < expression.len() {
+        let name = expression[index].to_uppercase();
+        if STATISTICS.contains(&name.as_str()) {
+            let percentile = if name == "PERCENTILE" {
+                let percentile_index = next_token_index(expression, index)?;
+                let percentile = parse_f64(&expression[percentile_index])?;
+                if !(0.0..=100.0).contains(&percentile) {
+                    return Err(format!("Percentile must be from 0 to 100, is: {}", percentile));
+                }
+                expression.remove(percentile_index);
+                percentile
+            } else {
+                0.0
+            };
+            let val_index = next_token_index(expression, index)?;
+            if data.is_empty() {
+                return Err(format!("No data to calculate {}", name.to_lowercase()));
+            }
             let column = validate_column_index(&expression[val_index], data[0].len())?;
-            let mut sum = 0.0;
-            for i in 0..data.len() {
-                sum += from_data(data, i, column)?;
+            if name == "MEDIAN" && data.len() < 2 {
+                return Err("Not enough data to calculate median".to_string());
             }
-            let av = sum / data.len() as f64;
-            expression[av_index] = "number".to_string();
-            expression[val_index] = format!("{}", av);
-            return Ok(());
+            let mut values = known_values(data, column)?;
+            if values.is_empty() {
+                return Err(format!(
+                    "No values to calculate {} of column {}",
+                    name.to_lowercase(),
+                    column
+                ));
+            }
+            values.sort_unstable_by(|v1, v2| v1.partial_cmp(v2).unwrap_or(Ordering::Equal));
+            let count = values.len() as f64;
+            let mean = values.iter().sum::<f64>() / count;
+            let value = match name.as_str() {
+                "AVERAGE" => mean,
+                "MEDIAN" => percentile_of(&values, 50.0),
+                "MIN" => values[0],
+                "MAX" => values[values.len() - 1],
+                "STD" => (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count).sqrt(),
+                _ => percentile_of(&values, percentile),
+            };
+            expression[index] = "number".to_string();
+            expression[val_index] = format!("{}", value);
         }
+        index += 1;
     }
     Ok(())
 }
 
+/// Percentile of sorted values, interpolated linearly between closest ranks
+fn percentile_of(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = percentile / 100.0 * (sorted.len() - 1) as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64)
+}
+
 fn validate_column_index(txt: &str, length: usize) -> Result<usize, String> {
     let column =
         txt.parse::<usize>().map_err(|_| format!("Unable to parse '{}' to an integer", txt))?;
@@ -228,44 +715,9 @@ fn next_token_index(expression: &mut Vec<String>, index: usize) -> Result<usize,
     }
 }
 
-// calculates median of column
-// changes '... median 5... ' in expression into 'number <median of column 5>'
-fn column_median_preprocessor(
-    expression: &mut Expression,
-    data: &Data<'_>,
-) -> Result<(), String> {
-    for (token_index, token) in expression.iter().enumerate() {
-        if token.to_uppercase() == "MEDIAN" {
-            if data.len() < 2 {
-                return Err("Not enough data to calculate median".to_string());
-            }
-            let val_index = next_token_index(expression, token_index)?;
-            let column = validate_column_index(&expression[val_index], data[0].len())?;
-            let mut values = Vec::with_capacity(data.len());
-            for i in 0..data.len() {
-                values.push(from_data(data, i, column)?);
-            }
-            values.sort_unstable_by(|v1, v2| v1.partial_cmp(v2).unwrap_or(Ordering::Equal));
-            let med_index = values.len() / 2;
-            let median = if values.len() % 2 == 0 {
-                (values[med_index] + values[med_index - 1]) / 2.0
-            } else {
-                values[med_index]
-            };
-            expression[token_index] = "number".to_string();
-            expression[val_index] = format!("{}", median);
-            return Ok(());
-        }
-    }
-    Ok(())
-}
-
 fn pre_process_expression(expression: &mut Expression, data: &Data<'_>) -> Result<(), String> {
-    let pre_processors: Vec<PreProcessor> = vec![
-        column_as_bool_preprocessor,
-        column_average_preprocessor,
-        column_median_preprocessor,
-    ];
+    let pre_processors: Vec<PreProcessor> =
+        vec![column_as_bool_preprocessor, column_statistics_preprocessor];
     for pp in pre_processors {
         pp(expression, data)?;
     }
@@ -274,7 +726,7 @@ fn pre_process_expression(expression: &mut Expression, data: &Data<'_>) -> Resul
 
 #[cfg(test)]
 mod test {
-    use crate::data::expression::parse;
+    use crate::data::expression::{parse, parse_value, uses_all_data};
 
     #[test]
     fn check_cell_row_offset() {
@@ -291,6 +743,14 @@ mod test {
         assert_eq!(result, true);
     }
 
+    #[test]
+    fn row_offsets() {
+        let data = vec![vec!["10.0", "5.0"], vec!["15.0", "25.0"]];
+        assert_eq!(parse("column 1 < column 2", &data).unwrap().row_offsets(), (0, 0));
+        let producer = parse("cell -2 1 < cell 1 2", &data).unwrap();
+        assert_eq!(producer.row_offsets(), (-2, 1));
+    }
+
     #[test]
     fn min_expression_len() {
         assert_expression_error("1 2 3 4");
@@ -569,6 +1029,148 @@ mod test {
         assert_expression_error("cell 0 1 < tomorrow 1");
     }
 
+    #[test]
+    fn values() {
+        let data = vec![vec!["2.0", "8.0"], vec!["3.0", ""], vec!["5.0", "0"]];
+        let values = |e: &str| {
+            let expression = parse_value(e, &data).unwrap();
+            (0..data.len()).map(|row| expression.value(&data, row).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(values("div column 2 column 1"), vec![Some(4.0), None, Some(0.0)]);
+        assert_eq!(values("div column 1 column 2"), vec![Some(0.25), None, None]);
+        assert_eq!(values("sub column 1 lag 1 1"), vec![None, Some(1.0), Some(2.0)]);
+        assert_eq!(values("mean 2 1"), vec![None, Some(2.5), Some(4.0)]);
+        assert_eq!(
+            values("add mul number 2 column 1 number 1"),
+            vec![Some(5.0), Some(7.0), Some(11.0)]
+        );
+        assert_eq!(parse_value("mean 3 1", &data).unwrap().row_offsets(), (-2, 0));
+        assert_eq!(parse_value("lag 2 1", &data).unwrap().row_offsets(), (-2, 0));
+        assert!(parse_value("add column 1", &data).is_err());
+        assert!(parse_value("column 1 column 2", &data).is_err());
+        assert!(parse_value("mean 0 1", &data).is_err());
+        assert!(parse_value("lag -1 1", &data).is_err());
+        assert!(parse_value("", &data).is_err());
+    }
+
+    #[test]
+    fn resolved_average() {
+        let data = vec![vec!["1.0"], vec!["3.0"]];
+        let expression = parse_value("sub column 1 average 1", &data).unwrap();
+        assert_eq!(expression.text(), "sub column 1 number 2");
+        assert_eq!(expression.value(&data, 1).unwrap(), Some(1.0));
+    }
+
+    #[test]
+    fn date_parts() {
+        let data = vec![vec!["2020-02-29 13:45:10"], vec!["1999/12/31T07:05Z"], vec![""]];
+        let values = |e: &str| {
+            let expression = parse_value(e, &data).unwrap();
+            (0..data.len()).map(|row| expression.value(&data, row).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(values("year 1"), vec![Some(2020.0), Some(1999.0), None]);
+        assert_eq!(values("month 1"), vec![Some(2.0), Some(12.0), None]);
+        assert_eq!(values("day 1"), vec![Some(29.0), Some(31.0), None]);
+        assert_eq!(values("weekday 1"), vec![Some(6.0), Some(5.0), None]);
+        assert_eq!(values("hour 1"), vec![Some(13.0), Some(7.0), None]);
+        assert_eq!(values("minute 1"), vec![Some(45.0), Some(5.0), None]);
+        let invalid = vec![vec!["2020-13-01"], vec!["yesterday"], vec!["2020-01-01 25:00"]];
+        let expression = parse_value("year 1", &invalid).unwrap();
+        for row in 0..invalid.len() {
+            assert!(expression.value(&invalid, row).is_err());
+        }
+    }
+
+    #[test]
+    fn empty_cell_is_missing() {
+        let data = vec![vec!["", "2.0"]];
+        let producer = parse("column 1 < column 2", &data).unwrap();
+        assert_eq!(producer.classify(&data, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn logic() {
+        let data = vec![vec!["1.0", "5.0"], vec!["4.0", "2.0"], vec!["6.0", ""]];
+        let classes = |e: &str| {
+            let producer = parse(e, &data).unwrap();
+            (0..data.len()).map(|row| producer.classify(&data, row).unwrap()).collect::<Vec<_>>()
+        };
+        let e = "column 1 > number 2 and column 2 < number 3";
+        assert_eq!(classes(e), vec![Some(false), Some(true), None]);
+        let e = "column 1 > number 5 or column 2 > number 3";
+        assert_eq!(classes(e), vec![Some(true), Some(false), Some(true)]);
+        let e = "not column 1 > number 2 and column 2 > number 3";
+        assert_eq!(classes(e), vec![Some(true), Some(false), Some(false)]);
+        let e = "not ( column 1 > number 2 or column 2 > number 6 )";
+        assert_eq!(classes(e), vec![Some(true), Some(false), Some(false)]);
+        let e = "column 1 < number 2 or column 1 > number 3 and column 2 > number 3";
+        assert_eq!(classes(e), vec![Some(true), Some(false), None]);
+        assert_expression_error("( column 1 > number 2");
+        assert_expression_error("column 1 > number 2 and");
+        assert_expression_error("not");
+    }
+
+    #[test]
+    fn arithmetic() {
+        let data = vec![vec!["2.0", "8.0"], vec!["3.0", ""]];
+        let values = |e: &str| {
+            let expression = parse_value(e, &data).unwrap();
+            (0..data.len()).map(|row| expression.value(&data, row).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(values("column 1 + column 2 * number 2"), vec![Some(18.0), None]);
+        assert_eq!(values("( column 1 + column 2 ) * number 2"), vec![Some(20.0), None]);
+        assert_eq!(values("number 12 / column 1 - number 1"), vec![Some(5.0), Some(3.0)]);
+        assert_eq!(values("number 12 - column 1 - number 1"), vec![Some(9.0), Some(8.0)]);
+        assert_eq!(values("(column 1 * mul column 1 number -1)"), vec![Some(-4.0), Some(-9.0)]);
+
+        let producer = parse("(column 1 + number 1) * number 2 >= number 8", &data).unwrap();
+        assert_eq!(producer.classify(&data, 0).unwrap(), Some(false));
+        assert_eq!(producer.classify(&data, 1).unwrap(), Some(true));
+        assert!(parse_value("column 1 +", &data).is_err());
+        assert!(parse_value("( column 1 + number 2", &data).is_err());
+    }
+
+    #[test]
+    fn statistics() {
+        let data = vec![vec!["1.0", "4"], vec!["2.0", ""], vec!["4.0", "x"], vec!["9.0", "x"]];
+        let value = |e: &str| parse_value(e, &data).unwrap().text().to_owned();
+        assert_eq!(value("min 1"), "number 1");
+        assert_eq!(value("max 1 - min 1"), "number 9 - number 1");
+        assert_eq!(value("std 1"), "number 3.082207001484488");
+        assert_eq!(value("percentile 50 1"), "number 3");
+        assert_eq!(value("percentile 25 1"), "number 1.75");
+        assert_eq!(value("percentile 100 1 + average 1"), "number 9 + number 4");
+        assert!(parse_value("percentile 101 1", &data).is_err());
+        assert!(parse_value("percentile 50", &data).is_err());
+        assert!(parse_value("min 2", &data).is_err());
+        assert!(parse_value("max 1", &[]).is_err());
+        assert!(uses_all_data("column 1 > PERCENTILE 10 1"));
+        assert!(!uses_all_data("column 1 > number 1"));
+    }
+
+    #[test]
+    fn bucket() {
+        let data = vec![vec!["5"], vec!["10"], vec!["35"], vec!["50"], vec![""]];
+        let producer = parse("bucket(column 1, [10, 20, 50])", &data).unwrap();
+        assert_eq!(producer.labels(), ["<10", "10..20", "20..50", ">=50"]);
+        let labels = (0..data.len()).map(|row| producer.label(&data, row).unwrap());
+        assert_eq!(labels.collect::<Vec<_>>(), vec![Some(0), Some(1), Some(2), Some(3), None]);
+        assert!(producer.classify(&data, 0).is_err());
+
+        let producer = parse("bucket ( column 1 / number 10 , [ 2.5 ] )", &data).unwrap();
+        assert_eq!(producer.labels(), ["<2.5", ">=2.5"]);
+        assert_eq!(producer.label(&data, 2).unwrap(), Some(1));
+
+        let producer = parse("column 1 > number 10", &data).unwrap();
+        assert_eq!(producer.labels(), ["false", "true"]);
+        assert_eq!(producer.label(&data, 2).unwrap(), Some(1));
+
+        assert_expression_error("bucket ( column 1 , [ 20 , 10 ] )");
+        assert_expression_error("bucket ( column 1 , [ ] )");
+        assert_expression_error("bucket ( column 1 , [ 10 ]");
+        assert_expression_error("bucket ( column 1 , [ 10 ] ) > number 1");
+    }
+
     fn assert_expression_error(e: &str) {
         let data = vec![vec!["0.0"]];
         assert_eq!(parse(e, &data).is_err(), true);
@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::expression::parse_value;
+use crate::data::importer::as_str_rows;
+use crate::error::PrimeclueErr;
+use crate::serialization::{Deserializable, Serializable, Serializator};
+use std::path::Path;
+
+const FEATURES_FILE_NAME: &str = "features.ssd";
+
+/// Column computed at import from other columns of a row and rows before it, e.g.
+/// `div column 1 column 2` or `mean 7 3`, see [`parse_value`].
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
+pub struct Feature {
+    pub name: String,
+    pub expression: String,
+}
+
+/// Derived columns added after all columns of imported rows, in order, so a feature
+/// can use the ones before it. Stored with data set and its classifiers to add the same
+/// columns to data to classify, which must have the same columns as imported content.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Features {
+    features: Vec<Feature>,
+    header: Vec<String>,
+}
+
+impl Features {
+    /// Adds `features` to `rows`. Averages and medians in expressions are calculated on
+    /// `rows` and kept as numbers, see [`ValueExpression::text`].
+    ///
+    /// [`ValueExpression::text`]: crate::data::expression::ValueExpression::text
+    pub fn fit(features: &[Feature], rows: &mut [Vec<String>]) -> Result<Self, PrimeclueErr> {
+        let mut fitted = Vec::with_capacity(features.len());
+        for feature in features {
+            if feature.name.trim().is_empty() {
+                return PrimeclueErr::result("Derived column must have a name".to_owned());
+            }
+            let expression = parse_value(&feature.expression, &as_str_rows(rows))
+                .map_err(|e| format!("Derived column '{}': {}", feature.name, e))?;
+            let feature = Feature {
+                name: feature.name.trim().to_owned(),
+                expression: expression.text().to_owned(),
+            };
+            add_column(&feature, rows)?;
+            fitted.push(feature);
+        }
+        Ok(Features { features: fitted, header: vec![] })
+    }
+
+    /// Adds the same columns as [`Features::fit`] did
+    pub fn apply(&self, rows: &mut [Vec<String>]) -> Result<(), PrimeclueErr> {
+        for feature in &self.features {
+            add_column(feature, rows)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// Derived columns with expressions as fitted
+    pub fn features(&self) -> &[Feature] {
+        &self.features
+    }
+
+    /// Header of content features were fitted on, without derived columns. Data to
+    /// classify with a header is put in its order before derived columns are added.
+    /// Empty if content had no header.
+    pub fn header(&self) -> &[String] {
+        &self.header
+    }
+
+    pub fn set_header(&mut self, header: Vec<String>) {
+        self.header = header;
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.features.iter().map(|f| f.name.clone()).collect()
+    }
+
+    /// Flags of columns to import, `columns` for content followed by derived columns
+    pub fn import_columns(&self, columns: &[bool]) -> Vec<bool> {
+        let mut columns = columns.to_vec();
+        columns.resize(columns.len() + self.features.len(), true);
+        columns
+    }
+
+    pub fn save_to_disk(&self, path: &Path) -> Result<usize, PrimeclueErr> {
+        let mut s = Serializator::new();
+        s.add(self);
+        s.save(path, FEATURES_FILE_NAME)
+    }
+
+    /// Reads features from data directory. Data imported without derived columns has
+    /// none.
+    pub fn read_from_disk(path: &Path) -> Result<Option<Self>, PrimeclueErr> {
+        let path = path.join(FEATURES_FILE_NAME);
+        if path.exists() {
+            Ok(Some(Features::deserialize(&mut Serializator::load(&path)?)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Appends values of `feature` to `rows`, empty if a value is missing
+fn add_column(feature: &Feature, rows: &mut [Vec<String>]) -> Result<(), PrimeclueErr> {
+    let values = {
+        let data = as_str_rows(rows);
+        if let Some((row, record)) =
+            data.iter().enumerate().find(|(_, record)| record.len() != data[0].len())
+        {
+            return PrimeclueErr::result(format!(
+                "Invalid row {} length for derived columns: found {}, expected {}",
+                row + 1,
+                record.len(),
+                data[0].len()
+            ));
+        }
+        let expression = parse_value(&feature.expression, &data)?;
+        let mut values = Vec::with_capacity(data.len());
+        for row in 0..data.len() {
+            let value = expression.value(&data, row).map_err(|e| {
+                format!("Derived column '{}': row {}: {}", feature.name, row + 1, e)
+            })?;
+            values.push(value.map_or_else(String::new, |v| format!("{}", v)));
+        }
+        values
+    };
+    for (row, value) in rows.iter_mut().zip(values) {
+        row.push(value);
+    }
+    Ok(())
+}
+
+impl Serializable for Features {
+    fn serialize(&self, s: &mut Serializator) {
+        let features = self
+            .features
+            .iter()
+            .map(|f| (f.name.clone(), f.expression.clone()))
+            .collect::<Vec<_>>();
+        s.add_items(&[&features, &self.header]);
+    }
+}
+
+impl Deserializable for Features {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        let features = Vec::<(String, String)>::deserialize(s)?
+            .into_iter()
+            .map(|(name, expression)| Feature { name, expression })
+            .collect();
+        let header = Vec::deserialize(s)?;
+        Ok(Features { features, header })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::features::{Feature, Features};
+    use crate::serialization::serializator::test::test_serialization;
+
+    fn feature(name: &str, expression: &str) -> Feature {
+        Feature { name: name.to_owned(), expression: expression.to_owned() }
+    }
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|r| r.iter().map(|&s| s.to_owned()).collect()).collect()
+    }
+
+    #[test]
+    fn fit_and_apply() {
+        let features = [
+            feature("ratio", "div column 1 column 2"),
+            feature("centered", "sub column 3 average 3"),
+            feature("previous", "lag 1 4"),
+        ];
+        let mut training = rows(&[&["1", "2"], &["3", "0"], &["4", "4"]]);
+        let fitted = Features::fit(&features, &mut training).unwrap();
+        assert_eq!(
+            training,
+            rows(&[
+                &["1", "2", "0.5", "-0.25", ""],
+                &["3", "0", "", "", "-0.25"],
+                &["4", "4", "1", "0.25", ""]
+            ])
+        );
+        assert_eq!(fitted.names(), vec!["ratio", "centered", "previous"]);
+        assert_eq!(fitted.import_columns(&[true, false]), vec![true, false, true, true, true]);
+
+        // average of training data is kept
+        let mut other = rows(&[&["2", "1"]]);
+        fitted.apply(&mut other).unwrap();
+        assert_eq!(other, rows(&[&["2", "1", "2", "1.25", ""]]));
+        test_serialization(fitted.clone());
+        let mut fitted = fitted;
+        fitted.set_header(vec!["a".to_owned(), "b".to_owned()]);
+        test_serialization(fitted);
+    }
+
+    #[test]
+    fn errors() {
+        let mut data = rows(&[&["1", "2"], &["3"]]);
+        assert!(Features::fit(&[feature("a", "column 1")], &mut data).is_err());
+        let mut data = rows(&[&["1", "x"]]);
+        assert!(Features::fit(&[feature("a", "column 2")], &mut data).is_err());
+        assert!(Features::fit(&[feature("a", "column")], &mut data).is_err());
+        assert!(Features::fit(&[feature(" ", "column 1")], &mut data).is_err());
+    }
+}
@@ -0,0 +1,567 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::csv::Tokenizer;
+use crate::data::data_set::StoredIndex;
+use crate::data::dictionary::{CategoryEncoding, Dictionary};
+use crate::data::expression::{parse, uses_all_data};
+use crate::data::formats::DataFormat;
+use crate::data::importer::{
+    as_str_rows, build_rows, known_classes, ClassProducer, ClassRequest, MissingValues,
+};
+use crate::data::imputation::Imputation;
+use crate::data::outcome::Class;
+use crate::error::PrimeclueErr;
+use crate::value::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Rows converted into data points at once, each batch is saved as one data segment
+const BATCH_ROWS: usize = 100_000;
+
+/// Import of a file, or of all files in a directory in name order, that doesn't need
+/// the file in memory. Files are read record by record a few times: to find categorical
+/// columns and classes, to collect categories, to calculate means of columns with
+/// missing values and finally to convert rows into data points in batches.
+/// Request's `content` is ignored.
+///
+/// Class expressions with `average` or `median` and [`MissingValues::Median`] need
+/// all data at once and are not supported. Neither are formats other than delimited
+/// text.
+#[derive(Debug)]
+pub struct FileImport<'a> {
+    request: &'a ClassRequest,
+    files: Vec<PathBuf>,
+    header: Vec<String>,
+    rows: usize,
+    dictionary: Dictionary,
+    class_producer: ClassProducer,
+    imputation: Imputation,
+    batch_rows: usize,
+}
+
+impl<'a> FileImport<'a> {
+    /// Reads file or directory `path` to prepare its import with `request`
+    pub fn scan(request: &'a ClassRequest, path: &Path) -> Result<Self, PrimeclueErr> {
+        let r = request;
+        if r.missing_values == MissingValues::Median {
+            return PrimeclueErr::result(
+                "Median of missing values is not supported for file import".to_owned(),
+            );
+        }
+        if r.format != DataFormat::Delimited {
+            return PrimeclueErr::result(
+                "Only delimited text is supported for file import".to_owned(),
+            );
+        }
+        if !r.features.is_empty() {
+            return PrimeclueErr::result(
+                "Derived columns are not supported for file import".to_owned(),
+            );
+        }
+        if r.group.is_some() {
+            return PrimeclueErr::result(
+                "Grouping rows by entity is not supported for file import".to_owned(),
+            );
+        }
+        let files = list_files(path)?;
+        let expression = if r.expression.is_empty() {
+            None
+        } else {
+            if uses_all_data(&r.expression) {
+                return PrimeclueErr::result(
+                    "Column statistics in class expression are not supported for file import"
+                        .to_owned(),
+                );
+            }
+            Some(parse(&r.expression, &[])?)
+        };
+
+        let imported_columns = r.import_columns.iter().filter(|&&keep| keep).count();
+        let mut numeric = vec![true; imported_columns];
+        let mut classes = known_classes(&r.class_names);
+        let mut rows = 0;
+        let header_record = for_each_record(r, &files, |record, _| {
+            let row = record.iter().map(String::as_str).collect::<Vec<_>>();
+            for (column, value) in imported(r, &row).enumerate() {
+                if !value.is_empty() && value.parse::<f64>().is_err() {
+                    numeric[column] = false;
+                }
+            }
+            if expression.is_none() {
+                let column = r.class_column - 1;
+                let v = row.get(column).ok_or_else(|| {
+                    PrimeclueErr::from(format!("No column {} in row {}", column, rows))
+                })?;
+                if !v.is_empty() && !classes.contains_key(*v) {
+                    classes.insert(v.to_string(), Class::new(classes.len() as u16));
+                }
+            }
+            rows += 1;
+            Ok(())
+        })?;
+        let header = header_record.map_or_else(Vec::new, |record| {
+            let row = record.iter().map(String::as_str).collect::<Vec<_>>();
+            imported(r, &row).map(str::to_owned).collect()
+        });
+        let class_producer = match expression {
+            Some(expression) => ClassProducer::Expression(expression),
+            None => ClassProducer::Column(r.class_column - 1, classes),
+        };
+
+        let dictionary = if r.categories == CategoryEncoding::Reject {
+            Dictionary::from_columns(r.categories, vec![None; imported_columns])
+        } else {
+            collect_categories(r, &files, &numeric)?
+        };
+        let imputation = Imputation::from_replacements(match r.missing_values {
+            MissingValues::Constant(value) => vec![Some(value); dictionary.encoded_columns()],
+            MissingValues::Mean => means(r, &files, &dictionary)?,
+            _ => vec![],
+        });
+        Ok(FileImport {
+            request,
+            files,
+            header,
+            rows,
+            dictionary,
+            class_producer,
+            imputation,
+            batch_rows: BATCH_ROWS,
+        })
+    }
+
+    /// Number of imported rows in all files, without headers
+    #[must_use]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Dictionary categorical columns are encoded with
+    #[must_use]
+    pub fn dictionary(&self) -> &Dictionary {
+        &self.dictionary
+    }
+
+    /// Replacements of missing values in encoded columns
+    #[must_use]
+    pub fn imputation(&self) -> &Imputation {
+        &self.imputation
+    }
+
+    /// Converts rows into data points and saves them into new data directory `path`,
+    /// see [`crate::data::data_set::DataSet::append_to_disk`]. `callback` gets the
+    /// number of rows converted so far. Returns number of saved data points.
+    pub fn save_to_disk<T: Value, F>(
+        &self,
+        path: &Path,
+        callback: F,
+    ) -> Result<usize, PrimeclueErr>
+    where
+        F: FnMut(usize) -> Result<(), PrimeclueErr>,
+    {
+        if path.exists() {
+            return PrimeclueErr::result(format!("Directory {:?} already exists", path));
+        }
+        // a directory left behind by a failed import would be taken for data
+        self.write_to_disk::<T, _>(path, callback).or_else(|e| {
+            if path.exists() {
+                fs::remove_dir_all(path)
+                    .map_err(|e| format!("Unable to remove {:?}: {}", path, e))?;
+            }
+            Err(e)
+        })
+    }
+
+    fn write_to_disk<T: Value, F>(
+        &self,
+        path: &Path,
+        mut callback: F,
+    ) -> Result<usize, PrimeclueErr>
+    where
+        F: FnMut(usize) -> Result<(), PrimeclueErr>,
+    {
+        let batch_rows = self.batch_rows;
+        let (before, after) = self.class_producer.context();
+        let before = before.max(self.request.rows_per_set.saturating_sub(1));
+        // own rows of the next batch start at `start`, earlier rows are kept as context
+        let mut window: Vec<Vec<String>> = Vec::new();
+        let mut numbers: Vec<Vec<T>> = Vec::new();
+        let mut start = 0;
+        let mut first_row = 0;
+        let mut saved = 0;
+        for_each_record(self.request, &self.files, |record, line| {
+            let row = record.iter().map(String::as_str).collect::<Vec<_>>();
+            let r = self.request;
+            let mut row =
+                self.dictionary.encode(&r.import_columns, line, &row, r.missing_values)?;
+            self.imputation.apply_row(&mut row);
+            numbers.push(row);
+            window.push(record);
+            if window.len() == start + batch_rows + after {
+                let own = start..start + batch_rows;
+                saved += self.save_batch(path, &window, &numbers, own, first_row)?;
+                callback(first_row + start + batch_rows)?;
+                let dropped = (start + batch_rows).saturating_sub(before);
+                window.drain(..dropped);
+                numbers.drain(..dropped);
+                first_row += dropped;
+                start = start + batch_rows - dropped;
+            }
+            Ok(())
+        })?;
+        if start < window.len() {
+            let own = start..window.len();
+            saved += self.save_batch(path, &window, &numbers, own, first_row)?;
+            callback(self.rows)?;
+        }
+        if saved == 0 {
+            return PrimeclueErr::result("No data points imported".to_owned());
+        }
+        if let Some(index) = StoredIndex::read(path)? {
+            self.class_producer.check_buckets(&index.total_counts())?;
+        }
+        Ok(saved)
+    }
+
+    fn save_batch<T: Value>(
+        &self,
+        path: &Path,
+        window: &[Vec<String>],
+        numbers: &[Vec<T>],
+        own: Range<usize>,
+        first_row: usize,
+    ) -> Result<usize, PrimeclueErr> {
+        let r = self.request;
+        let data = as_str_rows(window);
+        let mut data_set = build_rows(r, &data, numbers, own, first_row, &self.class_producer)?;
+        if data_set.is_empty() {
+            return Ok(0);
+        }
+        data_set.set_header(self.header.clone());
+        if path.exists() {
+            data_set.append_to_disk(path, |_| Ok(()))?;
+        } else {
+            data_set.save_to_disk(path, |_| Ok(()))?;
+        }
+        Ok(data_set.len())
+    }
+}
+
+/// Categories of non-numeric imported columns, in order of appearance
+fn collect_categories(
+    r: &ClassRequest,
+    files: &[PathBuf],
+    numeric: &[bool],
+) -> Result<Dictionary, PrimeclueErr> {
+    let mut columns = numeric
+        .iter()
+        .map(|&numeric| if numeric { None } else { Some((Vec::new(), HashSet::new())) })
+        .collect::<Vec<Option<(Vec<String>, HashSet<String>)>>>();
+    if columns.iter().any(Option::is_some) {
+        for_each_record(r, files, |record, _| {
+            let row = record.iter().map(String::as_str).collect::<Vec<_>>();
+            for (value, column) in imported(r, &row).zip(&mut columns) {
+                if let Some((categories, seen)) = column {
+                    if !value.is_empty() && seen.insert(value.to_owned()) {
+                        categories.push(value.to_owned());
+                    }
+                }
+            }
+            Ok(())
+        })?;
+    }
+    let columns = columns.into_iter().map(|c| c.map(|(categories, _)| categories)).collect();
+    Ok(Dictionary::from_columns(r.categories, columns))
+}
+
+/// Means of known values of each encoded column
+fn means(
+    r: &ClassRequest,
+    files: &[PathBuf],
+    dictionary: &Dictionary,
+) -> Result<Vec<Option<f64>>, PrimeclueErr> {
+    let mut sums = vec![(0.0, 0); dictionary.encoded_columns()];
+    for_each_record(r, files, |record, line| {
+        let row = record.iter().map(String::as_str).collect::<Vec<_>>();
+        let numbers =
+            dictionary.encode::<f64>(&r.import_columns, line, &row, r.missing_values)?;
+        for (value, (sum, count)) in numbers.into_iter().zip(&mut sums) {
+            if !value.is_nan() {
+                *sum += value;
+                *count += 1;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(sums
+        .into_iter()
+        .map(|(sum, count)| if count > 0 { Some(sum / count as f64) } else { None })
+        .collect())
+}
+
+/// Trimmed values of imported columns of a row
+fn imported<'b>(r: &'b ClassRequest, row: &'b [&str]) -> impl Iterator<Item = &'b str> {
+    r.import_columns
+        .iter()
+        .zip(row)
+        .filter_map(|(&keep, value)| if keep { Some(value.trim()) } else { None })
+}
+
+/// Files to import: `path` itself or files in directory `path`, sorted by name
+fn list_files(path: &Path) -> Result<Vec<PathBuf>, PrimeclueErr> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    for entry in
+        fs::read_dir(path).map_err(|e| format!("Unable to read directory {:?}: {}", path, e))?
+    {
+        let file = entry?.path();
+        if file.is_file() {
+            files.push(file);
+        }
+    }
+    files.sort();
+    if files.is_empty() {
+        PrimeclueErr::result(format!("No files to import in {:?}", path))
+    } else {
+        Ok(files)
+    }
+}
+
+/// Calls `f` with every record of `files`, split like
+/// [`crate::data::importer::split_to_vec`] does, and the line of its file it starts at.
+/// Errors of `f` are prefixed with the file name. If request's `ignore_first_row` is
+/// true, first record of each file is a header: it's skipped, must be the same in all
+/// files and is returned.
+fn for_each_record<F>(
+    r: &ClassRequest,
+    files: &[PathBuf],
+    mut f: F,
+) -> Result<Option<Vec<String>>, PrimeclueErr>
+where
+    F: FnMut(Vec<String>, usize) -> Result<(), PrimeclueErr>,
+{
+    let mut first_header: Option<Vec<String>> = None;
+    for file in files {
+        let reader = BufReader::new(
+            File::open(file).map_err(|e| format!("Unable to open {:?}: {}", file, e))?,
+        );
+        let in_file = |e: PrimeclueErr| PrimeclueErr::from(format!("{:?}: {}", file, e));
+        let mut tokenizer = Tokenizer::new(&r.csv, &r.separator)?;
+        let mut header_read = !r.ignore_first_row;
+        for line in reader.lines() {
+            let line = line.map_err(|e| format!("Unable to read {:?}: {}", file, e))?;
+            let record = match tokenizer.line(&line).map_err(in_file)? {
+                Some(record) => record,
+                None => continue,
+            };
+            if header_read {
+                f(record, tokenizer.record_line()).map_err(in_file)?;
+                continue;
+            }
+            header_read = true;
+            match &first_header {
+                None => first_header = Some(record),
+                Some(first) if *first != record => {
+                    return PrimeclueErr::result(format!(
+                        "Header of {:?} differs from header of {:?}",
+                        file, files[0]
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        tokenizer.finish().map_err(in_file)?;
+    }
+    Ok(first_header)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::data_set::DataSet;
+    use crate::data::dictionary::CategoryEncoding;
+    use crate::data::file_import::FileImport;
+    use crate::data::importer::{
+        build_data_set, ClassRequest, Grouping, IncompleteWindows, MissingValues,
+    };
+    use crate::data::test::temp_path;
+    use crate::error::PrimeclueErr;
+    use std::fs;
+
+    /// Imports `content` split into files of `lines_per_file` rows, in batches of
+    /// `batch_rows` rows, and checks it gives the same data as import from memory
+    fn assert_same_import(request: &ClassRequest, lines_per_file: usize, batch_rows: usize) {
+        let source = temp_path("source");
+        fs::create_dir(&source).unwrap();
+        let mut lines = request.content.lines();
+        let header = if request.ignore_first_row { lines.next() } else { None };
+        let lines = lines.collect::<Vec<_>>();
+        for (i, file_lines) in lines.chunks(lines_per_file).enumerate() {
+            let mut content = header.map_or(String::new(), |h| format!("{}\n", h));
+            content.push_str(&file_lines.join("\n"));
+            fs::write(source.join(format!("part_{:03}.csv", i)), content).unwrap();
+        }
+        let target = temp_path("target");
+        let mut import = FileImport::scan(request, &source).unwrap();
+        import.batch_rows = batch_rows;
+        let saved = import.save_to_disk::<f32, _>(&target, |_| Ok(())).unwrap();
+        let imported = DataSet::<f32>::read_from_disk(&target).unwrap();
+        fs::remove_dir_all(&source).unwrap();
+        fs::remove_dir_all(&target).unwrap();
+
+        let (expected, dictionary, _, _) = build_data_set::<f32>(request).unwrap();
+        assert_eq!(import.rows(), lines.len());
+        assert_eq!(&dictionary, import.dictionary());
+        assert_eq!(saved, expected.len());
+        assert_eq!(imported.header(), expected.header());
+        assert_eq!(imported.class_map(), expected.class_map());
+        assert!(imported.iter().eq(expected.iter()));
+    }
+
+    fn content() -> String {
+        let mut content = "a,b,colour,class\n".to_owned();
+        for i in 0..25 {
+            let b = if i % 7 == 3 { String::new() } else { format!("{}", i * 2) };
+            let colour = ["red", "green", "blue"][i % 3];
+            content.push_str(&format!("{},{},{},{}\n", i % 5, b, colour, i % 4 == 0));
+        }
+        content
+    }
+
+    #[test]
+    fn same_as_import_from_memory() {
+        let mut request = ClassRequest::simple_csv_request("file", content(), true);
+        request.categories = CategoryEncoding::OneHot;
+        request.missing_values = MissingValues::Mean;
+        for &(lines_per_file, batch_rows) in &[(100, 100), (10, 4), (7, 1)] {
+            assert_same_import(&request, lines_per_file, batch_rows);
+        }
+
+        request.rows_per_set = 3;
+        request.missing_values = MissingValues::DropRow;
+        request.expression = "cell -2 1 < cell 1 1".to_owned();
+        for &(lines_per_file, batch_rows) in &[(100, 100), (10, 4), (7, 1)] {
+            assert_same_import(&request, lines_per_file, batch_rows);
+        }
+
+        request.expression = "bucket ( column 1 , [ 1 , 3 ] )".to_owned();
+        for &(lines_per_file, batch_rows) in &[(100, 100), (10, 4)] {
+            assert_same_import(&request, lines_per_file, batch_rows);
+        }
+    }
+
+    #[test]
+    fn empty_bucket() {
+        let path = temp_path("bucket");
+        fs::write(&path, content()).unwrap();
+        let mut request = ClassRequest::simple_csv_request("file", content(), true);
+        request.categories = CategoryEncoding::OneHot;
+        request.missing_values = MissingValues::Keep;
+        request.expression = "bucket ( column 1 , [ 2 , 10 ] )".to_owned();
+        let mut import = FileImport::scan(&request, &path).unwrap();
+        import.batch_rows = 4;
+        let target = temp_path("bucket_target");
+        let error = import.save_to_disk::<f32, _>(&target, |_| Ok(())).unwrap_err().to_string();
+        fs::remove_file(&path).unwrap();
+        assert!(!target.exists());
+        assert!(error.contains(">=10"), "{}", error);
+    }
+
+    #[test]
+    fn error_location() {
+        let path = temp_path("location");
+        let content = "a,b,class\n1,2,x\n\n3,,y\n";
+        fs::write(&path, content).unwrap();
+        let mut request = ClassRequest::simple_csv_request("file", content.to_owned(), true);
+        request.missing_values = MissingValues::Reject;
+        let import = FileImport::scan(&request, &path).unwrap();
+        let target = temp_path("location_target");
+        let error = import.save_to_disk::<f32, _>(&target, |_| Ok(())).unwrap_err().to_string();
+        fs::remove_file(&path).unwrap();
+        assert!(!target.exists());
+        assert!(error.contains("location") && error.contains("line 4, column 2"), "{}", error);
+    }
+
+    #[test]
+    fn later_batch_error() {
+        let path = temp_path("later");
+        fs::write(&path, content()).unwrap();
+        let mut request = ClassRequest::simple_csv_request("file", content(), true);
+        request.categories = CategoryEncoding::OneHot;
+        request.missing_values = MissingValues::Reject;
+        let mut import = FileImport::scan(&request, &path).unwrap();
+        import.batch_rows = 2;
+        let target = temp_path("later_target");
+        let mut saved = 0;
+        let error = import.save_to_disk::<f32, _>(&target, |count| {
+            saved = count;
+            Ok(())
+        });
+        assert_eq!(saved, 2);
+        assert!(error.is_err());
+        assert!(!target.exists());
+
+        request.missing_values = MissingValues::Keep;
+        let mut import = FileImport::scan(&request, &path).unwrap();
+        import.batch_rows = 2;
+        let error = import.save_to_disk::<f32, _>(&target, |count| {
+            if count > 10 {
+                PrimeclueErr::result("Cancelled".to_owned())
+            } else {
+                Ok(())
+            }
+        });
+        assert!(error.is_err());
+        assert!(!target.exists());
+
+        fs::create_dir(&target).unwrap();
+        assert!(import.save_to_disk::<f32, _>(&target, |_| Ok(())).is_err());
+        assert!(target.exists());
+        fs::remove_dir(&target).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unsupported() {
+        let path = temp_path("unsupported");
+        fs::write(&path, content()).unwrap();
+        let mut request = ClassRequest::simple_csv_request("file", content(), true);
+        request.categories = CategoryEncoding::Ordinal;
+        assert!(FileImport::scan(&request, &path).is_ok());
+        request.missing_values = MissingValues::Median;
+        assert!(FileImport::scan(&request, &path).is_err());
+        request.missing_values = MissingValues::Keep;
+        request.expression = "column 1 > average 1".to_owned();
+        assert!(FileImport::scan(&request, &path).is_err());
+        request.expression.clear();
+        request.group = Some(Grouping {
+            id_column: 1,
+            order_column: None,
+            incomplete: IncompleteWindows::Drop,
+        });
+        assert!(FileImport::scan(&request, &path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
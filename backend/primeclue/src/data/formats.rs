@@ -0,0 +1,476 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::importer::ClassRequest;
+use crate::error::PrimeclueErr;
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Format of imported content. Whatever the format, content is read into rows of cells
+/// that are imported the same way, so request's columns refer to cells of those rows.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Default)]
+pub enum DataFormat {
+    /// Delimited text, see [`crate::data::csv::CsvFormat`]
+    #[default]
+    Delimited,
+    /// One JSON object per line. Columns are keys in order of first appearance, keys
+    /// missing from an object are missing values.
+    JsonLines,
+    /// `label index:value ...` per line with 1-based feature indexes. First column is
+    /// the label, column `n + 1` is feature `n`. Features not listed are 0.
+    Libsvm,
+    /// Weka's Attribute-Relation File Format, dense or sparse. Columns are attributes.
+    Arff,
+}
+
+/// Content of import request read into rows of cells
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    /// Column names, `None` if content has no header
+    pub header: Option<Vec<String>>,
+    pub rows: Vec<Vec<String>>,
+    /// Line of content each row starts at, from 1, for errors
+    pub lines: Vec<usize>,
+}
+
+impl Table {
+    /// Reads request's `content` according to its `format`. Only delimited text uses
+    /// `separator`, `csv` and `ignore_first_row`, other formats always have a header.
+    pub fn read(r: &ClassRequest) -> Result<Self, PrimeclueErr> {
+        match r.format {
+            DataFormat::Delimited => {
+                let (mut rows, mut lines) = r.csv.split_lines(&r.content, &r.separator)?;
+                let header = if r.ignore_first_row && !rows.is_empty() {
+                    lines.remove(0);
+                    Some(rows.remove(0))
+                } else {
+                    None
+                };
+                Ok(Table { header, rows, lines })
+            }
+            DataFormat::JsonLines => read_json_lines(&r.content),
+            DataFormat::Libsvm => read_libsvm(&r.content),
+            DataFormat::Arff => read_arff(&r.content),
+        }
+    }
+}
+
+/// JSON object with keys in their order in the text
+struct OrderedObject(Vec<(String, serde_json::Value)>);
+
+impl<'de> Deserialize<'de> for OrderedObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ObjectVisitor;
+
+        impl<'de> Visitor<'de> for ObjectVisitor {
+            type Value = OrderedObject;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<OrderedObject, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(OrderedObject(entries))
+            }
+        }
+
+        deserializer.deserialize_map(ObjectVisitor)
+    }
+}
+
+fn read_json_lines(content: &str) -> Result<Table, PrimeclueErr> {
+    let mut header: Vec<String> = Vec::new();
+    let mut objects = Vec::new();
+    let mut lines = Vec::new();
+    for (line_num, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let object: OrderedObject = serde_json::from_str(line)
+            .map_err(|e| format!("Invalid JSON: line {}: {}", line_num + 1, e))?;
+        let mut cells = Vec::with_capacity(object.0.len());
+        for (key, value) in object.0 {
+            let cell = match value {
+                serde_json::Value::Null => String::new(),
+                serde_json::Value::Bool(b) => b.to_string(),
+                serde_json::Value::Number(n) => n.to_string(),
+                serde_json::Value::String(s) => s,
+                _ => {
+                    return PrimeclueErr::result(format!(
+                        "Nested value of '{}' is not supported: line {}",
+                        key,
+                        line_num + 1
+                    ))
+                }
+            };
+            let column = match header.iter().position(|h| *h == key) {
+                Some(column) => column,
+                None => {
+                    header.push(key);
+                    header.len() - 1
+                }
+            };
+            cells.push((column, cell));
+        }
+        objects.push(cells);
+        lines.push(line_num + 1);
+    }
+    let rows = objects
+        .into_iter()
+        .map(|cells| {
+            let mut row = vec![String::new(); header.len()];
+            for (column, cell) in cells {
+                row[column] = cell;
+            }
+            row
+        })
+        .collect();
+    Ok(Table { header: Some(header), rows, lines })
+}
+
+fn read_libsvm(content: &str) -> Result<Table, PrimeclueErr> {
+    let mut features = 0;
+    let mut points = Vec::new();
+    let mut lines = Vec::new();
+    for (line_num, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut tokens = line.split_whitespace();
+        let label = tokens.next().unwrap_or_default().to_owned();
+        let mut values = Vec::new();
+        let mut last = 0;
+        for token in tokens.filter(|t| !t.starts_with("qid:")) {
+            let invalid = || format!("Invalid feature '{}': line {}", token, line_num + 1);
+            let mut pair = token.splitn(2, ':');
+            let index = pair.next().and_then(|i| i.parse::<usize>().ok()).ok_or_else(invalid)?;
+            let value = pair.next().ok_or_else(invalid)?;
+            if index <= last {
+                return PrimeclueErr::result(format!(
+                    "Feature indexes must be increasing and start at 1: line {}",
+                    line_num + 1
+                ));
+            }
+            last = index;
+            values.push((index, value.to_owned()));
+        }
+        features = features.max(last);
+        points.push((label, values));
+        lines.push(line_num + 1);
+    }
+    let mut header = vec!["label".to_owned()];
+    header.extend((1..=features).map(|i| i.to_string()));
+    let rows = points
+        .into_iter()
+        .map(|(label, values)| {
+            let mut row = vec!["0".to_owned(); features + 1];
+            row[0] = label;
+            for (index, value) in values {
+                row[index] = value;
+            }
+            row
+        })
+        .collect();
+    Ok(Table { header: Some(header), rows, lines })
+}
+
+fn read_arff(content: &str) -> Result<Table, PrimeclueErr> {
+    let mut header = Vec::new();
+    // value of attributes left out of sparse rows
+    let mut sparse_defaults = Vec::new();
+    let mut rows = Vec::new();
+    let mut lines = Vec::new();
+    let mut data = false;
+    for (line_num, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('%') {
+            continue;
+        }
+        let error = |message: &str| format!("{}: line {}", message, line_num + 1);
+        if data {
+            let row = if line.starts_with('{') {
+                let mut row = sparse_defaults.clone();
+                let inner = line.trim_start_matches('{').trim_end_matches('}');
+                for value in arff_values(inner).map_err(|e| error(&e))? {
+                    let mut pair = value.splitn(2, char::is_whitespace);
+                    let index = pair.next().and_then(|i| i.parse::<usize>().ok());
+                    let value = match pair.next() {
+                        Some(value) => arff_values(value.trim()).map_err(|e| error(&e))?,
+                        None => vec![],
+                    };
+                    match (index, value.first()) {
+                        (Some(index), Some(value)) if index < row.len() => {
+                            row[index] = arff_cell(value)
+                        }
+                        _ => return PrimeclueErr::result(error("Invalid sparse value")),
+                    }
+                }
+                row
+            } else {
+                arff_values(line).map_err(|e| error(&e))?.iter().map(|v| arff_cell(v)).collect()
+            };
+            if row.len() != header.len() {
+                return PrimeclueErr::result(error(&format!(
+                    "Expected {} values, got {}",
+                    header.len(),
+                    row.len()
+                )));
+            }
+            rows.push(row);
+            lines.push(line_num + 1);
+            continue;
+        }
+        let lower = line.to_lowercase();
+        if lower.starts_with("@data") {
+            data = true;
+        } else if lower.starts_with("@attribute") {
+            let definition = line["@attribute".len()..].trim();
+            let (name, kind) =
+                arff_name(definition).ok_or_else(|| error("Invalid attribute"))?;
+            let kind_lower = kind.to_lowercase();
+            let default = if kind.starts_with('{') {
+                let nominal = kind.trim_start_matches('{').trim_end_matches('}');
+                let first = arff_values(nominal).map_err(|e| error(&e))?.into_iter().next();
+                first.unwrap_or_default()
+            } else if ["numeric", "real", "integer"].iter().any(|t| kind_lower.starts_with(t)) {
+                "0".to_owned()
+            } else if kind_lower.starts_with("string") || kind_lower.starts_with("date") {
+                String::new()
+            } else {
+                return PrimeclueErr::result(error(&format!(
+                    "Unsupported type of attribute '{}'",
+                    name
+                )));
+            };
+            header.push(name);
+            sparse_defaults.push(default);
+        } else if !lower.starts_with("@relation") {
+            return PrimeclueErr::result(error("Unexpected line in ARFF header"));
+        }
+    }
+    if !data {
+        return PrimeclueErr::result("No @data section in ARFF content".to_owned());
+    }
+    Ok(Table { header: Some(header), rows, lines })
+}
+
+/// Splits attribute definition into its name, possibly quoted, and type
+fn arff_name(definition: &str) -> Option<(String, &str)> {
+    match definition.chars().next()? {
+        quote @ '\'' | quote @ '"' => {
+            let end = definition[1..].find(quote)? + 1;
+            Some((definition[1..end].to_owned(), definition[end + 1..].trim()))
+        }
+        _ => {
+            let mut parts = definition.splitn(2, char::is_whitespace);
+            Some((parts.next()?.to_owned(), parts.next()?.trim()))
+        }
+    }
+}
+
+/// Missing value `?` is an empty cell
+fn arff_cell(value: &str) -> String {
+    if value == "?" {
+        String::new()
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Splits comma separated ARFF values. Values may be quoted with `'` or `"`, with
+/// backslash escaping characters inside quotes.
+fn arff_values(line: &str) -> Result<Vec<String>, String> {
+    let mut values = Vec::new();
+    let mut value = String::new();
+    let mut quote: Option<char> = None;
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) if c == '\\' => value.extend(chars.next()),
+            Some(_) => value.push(c),
+            None if c == ',' => {
+                values.push(if quoted { value } else { value.trim().to_owned() });
+                value = String::new();
+                quoted = false;
+            }
+            None if (c == '\'' || c == '"') && value.trim().is_empty() => {
+                value.clear();
+                quote = Some(c);
+                quoted = true;
+            }
+            None if quoted && !c.is_whitespace() => {
+                return Err(format!("Unexpected '{}' after closing quote", c))
+            }
+            None => value.push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("Quoted value is not closed".to_owned());
+    }
+    values.push(if quoted { value } else { value.trim().to_owned() });
+    Ok(values)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::data_set::DataSet;
+    use crate::data::formats::{DataFormat, Table};
+    use crate::data::importer::{build_data_set, ClassRequest, MissingValues};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn test_data(file: &str) -> String {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../test_data").join(file);
+        fs::read_to_string(path).unwrap()
+    }
+
+    fn assert_same_data(expected: &ClassRequest, actual: &ClassRequest) {
+        let (expected, _, _, _) = build_data_set::<f32>(expected).unwrap();
+        let (actual, _, _, _) = build_data_set::<f32>(actual).unwrap();
+        assert!(!expected.is_empty());
+        assert_same_points(&expected, &actual);
+    }
+
+    fn assert_same_points(expected: &DataSet<f32>, actual: &DataSet<f32>) {
+        assert_eq!(actual.header(), expected.header());
+        assert_eq!(actual.class_map(), expected.class_map());
+        assert!(actual.iter().eq(expected.iter()));
+    }
+
+    /// Banknote data with class first, like LIBSVM has it
+    fn banknote_rows() -> Vec<Vec<String>> {
+        let content = test_data("processed/banknote.csv");
+        let rows = content.lines().filter(|l| !l.trim().is_empty());
+        rows.map(|line| {
+            let mut cells = line.trim().split(',').map(str::to_owned).collect::<Vec<_>>();
+            let class = cells.pop().unwrap();
+            cells.insert(0, class);
+            cells
+        })
+        .collect()
+    }
+
+    fn banknote_csv_request(header: &[&str]) -> ClassRequest {
+        let mut content = header.join(",");
+        for row in banknote_rows() {
+            content.push('\n');
+            content.push_str(&row.join(","));
+        }
+        let mut request = ClassRequest::simple_csv_request("banknote", content, true);
+        request.class_column = 1;
+        request.import_columns = vec![false, true, true, true, true];
+        request
+    }
+
+    #[test]
+    fn libsvm() {
+        let expected = banknote_csv_request(&["label", "1", "2", "3", "4"]);
+        let mut request = banknote_csv_request(&[]);
+        request.content = test_data("formats/banknote.libsvm");
+        request.format = DataFormat::Libsvm;
+        assert_same_data(&expected, &request);
+
+        request.content = "# comment\n1 qid:3 2:1.5 # trailing\n0 1:2\n".to_owned();
+        let table = Table::read(&request).unwrap();
+        assert_eq!(table.rows, vec![vec!["1", "0", "1.5"], vec!["0", "2", "0"]]);
+        assert_eq!(table.lines, [2, 3]);
+        request.content = "1 2:1 1:0\n".to_owned();
+        assert!(Table::read(&request).is_err());
+    }
+
+    #[test]
+    fn json_lines() {
+        let expected =
+            banknote_csv_request(&["class", "variance", "skewness", "curtosis", "entropy"]);
+        let mut request = banknote_csv_request(&[]);
+        request.content = test_data("formats/banknote.jsonl");
+        request.format = DataFormat::JsonLines;
+        assert_same_data(&expected, &request);
+
+        request.content = "{\"a\": 1, \"b\": \"x\"}\n\n{\"c\": true, \"a\": null}\n".to_owned();
+        let table = Table::read(&request).unwrap();
+        assert_eq!(table.header.unwrap(), ["a", "b", "c"]);
+        assert_eq!(table.rows, vec![vec!["1", "x", ""], vec!["", "", "true"]]);
+        assert_eq!(table.lines, [1, 3]);
+        request.content = "{\"a\": [1, 2]}".to_owned();
+        assert!(Table::read(&request).is_err());
+    }
+
+    #[test]
+    fn arff() {
+        let arff = test_data("original/companies_1_year.arff");
+        let mut request = ClassRequest::simple_csv_request("companies", arff.clone(), false);
+        request.format = DataFormat::Arff;
+        request.class_column = 65;
+        request.import_columns = vec![true; 65];
+        request.import_columns[64] = false;
+        // missing values are replaced as NaN is not equal to itself
+        request.missing_values = MissingValues::Constant(-1.0);
+
+        let mut lines = arff.lines().map(str::trim);
+        let mut content = lines
+            .by_ref()
+            .take_while(|l| !l.to_lowercase().starts_with("@data"))
+            .filter(|l| l.starts_with("@attribute"))
+            .filter_map(|l| l.split_whitespace().nth(1))
+            .collect::<Vec<_>>()
+            .join(",");
+        for line in lines.filter(|l| !l.is_empty()) {
+            content.push('\n');
+            content.push_str(&line.replace('?', ""));
+        }
+        let mut expected = ClassRequest::simple_csv_request("companies", content, true);
+        expected.missing_values = MissingValues::Constant(-1.0);
+        assert_same_data(&expected, &request);
+    }
+
+    #[test]
+    fn arff_sparse_and_quoted() {
+        let content = "% comment\n@RELATION test\n@attribute 'first name' {a, 'b c'}\n\
+            @attribute x NUMERIC\n@attribute when date 'yyyy'\n@data\n\
+            'b c', 1.5, '2020'\n{1 2, 2 '2021'}\na, ?, ?\n";
+        let mut request = ClassRequest::simple_csv_request("arff", String::new(), false);
+        request.content = content.to_owned();
+        request.format = DataFormat::Arff;
+        let table = Table::read(&request).unwrap();
+        assert_eq!(table.header.unwrap(), ["first name", "x", "when"]);
+        assert_eq!(
+            table.rows,
+            vec![vec!["b c", "1.5", "2020"], vec!["a", "2", "2021"], vec!["a", "", ""]]
+        );
+        assert_eq!(table.lines, [7, 8, 9]);
+        request.content = "@attribute x numeric\n@data\n1,2\n".to_owned();
+        let error = Table::read(&request).unwrap_err().to_string();
+        assert!(error.contains("line 3"), "{}", error);
+        request.content = "@attribute x relational\n@data\n".to_owned();
+        assert!(Table::read(&request).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::data_set::DataSet;
+use crate::data::outcome::Class;
+use crate::data::{Input, Outcome, Point};
+use crate::error::PrimeclueErr;
+use crate::value::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryInto;
+use std::fs;
+use std::path::Path;
+
+/// Reads images and their labels from uncompressed IDX files, the format MNIST is
+/// distributed in. Images file has dimensions `(count, height, width)` or
+/// `(count, width)`, labels file has dimension `(count)` of integers. Each image is
+/// an input with `height` rows and `width` columns, so `(row, column)` of input is
+/// pixel coordinates. Classes are named after labels.
+pub fn read_idx<T: Value>(images: &Path, labels: &Path) -> Result<DataSet<T>, PrimeclueErr> {
+    let images = IdxFile::read(images)?;
+    let labels = IdxFile::read(labels)?;
+    let (height, width) = match images.dims[..] {
+        [_, height, width] => (height, width),
+        [_, width] => (1, width),
+        _ => return PrimeclueErr::result("Images must have 2 or 3 dimensions".to_owned()),
+    };
+    if labels.dims.len() != 1 || labels.kind.is_float() {
+        return PrimeclueErr::result("Labels must be one dimension of integers".to_owned());
+    }
+    let count = images.dims[0];
+    if labels.dims[0] != count {
+        return PrimeclueErr::result(format!(
+            "Got {} images and {} labels",
+            count, labels.dims[0]
+        ));
+    }
+    let labels = (0..count).map(|i| labels.value(i) as i64).collect::<Vec<_>>();
+    let (classes, class_map) = classes(labels.iter().map(ToString::to_string));
+    let mut data_set = DataSet::new(class_map);
+    let size = height * width;
+    for (i, label) in labels.iter().enumerate() {
+        let pixels = (0..size).map(|p| T::from_f64(images.value(i * size + p)));
+        let rows = pixels.collect::<Vec<_>>().chunks(width).map(<[T]>::to_vec).collect();
+        let class = classes[&label.to_string()];
+        data_set.add_data_point(image_point(rows, class)?)?;
+    }
+    Ok(data_set)
+}
+
+/// Reads grayscale PGM images (binary P5 or plain P2) from subdirectories of `path`,
+/// each subdirectory holding images of the class it's named after. All images must
+/// have the same size, pixels are values between 0 and image's maximum value.
+pub fn read_pgm_directory<T: Value>(path: &Path) -> Result<DataSet<T>, PrimeclueErr> {
+    let mut images = Vec::new();
+    for entry in fs::read_dir(path).map_err(|e| format!("Unable to read {:?}: {}", path, e))? {
+        let dir = entry?.path();
+        if !dir.is_dir() {
+            continue;
+        }
+        let label = dir.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_owned();
+        for file in fs::read_dir(&dir)? {
+            let file = file?.path();
+            if file.is_file() {
+                images.push((label.clone(), file));
+            }
+        }
+    }
+    images.sort();
+    if images.is_empty() {
+        return PrimeclueErr::result(format!("No images in subdirectories of {:?}", path));
+    }
+    let (classes, class_map) = classes(images.iter().map(|(label, _)| label.clone()));
+    let mut data_set = DataSet::new(class_map);
+    for (label, file) in images {
+        let bytes = fs::read(&file).map_err(|e| format!("Unable to read {:?}: {}", file, e))?;
+        let rows = read_pgm(&bytes).map_err(|e| format!("{:?}: {}", file, e))?;
+        data_set
+            .add_data_point(image_point(rows, classes[&label])?)
+            .map_err(|e| format!("{:?}: {}", file, e))?;
+    }
+    Ok(data_set)
+}
+
+/// Classes for labels, numbered in labels' order
+fn classes<I: Iterator<Item = String>>(
+    labels: I,
+) -> (HashMap<String, Class>, HashMap<Class, String>) {
+    let labels = labels.collect::<BTreeSet<_>>();
+    let mut classes = HashMap::new();
+    let mut class_map = HashMap::new();
+    for (i, label) in labels.into_iter().enumerate() {
+        classes.insert(label.clone(), Class::new(i as u16));
+        class_map.insert(Class::new(i as u16), label);
+    }
+    (classes, class_map)
+}
+
+fn image_point<T: Value>(rows: Vec<Vec<T>>, class: Class) -> Result<Point<T>, PrimeclueErr> {
+    Ok(Point::new(Input::from_vector(rows)?, Outcome::new(class, 1.0, -1.0)))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum IdxType {
+    UnsignedByte,
+    SignedByte,
+    Short,
+    Int,
+    Float,
+    Double,
+}
+
+impl IdxType {
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x08 => Some(IdxType::UnsignedByte),
+            0x09 => Some(IdxType::SignedByte),
+            0x0B => Some(IdxType::Short),
+            0x0C => Some(IdxType::Int),
+            0x0D => Some(IdxType::Float),
+            0x0E => Some(IdxType::Double),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            IdxType::UnsignedByte | IdxType::SignedByte => 1,
+            IdxType::Short => 2,
+            IdxType::Int | IdxType::Float => 4,
+            IdxType::Double => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        self == IdxType::Float || self == IdxType::Double
+    }
+}
+
+/// Content of IDX file: big endian values of `kind` with dimensions `dims`
+#[derive(Debug)]
+struct IdxFile {
+    kind: IdxType,
+    dims: Vec<usize>,
+    data: Vec<u8>,
+}
+
+impl IdxFile {
+    fn read(path: &Path) -> Result<Self, PrimeclueErr> {
+        let bytes = fs::read(path).map_err(|e| format!("Unable to read {:?}: {}", path, e))?;
+        IdxFile::parse(bytes).map_err(|e| PrimeclueErr::from(format!("{:?}: {}", path, e)))
+    }
+
+    fn parse(mut bytes: Vec<u8>) -> Result<Self, PrimeclueErr> {
+        if bytes.len() < 4 || bytes[0] != 0 || bytes[1] != 0 {
+            return PrimeclueErr::result("Not an IDX file".to_owned());
+        }
+        let kind = IdxType::from_code(bytes[2])
+            .ok_or_else(|| format!("Unknown IDX data type {:#04x}", bytes[2]))?;
+        let dim_count = usize::from(bytes[3]);
+        let header = 4 + 4 * dim_count;
+        if dim_count == 0 || bytes.len() < header {
+            return PrimeclueErr::result("Invalid IDX dimensions".to_owned());
+        }
+        let dims = bytes[4..header]
+            .chunks(4)
+            .map(|d| u32::from_be_bytes(d.try_into().unwrap()) as usize)
+            .collect::<Vec<_>>();
+        if dims.contains(&0) {
+            return PrimeclueErr::result("IDX dimensions can't be zero".to_owned());
+        }
+        let expected = dims
+            .iter()
+            .try_fold(kind.size(), |size, &dim| size.checked_mul(dim))
+            .ok_or_else(|| "IDX dimensions are too large".to_owned())?;
+        if bytes.len() - header != expected {
+            return PrimeclueErr::result(format!(
+                "Expected {} bytes of data, got {}",
+                expected,
+                bytes.len() - header
+            ));
+        }
+        let data = bytes.split_off(header);
+        Ok(IdxFile { kind, dims, data })
+    }
+
+    /// `index`th value, counting from the first value of the first dimension
+    fn value(&self, index: usize) -> f64 {
+        let start = index * self.kind.size();
+        let bytes = &self.data[start..start + self.kind.size()];
+        match self.kind {
+            IdxType::UnsignedByte => f64::from(bytes[0]),
+            IdxType::SignedByte => f64::from(bytes[0] as i8),
+            IdxType::Short => f64::from(i16::from_be_bytes(bytes.try_into().unwrap())),
+            IdxType::Int => f64::from(i32::from_be_bytes(bytes.try_into().unwrap())),
+            IdxType::Float => f64::from(f32::from_be_bytes(bytes.try_into().unwrap())),
+            IdxType::Double => f64::from_be_bytes(bytes.try_into().unwrap()),
+        }
+    }
+}
+
+/// Rows of pixels of PGM image
+fn read_pgm<T: Value>(bytes: &[u8]) -> Result<Vec<Vec<T>>, PrimeclueErr> {
+    let mut position = 0;
+    let mut header = Vec::with_capacity(4);
+    while header.len() < 4 {
+        header.push(pgm_token(bytes, &mut position)?);
+    }
+    let number = |token: &str| {
+        token
+            .parse::<usize>()
+            .map_err(|_| PrimeclueErr::from(format!("Invalid number '{}'", token)))
+    };
+    let (width, height, max) = (number(&header[1])?, number(&header[2])?, number(&header[3])?);
+    if max == 0 || max > usize::from(u16::MAX) {
+        return PrimeclueErr::result(format!("Invalid maximum value {}", max));
+    }
+    let size = width
+        .checked_mul(height)
+        .filter(|&size| size > 0)
+        .ok_or_else(|| format!("Invalid image size {}x{}", width, height))?;
+    let pixels = match header[0].as_str() {
+        "P5" => {
+            // single whitespace separates header from pixels
+            let start = position + 1;
+            let pixel_size = if max > 255 { 2 } else { 1 };
+            let data = size
+                .checked_mul(pixel_size)
+                .and_then(|length| bytes.get(start..start.checked_add(length)?))
+                .ok_or_else(|| "Image is truncated".to_owned())?;
+            data.chunks(pixel_size)
+                .map(|p| p.iter().fold(0.0, |v, &b| v * 256.0 + f64::from(b)))
+                .collect::<Vec<_>>()
+        }
+        "P2" => {
+            // every pixel takes at least two bytes, size is not trusted for allocation
+            let mut pixels = Vec::with_capacity(size.min(bytes.len() / 2));
+            while pixels.len() < size {
+                pixels.push(number(&pgm_token(bytes, &mut position)?)? as f64);
+            }
+            pixels
+        }
+        _ => return PrimeclueErr::result("Not a grayscale PGM image".to_owned()),
+    };
+    Ok(pixels.chunks(width).map(|r| r.iter().map(|&p| T::from_f64(p)).collect()).collect())
+}
+
+/// Next whitespace separated token from `position`, skipping `#` comments
+fn pgm_token(bytes: &[u8], position: &mut usize) -> Result<String, PrimeclueErr> {
+    loop {
+        match bytes.get(*position) {
+            None => return PrimeclueErr::result("Image is truncated".to_owned()),
+            Some(b'#') => {
+                while bytes.get(*position).is_some_and(|&b| b != b'\n') {
+                    *position += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => *position += 1,
+            Some(_) => break,
+        }
+    }
+    let start = *position;
+    while bytes.get(*position).is_some_and(|b| !b.is_ascii_whitespace()) {
+        *position += 1;
+    }
+    Ok(String::from_utf8_lossy(&bytes[start..*position]).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::images::{read_idx, read_pgm, read_pgm_directory, IdxFile};
+    use crate::data::test::temp_path;
+    use crate::data::InputShape;
+    use std::fs;
+
+    fn idx(kind: u8, dims: &[u32], data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0, 0, kind, dims.len() as u8];
+        dims.iter().for_each(|d| bytes.extend_from_slice(&d.to_be_bytes()));
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn idx_images() {
+        let dir = temp_path("idx");
+        fs::create_dir(&dir).unwrap();
+        let pixels = (0..12).collect::<Vec<u8>>();
+        fs::write(dir.join("images"), idx(0x08, &[2, 2, 3], &pixels)).unwrap();
+        fs::write(dir.join("labels"), idx(0x08, &[2], &[7, 3])).unwrap();
+        fs::write(dir.join("short"), idx(0x08, &[1], &[7])).unwrap();
+        fs::write(dir.join("broken"), idx(0x08, &[2, 2, 3], &pixels[1..])).unwrap();
+
+        let data = read_idx::<f32>(&dir.join("images"), &dir.join("labels")).unwrap();
+        assert_eq!(data.len(), 2);
+        assert_eq!(data.input_shape(), &InputShape::new(2, 3));
+        assert_eq!(data.class_map().len(), 2);
+        let (input, outcome) = data.iter().nth(1).unwrap().data();
+        assert_eq!(input.get(1, 2), 11.0);
+        assert_eq!(data.class_map()[&outcome.class()], "3");
+        assert!(read_idx::<f32>(&dir.join("images"), &dir.join("short")).is_err());
+        assert!(read_idx::<f32>(&dir.join("broken"), &dir.join("labels")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert!(IdxFile::parse(idx(0x08, &[0, 2], &[])).is_err());
+        assert!(IdxFile::parse(idx(0x0D, &[u32::MAX; 3], &[])).is_err());
+        assert!(IdxFile::parse(idx(0x08, &[1, 2], &[1, 2])).is_ok());
+        assert!(read_pgm::<f32>(b"P2 0 2 255 ").is_err());
+        assert!(read_pgm::<f32>(b"P5 4294967296 4294967296 255 ").is_err());
+        assert!(read_pgm::<f32>(b"P2 100000 100000 255 1 2").is_err());
+    }
+
+    #[test]
+    fn pgm_directory() {
+        let dir = temp_path("pgm");
+        fs::create_dir_all(dir.join("cat")).unwrap();
+        fs::create_dir_all(dir.join("dog")).unwrap();
+        let mut binary = b"P5\n# comment\n3 2\n255\n".to_vec();
+        binary.extend_from_slice(&[0, 10, 20, 30, 40, 255]);
+        fs::write(dir.join("cat").join("1.pgm"), binary).unwrap();
+        fs::write(dir.join("dog").join("1.pgm"), "P2\n3 2 15\n0 1 2\n3 4 15\n").unwrap();
+
+        let data = read_pgm_directory::<f64>(&dir).unwrap();
+        assert_eq!(data.len(), 2);
+        assert_eq!(data.input_shape(), &InputShape::new(2, 3));
+        let (cat, outcome) = data.iter().next().unwrap().data();
+        assert_eq!(data.class_map()[&outcome.class()], "cat");
+        assert_eq!(cat.row(1), vec![30.0, 40.0, 255.0]);
+        let (dog, _) = data.iter().nth(1).unwrap().data();
+        assert_eq!(dog.get(1, 2), 15.0);
+
+        fs::write(dir.join("dog").join("2.pgm"), "P2\n2 2 15\n0 1\n2 3\n").unwrap();
+        assert!(read_pgm_directory::<f64>(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
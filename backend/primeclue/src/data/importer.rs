@@ -17,24 +17,35 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::data::csv::CsvFormat;
 use crate::data::data_set::DataSet;
-use crate::data::expression::{parse, OutcomeProducer};
+use crate::data::dictionary::{CategoryEncoding, Dictionary};
+use crate::data::expression::{parse, parse_value, OutcomeProducer};
+use crate::data::features::{Feature, Features};
+use crate::data::formats::{DataFormat, Table};
+use crate::data::imputation::Imputation;
 use crate::data::outcome::Class;
 use crate::data::{Input, Outcome, Point};
 use crate::error::PrimeclueErr;
+use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::{Precision, Value};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::ops::Deref;
+use std::ops::{Deref, Range};
+use std::path::Path;
 
-fn create_input_data(
+const GROUPING_FILE_NAME: &str = "grouping.ssd";
+
+/// Input of `rows_per_set` rows up to `line`. Rows before the first one are padded with
+/// it.
+fn create_input_data<T: Value>(
     line: usize,
-    floats: &[Vec<f32>],
+    floats: &[Vec<T>],
     rows_per_set: usize,
-) -> Result<Input, PrimeclueErr> {
-    let start_line = 1 + line - rows_per_set;
-
+) -> Result<Input<T>, PrimeclueErr> {
     let mut id = Input::new();
-    for floats_line in floats[start_line..=line].iter() {
+    for offset in (0..rows_per_set).rev() {
+        let floats_line = &floats[line.saturating_sub(offset)];
         id.add_row(floats_line.clone()).map_err(|e| {
             PrimeclueErr::from(format!("Unable to import line: {:?}: {}", floats_line, e))
         })?;
@@ -42,9 +53,10 @@ fn create_input_data(
     Ok(id)
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct ClassRequest {
     // TODO remove pub
+    #[serde(default, skip_serializing)]
     pub content: String,
     pub expression: String,
     pub class_column: usize,
@@ -56,9 +68,210 @@ pub struct ClassRequest {
     pub custom_reward_penalty_columns: bool,
     pub reward_column: usize,
     pub penalty_column: usize,
+    #[serde(default)]
+    pub precision: Precision,
+    #[serde(default)]
+    pub missing_values: MissingValues,
+    #[serde(default)]
+    pub categories: CategoryEncoding,
+    #[serde(default)]
+    pub csv: CsvFormat,
+    #[serde(default)]
+    pub format: DataFormat,
+    /// Names of classes numbered 0, 1 and so on. Other classes in class column are
+    /// numbered after them in order of appearance.
+    #[serde(default)]
+    pub class_names: Vec<String>,
+    /// Derived columns added after content's columns and imported, see [`Features`]
+    #[serde(default)]
+    pub features: Vec<Feature>,
+    /// Builds windows of `rows_per_set` rows from rows of the same entity only
+    #[serde(default)]
+    pub group: Option<Grouping>,
+    /// Name of the file content was read from, kept in data's metadata
+    #[serde(default)]
+    pub file_name: Option<String>,
+    /// Also stores data in chunks of this many data points, for training on data that
+    /// doesn't fit in memory, see [`ChunkWriter::write_stored`]
+    ///
+    /// [`ChunkWriter::write_stored`]: crate::data::chunked::ChunkWriter::write_stored
+    #[serde(default)]
+    pub chunk_size: Option<usize>,
+}
+
+/// Grouping of rows by entity, e.g. a stock or a patient, for content holding rows of
+/// many entities interleaved. Stored with data set and its classifiers. Classification
+/// doesn't group rows, so data classified with windows of rows must hold rows of one
+/// entity in order, see [`Grouping::check_entity`].
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq)]
+pub struct Grouping {
+    /// Column with entity's ID, starting at 1
+    pub id_column: usize,
+    /// Column to sort rows of an entity by, starting at 1. Values are sorted as numbers
+    /// if all of them are numbers, as text otherwise. Without it rows keep content's
+    /// order.
+    #[serde(default)]
+    pub order_column: Option<usize>,
+    #[serde(default)]
+    pub incomplete: IncompleteWindows,
+}
+
+/// What to do with rows having fewer than `rows_per_set` rows of their entity up to them
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Default)]
+pub enum IncompleteWindows {
+    /// Skip the rows
+    #[default]
+    Drop,
+    /// Fill missing rows of windows with entity's first row
+    Pad,
+}
+
+impl Grouping {
+    /// Numbers of rows of each entity, sorted by order column. Entities are in order of
+    /// their first rows in `data`.
+    fn entities(&self, data: &[Vec<&str>]) -> Result<Vec<Vec<usize>>, PrimeclueErr> {
+        let mut index = HashMap::new();
+        let mut entities: Vec<Vec<usize>> = Vec::new();
+        for (row_num, row) in data.iter().enumerate() {
+            let id = cell(row, self.id_column, row_num)?;
+            let entity = *index.entry(id).or_insert(entities.len());
+            if entity == entities.len() {
+                entities.push(Vec::new());
+            }
+            entities[entity].push(row_num);
+        }
+        if let Some(column) = self.order_column {
+            let order = data
+                .iter()
+                .enumerate()
+                .map(|(row_num, row)| cell(row, column, row_num).map(str::trim))
+                .collect::<Result<Vec<_>, _>>()?;
+            let numbers = order.iter().map(|v| v.parse::<f64>()).collect::<Result<Vec<_>, _>>();
+            for rows in &mut entities {
+                match &numbers {
+                    Ok(numbers) => rows.sort_by(|&a, &b| numbers[a].total_cmp(&numbers[b])),
+                    Err(_) => rows.sort_by_key(|&row| order[row]),
+                }
+            }
+        }
+        Ok(entities)
+    }
+
+    /// Fails unless `data` holds rows of a single entity, sorted by order column
+    pub fn check_entity(&self, data: &[Vec<&str>]) -> Result<(), PrimeclueErr> {
+        let entities = self.entities(data)?;
+        if entities.len() > 1 {
+            return PrimeclueErr::result(format!(
+                "Data has rows of {} entities in column {}, rows of one entity can be \
+                 classified at a time",
+                entities.len(),
+                self.id_column
+            ));
+        }
+        let sorted = entities.iter().flatten().enumerate().all(|(i, &row)| i == row);
+        match self.order_column {
+            Some(column) if !sorted => PrimeclueErr::result(format!(
+                "Rows must be sorted by column {} as they were at import",
+                column
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn save_to_disk(&self, path: &Path) -> Result<usize, PrimeclueErr> {
+        let mut s = Serializator::new();
+        s.add(self);
+        s.save(path, GROUPING_FILE_NAME)
+    }
+
+    /// Reads grouping from data directory. Data imported without grouping has none.
+    pub fn read_from_disk(path: &Path) -> Result<Option<Self>, PrimeclueErr> {
+        let path = path.join(GROUPING_FILE_NAME);
+        if path.exists() {
+            Ok(Some(Deserializable::deserialize(&mut Serializator::load(&path)?)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Serializable for Grouping {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_items(&[&self.id_column, &self.order_column]);
+        match self.incomplete {
+            IncompleteWindows::Drop => s.add_str("Drop"),
+            IncompleteWindows::Pad => s.add_str("Pad"),
+        }
+    }
+}
+
+impl Deserializable for Grouping {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        let id_column = Deserializable::deserialize(s)?;
+        let order_column = Deserializable::deserialize(s)?;
+        let incomplete = match s.next_token()?.as_str() {
+            "Drop" => IncompleteWindows::Drop,
+            "Pad" => IncompleteWindows::Pad,
+            t => return Err(format!("Invalid incomplete windows: {}", t)),
+        };
+        Ok(Grouping { id_column, order_column, incomplete })
+    }
+}
+
+/// Cell of `column`, starting at 1, in row `row_num`
+fn cell<'a>(row: &[&'a str], column: usize, row_num: usize) -> Result<&'a str, PrimeclueErr> {
+    column.checked_sub(1).and_then(|column| row.get(column)).copied().ok_or_else(|| {
+        PrimeclueErr::from(format!("No column {} in row {}", column, row_num + 1))
+    })
+}
+
+/// What to do with empty cells in imported columns.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Default)]
+pub enum MissingValues {
+    /// Fail import
+    #[default]
+    Reject,
+    /// Skip data points with any missing value
+    DropRow,
+    /// Replace with column's mean of known values
+    Mean,
+    /// Replace with column's median of known values
+    Median,
+    /// Replace with given value
+    Constant(f64),
+    /// Keep as NaN, trees have to deal with it
+    Keep,
 }
 
 impl ClassRequest {
+    /// Copy of the request with empty content, e.g. to keep its settings
+    #[must_use]
+    pub fn without_content(&self) -> Self {
+        ClassRequest {
+            content: String::new(),
+            expression: self.expression.clone(),
+            class_column: self.class_column,
+            separator: self.separator.clone(),
+            ignore_first_row: self.ignore_first_row,
+            rows_per_set: self.rows_per_set,
+            import_columns: self.import_columns.clone(),
+            data_name: self.data_name.clone(),
+            custom_reward_penalty_columns: self.custom_reward_penalty_columns,
+            reward_column: self.reward_column,
+            penalty_column: self.penalty_column,
+            precision: self.precision,
+            missing_values: self.missing_values,
+            categories: self.categories,
+            csv: self.csv.clone(),
+            format: self.format,
+            class_names: self.class_names.clone(),
+            features: self.features.clone(),
+            group: self.group,
+            file_name: self.file_name.clone(),
+            chunk_size: self.chunk_size,
+        }
+    }
+
     fn extract_reward_penalty(&self, row: &[&str]) -> Result<(f32, f32), PrimeclueErr> {
         if self.custom_reward_penalty_columns {
             let reward = row.get(self.reward_column - 1).ok_or_else(|| {
@@ -92,11 +305,12 @@ impl ClassRequest {
     /// * `content`: data string: '1.0,3.0,false\r\n2.0,1.0,true\r\n'
     /// * `ignore_first_row`: use first row in content as header (do not import)
     pub fn simple_csv_request(name: &str, content: String, ignore_first_row: bool) -> Self {
-        let line = &split_to_vec(&content, ",", ignore_first_row)[0];
-        let len = line.len();
+        let records = split_to_vec(&content, ",", ignore_first_row, &CsvFormat::default());
+        let len = records.map_or(0, |records| records.first().map_or(0, Vec::len));
         let mut import_columns = vec![true; len];
-        let last = import_columns.get_mut(len - 1).unwrap();
-        *last = false;
+        if let Some(last) = import_columns.last_mut() {
+            *last = false;
+        }
 
         ClassRequest {
             content,
@@ -110,6 +324,16 @@ impl ClassRequest {
             custom_reward_penalty_columns: false,
             reward_column: 0,
             penalty_column: 0,
+            precision: Precision::Single,
+            missing_values: MissingValues::Reject,
+            categories: CategoryEncoding::Reject,
+            csv: CsvFormat::default(),
+            format: DataFormat::Delimited,
+            class_names: vec![],
+            features: vec![],
+            group: None,
+            file_name: None,
+            chunk_size: None,
         }
     }
 }
@@ -122,39 +346,181 @@ pub struct Rewards {
     incorrect_false: f32,
 }
 
-pub fn build_data_set(r: &ClassRequest) -> Result<DataSet, PrimeclueErr> {
-    let data = split_to_vec(&r.content, &r.separator, r.ignore_first_row);
-    let class_producer = class_producer(&r, &data)?;
-    let mut numbers = vec![];
+/// Builds [`DataSet`] from request, with [`Dictionary`] its categorical columns were
+/// encoded with, [`Features`] of its derived columns and [`Imputation`] of its missing
+/// values. `T` should match request's precision.
+pub fn build_data_set<T: Value>(
+    r: &ClassRequest,
+) -> Result<(DataSet<T>, Dictionary, Features, Imputation), PrimeclueErr> {
+    let (table, features) = read_table(r, None)?;
+    let columns = features.import_columns(&r.import_columns);
+    let data = as_str_rows(&table.rows);
+    let dictionary = Dictionary::build(r.categories, &data, &columns);
+    let class_producer = class_producer(r, &data)?;
+    let (data_set, imputation) =
+        build_encoded(r, &table, &data, &dictionary, &columns, &class_producer)?;
+    class_producer.check_buckets(&data_set.class_counts())?;
+    Ok((data_set, dictionary, features, imputation))
+}
+
+/// Builds [`DataSet`] to append to data imported before, encoding categorical columns
+/// with `dictionary` and adding derived columns of `features` of that data. Categories
+/// missing from the dictionary are an error. Derived columns of the request are
+/// ignored.
+pub fn build_appended_data_set<T: Value>(
+    r: &ClassRequest,
+    dictionary: Option<&Dictionary>,
+    features: Option<&Features>,
+) -> Result<DataSet<T>, PrimeclueErr> {
+    let (table, features) = read_table(r, Some(features.cloned().unwrap_or_default()))?;
+    let columns = features.import_columns(&r.import_columns);
+    let data = as_str_rows(&table.rows);
+    let class_producer = class_producer(r, &data)?;
+    match dictionary {
+        Some(dictionary) => {
+            let unseen = dictionary.unseen(&columns, &data);
+            if let Some((column, value)) = unseen.first() {
+                return PrimeclueErr::result(format!(
+                    "Category '{}' in column {} not found in data's dictionary",
+                    value,
+                    column + 1
+                ));
+            }
+            Ok(build_encoded(r, &table, &data, dictionary, &columns, &class_producer)?.0)
+        }
+        None => {
+            let dictionary = Dictionary::build(CategoryEncoding::Reject, &data, &columns);
+            Ok(build_encoded(r, &table, &data, &dictionary, &columns, &class_producer)?.0)
+        }
+    }
+}
+
+/// Reads request's content and adds derived columns, of `features` if given or fitted
+/// on the content otherwise. Header, if any, gets names of derived columns and is kept
+/// by fitted features. Derived columns are added before rows are grouped by entity, so
+/// with grouping they can't look at other rows.
+pub fn read_table(
+    r: &ClassRequest,
+    features: Option<Features>,
+) -> Result<(Table, Features), PrimeclueErr> {
+    let mut table = Table::read(r)?;
+    let features = match features {
+        Some(features) => {
+            features.apply(&mut table.rows)?;
+            features
+        }
+        None => {
+            let mut features = Features::fit(&r.features, &mut table.rows)?;
+            features.set_header(table.header.clone().unwrap_or_default());
+            features
+        }
+    };
+    if r.group.is_some() {
+        let data = as_str_rows(&table.rows);
+        for feature in features.features() {
+            if parse_value(&feature.expression, &data)?.row_offsets() != (0, 0) {
+                return PrimeclueErr::result(format!(
+                    "Derived column '{}' looks at other rows, which can be rows of other \
+                     entities when rows are grouped",
+                    feature.name
+                ));
+            }
+        }
+    }
+    if let Some(header) = &mut table.header {
+        header.extend(features.names());
+    }
+    Ok((table, features))
+}
+
+/// Encodes `data`, with `columns` to import including derived ones, replacing its
+/// missing values with returned [`Imputation`]
+fn build_encoded<T: Value>(
+    r: &ClassRequest,
+    table: &Table,
+    data: &[Vec<&str>],
+    dictionary: &Dictionary,
+    columns: &[bool],
+    class_producer: &ClassProducer,
+) -> Result<(DataSet<T>, Imputation), PrimeclueErr> {
+    let mut numbers: Vec<Vec<T>> = Vec::with_capacity(data.len());
+    for (row, &line) in data.iter().zip(&table.lines) {
+        numbers.push(dictionary.encode(columns, line, row, r.missing_values)?);
+    }
+    let imputation = Imputation::fit(r.missing_values, &numbers);
+    imputation.apply(&mut numbers);
+    let mut data_set = match &r.group {
+        None => build_rows(r, data, &numbers, 0..data.len(), 0, class_producer)?,
+        Some(grouping) => {
+            let mut data_set = DataSet::new(class_producer.all_classes());
+            for rows in grouping.entities(data)? {
+                let data = rows.iter().map(|&row| data[row].clone()).collect::<Vec<_>>();
+                let numbers = rows.iter().map(|&row| numbers[row].clone()).collect::<Vec<_>>();
+                let entity = build_rows(r, &data, &numbers, 0..rows.len(), 0, class_producer)?;
+                for point in entity.iter() {
+                    data_set.add_data_point(point.clone())?;
+                }
+            }
+            data_set
+        }
+    };
+    data_set.set_header(imported_header(columns, table.header.as_deref()));
+    Ok((data_set, imputation))
+}
+
+/// Builds data points for rows `own` of `data`, with `numbers` being encoded `data`
+/// and `first_row` the number of `data`'s first row in all imported rows. Rows
+/// around `own` are only used by `rows_per_set` and class expressions looking at
+/// other rows. With [`IncompleteWindows::Pad`] windows reaching before `data` are padded.
+pub(crate) fn build_rows<T: Value>(
+    r: &ClassRequest,
+    data: &[Vec<&str>],
+    numbers: &[Vec<T>],
+    own: Range<usize>,
+    first_row: usize,
+    class_producer: &ClassProducer,
+) -> Result<DataSet<T>, PrimeclueErr> {
+    let pad = r.group.as_ref().is_some_and(|g| g.incomplete == IncompleteWindows::Pad);
     let mut data_set = DataSet::new(class_producer.all_classes());
-    for (row_num, row) in data.iter().enumerate() {
-        numbers.push(build_numbers_row(&r.import_columns, row_num, row)?);
-        if row_num + 1 < r.rows_per_set {
+    for row_num in own {
+        if first_row + row_num + 1 < r.rows_per_set && !pad {
             continue;
         }
-        if let Some(outcome) = class_producer.class(&data, row_num)? {
-            let (reward, penalty) = r.extract_reward_penalty(row)?;
+        if r.missing_values == MissingValues::DropRow
+            && numbers[(1 + row_num).saturating_sub(r.rows_per_set)..=row_num]
+                .iter()
+                .any(|n| n.iter().any(|v| v.is_nan()))
+        {
+            continue;
+        }
+        if let Some(outcome) = class_producer.class(data, row_num)? {
+            let (reward, penalty) = r.extract_reward_penalty(&data[row_num])?;
             data_set.add_data_point(build_data_point(
-                r,
-                &mut numbers,
-                row_num,
-                outcome,
-                reward,
-                penalty,
+                r, numbers, row_num, outcome, reward, penalty,
             )?)?;
         }
     }
     Ok(data_set)
 }
 
-fn build_data_point(
+/// Names of imported columns from names of all columns, if content has them.
+fn imported_header(columns: &[bool], header: Option<&[String]>) -> Vec<String> {
+    header
+        .unwrap_or_default()
+        .iter()
+        .zip(columns)
+        .filter_map(|(name, &keep)| if keep { Some(name.trim().to_owned()) } else { None })
+        .collect()
+}
+
+fn build_data_point<T: Value>(
     r: &ClassRequest,
-    numbers: &mut Vec<Vec<f32>>,
+    numbers: &[Vec<T>],
     row_num: usize,
     outcome: Class,
     reward: f32,
     penalty: f32,
-) -> Result<Point, PrimeclueErr> {
+) -> Result<Point<T>, PrimeclueErr> {
     let id = create_input_data(row_num, numbers, r.rows_per_set)?;
     let pd = Outcome::new(outcome, reward, penalty);
     Ok(Point::new(id, pd))
@@ -165,18 +531,15 @@ pub fn get_header_row(
     separator: &str,
     ignore_first_row: bool,
     mut names: Vec<String>,
-) -> Vec<String> {
-    let lines = content.split('\n').map(str::trim).collect::<Vec<_>>();
-    match lines.first() {
-        None => vec![],
+    format: &CsvFormat,
+) -> Result<Vec<String>, PrimeclueErr> {
+    match format.first_record(content, separator)? {
+        None => Ok(vec![]),
         Some(first) => {
-            let mut header = if ignore_first_row {
-                first.split(separator).map(|s| s.to_owned()).collect::<Vec<_>>()
-            } else {
-                vec!["".to_owned(); first.split(separator).count()]
-            };
+            let mut header =
+                if ignore_first_row { first } else { vec!["".to_owned(); first.len()] };
             header.append(&mut names);
-            header
+            Ok(header)
         }
     }
 }
@@ -199,30 +562,36 @@ pub fn remove_column<'a>(
     Ok(col_values)
 }
 
-pub fn split_to_vec<'a>(
-    content: &'a str,
+/// Splits `content` into records as described by `format`
+pub fn split_to_vec(
+    content: &str,
     separator: &str,
     ignore_first_row: bool,
-) -> Vec<Vec<&'a str>> {
-    let mut rows: Vec<&str> =
-        content.split('\n').map(str::trim).filter(|s| !s.is_empty()).collect();
-    if ignore_first_row && !rows.is_empty() {
-        rows.remove(0);
+    format: &CsvFormat,
+) -> Result<Vec<Vec<String>>, PrimeclueErr> {
+    let mut records = format.split(content, separator)?;
+    if ignore_first_row && !records.is_empty() {
+        records.remove(0);
     }
-    rows.iter().map(|r| r.split(separator).collect()).collect()
+    Ok(records)
+}
+
+/// Borrows fields of records, for functions that work on rows of cells
+pub fn as_str_rows(records: &[Vec<String>]) -> Vec<Vec<&str>> {
+    records.iter().map(|r| r.iter().map(String::as_str).collect()).collect()
 }
 
 #[derive(Debug)]
 pub enum ClassProducer {
-    Binary(OutcomeProducer),
+    Expression(OutcomeProducer),
     Column(usize, HashMap<String, Class>),
 }
 
 impl ClassProducer {
     pub fn class(&self, data: &[Vec<&str>], row: usize) -> Result<Option<Class>, PrimeclueErr> {
         match self {
-            ClassProducer::Binary(producer) => {
-                Ok(producer.classify(data, row)?.map(Class::from))
+            ClassProducer::Expression(producer) => {
+                Ok(producer.label(data, row)?.map(|label| Class::new(label as u16)))
             }
             ClassProducer::Column(column, classes) => {
                 let v = data[row][*column];
@@ -231,14 +600,42 @@ impl ClassProducer {
         }
     }
 
-    fn all_classes(&self) -> HashMap<Class, String> {
+    /// Number of rows before and after a row that are needed to classify it
+    pub(crate) fn context(&self) -> (usize, usize) {
         match self {
-            ClassProducer::Binary(_) => {
-                let mut classes = HashMap::new();
-                classes.insert(Class::from(false), "false".to_string());
-                classes.insert(Class::from(true), "true".to_string());
-                classes
+            ClassProducer::Expression(producer) => {
+                let (lowest, highest) = producer.row_offsets();
+                (-lowest as usize, highest as usize)
             }
+            ClassProducer::Column(_, _) => (0, 0),
+        }
+    }
+
+    /// Fails if a bucket of a bucketing expression has no data points in `counts` of
+    /// imported data. Buckets' classes are their positions, so an empty one would leave
+    /// a class no data point has.
+    pub(crate) fn check_buckets(&self, counts: &[(Class, usize)]) -> Result<(), PrimeclueErr> {
+        if let ClassProducer::Expression(producer) = self {
+            if let Some((class, _)) = counts.iter().find(|(_, count)| *count == 0) {
+                if producer.is_bucket() {
+                    return PrimeclueErr::result(format!(
+                        "Bucket '{}' has no data points, change bucket bounds",
+                        self.all_classes()[class]
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn all_classes(&self) -> HashMap<Class, String> {
+        match self {
+            ClassProducer::Expression(producer) => producer
+                .labels()
+                .into_iter()
+                .enumerate()
+                .map(|(label, name)| (Class::new(label as u16), name))
+                .collect(),
             ClassProducer::Column(_, current) => {
                 let mut classes = HashMap::new();
                 for (k, v) in current {
@@ -255,39 +652,54 @@ pub fn class_producer(
     data: &[Vec<&str>],
 ) -> Result<ClassProducer, PrimeclueErr> {
     if !r.expression.is_empty() {
-        Ok(ClassProducer::Binary(parse(&r.expression, data)?))
+        Ok(ClassProducer::Expression(parse(&r.expression, data)?))
     } else {
         let column = r.class_column - 1;
-        let classes = build_class_map(&data, column)?;
+        let classes = build_class_map(&data, column, &r.class_names)?;
         Ok(ClassProducer::Column(column, classes))
     }
 }
 
-pub fn build_numbers_row(
+/// Parses imported columns of a row that starts at `line` of content. Empty cells are
+/// an error for [`MissingValues::Reject`] and NaN for other policies.
+pub fn build_numbers_row<T: Value>(
     use_columns: &[bool],
-    row_num: usize,
+    line: usize,
     row: &[&str],
-) -> Result<Vec<f32>, PrimeclueErr> {
-    let to_import = use_columns
-        .iter()
-        .zip(row)
-        .filter_map(|(&keep, &s)| if keep { Some(s) } else { None })
-        .collect::<Vec<&str>>();
+    missing: MissingValues,
+) -> Result<Vec<T>, PrimeclueErr> {
     let mut num_row = vec![];
-    for value in to_import {
-        let n = value.trim().parse().map_err(|err| {
-            format!("Unable to parse '{}' to number: row {}: {}", value, row_num + 1, err)
-        })?;
-        num_row.push(n);
+    for (column, (_, value)) in use_columns.iter().zip(row).enumerate().filter(|(_, (&k, _))| k)
+    {
+        num_row.push(parse_cell(value, line, column + 1, missing)?);
     }
     Ok(num_row)
 }
 
+pub(crate) fn parse_cell<T: Value>(
+    value: &str,
+    line: usize,
+    column: usize,
+    missing: MissingValues,
+) -> Result<T, PrimeclueErr> {
+    if value.trim().is_empty() && missing != MissingValues::Reject {
+        return Ok(T::nan());
+    }
+    let n = value.trim().parse().map_err(|err| {
+        format!(
+            "Unable to parse '{}' to number: line {}, column {}: {}",
+            value, line, column, err
+        )
+    })?;
+    Ok(n)
+}
+
 pub fn build_class_map(
     data: &[Vec<&str>],
     column: usize,
+    class_names: &[String],
 ) -> Result<HashMap<String, Class>, PrimeclueErr> {
-    let mut classes = HashMap::new();
+    let mut classes = known_classes(class_names);
     for (i, row) in data.iter().enumerate() {
         let v = row
             .get(column)
@@ -300,6 +712,15 @@ pub fn build_class_map(
     Ok(classes)
 }
 
+/// Classes of names given in import request
+pub(crate) fn known_classes(class_names: &[String]) -> HashMap<String, Class> {
+    class_names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), Class::new(i as u16)))
+        .collect()
+}
+
 #[derive(Serialize, Debug)]
 pub struct ClassResponse {
     classes: Vec<String>,
@@ -310,3 +731,218 @@ impl ClassResponse {
         ClassResponse { classes }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::data::data_set::DataSet;
+    use crate::data::dictionary::CategoryEncoding;
+    use crate::data::features::Feature;
+    use crate::data::importer::{
+        build_appended_data_set, build_data_set, ClassRequest, Grouping, IncompleteWindows,
+        MissingValues,
+    };
+    use crate::serialization::serializator::test::test_serialization;
+
+    fn import(missing_values: MissingValues) -> Result<DataSet, String> {
+        let content = "1.0,4.0,a\n,5.0,b\n3.0,,a\n8.0,7.0,b\n".to_string();
+        let mut request = ClassRequest::simple_csv_request("missing", content, false);
+        request.missing_values = missing_values;
+        build_data_set(&request).map(|(data, _, _, _)| data).map_err(|e| e.to_string())
+    }
+
+    fn column(data: &DataSet, column: usize) -> Vec<f32> {
+        data.iter().map(|p| p.data().0.get(0, column)).collect()
+    }
+
+    #[test]
+    fn missing_values_policies() {
+        let error = import(MissingValues::Reject).unwrap_err();
+        assert!(error.contains("line 2, column 1"), "{}", error);
+
+        let data = import(MissingValues::DropRow).unwrap();
+        assert_eq!(column(&data, 0), vec![1.0, 8.0]);
+
+        let data = import(MissingValues::Mean).unwrap();
+        assert_eq!(column(&data, 0), vec![1.0, 4.0, 3.0, 8.0]);
+        assert_eq!(column(&data, 1), vec![4.0, 5.0, 5.333_333_5, 7.0]);
+
+        let data = import(MissingValues::Median).unwrap();
+        assert_eq!(column(&data, 0), vec![1.0, 3.0, 3.0, 8.0]);
+
+        let data = import(MissingValues::Constant(-1.0)).unwrap();
+        assert_eq!(column(&data, 1), vec![4.0, 5.0, -1.0, 7.0]);
+
+        let data = import(MissingValues::Keep).unwrap();
+        assert!(column(&data, 0)[1].is_nan());
+    }
+
+    #[test]
+    fn categorical_columns() {
+        let content = "1.0,red,a\n2.0,green,b\n3.0,red,a\n".to_string();
+        let mut request = ClassRequest::simple_csv_request("categories", content, false);
+        assert!(build_data_set::<f32>(&request).is_err());
+
+        request.categories = CategoryEncoding::OneHot;
+        let (data, dictionary, _, _) = build_data_set::<f32>(&request).unwrap();
+        assert_eq!(data.input_shape().columns(), 3);
+        assert_eq!(dictionary.encoded_columns(), 3);
+        assert_eq!(column(&data, 2), vec![0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn appended_categories() {
+        let content = "1.0,red,a\n2.0,green,b\n".to_string();
+        let mut request = ClassRequest::simple_csv_request("categories", content, false);
+        request.categories = CategoryEncoding::Ordinal;
+        let (_, dictionary, _, _) = build_data_set::<f32>(&request).unwrap();
+
+        request.content = "3.0,green,a\n".to_string();
+        let data = build_appended_data_set::<f32>(&request, Some(&dictionary), None).unwrap();
+        assert_eq!(column(&data, 1), vec![1.0]);
+        assert!(build_appended_data_set::<f32>(&request, None, None).is_err());
+        request.content = "3.0,blue,a\n".to_string();
+        assert!(build_appended_data_set::<f32>(&request, Some(&dictionary), None).is_err());
+    }
+
+    #[test]
+    fn header() {
+        let content = "a,b,class\n1.0,2.0,x\n3.0,4.0,y\n".to_string();
+        let mut request = ClassRequest::simple_csv_request("header", content, true);
+        request.import_columns = vec![false, true, false];
+        let (data, _, _, _) = build_data_set::<f32>(&request).unwrap();
+        assert_eq!(data.header(), ["b"]);
+        assert_eq!(column(&data, 0), vec![2.0, 4.0]);
+    }
+
+    #[test]
+    fn derived_columns() {
+        let content = "a,b,class\n1.0,2.0,x\n3.0,,y\n6.0,4.0,x\n".to_string();
+        let mut request = ClassRequest::simple_csv_request("derived", content, true);
+        request.missing_values = MissingValues::Keep;
+        request.features = vec![
+            Feature { name: "ratio".to_owned(), expression: "div column 1 column 2".to_owned() },
+            Feature { name: "change".to_owned(), expression: "sub column 1 lag 1 1".to_owned() },
+        ];
+        let (data, _, features, _) = build_data_set::<f32>(&request).unwrap();
+        assert_eq!(data.header(), ["a", "b", "ratio", "change"]);
+        assert_eq!(features.header(), ["a", "b", "class"]);
+        assert_eq!(column(&data, 2)[0], 0.5);
+        assert!(column(&data, 2)[1].is_nan());
+        assert_eq!(column(&data, 3)[2], 3.0);
+
+        // appended data gets stored features, not the request's ones
+        request.features.clear();
+        let appended = build_appended_data_set::<f32>(&request, None, Some(&features)).unwrap();
+        assert_eq!(appended.header(), data.header());
+        assert_eq!(column(&appended, 2)[2], 1.5);
+        assert_eq!(column(&appended, 3)[2], 3.0);
+    }
+
+    #[test]
+    fn bucket_classes() {
+        let content = "a,class\n5.0,x\n15.0,x\n25.0,x\n,x\n".to_string();
+        let mut request = ClassRequest::simple_csv_request("bucket", content, true);
+        request.missing_values = MissingValues::Keep;
+        request.expression = "bucket ( column 1 , [ 10 , 20 ] )".to_owned();
+        let (data, _, _, _) = build_data_set::<f32>(&request).unwrap();
+        let mut classes = data.class_map().values().cloned().collect::<Vec<_>>();
+        classes.sort();
+        assert_eq!(classes, ["10..20", "<10", ">=20"]);
+        let labels = data.iter().map(|point| data.class_map()[&point.data().1.class()].clone());
+        assert_eq!(labels.collect::<Vec<_>>(), ["<10", "10..20", ">=20"]);
+
+        // classes are positions of buckets, so none can be empty
+        request.expression = "bucket ( column 1 , [ 10 , 12 , 20 ] )".to_owned();
+        let error = build_data_set::<f32>(&request).unwrap_err().to_string();
+        assert!(error.contains("Bucket '10..12' has no data points"), "{}", error);
+        let appended = build_appended_data_set::<f32>(&request, None, None).unwrap();
+        assert_eq!(appended.len(), 3);
+    }
+
+    #[test]
+    fn grouped_windows() {
+        let content = "a,1,3,x\nb,1,30,x\na,3,2,y\nb,2,20,y\na,2,1,x\n".to_string();
+        let mut request = ClassRequest::simple_csv_request("grouped", content, false);
+        request.import_columns = vec![false, false, true, false];
+        request.rows_per_set = 2;
+        let windows = |request: &ClassRequest| {
+            let (data, _, _, _) = build_data_set::<f32>(request).unwrap();
+            let windows = data.iter().map(|p| (p.data().0.get(0, 0), p.data().0.get(1, 0)));
+            windows.collect::<Vec<_>>()
+        };
+        assert_eq!(windows(&request), vec![(3.0, 30.0), (30.0, 2.0), (2.0, 20.0), (20.0, 1.0)]);
+
+        let mut grouping = Grouping {
+            id_column: 1,
+            order_column: Some(2),
+            incomplete: IncompleteWindows::Drop,
+        };
+        request.group = Some(grouping);
+        assert_eq!(windows(&request), vec![(3.0, 1.0), (1.0, 2.0), (30.0, 20.0)]);
+
+        grouping.incomplete = IncompleteWindows::Pad;
+        request.group = Some(grouping);
+        let padded = vec![(3.0, 3.0), (3.0, 1.0), (1.0, 2.0), (30.0, 30.0), (30.0, 20.0)];
+        assert_eq!(windows(&request), padded);
+
+        // class expressions look at rows of the same entity only
+        request.expression = "cell 1 3 > column 3".to_owned();
+        let (data, _, _, _) = build_data_set::<f32>(&request).unwrap();
+        let classes = data.iter().map(|p| p.data().1.class().to_string()).collect::<Vec<_>>();
+        assert_eq!(classes, ["0", "1", "0"]);
+
+        grouping.order_column = None;
+        request.group = Some(grouping);
+        request.expression.clear();
+        assert_eq!(
+            windows(&request),
+            vec![(3.0, 3.0), (3.0, 2.0), (2.0, 1.0), (30.0, 30.0), (30.0, 20.0)]
+        );
+
+        // derived columns are added before grouping, so they can't look at other rows
+        request.features =
+            vec![Feature { name: "lag".to_owned(), expression: "lag 1 3".to_owned() }];
+        let error = build_data_set::<f32>(&request).unwrap_err().to_string();
+        assert!(error.contains("Derived column 'lag' looks at other rows"), "{}", error);
+        request.features[0].expression = "mul column 3 number 2".to_owned();
+        assert!(build_data_set::<f32>(&request).is_ok());
+        request.features.clear();
+
+        grouping.id_column = 5;
+        request.group = Some(grouping);
+        assert!(build_data_set::<f32>(&request).is_err());
+    }
+
+    #[test]
+    fn grouping_check_entity() {
+        let rows =
+            |rows: &[&[&'static str]]| rows.iter().map(|r| r.to_vec()).collect::<Vec<_>>();
+        let mut grouping = Grouping {
+            id_column: 1,
+            order_column: Some(2),
+            incomplete: IncompleteWindows::Drop,
+        };
+        assert!(grouping.check_entity(&rows(&[&["a", "1"], &["a", "2"]])).is_ok());
+        assert!(grouping.check_entity(&rows(&[&["a", "1"], &["b", "2"]])).is_err());
+        assert!(grouping.check_entity(&rows(&[&["a", "2"], &["a", "1"]])).is_err());
+        grouping.order_column = None;
+        assert!(grouping.check_entity(&rows(&[&["a", "2"], &["a", "1"]])).is_ok());
+        test_serialization(grouping);
+    }
+
+    #[test]
+    fn quoted() {
+        let content = "# comment\n\"a,1\",b,class\n1.0,\"red, dark\",x\n2.0,\"multi\nline\",y\n"
+            .to_string();
+        let mut request = ClassRequest::simple_csv_request("quoted", content, true);
+        request.csv.comment = Some("#".to_owned());
+        request.categories = CategoryEncoding::Ordinal;
+        let (data, dictionary, _, _) = build_data_set::<f32>(&request).unwrap();
+        assert_eq!(data.header(), ["a,1", "b"]);
+        assert_eq!(column(&data, 0), vec![1.0, 2.0]);
+        assert_eq!(dictionary.column_names(&["a,1".to_owned(), "b".to_owned()]), ["a,1", "b"]);
+        request.content.push_str("3.0,\"open,z\n");
+        let error = build_data_set::<f32>(&request).unwrap_err().to_string();
+        assert!(error.contains("line 6, column 5"), "{}", error);
+    }
+}
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::importer::MissingValues;
+use crate::error::PrimeclueErr;
+use crate::math::median;
+use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::Value;
+use std::path::Path;
+
+const IMPUTATION_FILE_NAME: &str = "imputation.ssd";
+
+/// Values that replaced missing values of imported columns at import, `None` for
+/// columns whose missing values were left as they are. Data being classified has its
+/// missing values replaced with the same values.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Imputation {
+    replacements: Vec<Option<f64>>,
+}
+
+impl Imputation {
+    /// Calculates replacements of missing (NaN) values in each column of `numbers`
+    /// according to `policy`.
+    pub fn fit<T: Value>(policy: MissingValues, numbers: &[Vec<T>]) -> Self {
+        let columns = numbers.first().map_or(0, Vec::len);
+        let replacements = (0..columns)
+            .map(|column| {
+                let mut known = numbers
+                    .iter()
+                    .filter_map(|row| row.get(column).copied())
+                    .filter(|v| !v.is_nan())
+                    .collect::<Vec<_>>();
+                match policy {
+                    MissingValues::Mean if !known.is_empty() => {
+                        let sum = known.iter().copied().sum::<T>();
+                        Some((sum / T::from_f64(known.len() as f64)).as_f64())
+                    }
+                    MissingValues::Median if !known.is_empty() => {
+                        Some(median(&mut known).as_f64())
+                    }
+                    MissingValues::Constant(value) => Some(value),
+                    _ => None,
+                }
+            })
+            .collect();
+        Imputation { replacements }
+    }
+
+    pub(crate) fn from_replacements(replacements: Vec<Option<f64>>) -> Self {
+        Imputation { replacements }
+    }
+
+    /// No column has missing values replaced
+    pub fn is_empty(&self) -> bool {
+        self.replacements.iter().all(Option::is_none)
+    }
+
+    /// Replaces missing (NaN) values in rows of encoded columns
+    pub fn apply<T: Value>(&self, numbers: &mut [Vec<T>]) {
+        for row in numbers {
+            self.apply_row(row);
+        }
+    }
+
+    pub fn apply_row<T: Value>(&self, row: &mut [T]) {
+        for (value, replacement) in row.iter_mut().zip(&self.replacements) {
+            if let Some(replacement) = replacement {
+                if value.is_nan() {
+                    *value = T::from_f64(*replacement);
+                }
+            }
+        }
+    }
+
+    /// Saves imputation into data directory, next to data file.
+    pub fn save_to_disk(&self, path: &Path) -> Result<usize, PrimeclueErr> {
+        let mut s = Serializator::new();
+        s.add(self);
+        s.save(path, IMPUTATION_FILE_NAME)
+    }
+
+    /// Reads imputation from data directory. Data imported without replacing missing
+    /// values has none.
+    pub fn read_from_disk(path: &Path) -> Result<Option<Self>, PrimeclueErr> {
+        let path = path.join(IMPUTATION_FILE_NAME);
+        if path.exists() {
+            Ok(Some(Imputation::deserialize(&mut Serializator::load(&path)?)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Serializable for Imputation {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add(&self.replacements);
+    }
+}
+
+impl Deserializable for Imputation {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        let replacements = Vec::deserialize(s)?;
+        Ok(Imputation { replacements })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::importer::MissingValues;
+    use crate::data::imputation::Imputation;
+    use crate::serialization::serializator::test::test_serialization;
+
+    #[test]
+    fn fit_and_apply() {
+        let numbers = vec![vec![1.0, f32::NAN], vec![f32::NAN, f32::NAN], vec![4.0, 2.0]];
+        let imputation = Imputation::fit(MissingValues::Mean, &numbers);
+        assert_eq!(imputation.replacements, vec![Some(2.5), Some(2.0)]);
+        let mut row = vec![f32::NAN, 7.0];
+        imputation.apply_row(&mut row);
+        assert_eq!(row, vec![2.5, 7.0]);
+
+        let imputation = Imputation::fit(MissingValues::Keep, &numbers);
+        assert!(imputation.is_empty());
+        test_serialization(Imputation::fit(MissingValues::Median, &numbers));
+    }
+}
@@ -21,24 +21,25 @@ use crate::data::Data;
 use crate::data::InputShape;
 use crate::error::PrimeclueErr;
 use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::Value;
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Input {
-    data: Data<f32>,
+pub struct Input<T: Value = f32> {
+    data: Data<T>,
 }
-impl Default for Input {
+impl<T: Value> Default for Input<T> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl Input {
+impl<T: Value> Input<T> {
     #[must_use]
-    pub fn new() -> Input {
+    pub fn new() -> Input<T> {
         Input { data: Data::new() }
     }
 
-    pub fn from_vector(data: Vec<Vec<f32>>) -> Result<Input, PrimeclueErr> {
+    pub fn from_vector(data: Vec<Vec<T>>) -> Result<Input<T>, PrimeclueErr> {
         let mut input_data = Input::new();
 
         for (id, row) in data.into_iter().enumerate() {
@@ -50,11 +51,11 @@ impl Input {
     }
 
     #[must_use]
-    pub fn get(&self, row: usize, column: usize) -> f32 {
+    pub fn get(&self, row: usize, column: usize) -> T {
         *self.data.get(row, column)
     }
 
-    pub fn add_row(&mut self, row: Vec<f32>) -> Result<usize, PrimeclueErr> {
+    pub fn add_row(&mut self, row: Vec<T>) -> Result<usize, PrimeclueErr> {
         self.data.add_row(row)
     }
 
@@ -64,12 +65,12 @@ impl Input {
     }
 
     #[must_use]
-    pub fn row(&self, r: usize) -> Vec<f32> {
+    pub fn row(&self, r: usize) -> Vec<T> {
         self.data.row(r).into_iter().copied().collect()
     }
 
     #[must_use]
-    pub fn to_view(&self) -> Data<Vec<f32>> {
+    pub fn to_view(&self) -> Data<Vec<T>> {
         let mut data = Data::new();
         for row in 0..self.input_shape().rows() {
             let mut vec = vec![];
@@ -82,7 +83,7 @@ impl Input {
     }
 }
 
-impl Serializable for Input {
+impl<T: Value> Serializable for Input<T> {
     fn serialize(&self, s: &mut Serializator) {
         s.add(self.data.input_shape());
         for i in 0..self.input_shape().rows() {
@@ -92,8 +93,8 @@ impl Serializable for Input {
     }
 }
 
-impl Deserializable for Input {
-    fn deserialize(s: &mut Serializator) -> Result<Input, String> {
+impl<T: Value> Deserializable for Input<T> {
+    fn deserialize(s: &mut Serializator) -> Result<Input<T>, String> {
         let mut id = Input::new();
         let input_shape = InputShape::deserialize(s)?;
         for _ in 0..input_shape.rows() {
@@ -0,0 +1,487 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::data_set::{
+    open_stored, read_stored_points, segment_file_name, StoredIndex, DATA_FILE_NAME,
+};
+use crate::data::importer::ClassRequest;
+use crate::data::outcome::Class;
+use crate::data::profile::ClassProfile;
+use crate::error::PrimeclueErr;
+use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::user::DELETE_IN_PROGRESS;
+use crate::value::Value;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Metadata is kept in data directory, next to data file
+pub(crate) const METADATA_FILE_NAME: &str = "metadata.ssd";
+
+/// Files of versions replaced by re-import are moved to numbered directories in it
+const VERSIONS_DIR: &str = "versions";
+
+/// History of data set's versions. Data imported before metadata was kept has none.
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct Metadata {
+    versions: Vec<Version>,
+}
+
+/// Data set after an import, append or re-import
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Version {
+    /// Starting at 1
+    pub number: usize,
+    pub operation: Operation,
+    /// Seconds since Unix epoch
+    pub created: u64,
+    pub source: Option<Source>,
+    /// Settings of import request, none for images
+    pub settings: Option<ImportSettings>,
+    /// Number of segments of data file, see
+    /// [`crate::data::data_set::DataSet::append_to_disk`]
+    pub segments: usize,
+    /// Points of the whole data set
+    pub points: usize,
+    /// Points of each class in the whole data set, in order of class numbers
+    pub classes: Vec<ClassProfile>,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+pub enum Operation {
+    Import,
+    Append,
+    /// Import replacing data, files of the previous version are moved to
+    /// `versions/<its number>` in data directory, see [`Metadata::replace`]
+    Reimport,
+}
+
+/// Where imported data comes from
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Source {
+    /// Name of file, or directory, if known
+    pub name: Option<String>,
+    /// FNV-1a hash of content, as 16 hex digits
+    pub hash: String,
+}
+
+/// Settings data was imported with, the whole [`ClassRequest`] except for its content.
+/// Stored as JSON, it keeps separators like tabs that serialization tokens can't hold.
+#[derive(Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct ImportSettings {
+    pub request: ClassRequest,
+}
+
+/// Version of data set a classifier was trained on
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DataVersion {
+    pub data_name: String,
+    pub version: usize,
+}
+
+impl Metadata {
+    /// Reads metadata of data directory `path`, empty if there is none
+    pub fn read_from_disk(path: &Path) -> Result<Self, PrimeclueErr> {
+        let path = path.join(METADATA_FILE_NAME);
+        if path.exists() {
+            Ok(Metadata::deserialize(&mut Serializator::load(&path)?)?)
+        } else {
+            Ok(Metadata::default())
+        }
+    }
+
+    /// Saves metadata into data directory `path`
+    pub fn save_to_disk(&self, path: &Path) -> Result<usize, PrimeclueErr> {
+        let mut s = Serializator::new();
+        s.add(self);
+        s.save(path, METADATA_FILE_NAME)
+    }
+
+    #[must_use]
+    pub fn versions(&self) -> &[Version] {
+        &self.versions
+    }
+
+    #[must_use]
+    pub fn latest(&self) -> Option<&Version> {
+        self.versions.last()
+    }
+
+    /// Version data set `data_name` is at, `None` if it has no metadata
+    #[must_use]
+    pub fn data_version(&self, data_name: &str) -> Option<DataVersion> {
+        self.latest().map(|version| DataVersion {
+            data_name: data_name.to_owned(),
+            version: version.number,
+        })
+    }
+
+    /// Adds a version of data stored in data directory `path` and saves metadata there.
+    /// Points of each class are taken from data's index, data stored without one has
+    /// its points counted. `T` must match data's precision. Returns the new version.
+    pub fn record<T: Value>(
+        &mut self,
+        path: &Path,
+        operation: Operation,
+        source: Option<Source>,
+        settings: Option<ImportSettings>,
+    ) -> Result<&Version, PrimeclueErr> {
+        let (_, class_map, _) = open_stored::<T>(&path.join(DATA_FILE_NAME))?;
+        let counts: HashMap<Class, usize> = match StoredIndex::read(path)? {
+            Some(index) => index.total_counts().into_iter().collect(),
+            None => {
+                let mut counts = HashMap::new();
+                read_stored_points::<T, _>(path, |_, point| {
+                    *counts.entry(point.data().1.class()).or_insert(0) += 1;
+                    Ok(())
+                })?;
+                counts
+            }
+        };
+        let points = counts.values().sum();
+        let mut classes = class_map.into_iter().collect::<Vec<_>>();
+        classes.sort();
+        let classes = classes
+            .into_iter()
+            .map(|(class, name)| ClassProfile {
+                name,
+                count: counts.get(&class).copied().unwrap_or(0),
+            })
+            .collect();
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| format!("Invalid system time: {}", e))?
+            .as_secs();
+        self.versions.push(Version {
+            number: self.latest().map_or(1, |latest| latest.number + 1),
+            operation,
+            created,
+            source,
+            settings,
+            segments: (1..).take_while(|&s| path.join(segment_file_name(s)).exists()).count(),
+            points,
+            classes,
+        });
+        self.save_to_disk(path)?;
+        Ok(self.versions.last().unwrap())
+    }
+
+    /// Replaces data directory `path` with directory `new` holding re-imported data and
+    /// its metadata, see [`Metadata::record`]. Files of replaced `version`, 0 for data
+    /// without versions, end up in `versions/<version>` of the new directory, next to
+    /// versions replaced before. Re-imported data is written to `new` before, and then
+    /// directories are only renamed, so a failing re-import leaves data as it was.
+    /// Returns the directory of replaced version.
+    pub fn replace(path: &Path, new: &Path, version: usize) -> Result<PathBuf, PrimeclueErr> {
+        let rename = |from: &Path, to: &Path| {
+            fs::rename(from, to).map_err(|e| {
+                PrimeclueErr::from(format!("Unable to move {:?} to {:?}: {}", from, to, e))
+            })
+        };
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let replaced = path.with_file_name(format!("{}_{}", DELETE_IN_PROGRESS, name));
+        if replaced.exists() {
+            fs::remove_dir_all(&replaced)
+                .map_err(|e| format!("Unable to remove {:?}: {}", replaced, e))?;
+        }
+        rename(path, &replaced)?;
+        let versions = new.join(VERSIONS_DIR);
+        if replaced.join(VERSIONS_DIR).exists() {
+            rename(&replaced.join(VERSIONS_DIR), &versions)?;
+        } else {
+            fs::create_dir(&versions)
+                .map_err(|e| format!("Unable to create directory {:?}: {}", versions, e))?;
+        }
+        rename(&replaced, &versions.join(version.to_string()))?;
+        rename(new, path)?;
+        Ok(path.join(VERSIONS_DIR).join(version.to_string()))
+    }
+}
+
+impl Source {
+    /// Source of content sent with import request
+    #[must_use]
+    pub fn of_request(r: &ClassRequest) -> Self {
+        let mut hash = ContentHash::new();
+        hash.update(r.content.as_bytes());
+        Source { name: r.file_name.clone(), hash: hash.hex() }
+    }
+
+    /// Source of files read from disk, named `name`. Directories are hashed file by
+    /// file, in order of names.
+    pub fn of_files(name: &str, paths: &[PathBuf]) -> Result<Self, PrimeclueErr> {
+        let mut hash = ContentHash::new();
+        for path in paths {
+            hash_path(&mut hash, path)?;
+        }
+        Ok(Source { name: Some(name.to_owned()), hash: hash.hex() })
+    }
+}
+
+fn hash_path(hash: &mut ContentHash, path: &Path) -> Result<(), PrimeclueErr> {
+    if path.is_dir() {
+        let mut entries = fs::read_dir(path)
+            .map_err(|e| format!("Unable to read directory {:?}: {}", path, e))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            hash_path(hash, &entry)?;
+        }
+        return Ok(());
+    }
+    let mut file =
+        fs::File::open(path).map_err(|e| format!("Unable to open {:?}: {}", path, e))?;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read =
+            file.read(&mut buffer).map_err(|e| format!("Unable to read {:?}: {}", path, e))?;
+        if read == 0 {
+            return Ok(());
+        }
+        hash.update(&buffer[..read]);
+    }
+}
+
+/// 64 bit FNV-1a, unlike std's hashers it's the same in every Rust version
+#[derive(Debug)]
+struct ContentHash(u64);
+
+impl ContentHash {
+    fn new() -> Self {
+        ContentHash(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn hex(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+impl From<&ClassRequest> for ImportSettings {
+    fn from(r: &ClassRequest) -> Self {
+        ImportSettings { request: r.without_content() }
+    }
+}
+
+impl Serializable for Metadata {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add(&self.versions);
+    }
+}
+
+impl Deserializable for Metadata {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        Ok(Metadata { versions: Vec::deserialize(s)? })
+    }
+}
+
+impl Serializable for Version {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_items(&[
+            &self.number,
+            &self.operation,
+            &self.created,
+            &self.source,
+            &self.settings,
+            &self.segments,
+            &self.points,
+            &self.classes,
+        ]);
+    }
+}
+
+impl Deserializable for Version {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        Ok(Version {
+            number: usize::deserialize(s)?,
+            operation: Operation::deserialize(s)?,
+            created: u64::deserialize(s)?,
+            source: Option::deserialize(s)?,
+            settings: Option::deserialize(s)?,
+            segments: usize::deserialize(s)?,
+            points: usize::deserialize(s)?,
+            classes: Vec::deserialize(s)?,
+        })
+    }
+}
+
+impl Serializable for Operation {
+    fn serialize(&self, s: &mut Serializator) {
+        match self {
+            Operation::Import => s.add_str("Import"),
+            Operation::Append => s.add_str("Append"),
+            Operation::Reimport => s.add_str("Reimport"),
+        }
+    }
+}
+
+impl Deserializable for Operation {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        match s.next_token()?.as_str() {
+            "Import" => Ok(Operation::Import),
+            "Append" => Ok(Operation::Append),
+            "Reimport" => Ok(Operation::Reimport),
+            other => Err(format!("Invalid operation: {}", other)),
+        }
+    }
+}
+
+impl Serializable for Source {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_items(&[&self.name, &self.hash]);
+    }
+}
+
+impl Deserializable for Source {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        Ok(Source { name: Option::deserialize(s)?, hash: String::deserialize(s)? })
+    }
+}
+
+impl Serializable for ImportSettings {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_string(serde_json::to_string(self).unwrap());
+    }
+}
+
+impl Deserializable for ImportSettings {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        let json = String::deserialize(s)?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid import settings: {}", e))
+    }
+}
+
+impl Serializable for DataVersion {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_items(&[&self.data_name, &self.version]);
+    }
+}
+
+impl Deserializable for DataVersion {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        Ok(DataVersion { data_name: String::deserialize(s)?, version: usize::deserialize(s)? })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::data_set::{DataSet, DATA_FILE_NAME};
+    use crate::data::importer::{build_data_set, ClassRequest};
+    use crate::data::metadata::{ImportSettings, Metadata, Operation, Source};
+    use crate::data::test::temp_path;
+    use crate::serialization::serializator::test::test_serialization;
+    use std::fs;
+
+    fn request(content: &str) -> ClassRequest {
+        let mut request = ClassRequest::simple_csv_request("metadata", content.to_owned(), true);
+        request.separator = "\t".to_owned();
+        request.import_columns = vec![true, false];
+        request.class_column = 2;
+        request.file_name = Some("data.tsv".to_owned());
+        request
+    }
+
+    #[test]
+    fn versions() {
+        let path = temp_path("metadata");
+        let first = request("a\tclass\n1\tx\n2\ty\n3\tx\n");
+        let (data, _, _, _) = build_data_set::<f32>(&first).unwrap();
+        data.save_to_disk(&path, |_| Ok(())).unwrap();
+        let mut metadata = Metadata::read_from_disk(&path).unwrap();
+        assert_eq!(metadata.data_version("metadata"), None);
+        let source = Some(Source::of_request(&first));
+        let settings = Some(ImportSettings::from(&first));
+        metadata.record::<f32>(&path, Operation::Import, source, settings).unwrap();
+
+        let appended = request("a\tclass\n4\ty\n");
+        let (data, _, _, _) = build_data_set::<f32>(&appended).unwrap();
+        data.append_to_disk(&path, |_| Ok(())).unwrap();
+        let source = Some(Source::of_request(&appended));
+        let version = metadata.record::<f32>(&path, Operation::Append, source, None).unwrap();
+        assert_eq!((version.number, version.segments, version.points), (2, 1, 4));
+        let counts = version.classes.iter().map(|c| (c.name.as_str(), c.count));
+        assert_eq!(counts.collect::<Vec<_>>(), vec![("x", 2), ("y", 2)]);
+
+        let metadata = Metadata::read_from_disk(&path).unwrap();
+        let first_version = &metadata.versions()[0];
+        assert_eq!(first_version.points, 3);
+        let settings = &first_version.settings.as_ref().unwrap().request;
+        assert_eq!((settings.separator.as_str(), settings.content.as_str()), ("\t", ""));
+        assert_eq!(settings.file_name.as_deref(), Some("data.tsv"));
+        let source = first_version.source.as_ref().unwrap();
+        assert_eq!(source.name.as_deref(), Some("data.tsv"));
+        assert_ne!(source.hash, metadata.versions()[1].source.as_ref().unwrap().hash);
+        assert_eq!(metadata.data_version("metadata").unwrap().version, 2);
+        test_serialization(metadata.clone());
+
+        let new = temp_path("metadata_new");
+        let (data, _, _, _) = build_data_set::<f32>(&request("a\tclass\n5\tz\n")).unwrap();
+        data.save_to_disk(&new, |_| Ok(())).unwrap();
+        let mut metadata = metadata;
+        let version = metadata.record::<f32>(&new, Operation::Reimport, None, None).unwrap();
+        assert_eq!((version.number, version.segments, version.points), (3, 0, 1));
+        let archive = Metadata::replace(&path, &new, 2).unwrap();
+        assert!(!new.exists());
+        assert_eq!(DataSet::<f32>::read_from_disk(&archive).unwrap().len(), 4);
+        assert_eq!(DataSet::<f32>::read_from_disk(&path).unwrap().len(), 1);
+        assert_eq!(Metadata::read_from_disk(&path).unwrap(), metadata);
+
+        // versions replaced before are kept
+        let new = temp_path("metadata_new");
+        data.save_to_disk(&new, |_| Ok(())).unwrap();
+        Metadata::replace(&path, &new, 3).unwrap();
+        assert!(archive.join(DATA_FILE_NAME).exists());
+        assert!(path.join("versions").join("3").join(DATA_FILE_NAME).exists());
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn counts_without_index() {
+        let path = temp_path("metadata_index");
+        let (data, _, _, _) =
+            build_data_set::<f32>(&request("a\tclass\n1\tx\n2\ty\n3\tx\n")).unwrap();
+        data.save_to_disk(&path, |_| Ok(())).unwrap();
+        let mut metadata = Metadata::default();
+        let indexed =
+            metadata.record::<f32>(&path, Operation::Import, None, None).unwrap().clone();
+        fs::remove_file(path.join("index.ssd")).unwrap();
+        let counted = metadata.record::<f32>(&path, Operation::Import, None, None).unwrap();
+        assert_eq!((counted.points, &counted.classes), (indexed.points, &indexed.classes));
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn content_hash() {
+        let hash = |content: &str| Source::of_request(&request(content)).hash;
+        assert_eq!(hash(""), "cbf29ce484222325");
+        assert_eq!(hash("a"), "af63dc4c8601ec8c");
+        assert_ne!(hash("a\tclass\n1\tx\n"), hash("a\tclass\n1\ty\n"));
+    }
+}
@@ -23,9 +23,34 @@ pub use self::data_set::Point;
 pub use self::input::Input;
 pub use self::outcome::Outcome;
 
+pub mod chunked;
 pub mod common;
+pub mod csv;
 pub mod data_set;
+pub mod dictionary;
 pub mod expression;
+pub mod features;
+pub mod file_import;
+pub mod formats;
+pub mod images;
 pub mod importer;
+pub mod imputation;
 pub mod input;
+pub mod metadata;
 pub mod outcome;
+pub mod preview;
+pub mod profile;
+pub mod scaling;
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::rand::GET_RNG;
+    use rand::Rng;
+    use std::path::PathBuf;
+
+    /// Path named after `name` in system's temporary directory, different on every call
+    /// and not created yet. Tests should remove it after use.
+    pub(crate) fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("primeclue_{}_{}", name, GET_RNG().gen::<u64>()))
+    }
+}
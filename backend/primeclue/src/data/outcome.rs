@@ -18,11 +18,14 @@
 */
 
 use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::Value;
 use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
-#[derive(Clone, Copy, Default, PartialEq, Debug, Eq, Hash, serde::Serialize)]
+#[derive(
+    Clone, Copy, Default, PartialEq, PartialOrd, Debug, Eq, Ord, Hash, serde::Serialize,
+)]
 pub struct Class(u16);
 
 impl Class {
@@ -121,9 +124,9 @@ impl Deserializable for Outcome {
 }
 
 #[must_use]
-pub fn sort_guesses(guesses: Vec<f32>, outcomes: &[Outcome]) -> Vec<(f32, Outcome)> {
+pub fn sort_guesses<T: Value>(guesses: &[T], outcomes: &[Outcome]) -> Vec<(T, Outcome)> {
     let mut outcomes =
-        guesses.into_iter().zip(outcomes.iter().copied()).collect::<Vec<(f32, Outcome)>>();
+        guesses.iter().copied().zip(outcomes.iter().copied()).collect::<Vec<(T, Outcome)>>();
     outcomes.sort_unstable_by(|(first, _), (second, _)| {
         first.partial_cmp(second).unwrap_or(Ordering::Greater)
     });
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::data_set::{
+    open_stored, read_stored_points, stored_files, Point, StoredIndex, DATA_FILE_NAME,
+};
+use crate::data::outcome::Class;
+use crate::error::PrimeclueErr;
+use crate::serialization::Deserializable;
+use crate::value::Value;
+use serde::Serialize;
+use std::path::Path;
+
+/// Most points returned in one page
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Part of stored data points, for viewing imported data
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Page {
+    /// Number of points matching the filter in the whole data
+    pub total: usize,
+    pub offset: usize,
+    /// Names of input columns, empty if data has no header
+    pub columns: Vec<String>,
+    pub rows: Vec<PageRow>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PageRow {
+    /// Position of the point in all data
+    pub index: usize,
+    /// Input values, row after row. Missing (NaN) values are `null` in JSON.
+    pub input: Vec<Vec<f64>>,
+    pub class: String,
+    pub reward: f32,
+    pub penalty: f32,
+}
+
+/// Reads up to `limit` (at most [`MAX_PAGE_SIZE`]) points of data stored in data
+/// directory `path`, skipping first `offset` of them. If `class` is given, only points
+/// of class with that name count. With [`StoredIndex`] files before the page are skipped
+/// and reading stops once the page is full, older data is read point by point, see
+/// [`read_stored_points`].
+pub fn read_page<T: Value>(
+    path: &Path,
+    offset: usize,
+    limit: usize,
+    class: Option<&str>,
+) -> Result<Page, PrimeclueErr> {
+    let limit = limit.min(MAX_PAGE_SIZE);
+    let mut rows = Vec::with_capacity(limit);
+    let (header, total) = match StoredIndex::read(path)? {
+        Some(index) => {
+            let total = read_indexed::<T>(path, &index, offset, limit, class, &mut rows)?;
+            (index.header, total)
+        }
+        None => {
+            let mut index = 0;
+            let mut total = 0;
+            let header = read_stored_points::<T, _>(path, |classes, point| {
+                let name = classes.get(&point.data().1.class()).map_or("", String::as_str);
+                if class.is_none_or(|class| class == name) {
+                    if total >= offset && rows.len() < limit {
+                        rows.push(page_row(index, name, &point));
+                    }
+                    total += 1;
+                }
+                index += 1;
+                Ok(())
+            })?;
+            (header, total)
+        }
+    };
+    let columns = rows.first().map_or(header.len(), |row| row.input[0].len());
+    let columns = if header.len() == columns { header } else { vec![] };
+    Ok(Page { total, offset, columns, rows })
+}
+
+/// Reads page rows of files holding them, as counted by `index`. Returns number of
+/// points of `class`.
+fn read_indexed<T: Value>(
+    path: &Path,
+    index: &StoredIndex,
+    offset: usize,
+    limit: usize,
+    class: Option<&str>,
+    rows: &mut Vec<PageRow>,
+) -> Result<usize, PrimeclueErr> {
+    // class map is the same in all files, this also checks precision
+    let (_, classes, _) = open_stored::<T>(&path.join(DATA_FILE_NAME))?;
+    let matches =
+        |c: &Class| class.is_none_or(|class| classes.get(c).is_some_and(|n| n == class));
+    // points and matching points in files before the current one
+    let mut first_index = 0;
+    let mut total = 0;
+    for (file, counts) in stored_files(path).iter().zip(&index.counts) {
+        let matching = counts.iter().filter(|(c, _)| matches(c)).map(|(_, n)| n).sum::<usize>();
+        if rows.len() < limit && total + matching > offset {
+            let (mut s, _, len) = open_stored::<T>(file)?;
+            let mut matched = total;
+            for index in first_index..first_index + len {
+                if rows.len() == limit {
+                    break;
+                }
+                let point = Point::<T>::deserialize(&mut s)
+                    .map_err(|e| format!("Invalid point in {:?}: {}", file, e))?;
+                let point_class = point.data().1.class();
+                if matches(&point_class) {
+                    if matched >= offset {
+                        let name = classes.get(&point_class).map_or("", String::as_str);
+                        rows.push(page_row(index, name, &point));
+                    }
+                    matched += 1;
+                }
+            }
+        }
+        first_index += counts.iter().map(|(_, n)| n).sum::<usize>();
+        total += matching;
+    }
+    Ok(total)
+}
+
+fn page_row<T: Value>(index: usize, class: &str, point: &Point<T>) -> PageRow {
+    let (input, outcome) = point.data();
+    let shape = input.input_shape();
+    PageRow {
+        index,
+        input: (0..shape.rows())
+            .map(|r| input.row(r).into_iter().map(Value::as_f64).collect())
+            .collect(),
+        class: class.to_owned(),
+        reward: outcome.reward(),
+        penalty: outcome.penalty(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::data_set::test::create_multiclass_data;
+    use crate::data::preview::read_page;
+    use crate::data::test::temp_path;
+    use std::fs;
+
+    #[test]
+    fn pages() {
+        let path = temp_path("preview");
+        let mut data = create_multiclass_data();
+        data.set_header(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]);
+        data.save_to_disk(&path, |_| Ok(())).unwrap();
+        data.append_to_disk(&path, |_| Ok(())).unwrap();
+
+        let page = read_page::<f32>(&path, 2, 3, None).unwrap();
+        assert_eq!(page.total, 2 * data.len());
+        assert_eq!(page.columns, ["a", "b", "c"]);
+        let indexes = page.rows.iter().map(|r| r.index).collect::<Vec<_>>();
+        assert_eq!(indexes, vec![2, 3, 4]);
+        let first = data.iter().nth(2).unwrap().data().0;
+        assert_eq!(page.rows[0].input[1][2], f64::from(first.get(1, 2)));
+
+        let page = read_page::<f32>(&path, 3, 10, Some("0")).unwrap();
+        assert!(page.rows.iter().all(|r| r.class == "0"));
+        let all = read_page::<f32>(&path, 0, 100, Some("0")).unwrap();
+        assert_eq!(page.rows[..], all.rows[3..]);
+        assert_eq!(read_page::<f32>(&path, 0, 10, Some("none")).unwrap().total, 0);
+        assert!(read_page::<f64>(&path, 0, 10, None).is_err());
+
+        // without index all points are read, pages are the same
+        let indexed = read_page::<f32>(&path, 1, 4, Some("0")).unwrap();
+        fs::rename(path.join("index.ssd"), path.join("index.bak")).unwrap();
+        assert_eq!(read_page::<f32>(&path, 1, 4, Some("0")).unwrap(), indexed);
+        fs::rename(path.join("index.bak"), path.join("index.ssd")).unwrap();
+
+        // files after a full page and files before the offset are not read
+        data.append_to_disk(&path, |_| Ok(())).unwrap();
+        let last = read_page::<f32>(&path, 2 * data.len(), 100, None).unwrap();
+        let first = read_page::<f32>(&path, 0, 2, None).unwrap();
+        fs::write(path.join("data_1.ssd"), "broken").unwrap();
+        fs::write(path.join("data_2.ssd"), "broken").unwrap();
+        assert_eq!(read_page::<f32>(&path, 0, 2, None).unwrap(), first);
+        fs::copy(path.join("data.ssd"), path.join("data_2.ssd")).unwrap();
+        assert_eq!(read_page::<f32>(&path, 2 * data.len(), 100, None).unwrap(), last);
+        assert_eq!(last.total, 3 * data.len());
+        assert_eq!(last.rows[0].index, 2 * data.len());
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
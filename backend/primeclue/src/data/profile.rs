@@ -0,0 +1,300 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::data_set::DataSet;
+use crate::error::PrimeclueErr;
+use crate::math::valid;
+use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::Value;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Profile is cached in data directory, next to data file
+pub(crate) const PROFILE_FILE_NAME: &str = "profile.ssd";
+
+/// Statistics of data set's columns, classes and rewards, see [`DataSet::profile`].
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub points: usize,
+    pub columns: Vec<ColumnProfile>,
+    /// Classes in order of their numbers
+    pub classes: Vec<ClassProfile>,
+    /// Lowest and highest reward of a point
+    pub reward_range: (f32, f32),
+    /// Lowest and highest penalty of a point
+    pub penalty_range: (f32, f32),
+    /// See [`DataSet::cost_range`]
+    pub cost_range: (f32, f32),
+}
+
+/// Statistics of values of one input cell across data points. Minimum, maximum, mean,
+/// standard deviation and distinct values are of finite values only, `None` if there
+/// are none.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ColumnProfile {
+    /// Name from data's header, empty if data has no header for the column
+    pub name: String,
+    pub row: usize,
+    pub column: usize,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    pub std_dev: Option<f64>,
+    pub distinct: usize,
+    /// Fraction of NaN (missing) or infinite values
+    pub invalid: f64,
+    /// True if finite values don't change, trees can't use such column
+    pub constant: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ClassProfile {
+    pub name: String,
+    pub count: usize,
+}
+
+impl Profile {
+    pub(crate) fn new<T: Value>(data: &DataSet<T>) -> Self {
+        let mut columns = Vec::new();
+        if !data.is_empty() {
+            let shape = data.input_shape();
+            let header = data.header();
+            for row in 0..shape.rows() {
+                for column in 0..shape.columns() {
+                    let values = data.iter().map(|p| p.data().0.get(row, column));
+                    let name = if header.len() == shape.columns() {
+                        header[column].clone()
+                    } else {
+                        String::new()
+                    };
+                    columns.push(ColumnProfile::new(name, row, column, values));
+                }
+            }
+        }
+        let mut counts = HashMap::new();
+        let mut reward_range: Option<(f32, f32)> = None;
+        let mut penalty_range: Option<(f32, f32)> = None;
+        for point in data.iter() {
+            let outcome = point.data().1;
+            *counts.entry(outcome.class()).or_insert(0) += 1;
+            let widen = |range: Option<(f32, f32)>, v: f32| {
+                range.map_or((v, v), |(min, max)| (min.min(v), max.max(v)))
+            };
+            reward_range = Some(widen(reward_range, outcome.reward()));
+            penalty_range = Some(widen(penalty_range, outcome.penalty()));
+        }
+        let mut classes = data.class_map().iter().collect::<Vec<_>>();
+        classes.sort();
+        let classes = classes
+            .into_iter()
+            .map(|(class, name)| ClassProfile {
+                name: name.clone(),
+                count: counts.get(class).copied().unwrap_or(0),
+            })
+            .collect();
+        Profile {
+            points: data.len(),
+            columns,
+            classes,
+            reward_range: reward_range.unwrap_or_default(),
+            penalty_range: penalty_range.unwrap_or_default(),
+            cost_range: data.cost_range(),
+        }
+    }
+
+    /// Saves profile into data directory `path`
+    pub fn save_to_disk(&self, path: &Path) -> Result<usize, PrimeclueErr> {
+        let mut s = Serializator::new();
+        s.add(self);
+        s.save(path, PROFILE_FILE_NAME)
+    }
+
+    /// Reads profile cached in data directory `path`. There is none if it wasn't saved
+    /// or data changed since.
+    pub fn read_from_disk(path: &Path) -> Result<Option<Self>, PrimeclueErr> {
+        let path = path.join(PROFILE_FILE_NAME);
+        if path.exists() {
+            Ok(Some(Profile::deserialize(&mut Serializator::load(&path)?)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl ColumnProfile {
+    fn new<T: Value, I: Iterator<Item = T>>(
+        name: String,
+        row: usize,
+        column: usize,
+        values: I,
+    ) -> Self {
+        let mut count = 0;
+        let mut finite = Vec::new();
+        for value in values {
+            count += 1;
+            if value.is_finite() {
+                finite.push(value);
+            }
+        }
+        let constant = !valid(&finite);
+        let mut finite = finite.into_iter().map(Value::as_f64).collect::<Vec<_>>();
+        let known = finite.len();
+        let invalid = if count == 0 { 0.0 } else { (count - known) as f64 / count as f64 };
+        let (mean, std_dev) = if known == 0 {
+            (None, None)
+        } else {
+            let mean = finite.iter().sum::<f64>() / known as f64;
+            let variance = finite.iter().map(|v| (v - mean).powi(2)).sum::<f64>()
+                / known.saturating_sub(1).max(1) as f64;
+            (Some(mean), Some(variance.sqrt()))
+        };
+        finite.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let (min, max) = (finite.first().copied(), finite.last().copied());
+        finite.dedup();
+        ColumnProfile {
+            name,
+            row,
+            column,
+            min,
+            max,
+            mean,
+            std_dev,
+            distinct: finite.len(),
+            invalid,
+            constant,
+        }
+    }
+}
+
+impl Serializable for Profile {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_items(&[
+            &self.points,
+            &self.columns,
+            &self.classes,
+            &self.reward_range,
+            &self.penalty_range,
+            &self.cost_range,
+        ]);
+    }
+}
+
+impl Deserializable for Profile {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        Ok(Profile {
+            points: usize::deserialize(s)?,
+            columns: Vec::deserialize(s)?,
+            classes: Vec::deserialize(s)?,
+            reward_range: <(f32, f32)>::deserialize(s)?,
+            penalty_range: <(f32, f32)>::deserialize(s)?,
+            cost_range: <(f32, f32)>::deserialize(s)?,
+        })
+    }
+}
+
+impl Serializable for ColumnProfile {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_items(&[
+            &self.name,
+            &self.row,
+            &self.column,
+            &self.min,
+            &self.max,
+            &self.mean,
+            &self.std_dev,
+            &self.distinct,
+            &self.invalid,
+            &self.constant,
+        ]);
+    }
+}
+
+impl Deserializable for ColumnProfile {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        Ok(ColumnProfile {
+            name: String::deserialize(s)?,
+            row: usize::deserialize(s)?,
+            column: usize::deserialize(s)?,
+            min: Option::deserialize(s)?,
+            max: Option::deserialize(s)?,
+            mean: Option::deserialize(s)?,
+            std_dev: Option::deserialize(s)?,
+            distinct: usize::deserialize(s)?,
+            invalid: f64::deserialize(s)?,
+            constant: bool::deserialize(s)?,
+        })
+    }
+}
+
+impl Serializable for ClassProfile {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_items(&[&self.name, &self.count]);
+    }
+}
+
+impl Deserializable for ClassProfile {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        Ok(ClassProfile { name: String::deserialize(s)?, count: usize::deserialize(s)? })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::data_set::DataSet;
+    use crate::data::importer::{build_data_set, ClassRequest, MissingValues};
+    use crate::data::profile::Profile;
+    use crate::serialization::serializator::test::test_serialization;
+    use std::collections::HashMap;
+
+    #[test]
+    fn profile() {
+        let content = "a,b,c,class\n1,5,,x\n3,5,inf,y\n2,5,4,x\n2,5,8,x\n".to_owned();
+        let mut request = ClassRequest::simple_csv_request("profile", content, true);
+        request.missing_values = MissingValues::Keep;
+        let (data, _, _, _) = build_data_set::<f32>(&request).unwrap();
+        let profile = data.profile();
+        assert_eq!(profile.points, 4);
+        assert_eq!(profile.columns.len(), 3);
+
+        let a = &profile.columns[0];
+        assert_eq!(a.name, "a");
+        assert_eq!((a.min, a.max, a.mean), (Some(1.0), Some(3.0), Some(2.0)));
+        assert!((a.std_dev.unwrap() - (2.0f64 / 3.0).sqrt()).abs() < 1e-9);
+        assert_eq!((a.distinct, a.invalid, a.constant), (3, 0.0, false));
+        let b = &profile.columns[1];
+        assert_eq!((b.distinct, b.constant, b.std_dev), (1, true, Some(0.0)));
+        let c = &profile.columns[2];
+        assert_eq!((c.min, c.max, c.invalid, c.distinct), (Some(4.0), Some(8.0), 0.5, 2));
+
+        let classes = profile.classes.iter().map(|c| (c.name.as_str(), c.count));
+        assert_eq!(classes.collect::<Vec<_>>(), vec![("x", 3), ("y", 1)]);
+        assert_eq!(profile.reward_range, (1.0, 1.0));
+        assert_eq!(profile.cost_range, (-4.0, 4.0));
+        test_serialization(profile);
+    }
+
+    #[test]
+    fn empty() {
+        let data = DataSet::<f32>::new(HashMap::new());
+        let profile = Profile::new(&data);
+        assert!(profile.columns.is_empty() && profile.classes.is_empty());
+        assert_eq!(profile.points, 0);
+    }
+}
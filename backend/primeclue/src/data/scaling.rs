@@ -0,0 +1,349 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::data_set::DataView;
+use crate::error::PrimeclueErr;
+use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::Value;
+
+/// Most values kept for each column by [`Scaler::Rank`]
+const MAX_QUANTILES: usize = 1000;
+
+/// Transformation of input columns, applied before training and classification.
+#[derive(serde::Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Scaler {
+    /// Subtract mean and divide by standard deviation
+    Standardize,
+    /// Map minimum to 0 and maximum to 1
+    MinMax,
+    /// `sign(x) * ln(1 + |x|)`, defined for all values
+    Log,
+    /// Position among sorted training values, from 0 to 1
+    Rank,
+}
+
+/// Parameters of one [`Scaler`] for each input column.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+enum Step {
+    /// Mean and standard deviation
+    Standardize(Vec<(f64, f64)>),
+    /// Minimum and range
+    MinMax(Vec<(f64, f64)>),
+    Log,
+    /// Sorted distinct training values, at most [`MAX_QUANTILES`]
+    Rank(Vec<Vec<f64>>),
+}
+
+/// Pipeline of [`Scaler`]s fitted on training data. Each scaler is fitted on output of
+/// the previous ones. All rows of multi-row inputs share parameters of their column.
+/// Missing (NaN) values stay missing.
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct Scaling {
+    steps: Vec<Step>,
+}
+
+impl Scaling {
+    /// Fits `scalers`, in order, on `data`
+    pub fn fit<T: Value>(scalers: &[Scaler], data: &DataView<T>) -> Result<Self, PrimeclueErr> {
+        if scalers.is_empty() {
+            return PrimeclueErr::result("No scalers to fit".to_owned());
+        }
+        let shape = data.input_shape();
+        let mut columns = (0..shape.columns())
+            .map(|column| {
+                (0..shape.rows())
+                    .flat_map(|row| data.cells().get(row, column))
+                    .map(|v| v.as_f64())
+                    .filter(|v| v.is_finite())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut steps = Vec::with_capacity(scalers.len());
+        for scaler in scalers {
+            let step = Step::fit(*scaler, &columns);
+            for (column, values) in columns.iter_mut().enumerate() {
+                for value in values.iter_mut() {
+                    *value = step.apply(column, *value);
+                }
+            }
+            steps.push(step);
+        }
+        Ok(Scaling { steps })
+    }
+
+    /// Scales all values of `data`
+    pub fn apply<T: Value>(&self, data: &mut DataView<T>) {
+        let shape = *data.input_shape();
+        let cells = data.cells_mut();
+        for row in 0..shape.rows() {
+            for column in 0..shape.columns() {
+                for value in cells.get_mut(row, column).iter_mut() {
+                    *value = T::from_f64(self.scale(column, value.as_f64()));
+                }
+            }
+        }
+    }
+
+    /// Scaled copy of `data`
+    #[must_use]
+    pub fn scaled<T: Value>(&self, data: &DataView<T>) -> DataView<T> {
+        let mut data = data.clone();
+        self.apply(&mut data);
+        data
+    }
+
+    /// Scales `value` of input column `column`
+    #[must_use]
+    pub fn scale(&self, column: usize, value: f64) -> f64 {
+        self.steps.iter().fold(value, |value, step| step.apply(column, value))
+    }
+
+    /// How each column is scaled, as `name = formula` of its scaled value. Rank scaling
+    /// only tells the number of training values, positions among them are in
+    /// serialized [`Scaling`].
+    #[must_use]
+    pub fn describe(&self, names: &[String]) -> Vec<String> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(column, name)| {
+                let formula =
+                    self.steps.iter().fold(name.clone(), |x, step| step.describe(column, &x));
+                format!("{} = {}", name, formula)
+            })
+            .collect()
+    }
+}
+
+impl Step {
+    fn fit(scaler: Scaler, columns: &[Vec<f64>]) -> Self {
+        match scaler {
+            Scaler::Standardize => Step::Standardize(
+                columns
+                    .iter()
+                    .map(|values| {
+                        let count = values.len().max(1) as f64;
+                        let mean = values.iter().sum::<f64>() / count;
+                        let variance =
+                            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+                        (mean, non_zero(variance.sqrt()))
+                    })
+                    .collect(),
+            ),
+            Scaler::MinMax => Step::MinMax(
+                columns
+                    .iter()
+                    .map(|values| {
+                        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                        if values.is_empty() {
+                            (0.0, 1.0)
+                        } else {
+                            (min, non_zero(max - min))
+                        }
+                    })
+                    .collect(),
+            ),
+            Scaler::Log => Step::Log,
+            Scaler::Rank => Step::Rank(columns.iter().map(|values| quantiles(values)).collect()),
+        }
+    }
+
+    /// Formula of this step applied to `x` of column `column`
+    fn describe(&self, column: usize, x: &str) -> String {
+        match self {
+            Step::Standardize(parameters) | Step::MinMax(parameters) => {
+                let (offset, scale) = parameters[column];
+                format!("({} - {}) / {}", x, offset, scale)
+            }
+            Step::Log => format!("sign({x}) * ln(1 + abs({x}))", x = x),
+            Step::Rank(quantiles) => {
+                format!("rank({} among {} training values)", x, quantiles[column].len())
+            }
+        }
+    }
+
+    fn apply(&self, column: usize, value: f64) -> f64 {
+        match self {
+            Step::Standardize(parameters) | Step::MinMax(parameters) => {
+                let (offset, scale) = parameters[column];
+                (value - offset) / scale
+            }
+            Step::Log => value.signum() * value.abs().ln_1p(),
+            Step::Rank(quantiles) => rank(&quantiles[column], value),
+        }
+    }
+}
+
+/// Constant columns are only shifted
+fn non_zero(scale: f64) -> f64 {
+    if scale > 0.0 {
+        scale
+    } else {
+        1.0
+    }
+}
+
+fn quantiles(values: &[f64]) -> Vec<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+    sorted.dedup();
+    if sorted.len() <= MAX_QUANTILES {
+        sorted
+    } else {
+        let last = sorted.len() - 1;
+        (0..MAX_QUANTILES).map(|i| sorted[i * last / (MAX_QUANTILES - 1)]).collect()
+    }
+}
+
+/// Position of `value` among `quantiles`, interpolated linearly between them
+fn rank(quantiles: &[f64], value: f64) -> f64 {
+    if value.is_nan() {
+        return value;
+    }
+    if quantiles.len() < 2 {
+        return 0.0;
+    }
+    let last = (quantiles.len() - 1) as f64;
+    match quantiles.binary_search_by(|q| q.partial_cmp(&value).unwrap()) {
+        Ok(index) => index as f64 / last,
+        Err(0) => 0.0,
+        Err(index) if index == quantiles.len() => 1.0,
+        Err(index) => {
+            let (low, high) = (quantiles[index - 1], quantiles[index]);
+            ((index - 1) as f64 + (value - low) / (high - low)) / last
+        }
+    }
+}
+
+impl Serializable for Step {
+    fn serialize(&self, s: &mut Serializator) {
+        match self {
+            Step::Standardize(parameters) => {
+                s.add_str("Standardize");
+                s.add(parameters);
+            }
+            Step::MinMax(parameters) => {
+                s.add_str("MinMax");
+                s.add(parameters);
+            }
+            Step::Log => s.add_str("Log"),
+            Step::Rank(quantiles) => {
+                s.add_str("Rank");
+                s.add(quantiles);
+            }
+        }
+    }
+}
+
+impl Deserializable for Step {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        let v = s.next_token()?;
+        match v.as_str() {
+            "Standardize" => Ok(Step::Standardize(Vec::deserialize(s)?)),
+            "MinMax" => Ok(Step::MinMax(Vec::deserialize(s)?)),
+            "Log" => Ok(Step::Log),
+            "Rank" => Ok(Step::Rank(Vec::deserialize(s)?)),
+            _ => Err(format!("Invalid scaler: {}", v)),
+        }
+    }
+}
+
+impl Serializable for Scaling {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add(&self.steps);
+    }
+}
+
+impl Deserializable for Scaling {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        Ok(Scaling { steps: Vec::deserialize(s)? })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::data_set::test::create_multiclass_data;
+    use crate::data::scaling::{rank, Scaler, Scaling};
+    use crate::serialization::serializator::test::test_serialization;
+
+    #[test]
+    fn standardize_and_min_max() {
+        let data = create_multiclass_data().into_view();
+        let scaling = Scaling::fit(&[Scaler::Standardize], &data).unwrap();
+        let scaled = scaling.scaled(&data);
+        for column in 0..3 {
+            let values = (0..2)
+                .flat_map(|row| scaled.cells().get(row, column).clone())
+                .collect::<Vec<_>>();
+            let mean = values.iter().sum::<f32>() / values.len() as f32;
+            let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>();
+            assert!(mean.abs() < 1e-5);
+            assert!((variance / values.len() as f32 - 1.0).abs() < 1e-4);
+        }
+        let scaling = Scaling::fit(&[Scaler::Log, Scaler::MinMax], &data).unwrap();
+        let scaled = scaling.scaled(&data);
+        let values = (0..2).flat_map(|row| scaled.cells().get(row, 0).clone());
+        let (min, max) = values.fold((1.0f32, 0.0f32), |(min, max), v| (min.min(v), max.max(v)));
+        assert_eq!((min, max), (0.0, 1.0));
+        assert!(Scaling::fit(&[], &data).is_err());
+    }
+
+    #[test]
+    fn log_and_rank() {
+        let data = create_multiclass_data().into_view();
+        let scaling = Scaling::fit(&[Scaler::Log], &data).unwrap();
+        assert_eq!(scaling.scale(0, 0.0), 0.0);
+        assert_eq!(scaling.scale(0, -(1.0f64.exp() - 1.0)), -1.0);
+        assert!(scaling.scale(0, f64::NAN).is_nan());
+        let quantiles = [1.0, 2.0, 4.0];
+        assert_eq!(rank(&quantiles, 0.0), 0.0);
+        assert_eq!(rank(&quantiles, 2.0), 0.5);
+        assert_eq!(rank(&quantiles, 3.0), 0.75);
+        assert_eq!(rank(&quantiles, 5.0), 1.0);
+        assert_eq!(rank(&[3.0], 5.0), 0.0);
+    }
+
+    #[test]
+    fn describe() {
+        let data = create_multiclass_data().into_view();
+        let names = ["a", "b", "c"].iter().map(|&n| n.to_owned()).collect::<Vec<_>>();
+        let scaling = Scaling::fit(&[Scaler::Log, Scaler::Rank], &data).unwrap();
+        let described = scaling.describe(&names);
+        assert_eq!(described.len(), 3);
+        assert_eq!(described[1], "b = rank(sign(b) * ln(1 + abs(b)) among 13 training values)");
+        let scaling = Scaling::fit(&[Scaler::MinMax], &data).unwrap();
+        assert_eq!(scaling.describe(&names)[0], "a = (a - 1) / 159");
+    }
+
+    #[test]
+    fn constant_column() {
+        let data = create_multiclass_data().into_view();
+        let scaling = Scaling::fit(&[Scaler::MinMax, Scaler::Standardize], &data).unwrap();
+        let scaled = scaling.scaled(&data);
+        assert!(scaled.cells().get(1, 2).iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn serialize() {
+        let data = create_multiclass_data().into_view();
+        let scalers = [Scaler::Log, Scaler::Rank, Scaler::MinMax, Scaler::Standardize];
+        test_serialization(Scaling::fit(&scalers, &data).unwrap());
+    }
+}
@@ -17,28 +17,102 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::data::chunked::ChunkedView;
 use crate::data::data_set::DataView;
 use crate::data::outcome::Class;
 use crate::data::InputShape;
+use crate::error::PrimeclueErr;
+use crate::exec::compiled_tree::{CompiledTree, Registers, SubtreeCache};
 use crate::exec::functions::TWO_ARG_FUNCTIONS;
 use crate::exec::score::{Objective, Score};
 use crate::exec::scored_tree::ScoredTree;
 use crate::exec::tree::Tree;
 use crate::rand::GET_RNG;
+use crate::value::Value;
 use rand::prelude::SliceRandom;
 use rand::seq::IteratorRandom;
 use rand::Rng;
 use rayon::iter::IntoParallelRefMutIterator;
 use rayon::iter::ParallelIterator;
-use std::cmp::Ordering::Equal;
 use std::collections::HashMap;
 use std::fmt::{Debug, Error, Formatter};
 use std::mem::replace;
 
+/// Maximal number of values kept in [`SubtreeCache`]s of all groups of a class training,
+/// divided evenly between its groups
+const SUBTREE_CACHE_SIZE: usize = 1 << 23;
+
 #[derive(Eq, PartialEq, Hash, Copy, Clone)]
 struct GroupId(u64);
 
-pub struct ClassTraining {
+/// Determines which training data points are used to score fresh trees. Scoring on a
+/// subsample is much faster for big data sets, at the cost of noisier scores. Best tree
+/// selection always uses all training data points.
+/// * `Full` - every tree is scored on all training data points
+/// * `Subsample(size)` - every tree is scored on a random subsample of `size` data points,
+///   different in every generation
+/// * `Racing(size)` - successive halving: trees are scored on a random subsample of `size`
+///   data points, then only the better half is scored on a twice as large subsample and so
+///   on. Trees that survive all rounds are scored on all training data points.
+#[derive(Debug, PartialEq, Copy, Clone, Default, serde::Deserialize)]
+pub enum Sampling {
+    #[default]
+    Full,
+    Subsample(usize),
+    Racing(usize),
+}
+
+impl Sampling {
+    /// Sizes of subsamples, from the smallest, needed for data with `len` points.
+    /// Empty if all data points should be used.
+    #[must_use]
+    pub fn sample_sizes(&self, len: usize) -> Vec<usize> {
+        match *self {
+            Sampling::Full => vec![],
+            Sampling::Subsample(size) => {
+                if size < len {
+                    vec![size]
+                } else {
+                    vec![]
+                }
+            }
+            Sampling::Racing(size) => {
+                let mut sizes = vec![];
+                let mut size = size;
+                while size < len {
+                    sizes.push(size);
+                    size *= 2;
+                }
+                sizes
+            }
+        }
+    }
+}
+
+/// Training data used to score fresh trees in one generation, see [`Sampling`].
+/// `Streamed` is like `Subsample` for training data that doesn't fit in memory: the
+/// best tree is rescored on data streamed from disk.
+#[derive(Debug, Copy, Clone)]
+pub enum ScoringData<'a, T: Value = f32> {
+    Full(&'a DataView<T>),
+    Subsample { full: &'a DataView<T>, sample: &'a DataView<T> },
+    Racing { full: &'a DataView<T>, samples: &'a [DataView<T>] },
+    Streamed { full: &'a ChunkedView<T>, sample: &'a DataView<T> },
+}
+
+impl<'a, T: Value> ScoringData<'a, T> {
+    #[must_use]
+    pub fn input_shape(&self) -> &'a InputShape {
+        match *self {
+            ScoringData::Full(full)
+            | ScoringData::Subsample { full, .. }
+            | ScoringData::Racing { full, .. } => full.input_shape(),
+            ScoringData::Streamed { full, .. } => full.input_shape(),
+        }
+    }
+}
+
+pub struct ClassTraining<T: Value = f32> {
     next_id: GroupId,
     objective: Objective,
     size: usize,
@@ -46,16 +120,18 @@ pub struct ClassTraining {
     forbidden_cols: Vec<usize>,
     best_tree: Option<ScoredTree>,
     class: Class,
-    groups: HashMap<GroupId, ClassGroup>,
+    groups: HashMap<GroupId, ClassGroup<T>>,
+    cache_hits: u64,
+    cache_lookups: u64,
 }
 
-impl Debug for ClassTraining {
+impl<T: Value> Debug for ClassTraining<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         write!(f, "{:?}", self.class)
     }
 }
 
-impl ClassTraining {
+impl<T: Value> ClassTraining<T> {
     #[must_use]
     pub fn new(
         size: usize,
@@ -73,6 +149,8 @@ impl ClassTraining {
             best_tree: None,
             objective,
             class,
+            cache_hits: 0,
+            cache_lookups: 0,
         }
     }
 
@@ -84,26 +162,79 @@ impl ClassTraining {
         self.best_tree.as_ref().map(|t| t.score().value())
     }
 
+    /// Fraction of subtree cache lookups that were served from cache since training start
+    pub fn cache_hit_rate(&self) -> f32 {
+        if self.cache_lookups == 0 {
+            0.0
+        } else {
+            self.cache_hits as f32 / self.cache_lookups as f32
+        }
+    }
+
+    /// Adds a group starting from `tree`, e.g. a hand written rule. The group competes
+    /// with random ones and is removed once it's among the weak.
+    pub fn seed(&mut self, tree: Tree) {
+        let id = self.next_id;
+        self.next_id.0 += 1;
+        let group = ClassGroup::create_from_tree(self.size, id, tree, &self.forbidden_cols);
+        self.groups.insert(id, group);
+    }
+
     #[must_use]
     pub fn best_tree(&self) -> Option<&ScoredTree> {
         self.best_tree.as_ref()
     }
 
-    pub fn next_generation(&mut self, training_data: &DataView, verification_data: &DataView) {
-        self.fill_up(training_data.input_shape());
+    pub fn next_generation(
+        &mut self,
+        training_data: &DataView<T>,
+        verification_data: &DataView<T>,
+    ) -> Result<(), PrimeclueErr> {
+        self.next_generation_scored_on(ScoringData::Full(training_data), verification_data)
+    }
+
+    /// Same as [`next_generation`] but fresh trees are scored according to `scoring`.
+    /// Fails only if streamed data can't be read.
+    ///
+    /// [`next_generation`]: ClassTraining::next_generation
+    pub fn next_generation_scored_on(
+        &mut self,
+        scoring: ScoringData<'_, T>,
+        verification_data: &DataView<T>,
+    ) -> Result<(), PrimeclueErr> {
+        self.fill_up(scoring.input_shape());
+        self.divide_cache();
         let objective = self.objective;
         let class = self.class;
         let length = self.size;
         let forbidden_cols = &self.forbidden_cols;
         self.groups.par_iter_mut().for_each(|(_, group)| {
             group.breed(forbidden_cols, length);
-            group.execute_and_score(objective, training_data, class);
+            group.execute_and_score(objective, scoring, class);
             group.remove_weak_trees(length);
         });
+        self.collect_cache_stats();
         self.remove_empty_groups();
-        self.select_best(verification_data);
+        self.select_best(verification_data, scoring)?;
         self.keep_node_limit();
         self.groups.shrink_to_fit();
+        Ok(())
+    }
+
+    /// Gives each group an equal part of [`SUBTREE_CACHE_SIZE`]
+    fn divide_cache(&mut self) {
+        let capacity = SUBTREE_CACHE_SIZE / self.groups.len().max(1);
+        for group in self.groups.values_mut() {
+            group.cache.set_capacity(capacity);
+        }
+    }
+
+    fn collect_cache_stats(&mut self) {
+        for group in self.groups.values_mut() {
+            let (hits, lookups) = group.cache.take_stats();
+            self.cache_hits += hits;
+            self.cache_lookups += lookups;
+        }
     }
 
     fn remove_empty_groups(&mut self) {
@@ -136,10 +267,15 @@ impl ClassTraining {
         }
     }
 
-    fn select_best(&mut self, data: &DataView) {
+    fn select_best(
+        &mut self,
+        data: &DataView<T>,
+        scoring: ScoringData<'_, T>,
+    ) -> Result<(), PrimeclueErr> {
         let mut sorted_scores = self.sorted_by_score(data);
-        self.assign_best_tree(&sorted_scores);
+        self.assign_best_tree(&sorted_scores, scoring)?;
         self.remove_bad_groups(&mut sorted_scores);
+        Ok(())
     }
 
     fn remove_bad_groups(&mut self, sorted_scores: &mut Vec<(GroupId, Score)>) {
@@ -156,12 +292,32 @@ impl ClassTraining {
         self.groups = new_group_map;
     }
 
-    fn assign_best_tree(&mut self, sorted_scores: &[(GroupId, Score)]) {
+    /// Assigns the best tree of the best group as the best tree so far if it's better.
+    /// Tree's training score is recalculated on all training data if it was calculated
+    /// on a subsample only. A tree that can't be rescored is not assigned. Streamed data
+    /// that can't be read is an error.
+    fn assign_best_tree(
+        &mut self,
+        sorted_scores: &[(GroupId, Score)],
+        scoring: ScoringData<'_, T>,
+    ) -> Result<(), PrimeclueErr> {
         if !sorted_scores.is_empty() {
             let mut best_now =
                 ScoredTree::best_tree(&self.groups.get(&sorted_scores[0].0).unwrap().scored)
                     .unwrap()
                     .clone();
+            let rescored = match scoring {
+                ScoringData::Subsample { full, .. } => Some(best_now.execute_for_score(full)),
+                ScoringData::Streamed { full, .. } => {
+                    Some(best_now.execute_for_score_streamed(full)?)
+                }
+                ScoringData::Full(_) | ScoringData::Racing { .. } => None,
+            };
+            match rescored {
+                Some(Some(score)) => best_now.set_score(score),
+                Some(None) => return Ok(()),
+                None => {}
+            }
             let score_value = (sorted_scores[0].1.value() + best_now.score().value()) / 2.0;
             let score = Score::new(
                 best_now.score().objective(),
@@ -174,9 +330,10 @@ impl ClassTraining {
                 self.best_tree = Some(best_now);
             }
         }
+        Ok(())
     }
 
-    fn sorted_by_score(&self, data: &DataView) -> Vec<(GroupId, Score)> {
+    fn sorted_by_score(&self, data: &DataView<T>) -> Vec<(GroupId, Score)> {
         let mut scores = Vec::with_capacity(self.groups.len());
         for g in self.groups.values() {
             if let Some(tree) = ScoredTree::best_tree(&g.scored) {
@@ -185,28 +342,29 @@ impl ClassTraining {
                 }
             }
         }
-        scores.sort_unstable_by(|(_, s1), (_, s2)| s1.partial_cmp(s2).unwrap());
+        scores.sort_unstable_by(|(_, s1), (_, s2)| s1.value().total_cmp(&s2.value()));
         scores.reverse();
         scores
     }
 }
 
-pub struct ClassGroup {
+pub struct ClassGroup<T: Value = f32> {
     id: GroupId,
     fresh: Vec<Tree>,
     scored: Vec<ScoredTree>,
+    cache: SubtreeCache<T>,
 }
 
-impl Debug for ClassGroup {
+impl<T: Value> Debug for ClassGroup<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         write!(f, "{}", self.id.0)
     }
 }
 
-impl ClassGroup {
+impl<T: Value> ClassGroup<T> {
     fn create_joined(
         group_size: usize,
-        existing: &HashMap<GroupId, ClassGroup>,
+        existing: &HashMap<GroupId, ClassGroup<T>>,
         id: GroupId,
         forbidden_cols: &[usize],
     ) -> Option<Self> {
@@ -241,7 +399,7 @@ impl ClassGroup {
         id: GroupId,
         tree: Tree,
         forbidden_cols: &[usize],
-    ) -> ClassGroup {
+    ) -> ClassGroup<T> {
         let mut trees = Vec::with_capacity(group_size);
         trees.push(tree);
         while trees.len() < group_size {
@@ -250,7 +408,13 @@ impl ClassGroup {
             t.mutate(forbidden_cols);
             trees.push(t);
         }
-        ClassGroup { id, fresh: trees, scored: Vec::new() }
+        ClassGroup {
+            id,
+            fresh: trees,
+            scored: Vec::new(),
+            // capacity is given by class training, see `ClassTraining::divide_cache`
+            cache: SubtreeCache::new(0),
+        }
     }
 
     fn breed(&mut self, forbidden_cols: &[usize], count: usize) {
@@ -274,19 +438,46 @@ impl ClassGroup {
     }
 
     fn remove_weak_trees(&mut self, length: usize) {
-        self.scored.sort_unstable_by(|t1, t2| t1.partial_cmp(&t2).unwrap_or(Equal));
-        self.scored.reverse();
+        sort_descending(&mut self.scored);
         self.scored.truncate(length);
     }
 
-    fn execute_and_score(&mut self, objective: Objective, data: &DataView, class: Class) {
+    fn execute_and_score(
+        &mut self,
+        objective: Objective,
+        scoring: ScoringData<'_, T>,
+        class: Class,
+    ) {
         let len = self.fresh.len();
         let trees = replace(&mut self.fresh, Vec::with_capacity(len));
-        for tree in trees {
-            if let Some(score) = tree.execute_for_score(data, class, objective) {
-                self.scored.push(ScoredTree::new(tree, score))
+        let mut registers = Registers::new();
+        let mut scored = match scoring {
+            ScoringData::Full(data) => {
+                score_trees(trees, data, class, objective, &mut registers, Some(&mut self.cache))
             }
-        }
+            ScoringData::Subsample { sample, .. } | ScoringData::Streamed { sample, .. } => {
+                score_trees(trees, sample, class, objective, &mut registers, None)
+            }
+            ScoringData::Racing { full, samples } => {
+                let mut candidates = trees;
+                for sample in samples {
+                    let mut scored =
+                        score_trees(candidates, sample, class, objective, &mut registers, None);
+                    sort_descending(&mut scored);
+                    scored.truncate(scored.len().div_ceil(2));
+                    candidates = scored.into_iter().map(ScoredTree::into_tree).collect();
+                }
+                score_trees(
+                    candidates,
+                    full,
+                    class,
+                    objective,
+                    &mut registers,
+                    Some(&mut self.cache),
+                )
+            }
+        };
+        self.scored.append(&mut scored);
     }
 
     #[must_use]
@@ -296,13 +487,42 @@ impl ClassGroup {
     }
 }
 
-fn generate_group(
-    training: &ClassTraining,
+fn sort_descending(trees: &mut [ScoredTree]) {
+    trees.sort_unstable_by(ScoredTree::sort_order);
+    trees.reverse();
+}
+
+fn score_trees<T: Value>(
+    trees: Vec<Tree>,
+    data: &DataView<T>,
+    class: Class,
+    objective: Objective,
+    registers: &mut Registers<T>,
+    mut cache: Option<&mut SubtreeCache<T>>,
+) -> Vec<ScoredTree> {
+    let mut scored = Vec::with_capacity(trees.len());
+    for tree in trees {
+        let compiled = CompiledTree::new(&tree);
+        let score = match cache.as_mut() {
+            Some(cache) => {
+                compiled.execute_for_score_cached(data, class, objective, registers, cache)
+            }
+            None => compiled.execute_for_score(data, class, objective, registers),
+        };
+        if let Some(score) = score {
+            scored.push(ScoredTree::new(tree, score))
+        }
+    }
+    scored
+}
+
+fn generate_group<T: Value>(
+    training: &ClassTraining<T>,
     input_shape: &InputShape,
     id: GroupId,
     forbidden_cols: &[usize],
     max_depth: usize,
-) -> ClassGroup {
+) -> ClassGroup<T> {
     let mut rng = GET_RNG();
     if !training.groups.is_empty() && rng.gen_bool(0.5) {
         if let Some(group) =
@@ -18,17 +18,31 @@
 */
 
 use crate::data::data_set::DataView;
+use crate::data::dictionary::Dictionary;
+use crate::data::features::Features;
+use crate::data::importer::Grouping;
+use crate::data::imputation::Imputation;
+use crate::data::metadata::DataVersion;
+use crate::data::outcome::sort_guesses;
 use crate::data::outcome::Class;
+use crate::data::scaling::Scaling;
 use crate::data::InputShape;
 use crate::error::PrimeclueErr;
-use crate::exec::score::calculate_auc;
+use crate::exec::compiled_tree::{CompiledTree, Registers};
+use crate::exec::formula::quote_name;
+use crate::exec::functions::MissingSemantics;
+use crate::exec::score::{calculate_auc, known_guesses, Score};
 use crate::exec::scored_tree::ScoredTree;
+use crate::exec::tree::Tree;
 use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::{Precision, Value};
 use serde::Serialize;
-use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::path::Path;
 
+/// Response for data points a classifier couldn't classify, e.g. due to missing values.
+pub const UNKNOWN_CLASS: &str = "unknown";
+
 #[derive(Clone, Debug, Serialize)]
 pub struct ClassifierScore {
     pub auc: f32,
@@ -37,17 +51,46 @@ pub struct ClassifierScore {
     pub label_count_map: HashMap<String, usize>,
 }
 
+/// Human readable description of a classifier's tree.
+#[derive(Clone, Debug, Serialize)]
+pub struct TreeDescription {
+    pub class: String,
+    pub threshold: f64,
+    pub score: f32,
+    pub formula: String,
+}
+
+/// Structure of a classifier's tree, e.g. for drawing it.
+#[derive(Clone, Debug, Serialize)]
+pub struct TreeStructure {
+    pub class: String,
+    pub threshold: f64,
+    pub score: f32,
+    pub tree: Tree,
+}
+
 /// A structure containing a classifier trained via [`TrainingGroup`]
 #[derive(Debug, PartialEq)]
 pub struct Classifier {
     classes: HashMap<Class, String>,
     trees: Vec<ScoredTree>,
+    precision: Precision,
+    dictionary: Option<Dictionary>,
+    header: Vec<String>,
+    scaling: Option<Scaling>,
+    features: Option<Features>,
+    data_version: Option<DataVersion>,
+    imputation: Option<Imputation>,
+    grouping: Option<Grouping>,
 }
 
 impl Classifier {
+    /// Creates a new [`Classifier`]. `precision` is the one trees were trained with and
+    /// should be used for classification.
     pub fn new(
         classes: HashMap<Class, String>,
         trees: Vec<ScoredTree>,
+        precision: Precision,
     ) -> Result<Self, PrimeclueErr> {
         if classes.is_empty() {
             PrimeclueErr::result("Class map is empty".to_string())
@@ -60,7 +103,18 @@ impl Classifier {
                 trees.len()
             ))
         } else {
-            Ok(Classifier { classes, trees })
+            Ok(Classifier {
+                classes,
+                trees,
+                precision,
+                dictionary: None,
+                header: vec![],
+                scaling: None,
+                features: None,
+                data_version: None,
+                imputation: None,
+                grouping: None,
+            })
         }
     }
 
@@ -68,10 +122,127 @@ impl Classifier {
         &self.classes
     }
 
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
     pub fn input_shape(&self) -> &InputShape {
         self.trees[0].input_shape()
     }
 
+    /// Dictionary of categorical columns that data to classify has to be encoded with.
+    pub fn dictionary(&self) -> Option<&Dictionary> {
+        self.dictionary.as_ref()
+    }
+
+    pub fn set_dictionary(&mut self, dictionary: Option<Dictionary>) {
+        self.dictionary = dictionary;
+    }
+
+    /// Names of columns data was imported from, see [`DataSet::header`]. Data to
+    /// classify is matched with them by name.
+    ///
+    /// [`DataSet::header`]: crate::data::data_set::DataSet::header
+    pub fn header(&self) -> &[String] {
+        &self.header
+    }
+
+    pub fn set_header(&mut self, header: Vec<String>) {
+        self.header = header;
+    }
+
+    /// Scaling trees were trained with. Data given to [`Classifier::classify`] and
+    /// [`Classifier::score`] is scaled with it, so tree thresholds and constants refer
+    /// to scaled values.
+    pub fn scaling(&self) -> Option<&Scaling> {
+        self.scaling.as_ref()
+    }
+
+    pub fn set_scaling(&mut self, scaling: Option<Scaling>) {
+        self.scaling = scaling;
+    }
+
+    /// Derived columns that have to be added to data to classify before encoding it
+    /// with [`Classifier::dictionary`].
+    pub fn features(&self) -> Option<&Features> {
+        self.features.as_ref()
+    }
+
+    pub fn set_features(&mut self, features: Option<Features>) {
+        self.features = features;
+    }
+
+    /// Version of data set trees were trained on, `None` if data had no metadata
+    pub fn data_version(&self) -> Option<&DataVersion> {
+        self.data_version.as_ref()
+    }
+
+    pub fn set_data_version(&mut self, data_version: Option<DataVersion>) {
+        self.data_version = data_version;
+    }
+
+    /// Replacements of missing values that data to classify has to have applied after
+    /// encoding it with [`Classifier::dictionary`].
+    pub fn imputation(&self) -> Option<&Imputation> {
+        self.imputation.as_ref()
+    }
+
+    pub fn set_imputation(&mut self, imputation: Option<Imputation>) {
+        self.imputation = imputation;
+    }
+
+    /// Grouping of rows by entity data was imported with. Data to classify with it has
+    /// to hold rows of one entity, see [`Grouping::check_entity`].
+    pub fn grouping(&self) -> Option<&Grouping> {
+        self.grouping.as_ref()
+    }
+
+    pub fn set_grouping(&mut self, grouping: Option<Grouping>) {
+        self.grouping = grouping;
+    }
+
+    /// How trees treat missing values, see [`MissingSemantics`]. Classifiers saved
+    /// before missing values were supported keep applying functions to NaN.
+    pub fn missing_semantics(&self) -> MissingSemantics {
+        self.trees
+            .first()
+            .map_or_else(MissingSemantics::default, |t| t.tree().missing_semantics())
+    }
+
+    /// Names of columns trees use, i.e. [`Classifier::header`] after categorical
+    /// encoding. `None` if header is unknown.
+    pub fn column_names(&self) -> Option<Vec<String>> {
+        if self.header.is_empty() {
+            None
+        } else {
+            match &self.dictionary {
+                Some(dictionary) => Some(dictionary.column_names(&self.header)),
+                None => Some(self.header.clone()),
+            }
+        }
+    }
+
+    /// What happens to data before trees see it, for descriptions of the classifier:
+    /// derived columns added, then columns scaled, see [`Scaling::describe`]. Empty if
+    /// data is only encoded.
+    pub fn preprocessing(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for feature in self.features.iter().flat_map(Features::features) {
+            lines.push(format!("derived: {} = {}", feature.name, feature.expression));
+        }
+        if let Some(scaling) = &self.scaling {
+            let names = self.column_names().unwrap_or_default();
+            let names = (0..self.input_shape().columns())
+                .map(|column| match names.get(column).filter(|n| !n.trim().is_empty()) {
+                    Some(name) => quote_name(name.trim()),
+                    None => format!("col{}", column),
+                })
+                .collect::<Vec<_>>();
+            lines.extend(scaling.describe(&names).into_iter().map(|l| format!("scaled: {}", l)));
+        }
+        lines
+    }
+
     pub fn average_score(&self) -> Option<f32> {
         let mut val = 0.0;
         for tree in &self.trees {
@@ -92,27 +263,74 @@ impl Classifier {
 
     pub fn sorted_trees(&self) -> Vec<&ScoredTree> {
         let mut scores = self.trees.iter().collect::<Vec<_>>();
-        scores.sort_unstable_by(|&t1, &t2| t1.partial_cmp(&t2).unwrap_or(Ordering::Equal));
+        scores.sort_unstable_by(|&t1, &t2| t1.sort_order(t2));
         scores
     }
 
-    pub fn classify(&self, data: &DataView) -> Vec<&str> {
+    /// Describes trees in order they are applied by [`Classifier::classify`]: a data
+    /// point gets class of the last tree with formula's value at or above threshold.
+    pub fn describe(&self, header: Option<&[String]>) -> Vec<TreeDescription> {
+        self.sorted_trees()
+            .into_iter()
+            .map(|tree| TreeDescription {
+                class: self.classes.get(&tree.score().class()).cloned().unwrap_or_default(),
+                threshold: tree.score().threshold().value(),
+                score: tree.score().value(),
+                formula: tree.tree().to_formula(header),
+            })
+            .collect()
+    }
+
+    /// Structures of trees in the same order as [`Classifier::describe`].
+    pub fn structure(&self) -> Vec<TreeStructure> {
+        self.sorted_trees()
+            .into_iter()
+            .map(|tree| TreeStructure {
+                class: self.classes.get(&tree.score().class()).cloned().unwrap_or_default(),
+                threshold: tree.score().threshold().value(),
+                score: tree.score().value(),
+                tree: tree.tree().clone(),
+            })
+            .collect()
+    }
+
+    /// Classifies data points. A point no tree guessed is reported as an empty string,
+    /// unless some tree couldn't tell (e.g. due to missing values), then it's
+    /// [`UNKNOWN_CLASS`].
+    pub fn classify<T: Value>(&self, data: &DataView<T>) -> Vec<&str> {
+        match &self.scaling {
+            Some(scaling) => self.classify_scaled(&scaling.scaled(data)),
+            None => self.classify_scaled(data),
+        }
+    }
+
+    fn classify_scaled<T: Value>(&self, data: &DataView<T>) -> Vec<&str> {
         let trees = self.sorted_trees();
         let mut responses = vec![""; data.cells().get(0, 0).len()];
+        let mut unknown = vec![false; responses.len()];
+        let mut registers = Registers::new();
         for tree in trees {
-            let values = tree.execute(&data);
+            let values = CompiledTree::new(tree.tree()).execute(data.cells(), &mut registers);
             let class_string = self.classes.get(&tree.score().class()).unwrap();
-            for (value, response) in values.iter().zip(responses.iter_mut()) {
+            for ((value, response), unknown) in
+                values.iter().zip(responses.iter_mut()).zip(unknown.iter_mut())
+            {
                 match tree.guess(*value) {
                     Some(guess) if guess => *response = class_string,
+                    None => *unknown = true,
                     _ => {}
                 }
             }
         }
+        for (response, unknown) in responses.iter_mut().zip(unknown) {
+            if response.is_empty() && unknown {
+                *response = UNKNOWN_CLASS;
+            }
+        }
         responses
     }
 
-    fn execute_for_auc(&self, data: &DataView) -> Option<f32> {
+    fn execute_for_auc<T: Value>(&self, data: &DataView<T>) -> Option<f32> {
         let mut sum_score = 0.0;
         for tree in &self.trees {
             sum_score += Classifier::calc_tree_auc(tree, data)?;
@@ -120,9 +338,16 @@ impl Classifier {
         Some(sum_score / self.trees.len() as f32)
     }
 
-    pub fn score(&self, data: &DataView) -> Option<ClassifierScore> {
+    pub fn score<T: Value>(&self, data: &DataView<T>) -> Option<ClassifierScore> {
+        match &self.scaling {
+            Some(scaling) => self.score_scaled(&scaling.scaled(data)),
+            None => self.score_scaled(data),
+        }
+    }
+
+    fn score_scaled<T: Value>(&self, data: &DataView<T>) -> Option<ClassifierScore> {
         let auc = self.execute_for_auc(data)?;
-        let predictions = self.classify(data);
+        let predictions = self.classify_scaled(data);
         let mut label_count_map = HashMap::new();
         let mut correct = 0;
         let mut total = 0;
@@ -135,6 +360,9 @@ impl Classifier {
             let label = (*prediction).to_owned();
             let class_count = label_count_map.remove(&label).unwrap_or(0);
             label_count_map.insert(label, class_count + 1);
+            if *prediction == UNKNOWN_CLASS {
+                continue;
+            }
             total += 1;
             let expected = data.class_map().get(&outcome.class())?;
             if prediction == expected {
@@ -149,46 +377,109 @@ impl Classifier {
         Some(ClassifierScore { auc, accuracy, cost, label_count_map })
     }
 
-    fn calc_tree_auc(tree: &ScoredTree, data: &DataView) -> Option<f32> {
+    fn calc_tree_auc<T: Value>(tree: &ScoredTree, data: &DataView<T>) -> Option<f32> {
         let values = tree.execute(data);
-        if values.iter().any(|v| !v.is_finite()) {
+        let (values, outcomes) = known_guesses(&values, data.outcomes());
+        if values.is_empty() {
             None
         } else {
-            let mut outcomes =
-                values.into_iter().zip(data.outcomes().iter().copied()).collect::<Vec<_>>();
-            outcomes.sort_unstable_by(|(v1, _), (v2, _)| v1.partial_cmp(v2).unwrap());
-            Some(calculate_auc(&outcomes, tree.score().class()))
+            let coverage = values.len() as f32 / data.outcomes().len() as f32;
+            let outcomes = sort_guesses(&values, &outcomes);
+            Some(calculate_auc(&outcomes, tree.score().class()) * coverage)
         }
     }
 }
 
 impl Serializable for Classifier {
     fn serialize(&self, s: &mut Serializator) {
+        s.add(&self.precision);
         s.add(&self.classes);
-        s.add(&self.trees)
+        s.add(&self.trees);
+        s.add(&self.dictionary);
+        s.add(&self.header);
+        s.add(&self.scaling);
+        s.add(&self.features);
+        s.add(&self.data_version);
+        s.add(&self.imputation);
+        s.add(&self.missing_semantics());
+        s.add(&self.grouping);
     }
 }
 
 impl Deserializable for Classifier {
     fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        let legacy = !matches!(s.peek_token().map(String::as_str), Some("Single" | "Double"));
+        let precision = Precision::deserialize_or_single(s)?;
         let classes = HashMap::deserialize(s)?;
-        let trees = Vec::deserialize(s)?;
-        Ok(Classifier { classes, trees })
+        let mut trees: Vec<ScoredTree> = Vec::deserialize(s)?;
+        // classifiers saved before precision was kept have thresholds written as `f32`,
+        // read back as `f64` they'd be a bit off and change guesses at the threshold
+        if legacy {
+            for tree in &mut trees {
+                let score = tree.score();
+                tree.set_score(Score::new(
+                    score.objective(),
+                    score.class(),
+                    score.value(),
+                    score.threshold().to_single(),
+                ));
+            }
+        }
+        // classifiers saved before dictionaries, headers, scaling, features, data
+        // versions, imputation, missing semantics and grouping were introduced end here
+        let dictionary = if s.peek_token().is_some() { Option::deserialize(s)? } else { None };
+        let header = if s.peek_token().is_some() { Vec::deserialize(s)? } else { vec![] };
+        let scaling = if s.peek_token().is_some() { Option::deserialize(s)? } else { None };
+        let features = if s.peek_token().is_some() { Option::deserialize(s)? } else { None };
+        let data_version = if s.peek_token().is_some() { Option::deserialize(s)? } else { None };
+        let imputation = if s.peek_token().is_some() { Option::deserialize(s)? } else { None };
+        let missing = if s.peek_token().is_some() {
+            MissingSemantics::deserialize(s)?
+        } else {
+            MissingSemantics::Legacy
+        };
+        let grouping = if s.peek_token().is_some() { Option::deserialize(s)? } else { None };
+        for tree in &mut trees {
+            tree.set_missing_semantics(missing);
+        }
+        Ok(Classifier {
+            classes,
+            trees,
+            precision,
+            dictionary,
+            header,
+            scaling,
+            features,
+            data_version,
+            imputation,
+            grouping,
+        })
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::data::data_set::test::create_simple_data;
+    use crate::data::data_set::{DataSet, Point};
+    use crate::data::dictionary::{CategoryEncoding, Dictionary};
+    use crate::data::features::{Feature, Features};
+    use crate::data::importer::{Grouping, IncompleteWindows, MissingValues};
+    use crate::data::imputation::Imputation;
+    use crate::data::metadata::DataVersion;
     use crate::data::outcome::Class;
-    use crate::data::InputShape;
-    use crate::exec::classifier::Classifier;
+    use crate::data::scaling::{Scaler, Scaling};
+    use crate::data::{Input, InputShape, Outcome};
+    use crate::exec::classifier::{Classifier, UNKNOWN_CLASS};
+    use crate::exec::functions::MissingSemantics;
     use crate::exec::score::Objective::Auc;
     use crate::exec::score::{Score, Threshold};
     use crate::exec::scored_tree::ScoredTree;
     use crate::exec::training_group::TrainingGroup;
+    use crate::exec::tree::test::create_short_tree;
     use crate::exec::tree::Tree;
     use crate::serialization::serializator::test::test_serialization;
+    use crate::serialization::{Deserializable, Serializator};
+    use crate::value::Precision;
     use std::collections::HashMap;
 
     #[test]
@@ -197,7 +488,7 @@ mod test {
             let (d1, d2, _) = create_simple_data(100).shuffle().into_3_views_split();
             let mut training_group = TrainingGroup::new(d1, d2, Auc, 5, &[]).unwrap();
             loop {
-                training_group.next_generation();
+                training_group.next_generation().unwrap();
                 if let Ok(classifier) = training_group.classifier() {
                     test_serialization(classifier);
                     break;
@@ -206,16 +497,137 @@ mod test {
         }
     }
 
+    #[test]
+    fn classify_unknown() {
+        let mut classes = HashMap::new();
+        classes.insert(Class::new(0), "false".to_string());
+        classes.insert(Class::new(1), "true".to_string());
+        let trees = vec![
+            ScoredTree::new(
+                create_short_tree(),
+                Score::new(Auc, Class::new(0), 0.9, Threshold::new(10.0)),
+            ),
+            ScoredTree::new(
+                create_short_tree(),
+                Score::new(Auc, Class::new(1), 0.9, Threshold::new(0.5)),
+            ),
+        ];
+        let classifier = Classifier::new(classes.clone(), trees, Precision::Single).unwrap();
+        let mut data = DataSet::new(classes);
+        for &value in &[1.0, 0.0, f32::NAN] {
+            data.add_data_point(Point::new(
+                Input::from_vector(vec![vec![value]]).unwrap(),
+                Outcome::new(Class::new(1), 1.0, -1.0),
+            ))
+            .unwrap();
+        }
+        let responses = classifier.classify(&data.into_view());
+        assert_eq!(responses, vec!["true", "", UNKNOWN_CLASS]);
+    }
+
+    #[test]
+    fn legacy_thresholds() {
+        let mut classes = HashMap::new();
+        classes.insert(Class::new(1), "true".to_string());
+        // written as f32 used to be, `0.7`, which is more than 0.7_f32 as f64
+        let trees = vec![ScoredTree::new(
+            create_short_tree(),
+            Score::new(Auc, Class::new(1), 0.9, Threshold::new(0.7_f64)),
+        )];
+        let mut s = Serializator::new();
+        s.add_items(&[&classes, &trees]);
+        let classifier = Classifier::deserialize(&mut s).unwrap();
+        assert_eq!(classifier.precision(), Precision::Single);
+        let mut data = DataSet::new(classes.clone());
+        for &value in &[0.7_f32, 0.69] {
+            data.add_data_point(Point::new(
+                Input::from_vector(vec![vec![value]]).unwrap(),
+                Outcome::new(Class::new(1), 1.0, -1.0),
+            ))
+            .unwrap();
+        }
+        let data = data.into_view();
+        assert_eq!(classifier.classify(&data), vec!["true", ""]);
+
+        // current classifiers keep their thresholds
+        let mut s = Serializator::new();
+        s.add_items(&[&Precision::Single, &classes, &trees]);
+        let classifier = Classifier::deserialize(&mut s).unwrap();
+        assert_eq!(classifier.classify(&data), vec!["", ""]);
+    }
+
+    #[test]
+    fn serialize_dictionary() {
+        let mut classes = HashMap::new();
+        classes.insert(Class::new(0), "false".to_string());
+        let trees = vec![ScoredTree::new(
+            create_short_tree(),
+            Score::new(Auc, Class::new(0), 0.9, Threshold::new(0.5)),
+        )];
+        // older classifiers end with trees
+        let mut s = Serializator::new();
+        s.add_items(&[&Precision::Single, &classes, &trees]);
+        let mut classifier = Classifier::deserialize(&mut s).unwrap();
+        assert_eq!(classifier.dictionary(), None);
+        assert_eq!(classifier.missing_semantics(), MissingSemantics::Legacy);
+
+        let dictionary = Dictionary::build(CategoryEncoding::Ordinal, &[vec!["a"]], &[true]);
+        classifier.set_dictionary(Some(dictionary));
+        assert_eq!(classifier.column_names(), None);
+        classifier.set_header(vec!["name".to_owned()]);
+        assert_eq!(classifier.column_names(), Some(vec!["name".to_owned()]));
+        let feature = Feature {
+            name: "double".to_owned(),
+            expression: "mul column 1 number 2".to_owned(),
+        };
+        let features = Features::fit(&[feature], &mut [vec!["1".to_owned()]]).unwrap();
+        classifier.set_features(Some(features));
+        let data_version = DataVersion { data_name: "data".to_owned(), version: 2 };
+        classifier.set_data_version(Some(data_version));
+        let imputation = Imputation::fit(MissingValues::Constant(-1.0), &[vec![f32::NAN]]);
+        classifier.set_imputation(Some(imputation));
+        let grouping =
+            Grouping { id_column: 1, order_column: Some(2), incomplete: IncompleteWindows::Pad };
+        classifier.set_grouping(Some(grouping));
+        test_serialization(classifier);
+    }
+
+    #[test]
+    fn classify_scaled() {
+        let mut classes = HashMap::new();
+        classes.insert(Class::new(1), "true".to_string());
+        let trees = vec![ScoredTree::new(
+            create_short_tree(),
+            Score::new(Auc, Class::new(1), 0.9, Threshold::new(0.5)),
+        )];
+        let mut classifier = Classifier::new(classes.clone(), trees, Precision::Single).unwrap();
+        let mut data = DataSet::new(classes);
+        for &value in &[10.0, 40.0, 20.0] {
+            data.add_data_point(Point::new(
+                Input::from_vector(vec![vec![value]]).unwrap(),
+                Outcome::new(Class::new(1), 1.0, -1.0),
+            ))
+            .unwrap();
+        }
+        let data = data.into_view();
+        assert_eq!(classifier.classify(&data), vec!["true", "true", "true"]);
+        let scaling = Scaling::fit(&[Scaler::MinMax], &data).unwrap();
+        classifier.set_scaling(Some(scaling));
+        assert_eq!(classifier.classify(&data), vec!["", "true", ""]);
+        assert_eq!(classifier.score(&data).unwrap().accuracy, 1.0);
+        test_serialization(classifier);
+    }
+
     #[test]
     fn test_empty_classifier() {
         let classes = HashMap::new();
-        let r = Classifier::new(classes, vec![]);
+        let r = Classifier::new(classes, vec![], Precision::Single);
         assert!(r.is_err());
 
         let mut classes = HashMap::new();
         classes.insert(Class::new(0), "true".to_string());
         classes.insert(Class::new(1), "false".to_string());
-        let r = Classifier::new(classes, vec![]);
+        let r = Classifier::new(classes, vec![], Precision::Single);
         assert!(r.is_err());
 
         let t = Tree::new(&InputShape::new(1, 1), 3, &[], 0.5, 0.5);
@@ -223,6 +635,7 @@ mod test {
         let r = Classifier::new(
             classes,
             vec![ScoredTree::new(t, Score::new(Auc, Class::new(0), 0.9, Threshold::new(0.0)))],
+            Precision::Single,
         );
         assert!(r.is_err());
     }
@@ -240,7 +653,7 @@ mod test {
         let mut classes = HashMap::new();
         classes.insert(Class::new(0), "true".to_string());
         classes.insert(Class::new(1), "false".to_string());
-        let r = Classifier::new(classes, trees);
+        let r = Classifier::new(classes, trees, Precision::Single);
         assert!(r.is_err());
 
         let mut trees = vec![];
@@ -255,7 +668,7 @@ mod test {
         classes.insert(Class::new(0), "true".to_string());
         classes.insert(Class::new(1), "false".to_string());
         classes.insert(Class::new(2), "null".to_string());
-        let r = Classifier::new(classes, trees);
+        let r = Classifier::new(classes, trees, Precision::Single);
         assert!(r.is_err());
     }
 }
@@ -0,0 +1,566 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::data_set::DataView;
+use crate::data::outcome::Class;
+use crate::data::Data;
+use crate::exec::functions::{DoubleArgFunction, MissingSemantics, SingleArgFunction};
+use crate::exec::score::{Objective, Score};
+use crate::exec::tree::{score_guesses, Tree};
+use crate::math::std_dev_into;
+use crate::value::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+/// A single step of [`CompiledTree`] program. Every instruction leaves exactly one
+/// vector on the stack, already multiplied by its weight.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Instruction {
+    DataValue(usize, usize, f32),
+    StdDev(usize, usize, f32),
+    Constant(f64, f32),
+    SingleArgFunction(&'static SingleArgFunction, f32),
+    DoubleArgFunction(&'static DoubleArgFunction, f32),
+}
+
+impl PartialEq for Instruction {
+    fn eq(&self, other: &Self) -> bool {
+        use Instruction::*;
+        match (*self, *other) {
+            (DataValue(r1, c1, w1), DataValue(r2, c2, w2))
+            | (StdDev(r1, c1, w1), StdDev(r2, c2, w2)) => {
+                (r1, c1, w1.to_bits()) == (r2, c2, w2.to_bits())
+            }
+            (Constant(v1, w1), Constant(v2, w2)) => {
+                (v1.to_bits(), w1.to_bits()) == (v2.to_bits(), w2.to_bits())
+            }
+            (SingleArgFunction(f1, w1), SingleArgFunction(f2, w2)) => {
+                std::ptr::eq(f1, f2) && w1.to_bits() == w2.to_bits()
+            }
+            (DoubleArgFunction(f1, w1), DoubleArgFunction(f2, w2)) => {
+                std::ptr::eq(f1, f2) && w1.to_bits() == w2.to_bits()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Reusable vector buffers for [`CompiledTree`] execution. One instance can be shared
+/// by many trees executed one after another, so vectors are allocated only once.
+#[derive(Debug, Default)]
+pub struct Registers<T: Value = f32> {
+    stack: Vec<Vec<T>>,
+    free: Vec<Vec<T>>,
+}
+
+impl<T: Value> Registers<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Registers::default()
+    }
+
+    fn reset(&mut self) {
+        let free = &mut self.free;
+        self.stack.drain(..).for_each(|v| free.push(v));
+    }
+
+    fn take(&mut self, len: usize) -> Vec<T> {
+        let mut v = self.free.pop().unwrap_or_else(|| Vec::with_capacity(len));
+        v.clear();
+        v
+    }
+}
+
+/// Minimal node count of a subtree whose output is stored in [`SubtreeCache`]. Smaller
+/// subtrees are as cheap to recompute as to copy from the cache.
+const MIN_CACHED_NODES: usize = 3;
+
+/// A bounded cache of subtree outputs keyed by subtree structural hash. Trees bred from
+/// the same parent share most of their structure, so their unchanged subtrees can be
+/// copied from here instead of being computed again. Every entry keeps its subtree's
+/// program, so a hash collision is a miss, not someone else's values.
+///
+/// Cached values are valid only for the data and [`MissingSemantics`] they were computed
+/// with, so the cache is cleared whenever it's used with a different [`DataView`], see
+/// [`DataView::id`], or a tree with different semantics.
+#[derive(Debug)]
+pub struct SubtreeCache<T: Value = f32> {
+    values: HashMap<u64, CachedSubtree<T>>,
+    order: VecDeque<u64>,
+    capacity: usize,
+    size: usize,
+    data_id: Option<(u64, MissingSemantics)>,
+    hits: u64,
+    lookups: u64,
+}
+
+#[derive(Debug)]
+struct CachedSubtree<T: Value> {
+    program: Vec<Instruction>,
+    values: Vec<T>,
+}
+
+impl<T: Value> SubtreeCache<T> {
+    /// Creates an empty cache.
+    ///
+    /// # Arguments
+    /// * `capacity` - maximal number of values (sum of all vectors' lengths) to keep
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        SubtreeCache {
+            values: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+            size: 0,
+            data_id: None,
+            hits: 0,
+            lookups: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.order.clear();
+        self.size = 0;
+    }
+
+    /// Changes maximal number of values to keep, dropping the oldest ones above it
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict(0);
+    }
+
+    /// Returns number of cache hits and lookups since the last call and resets both.
+    pub fn take_stats(&mut self) -> (u64, u64) {
+        let stats = (self.hits, self.lookups);
+        self.hits = 0;
+        self.lookups = 0;
+        stats
+    }
+
+    fn prepare(&mut self, data_id: u64, missing: MissingSemantics) {
+        if self.data_id != Some((data_id, missing)) {
+            self.clear();
+            self.data_id = Some((data_id, missing));
+        }
+    }
+
+    fn get(&mut self, hash: u64, program: &[Instruction]) -> Option<&Vec<T>> {
+        self.lookups += 1;
+        let values = self
+            .values
+            .get(&hash)
+            .filter(|cached| cached.program == program)
+            .map(|cached| &cached.values);
+        if values.is_some() {
+            self.hits += 1;
+        }
+        values
+    }
+
+    fn insert(&mut self, hash: u64, program: &[Instruction], values: &[T]) {
+        if values.len() > self.capacity || self.values.contains_key(&hash) {
+            return;
+        }
+        let mut buffer = self.evict(values.len());
+        buffer.clear();
+        buffer.extend_from_slice(values);
+        self.size += buffer.len();
+        self.values.insert(hash, CachedSubtree { program: program.to_vec(), values: buffer });
+        self.order.push_back(hash);
+    }
+
+    /// Drops the oldest values until `extra` more fit, returns the last dropped vector
+    /// for reuse
+    fn evict(&mut self, extra: usize) -> Vec<T> {
+        let mut buffer = Vec::new();
+        while self.size + extra > self.capacity {
+            match self.order.pop_front().and_then(|h| self.values.remove(&h)) {
+                Some(old) => {
+                    self.size -= old.values.len();
+                    buffer = old.values;
+                }
+                None => break,
+            }
+        }
+        buffer
+    }
+}
+
+/// A [`Tree`] flattened into a linear stack program. Produces exactly the same output
+/// as [`Tree`] execution but without recursion and without allocating a new vector
+/// for every node.
+#[derive(Debug, Clone)]
+pub struct CompiledTree {
+    program: Vec<Instruction>,
+    hashes: Vec<u64>,
+    sizes: Vec<usize>,
+    missing: MissingSemantics,
+}
+
+impl CompiledTree {
+    #[must_use]
+    pub fn new(tree: &Tree) -> Self {
+        let mut program = Vec::with_capacity(tree.node_count());
+        tree.get_start_node().compile(&mut program);
+        let mut hashes = Vec::with_capacity(program.len());
+        let mut sizes = Vec::with_capacity(program.len());
+        for (i, instruction) in program.iter().enumerate() {
+            let mut hasher = DefaultHasher::new();
+            let size = match *instruction {
+                Instruction::DataValue(r, c, w) => {
+                    (0_u8, r, c, w.to_bits()).hash(&mut hasher);
+                    1
+                }
+                Instruction::StdDev(r, c, w) => {
+                    (1_u8, r, c, w.to_bits()).hash(&mut hasher);
+                    1
+                }
+                Instruction::Constant(value, w) => {
+                    (2_u8, value.to_bits(), w.to_bits()).hash(&mut hasher);
+                    1
+                }
+                Instruction::SingleArgFunction(f, w) => {
+                    (3_u8, f.name, w.to_bits(), hashes[i - 1]).hash(&mut hasher);
+                    1 + sizes[i - 1]
+                }
+                Instruction::DoubleArgFunction(f, w) => {
+                    let second = i - 1;
+                    let first = second - sizes[second];
+                    (4_u8, f.name, w.to_bits(), hashes[first], hashes[second]).hash(&mut hasher);
+                    1 + sizes[first] + sizes[second]
+                }
+            };
+            hashes.push(hasher.finish());
+            sizes.push(size);
+        }
+        CompiledTree { program, hashes, sizes, missing: tree.missing_semantics() }
+    }
+
+    /// Structural hash of the whole tree. Trees with the same structure, functions,
+    /// data references and weights have the same hash.
+    #[must_use]
+    pub fn structural_hash(&self) -> u64 {
+        self.hashes[self.hashes.len() - 1]
+    }
+
+    /// Executes the program over data columns and returns a slice of guesses, one per
+    /// data point. The slice is valid until `registers` are used again.
+    pub fn execute<'a, T: Value>(
+        &self,
+        data: &Data<Vec<T>>,
+        registers: &'a mut Registers<T>,
+    ) -> &'a [T] {
+        let len = data.get(0, 0).len();
+        registers.reset();
+        for instruction in &self.program {
+            step(*instruction, data, len, registers, self.missing);
+        }
+        &registers.stack[0]
+    }
+
+    /// Same as [`execute`] but takes outputs of subtrees from `cache` when present and
+    /// stores outputs of computed subtrees there.
+    ///
+    /// [`execute`]: CompiledTree::execute
+    pub fn execute_cached<'a, T: Value>(
+        &self,
+        data: &DataView<T>,
+        registers: &'a mut Registers<T>,
+        cache: &mut SubtreeCache<T>,
+    ) -> &'a [T] {
+        cache.prepare(data.id(), self.missing);
+        let data = data.cells();
+        let len = data.get(0, 0).len();
+        registers.reset();
+        self.execute_subtree(self.program.len() - 1, data, len, registers, cache);
+        &registers.stack[0]
+    }
+
+    fn execute_subtree<T: Value>(
+        &self,
+        id: usize,
+        data: &Data<Vec<T>>,
+        len: usize,
+        registers: &mut Registers<T>,
+        cache: &mut SubtreeCache<T>,
+    ) {
+        let cacheable = self.sizes[id] >= MIN_CACHED_NODES;
+        let subtree = &self.program[id + 1 - self.sizes[id]..=id];
+        if cacheable {
+            if let Some(values) = cache.get(self.hashes[id], subtree) {
+                let mut v = registers.take(len);
+                v.extend_from_slice(values);
+                registers.stack.push(v);
+                return;
+            }
+        }
+        match self.program[id] {
+            Instruction::DataValue(..) | Instruction::StdDev(..) | Instruction::Constant(..) => {
+            }
+            Instruction::SingleArgFunction(..) => {
+                self.execute_subtree(id - 1, data, len, registers, cache);
+            }
+            Instruction::DoubleArgFunction(..) => {
+                let second = id - 1;
+                let first = second - self.sizes[second];
+                self.execute_subtree(first, data, len, registers, cache);
+                self.execute_subtree(second, data, len, registers, cache);
+            }
+        }
+        step(self.program[id], data, len, registers, self.missing);
+        if cacheable {
+            cache.insert(self.hashes[id], subtree, registers.stack.last().unwrap());
+        }
+    }
+
+    #[must_use]
+    pub fn execute_for_score<T: Value>(
+        &self,
+        data: &DataView<T>,
+        class: Class,
+        objective: Objective,
+        registers: &mut Registers<T>,
+    ) -> Option<Score> {
+        if data.cells().get(0, 0).len() < 2 {
+            None
+        } else {
+            score_guesses(self.execute(data.cells(), registers), data, class, objective)
+        }
+    }
+
+    /// Same as [`execute_for_score`] but uses [`SubtreeCache`].
+    ///
+    /// [`execute_for_score`]: CompiledTree::execute_for_score
+    #[must_use]
+    pub fn execute_for_score_cached<T: Value>(
+        &self,
+        data: &DataView<T>,
+        class: Class,
+        objective: Objective,
+        registers: &mut Registers<T>,
+        cache: &mut SubtreeCache<T>,
+    ) -> Option<Score> {
+        if data.cells().get(0, 0).len() < 2 {
+            None
+        } else {
+            let guesses = self.execute_cached(data, registers, cache);
+            score_guesses(guesses, data, class, objective)
+        }
+    }
+}
+
+fn step<T: Value>(
+    instruction: Instruction,
+    data: &Data<Vec<T>>,
+    len: usize,
+    registers: &mut Registers<T>,
+    missing: MissingSemantics,
+) {
+    match instruction {
+        Instruction::DataValue(r, c, w) => {
+            let mut v = registers.take(len);
+            let w = T::from_f32(w);
+            v.extend(data.get(r, c).iter().map(|value| w * *value));
+            registers.stack.push(v);
+        }
+        Instruction::StdDev(r, c, w) => {
+            let mut v = registers.take(len);
+            std_dev_into(data.get(r, c), &mut v);
+            multiply(&mut v, w);
+            registers.stack.push(v);
+        }
+        Instruction::Constant(value, w) => {
+            let mut v = registers.take(len);
+            v.resize(len, T::from_f32(w) * T::from_f64(value));
+            registers.stack.push(v);
+        }
+        Instruction::SingleArgFunction(f, w) => {
+            let v = registers.stack.pop().unwrap();
+            let mut v = f.apply(v, missing);
+            multiply(&mut v, w);
+            registers.stack.push(v);
+        }
+        Instruction::DoubleArgFunction(f, w) => {
+            let v2 = registers.stack.pop().unwrap();
+            let v1 = registers.stack.pop().unwrap();
+            let mut v1 = f.apply(v1, &v2, missing);
+            multiply(&mut v1, w);
+            registers.stack.push(v1);
+            registers.free.push(v2);
+        }
+    }
+}
+
+fn multiply<T: Value>(v: &mut [T], weight: f32) {
+    let weight = T::from_f32(weight);
+    v.iter_mut().for_each(|value| *value = *value * weight);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::data_set::test::{create_multiclass_data, create_simple_data_as};
+    use crate::data::outcome::Class;
+    use crate::data::InputShape;
+    use crate::exec::compiled_tree::{CompiledTree, Instruction, Registers, SubtreeCache};
+    use crate::exec::functions::MissingSemantics;
+    use crate::exec::score::Objective;
+    use crate::exec::tree::Tree;
+    use crate::rand::GET_RNG;
+    use rand::Rng;
+
+    #[test]
+    fn same_output_as_tree() {
+        let data = create_multiclass_data().into_view();
+        let mut registers = Registers::new();
+        let mut rng = GET_RNG();
+        for _ in 0..10_000 {
+            let max_depth = rng.gen_range(2, 10);
+            let data_prob = rng.gen_range(0.01, 0.99);
+            let branch_prob = rng.gen_range(0.01, 0.99);
+            let tree = Tree::new(data.input_shape(), max_depth, &[], branch_prob, data_prob);
+            let expected = tree.execute(&data);
+            let actual = CompiledTree::new(&tree).execute(data.cells(), &mut registers);
+            let expected = expected.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+            let actual = actual.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn same_double_output_as_tree() {
+        let data = create_simple_data_as::<f64>(100).into_view();
+        let mut registers = Registers::new();
+        for _ in 0..1_000 {
+            let tree = Tree::new(data.input_shape(), 6, &[], 0.5, 0.5);
+            let expected = tree.execute(&data);
+            let actual = CompiledTree::new(&tree).execute(data.cells(), &mut registers);
+            let expected = expected.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+            let actual = actual.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn same_score_as_tree() {
+        let data = create_multiclass_data().into_view();
+        let mut registers = Registers::new();
+        for _ in 0..1_000 {
+            let tree = Tree::new(data.input_shape(), 5, &[], 0.5, 0.5);
+            let compiled = CompiledTree::new(&tree);
+            for objective in &[Objective::Auc, Objective::Accuracy, Objective::Cost] {
+                let expected = tree.execute_for_score(&data, Class::new(1), *objective);
+                let actual =
+                    compiled.execute_for_score(&data, Class::new(1), *objective, &mut registers);
+                assert_eq!(
+                    expected.map(|s| s.value().to_bits()),
+                    actual.map(|s| s.value().to_bits())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cached_same_output_as_tree() {
+        let data = create_multiclass_data().into_view();
+        let mut registers = Registers::new();
+        let mut cache = SubtreeCache::new(1_000);
+        for _ in 0..1_000 {
+            let parent = Tree::new(data.input_shape(), 6, &[], 0.5, 0.5);
+            for _ in 0..10 {
+                let mut child = parent.clone();
+                child.mutate(&[]);
+                child.change_weights();
+                let expected = child.execute(&data);
+                let compiled = CompiledTree::new(&child);
+                let actual = compiled.execute_cached(&data, &mut registers, &mut cache);
+                let expected = expected.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+                let actual = actual.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+                assert_eq!(expected, actual);
+            }
+        }
+        let (hits, lookups) = cache.take_stats();
+        assert!(hits > 0);
+        assert!(lookups > hits);
+        assert_eq!(cache.take_stats(), (0, 0));
+    }
+
+    #[test]
+    fn cache_capacity() {
+        let program = [Instruction::Constant(1.0, 1.0)];
+        let mut cache = SubtreeCache::new(10);
+        cache.prepare(4, MissingSemantics::Propagate);
+        cache.insert(1, &program, &[1.0, 2.0, 3.0, 4.0]);
+        cache.insert(2, &program, &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(cache.size, 8);
+        cache.insert(3, &program, &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(cache.size, 8);
+        assert!(cache.get(1, &program).is_none());
+        assert!(cache.get(2, &program).is_some());
+        assert!(cache.get(3, &program).is_some());
+        cache.set_capacity(5);
+        assert_eq!(cache.size, 4);
+        assert!(cache.get(2, &program).is_none());
+        assert!(cache.get(3, &program).is_some());
+        cache.prepare(5, MissingSemantics::Propagate);
+        assert!(cache.get(3, &program).is_none());
+    }
+
+    #[test]
+    fn cache_checks_subtree_and_data() {
+        let program = [Instruction::Constant(1.0, 1.0)];
+        let other = [Instruction::Constant(2.0, 1.0)];
+        let mut cache = SubtreeCache::new(10);
+        cache.prepare(1, MissingSemantics::Propagate);
+        cache.insert(1, &program, &[1.0, 2.0]);
+        assert!(cache.get(1, &other).is_none());
+        assert!(cache.get(1, &program).is_some());
+
+        // same length, different data
+        let data = create_multiclass_data().into_view();
+        let mut scaled = data.clone();
+        scaled.cells_mut().get_mut(0, 0)[0] += 1.0;
+        assert_ne!(data.id(), scaled.id());
+        let tree = Tree::new(data.input_shape(), 6, &[], 0.5, 0.5);
+        let compiled = CompiledTree::new(&tree);
+        let mut registers = Registers::new();
+        compiled.execute_cached(&data, &mut registers, &mut cache);
+        let cached = compiled.execute_cached(&scaled, &mut registers, &mut cache).to_vec();
+        let expected = compiled.execute(scaled.cells(), &mut registers).to_vec();
+        let bits = |v: &[f32]| v.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&cached), bits(&expected));
+    }
+
+    #[test]
+    fn structural_hash() {
+        let tree = Tree::new(&InputShape::new(2, 3), 6, &[], 0.5, 0.5);
+        let copy = tree.clone();
+        assert_eq!(
+            CompiledTree::new(&tree).structural_hash(),
+            CompiledTree::new(&copy).structural_hash()
+        );
+        let mut other = tree.clone();
+        other.select_node_mut(0).change_weight(2.0);
+        assert_ne!(
+            CompiledTree::new(&tree).structural_hash(),
+            CompiledTree::new(&other).structural_hash()
+        );
+    }
+}
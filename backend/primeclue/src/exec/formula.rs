@@ -0,0 +1,470 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::InputShape;
+use crate::error::PrimeclueErr;
+use crate::exec::functions::{MathConst, TWO_ARG_FUNCTIONS};
+use crate::exec::functions::{INFIX_OPERATORS, MATH_CONSTANTS, ONE_ARG_FUNCTIONS};
+use crate::exec::node::{Node, Weighted};
+
+const STD_DEV: &str = "std_dev";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Function, column or constant name, or a number
+    Word(String),
+    /// Column name in double quotes, unescaped
+    Quoted(String),
+    Symbol(char),
+}
+
+/// Parses an infix formula into a tree's root node. It's the syntax of
+/// [`Tree::to_formula`](crate::exec::tree::Tree::to_formula):
+/// * `+`, `-`, `*` and `/` stand for `add`, `sub`, `mul` and `div`, with the usual
+///   precedence; parentheses group
+/// * other functions are called by name: `sine(col0)`, `higher(col0, col1)`
+/// * `0`, `1`, `2`, `e`, `pi` and `2pi` are constants
+/// * any other number multiplied by something is its weight: `0.5 * col0`
+/// * a column is referenced by `header` name or as `col` with index, e.g. `col2`,
+///   followed by row index for multi row inputs: `col2[1]`. Standardized value is
+///   `std_dev(col2)`. Names that aren't plain words, look like numbers, constants or
+///   `col` with index are in double quotes, with `\"` and `\\` inside escaped:
+///   `"body mass" / "e"`, see [`quote_name`]
+/// * `-` in front of a value is `neg`
+///
+/// Errors tell the position (in characters, from 1) the formula failed at.
+pub fn parse(
+    formula: &str,
+    input_shape: &InputShape,
+    header: Option<&[String]>,
+) -> Result<Weighted, PrimeclueErr> {
+    let (tokens, end) = tokenize(formula)?;
+    let mut parser = Parser { tokens, next: 0, end, input_shape, header };
+    let node = parser.expression()?;
+    match parser.tokens.get(parser.next) {
+        None => Ok(node),
+        Some((token, position)) => error(format!("Unexpected {}", describe(token)), *position),
+    }
+}
+
+fn error<T>(message: String, position: usize) -> Result<T, PrimeclueErr> {
+    PrimeclueErr::result(format!("{} at position {}", message, position + 1))
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("'{}'", word),
+        Token::Quoted(name) => format!("'{}'", quote_name(name)),
+        Token::Symbol(symbol) => format!("'{}'", symbol),
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+/// `col` followed by a column index, e.g. `col2`
+fn is_column_index(word: &str) -> bool {
+    word.strip_prefix("col")
+        .is_some_and(|i| !i.is_empty() && i.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Writes column `name` so that [`parse`] reads it back as that name: as it is when
+/// it's a plain word, in double quotes otherwise.
+pub fn quote_name(name: &str) -> String {
+    let plain = name.chars().all(is_word_char)
+        && !name.is_empty()
+        && !is_number(name)
+        && constant(name).is_none()
+        && !is_column_index(name);
+    if plain {
+        return name.to_owned();
+    }
+    let mut quoted = String::with_capacity(name.len() + 2);
+    quoted.push('"');
+    for c in name.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Splits formula into tokens with their positions. Returns position of formula's end
+/// too.
+fn tokenize(formula: &str) -> Result<(Vec<(Token, usize)>, usize), PrimeclueErr> {
+    let chars = formula.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if is_word_char(c) {
+            let start = i;
+            while i < chars.len() && is_word_char(chars[i]) {
+                i += 1;
+                // signed exponent of a number, e.g. 1.5e-7
+                let exponent = i + 1 < chars.len()
+                    && (chars[start].is_ascii_digit() || chars[start] == '.')
+                    && (chars[i - 1] == 'e' || chars[i - 1] == 'E')
+                    && (chars[i] == '-' || chars[i] == '+')
+                    && chars[i + 1].is_ascii_digit();
+                if exponent {
+                    i += 1;
+                }
+            }
+            tokens.push((Token::Word(chars[start..i].iter().collect()), start));
+        } else if c == '"' {
+            let start = i;
+            let mut name = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return error("Unterminated name".to_owned(), start),
+                    Some('"') => break,
+                    Some('\\') if i + 1 < chars.len() => {
+                        name.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(&c) => {
+                        name.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push((Token::Quoted(name), start));
+        } else if "+-*/(),[]".contains(c) {
+            tokens.push((Token::Symbol(c), i));
+            i += 1;
+        } else {
+            return error(format!("Unexpected character '{}'", c), i);
+        }
+    }
+    Ok((tokens, chars.len()))
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    end: usize,
+    input_shape: &'a InputShape,
+    header: Option<&'a [String]>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |(_, position)| *position)
+    }
+
+    /// Consumes next token if it's one of `symbols`.
+    fn symbol(&mut self, symbols: &str) -> Option<char> {
+        match self.peek() {
+            Some(Token::Symbol(symbol)) if symbols.contains(*symbol) => {
+                let symbol = *symbol;
+                self.next += 1;
+                Some(symbol)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), PrimeclueErr> {
+        match self.symbol(&symbol.to_string()) {
+            Some(_) => Ok(()),
+            None => error(format!("Expected '{}'", symbol), self.position()),
+        }
+    }
+
+    fn word(&mut self) -> Result<(String, usize), PrimeclueErr> {
+        let position = self.position();
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                let word = word.clone();
+                self.next += 1;
+                Ok((word, position))
+            }
+            Some(token) => error(format!("Unexpected {}", describe(token)), position),
+            None => error("Unexpected end of formula".to_owned(), position),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Weighted, PrimeclueErr> {
+        let mut node = self.term()?;
+        while let Some(operator) = self.symbol("+-") {
+            node = infix(operator, node, self.term()?);
+        }
+        Ok(node)
+    }
+
+    fn term(&mut self) -> Result<Weighted, PrimeclueErr> {
+        let mut node = self.unary()?;
+        while let Some(operator) = self.symbol("*/") {
+            node = infix(operator, node, self.unary()?);
+        }
+        Ok(node)
+    }
+
+    /// Value with its weight or negation, if any.
+    fn unary(&mut self) -> Result<Weighted, PrimeclueErr> {
+        let negative = self.symbol("-").is_some();
+        let weight = match self.peek() {
+            Some(Token::Word(word)) if is_number(word) && constant(word).is_none() => {
+                let (word, position) = self.word()?;
+                let weight = word.parse::<f32>().or_else(|_| {
+                    error(format!("Unable to parse '{}' to a number", word), position)
+                })?;
+                if self.symbol("*").is_none() {
+                    return error(format!("Expected '*' after weight '{}'", word), position);
+                }
+                Some(weight)
+            }
+            _ => None,
+        };
+        match (weight, negative) {
+            (Some(weight), negative) => {
+                let mut node = self.unary()?;
+                node.change_weight(if negative { -weight } else { weight });
+                Ok(node)
+            }
+            (None, true) => {
+                let neg = ONE_ARG_FUNCTIONS.iter().find(|f| f.name == "neg").unwrap();
+                Ok(Weighted::from(Node::one_arg_node(neg, self.unary()?)))
+            }
+            (None, false) => self.primary(),
+        }
+    }
+
+    /// Quoted column name, if it's next
+    fn quoted(&mut self) -> Option<(String, usize)> {
+        match self.tokens.get(self.next) {
+            Some((Token::Quoted(name), position)) => {
+                let quoted = (name.clone(), *position);
+                self.next += 1;
+                Some(quoted)
+            }
+            _ => None,
+        }
+    }
+
+    fn primary(&mut self) -> Result<Weighted, PrimeclueErr> {
+        if self.symbol("(").is_some() {
+            let node = self.expression()?;
+            self.expect(')')?;
+            return Ok(node);
+        }
+        if let Some((name, position)) = self.quoted() {
+            let (row, column) = self.column(&name, true, position)?;
+            return Ok(Weighted::from(Node::DataValue(row, column)));
+        }
+        let (word, position) = self.word()?;
+        if let Some(constant) = constant(&word) {
+            return Ok(Weighted::from(Node::MathConstant(constant)));
+        }
+        if self.symbol("(").is_none() {
+            let (row, column) = self.column(&word, false, position)?;
+            return Ok(Weighted::from(Node::DataValue(row, column)));
+        }
+        if word == STD_DEV {
+            let (row, column) = match self.quoted() {
+                Some((name, name_position)) => self.column(&name, true, name_position)?,
+                None => {
+                    let (name, name_position) = self.word()?;
+                    self.column(&name, false, name_position)?
+                }
+            };
+            self.expect(')')?;
+            return Ok(Weighted::from(Node::StdDev(row, column)));
+        }
+        let mut args = vec![self.expression()?];
+        while self.symbol(",").is_some() {
+            args.push(self.expression()?);
+        }
+        self.expect(')')?;
+        if let Some(f) = ONE_ARG_FUNCTIONS.iter().find(|f| f.name == word) {
+            if args.len() == 1 {
+                return Ok(Weighted::from(Node::one_arg_node(f, args.remove(0))));
+            }
+        } else if let Some(f) = TWO_ARG_FUNCTIONS.iter().find(|f| f.name == word) {
+            if args.len() == 2 {
+                let second = args.remove(1);
+                return Ok(Weighted::from(Node::two_arg_node(f, args.remove(0), second)));
+            }
+        } else {
+            return error(format!("Unknown function '{}'", word), position);
+        }
+        error(format!("Wrong number of arguments for '{}': {}", word, args.len()), position)
+    }
+
+    /// Column reference by name with optional row index. Unquoted `col` with index is
+    /// the column of that index, even if some column has such name.
+    fn column(
+        &mut self,
+        name: &str,
+        quoted: bool,
+        position: usize,
+    ) -> Result<(usize, usize), PrimeclueErr> {
+        let column = if !quoted && is_column_index(name) {
+            name[3..].parse::<usize>().ok()
+        } else {
+            self.header
+                .and_then(|h| h.iter().position(|n| !n.trim().is_empty() && n.trim() == name))
+        };
+        let column = match column {
+            Some(column) if column < self.input_shape.columns() => column,
+            Some(_) => return error(format!("Column '{}' is out of input", name), position),
+            None => return error(format!("Unknown column '{}'", name), position),
+        };
+        let row = if self.symbol("[").is_some() {
+            let (row, row_position) = self.word()?;
+            let row = match row.parse::<usize>() {
+                Ok(row) if row < self.input_shape.rows() => row,
+                _ => return error(format!("Invalid row '{}'", row), row_position),
+            };
+            self.expect(']')?;
+            row
+        } else if self.input_shape.rows() == 1 {
+            0
+        } else {
+            return error(format!("Row of column '{}' is missing", name), self.position());
+        };
+        Ok((row, column))
+    }
+}
+
+fn is_number(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+}
+
+fn constant(word: &str) -> Option<&'static MathConst> {
+    MATH_CONSTANTS.iter().find(|c| c.name() == word)
+}
+
+fn infix(operator: char, n1: Weighted, n2: Weighted) -> Weighted {
+    let name = INFIX_OPERATORS.iter().find(|(_, op)| op.starts_with(operator)).unwrap().0;
+    let f = TWO_ARG_FUNCTIONS.iter().find(|f| f.name == name).unwrap();
+    Weighted::from(Node::two_arg_node(f, n1, n2))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::InputShape;
+    use crate::exec::formula::parse;
+    use crate::exec::tree::Tree;
+
+    #[test]
+    fn parses_formulas_back() {
+        for rows in 1..3 {
+            let input_shape = InputShape::new(rows, 4);
+            for _ in 0..1000 {
+                let mut tree = Tree::new(&input_shape, 6, &[], 0.5, 0.5);
+                tree.change_weights();
+                let formula = tree.to_formula(None);
+                let parsed = Tree::from_formula(&formula, &input_shape, None).unwrap();
+                assert_eq!(parsed, tree, "{}", formula);
+            }
+        }
+        let header = vec!["age".to_owned(), "".to_owned(), "height".to_owned()];
+        let input_shape = InputShape::new(1, 3);
+        for _ in 0..100 {
+            let tree = Tree::new(&input_shape, 4, &[], 0.5, 0.5);
+            let formula = tree.to_formula(Some(&header));
+            let parsed = Tree::from_formula(&formula, &input_shape, Some(&header)).unwrap();
+            assert_eq!(parsed, tree, "{}", formula);
+        }
+    }
+
+    #[test]
+    fn quoted_names() {
+        let header = ["body mass", "e", "a+b", "2nd", "col0", "say \"hi\"", "x\\y", "ok"]
+            .iter()
+            .map(|&n| n.to_owned())
+            .collect::<Vec<_>>();
+        for rows in 1..3 {
+            let input_shape = InputShape::new(rows, header.len());
+            for _ in 0..200 {
+                let tree = Tree::new(&input_shape, 5, &[], 0.5, 0.7);
+                let formula = tree.to_formula(Some(&header));
+                let parsed = Tree::from_formula(&formula, &input_shape, Some(&header)).unwrap();
+                assert_eq!(parsed, tree, "{}", formula);
+            }
+        }
+        let input_shape = InputShape::new(1, header.len());
+        let parse_str = |formula| {
+            Tree::from_formula(formula, &input_shape, Some(&header))
+                .unwrap()
+                .to_formula(Some(&header))
+        };
+        assert_eq!(parse_str(r#""body mass" / e * "e""#), r#"(("body mass" / e) * "e")"#);
+        assert_eq!(parse_str(r#"std_dev("a+b") - col0"#), r#"(std_dev("a+b") - "body mass")"#);
+        assert_eq!(parse_str(r#""col0" + ok"#), r#"("col0" + ok)"#);
+        assert_eq!(parse_str(r#""say \"hi\"" + "x\\y""#), r#"("say \"hi\"" + "x\\y")"#);
+        let error =
+            |formula| parse(formula, &input_shape, Some(&header)).unwrap_err().to_string();
+        assert_eq!(error(r#"1 + "body"#), "Unterminated name at position 5");
+        assert_eq!(error(r#"1 + "body""#), "Unknown column 'body' at position 5");
+    }
+
+    #[test]
+    fn hand_written_formula() {
+        let input_shape = InputShape::new(1, 2);
+        let parse_str =
+            |formula| Tree::from_formula(formula, &input_shape, None).unwrap().to_formula(None);
+        assert_eq!(
+            parse_str("col0 + 3 * col1 * 2 - -pi"),
+            "((col0 + (3.0 * col1 * 2)) - neg(pi))"
+        );
+        assert_eq!(
+            parse_str(" ( col0+col1 ) / 1e-3*sine(col1)"),
+            "((col0 + col1) / 0.001 * sine(col1))"
+        );
+        assert_eq!(
+            parse_str("-0.5 * higher(std_dev(col1), 2pi)"),
+            "-0.5 * higher(std_dev(col1), 2pi)"
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        let input_shape = InputShape::new(2, 2);
+        let error = |formula| parse(formula, &input_shape, None).unwrap_err().to_string();
+        assert_eq!(error(""), "Unexpected end of formula at position 1");
+        assert_eq!(error("col0[0] + "), "Unexpected end of formula at position 11");
+        assert_eq!(error("col0[0] % 2"), "Unexpected character '%' at position 9");
+        assert_eq!(error("col0[0] col1[1]"), "Unexpected 'col1' at position 9");
+        assert_eq!(error("(col0[0] + 1"), "Expected ')' at position 13");
+        assert_eq!(
+            error("sine(col0[1], 1)"),
+            "Wrong number of arguments for 'sine': 2 at position 1"
+        );
+        assert_eq!(error("cosine(1)"), "Unknown function 'cosine' at position 1");
+        assert_eq!(error("1 + col2[0]"), "Column 'col2' is out of input at position 5");
+        assert_eq!(error("1 + col1[2]"), "Invalid row '2' at position 10");
+        assert_eq!(error("1 + col1"), "Row of column 'col1' is missing at position 9");
+        assert_eq!(error("1 + age[0]"), "Unknown column 'age' at position 5");
+        assert_eq!(error("col0[0] + 3"), "Expected '*' after weight '3' at position 11");
+    }
+}
@@ -18,8 +18,9 @@
 */
 
 use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::Value;
 use std::cmp::Ordering;
-use std::f32::consts::*;
+use std::f64::consts::*;
 use std::fmt::{Debug, Error, Formatter};
 
 pub static MATH_CONSTANTS: [MathConst; 6] = [
@@ -34,12 +35,16 @@ pub static MATH_CONSTANTS: [MathConst; 6] = [
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct MathConst {
     name: &'static str,
-    value: f32,
+    value: f64,
 }
 
 impl MathConst {
-    pub fn value(&self) -> f32 {
-        self.value
+    pub fn value<T: Value>(&self) -> T {
+        T::from_f64(self.value)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
     }
 }
 
@@ -61,151 +66,231 @@ impl Deserializable for &MathConst {
     }
 }
 
-fn single_array_fun(f: fn(f32) -> f32, mut v: Vec<f32>) -> Vec<f32> {
+/// How functions treat missing (NaN) values in their arguments.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum MissingSemantics {
+    /// Result is missing if any argument is, except for functions about missing values
+    #[default]
+    Propagate,
+    /// Functions are applied to NaN as to any other value. Trees of classifiers saved
+    /// before missing values were supported work this way, so they keep their output.
+    Legacy,
+}
+
+impl Serializable for MissingSemantics {
+    fn serialize(&self, s: &mut Serializator) {
+        match self {
+            MissingSemantics::Propagate => s.add_str("Propagate"),
+            MissingSemantics::Legacy => s.add_str("Legacy"),
+        }
+    }
+}
+
+impl Deserializable for MissingSemantics {
+    fn deserialize(s: &mut Serializator) -> Result<Self, String> {
+        match s.next_token()?.as_str() {
+            "Propagate" => Ok(MissingSemantics::Propagate),
+            "Legacy" => Ok(MissingSemantics::Legacy),
+            t => Err(format!("Invalid missing semantics: {}", t)),
+        }
+    }
+}
+
+/// Sets values to missing (NaN) where `missing` is set
+fn restore_missing<T: Value>(v: &mut [T], missing: &[bool]) {
+    for (value, &missing) in v.iter_mut().zip(missing) {
+        if missing {
+            *value = T::nan();
+        }
+    }
+}
+
+fn single_array_fun<T: Value>(f: fn(T) -> T, mut v: Vec<T>) -> Vec<T> {
     for value in &mut v {
         *value = f(*value);
     }
     v
 }
 
-fn sqrt_array(v: Vec<f32>) -> Vec<f32> {
+fn sqrt_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(sqrt, v)
 }
 
-fn sqrt(v: f32) -> f32 {
+fn sqrt<T: Value>(v: T) -> T {
     v.sqrt()
 }
 
-fn square_array(v: Vec<f32>) -> Vec<f32> {
+fn square_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(square, v)
 }
 
-fn square(v: f32) -> f32 {
+fn square<T: Value>(v: T) -> T {
     v * v
 }
 
-fn log_array(v: Vec<f32>) -> Vec<f32> {
+fn log_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(log, v)
 }
 
-fn log(v: f32) -> f32 {
-    v.log(E)
+fn log<T: Value>(v: T) -> T {
+    v.log(T::E())
 }
 
-fn reciprocal_array(v: Vec<f32>) -> Vec<f32> {
+fn reciprocal_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(reciprocal, v)
 }
 
-fn reciprocal(v: f32) -> f32 {
-    1.0 / v
+fn reciprocal<T: Value>(v: T) -> T {
+    T::one() / v
 }
 
-fn sine_array(v: Vec<f32>) -> Vec<f32> {
+fn sine_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(sine, v)
 }
 
-fn sine(v: f32) -> f32 {
-    f32::sin(v)
+fn sine<T: Value>(v: T) -> T {
+    v.sin()
 }
 
-fn abs_array(v: Vec<f32>) -> Vec<f32> {
+fn abs_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(abs, v)
 }
 
-fn ceil_array(v: Vec<f32>) -> Vec<f32> {
+fn ceil_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(ceil, v)
 }
 
-fn ceil(v: f32) -> f32 {
+fn ceil<T: Value>(v: T) -> T {
     v.ceil()
 }
 
-fn abs(v: f32) -> f32 {
+fn abs<T: Value>(v: T) -> T {
     v.abs()
 }
 
-fn inc_array(v: Vec<f32>) -> Vec<f32> {
+fn inc_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(inc, v)
 }
 
-fn inc(v: f32) -> f32 {
-    v + 1.0
+fn inc<T: Value>(v: T) -> T {
+    v + T::one()
 }
 
-fn dec_array(v: Vec<f32>) -> Vec<f32> {
+fn dec_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(dec, v)
 }
 
-fn floor_array(v: Vec<f32>) -> Vec<f32> {
+fn floor_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(floor, v)
 }
 
-fn dec(v: f32) -> f32 {
-    v - 1.0
+fn dec<T: Value>(v: T) -> T {
+    v - T::one()
 }
 
-fn floor(v: f32) -> f32 {
+fn floor<T: Value>(v: T) -> T {
     v.floor()
 }
 
-fn neg_array(v: Vec<f32>) -> Vec<f32> {
+fn neg_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(neg, v)
 }
 
-fn neg(v: f32) -> f32 {
+fn neg<T: Value>(v: T) -> T {
     -v
 }
 
-fn to_one_array(v: Vec<f32>) -> Vec<f32> {
+fn to_one_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(to_one, v)
 }
 
-fn to_one(v: f32) -> f32 {
-    match v.partial_cmp(&0.0) {
-        Some(Ordering::Equal) | None => 0.0,
-        Some(Ordering::Greater) => 1.0,
-        Some(Ordering::Less) => -1.0,
+fn to_one<T: Value>(v: T) -> T {
+    match v.partial_cmp(&T::zero()) {
+        Some(Ordering::Equal) | None => T::zero(),
+        Some(Ordering::Greater) => T::one(),
+        Some(Ordering::Less) => -T::one(),
     }
 }
 
-fn tau_sigmoid_array(v: Vec<f32>) -> Vec<f32> {
+fn tau_sigmoid_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(tau_sigmoid, v)
 }
 
-fn tau_sigmoid(v: f32) -> f32 {
-    (2.0 * PI).powf(v)
+fn tau_sigmoid<T: Value>(v: T) -> T {
+    (T::from_f64(2.0) * T::PI()).powf(v)
 }
 
-fn tang_hyper_array(v: Vec<f32>) -> Vec<f32> {
+fn tang_hyper_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(tang_hyper, v)
 }
 
-fn tang_hyper(v: f32) -> f32 {
-    let e_pos = E.powf(v);
-    let e_neg = E.powf(-v);
+fn tang_hyper<T: Value>(v: T) -> T {
+    let e_pos = T::E().powf(v);
+    let e_neg = T::E().powf(-v);
     (e_pos - e_neg) / (e_pos + e_neg)
 }
 
-fn relu_array(v: Vec<f32>) -> Vec<f32> {
+fn relu_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(relu, v)
 }
 
-fn round_array(v: Vec<f32>) -> Vec<f32> {
+fn round_array<T: Value>(v: Vec<T>) -> Vec<T> {
     single_array_fun(round, v)
 }
 
-fn round(v: f32) -> f32 {
+fn round<T: Value>(v: T) -> T {
     v.round()
 }
 
-fn relu(v: f32) -> f32 {
-    v.max(0.0)
+fn relu<T: Value>(v: T) -> T {
+    v.max(T::zero())
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+fn is_missing_array<T: Value>(mut v: Vec<T>) -> Vec<T> {
+    for value in &mut v {
+        *value = is_missing(*value);
+    }
+    v
+}
+
+fn is_missing<T: Value>(v: T) -> T {
+    if v.is_nan() {
+        T::one()
+    } else {
+        T::zero()
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
 pub struct SingleArgFunction {
     pub name: &'static str,
     pub fun: fn(Vec<f32>) -> Vec<f32>,
+    pub fun64: fn(Vec<f64>) -> Vec<f64>,
+    /// Function is about missing values, so it gets them as they are
+    pub missing_aware: bool,
+}
+
+impl SingleArgFunction {
+    /// Applies function to every value, treating missing ones according to `missing`
+    pub(crate) fn apply<T: Value>(&self, v: Vec<T>, missing: MissingSemantics) -> Vec<T> {
+        let f = T::single_arg_fun(self);
+        if missing == MissingSemantics::Legacy
+            || self.missing_aware
+            || !v.iter().any(|v| v.is_nan())
+        {
+            return f(v);
+        }
+        let was_missing = v.iter().map(|v| v.is_nan()).collect::<Vec<_>>();
+        let mut v = f(v);
+        restore_missing(&mut v, &was_missing);
+        v
+    }
+}
+
+impl PartialEq for SingleArgFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
 }
 
 impl Serializable for SingleArgFunction {
@@ -226,182 +311,277 @@ impl Deserializable for &SingleArgFunction {
     }
 }
 
-pub static ONE_ARG_FUNCTIONS: [SingleArgFunction; 16] = [
-    SingleArgFunction { name: "abs", fun: abs_array },
-    SingleArgFunction { name: "ceil", fun: ceil_array },
-    SingleArgFunction { name: "dec", fun: dec_array },
-    SingleArgFunction { name: "floor", fun: floor_array },
-    SingleArgFunction { name: "inc", fun: inc_array },
-    SingleArgFunction { name: "log", fun: log_array },
-    SingleArgFunction { name: "neg", fun: neg_array },
-    SingleArgFunction { name: "normalize", fun: to_one_array },
-    SingleArgFunction { name: "reciprocal", fun: reciprocal_array },
-    SingleArgFunction { name: "relu", fun: relu_array },
-    SingleArgFunction { name: "round", fun: round_array },
-    SingleArgFunction { name: "sine", fun: sine_array },
-    SingleArgFunction { name: "sqrt", fun: sqrt_array },
-    SingleArgFunction { name: "square", fun: square_array },
-    SingleArgFunction { name: "tau_sigmoid", fun: tau_sigmoid_array },
-    SingleArgFunction { name: "tang_hyper", fun: tang_hyper_array },
+pub static ONE_ARG_FUNCTIONS: [SingleArgFunction; 17] = [
+    SingleArgFunction { name: "abs", fun: abs_array, fun64: abs_array, missing_aware: false },
+    SingleArgFunction { name: "ceil", fun: ceil_array, fun64: ceil_array, missing_aware: false },
+    SingleArgFunction { name: "dec", fun: dec_array, fun64: dec_array, missing_aware: false },
+    SingleArgFunction {
+        name: "floor",
+        fun: floor_array,
+        fun64: floor_array,
+        missing_aware: false,
+    },
+    SingleArgFunction { name: "inc", fun: inc_array, fun64: inc_array, missing_aware: false },
+    SingleArgFunction {
+        name: "is_missing",
+        fun: is_missing_array,
+        fun64: is_missing_array,
+        missing_aware: true,
+    },
+    SingleArgFunction { name: "log", fun: log_array, fun64: log_array, missing_aware: false },
+    SingleArgFunction { name: "neg", fun: neg_array, fun64: neg_array, missing_aware: false },
+    SingleArgFunction {
+        name: "normalize",
+        fun: to_one_array,
+        fun64: to_one_array,
+        missing_aware: false,
+    },
+    SingleArgFunction {
+        name: "reciprocal",
+        fun: reciprocal_array,
+        fun64: reciprocal_array,
+        missing_aware: false,
+    },
+    SingleArgFunction { name: "relu", fun: relu_array, fun64: relu_array, missing_aware: false },
+    SingleArgFunction {
+        name: "round",
+        fun: round_array,
+        fun64: round_array,
+        missing_aware: false,
+    },
+    SingleArgFunction { name: "sine", fun: sine_array, fun64: sine_array, missing_aware: false },
+    SingleArgFunction { name: "sqrt", fun: sqrt_array, fun64: sqrt_array, missing_aware: false },
+    SingleArgFunction {
+        name: "square",
+        fun: square_array,
+        fun64: square_array,
+        missing_aware: false,
+    },
+    SingleArgFunction {
+        name: "tau_sigmoid",
+        fun: tau_sigmoid_array,
+        fun64: tau_sigmoid_array,
+        missing_aware: false,
+    },
+    SingleArgFunction {
+        name: "tang_hyper",
+        fun: tang_hyper_array,
+        fun64: tang_hyper_array,
+        missing_aware: false,
+    },
 ];
 
-fn two_arrays_fun(f: fn(f32, f32) -> f32, mut v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn two_arrays_fun<T: Value>(f: fn(T, T) -> T, mut v1: Vec<T>, v2: &[T]) -> Vec<T> {
     for (value1, value2) in v1.iter_mut().zip(v2) {
         *value1 = f(*value1, *value2);
     }
     v1
 }
 
-fn add_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn add_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(add, v1, v2)
 }
-fn add(v1: f32, v2: f32) -> f32 {
+fn add<T: Value>(v1: T, v2: T) -> T {
     v1 + v2
 }
 
-fn sub_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn sub_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(sub, v1, v2)
 }
-fn sub(v1: f32, v2: f32) -> f32 {
+fn sub<T: Value>(v1: T, v2: T) -> T {
     v1 - v2
 }
 
-fn div_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn div_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(div, v1, v2)
 }
-fn div(v1: f32, v2: f32) -> f32 {
+fn div<T: Value>(v1: T, v2: T) -> T {
     v1 / v2
 }
 
-fn mul_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn mul_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(mul, v1, v2)
 }
-fn mul(v1: f32, v2: f32) -> f32 {
+fn mul<T: Value>(v1: T, v2: T) -> T {
     v1 * v2
 }
 
-fn higher_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn higher_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(higher, v1, v2)
 }
-fn higher(v1: f32, v2: f32) -> f32 {
+fn higher<T: Value>(v1: T, v2: T) -> T {
     v1.max(v2)
 }
 
-fn lower_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn lower_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(lower, v1, v2)
 }
-fn lower(v1: f32, v2: f32) -> f32 {
+fn lower<T: Value>(v1: T, v2: T) -> T {
     v1.min(v2)
 }
 
-fn equal_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn equal_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(equal, v1, v2)
 }
 
-fn equal(v1: f32, v2: f32) -> f32 {
-    if (1.0 - v1 / v2).abs() < 0.01 {
-        1.0
+fn equal<T: Value>(v1: T, v2: T) -> T {
+    if (T::one() - v1 / v2).abs() < T::from_f64(0.01) {
+        T::one()
     } else {
-        0.0
+        T::zero()
     }
 }
 
-fn abs_higher_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn abs_higher_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(abs_higher, v1, v2)
 }
-fn abs_higher(v1: f32, v2: f32) -> f32 {
+fn abs_higher<T: Value>(v1: T, v2: T) -> T {
     v1.abs().max(v2.abs())
 }
 
-fn abs_lower_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn abs_lower_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(abs_lower, v1, v2)
 }
-fn abs_lower(v1: f32, v2: f32) -> f32 {
+fn abs_lower<T: Value>(v1: T, v2: T) -> T {
     v1.abs().min(v2.abs())
 }
 
-fn mid_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn mid_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(mid, v1, v2)
 }
-fn mid(v1: f32, v2: f32) -> f32 {
-    (v1 + v2) / 2.0
+fn mid<T: Value>(v1: T, v2: T) -> T {
+    (v1 + v2) / T::from_f64(2.0)
 }
 
-fn sum_of_squares_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn sum_of_squares_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(sum_of_squares, v1, v2)
 }
-fn sum_of_squares(v1: f32, v2: f32) -> f32 {
+fn sum_of_squares<T: Value>(v1: T, v2: T) -> T {
     v1 * v1 + v2 * v2
 }
 
-fn first_is_higher_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn first_is_higher_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(first_is_higher, v1, v2)
 }
-fn first_is_higher(v1: f32, v2: f32) -> f32 {
+fn first_is_higher<T: Value>(v1: T, v2: T) -> T {
     if v1 > v2 {
-        1.0
+        T::one()
     } else {
-        0.0
+        T::zero()
     }
 }
 
-fn xor_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn xor_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(xor, v1, v2)
 }
-fn xor(v1: f32, v2: f32) -> f32 {
-    if (v1 == 0.0 && v2 != 0.0) || (v1 != 0.0 && v2 == 0.0) {
-        1.0
+fn xor<T: Value>(v1: T, v2: T) -> T {
+    if (v1.is_zero() && !v2.is_zero()) || (!v1.is_zero() && v2.is_zero()) {
+        T::one()
     } else {
-        0.0
+        T::zero()
     }
 }
 
-fn or_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn or_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(or, v1, v2)
 }
-fn or(v1: f32, v2: f32) -> f32 {
-    if v1 != 0.0 || v2 != 0.0 {
-        1.0
+fn or<T: Value>(v1: T, v2: T) -> T {
+    if !v1.is_zero() || !v2.is_zero() {
+        T::one()
     } else {
-        0.0
+        T::zero()
     }
 }
 
-fn round_equal_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn round_equal_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(round_equal, v1, v2)
 }
 
 #[allow(clippy::float_cmp)]
-fn round_equal(v1: f32, v2: f32) -> f32 {
+fn round_equal<T: Value>(v1: T, v2: T) -> T {
     if v1.round() == v2.round() {
-        1.0
+        T::one()
     } else {
-        0.0
+        T::zero()
     }
 }
 
-fn and_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn and_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(and, v1, v2)
 }
-fn and(v1: f32, v2: f32) -> f32 {
-    if v1 != 0.0 && v2 != 0.0 {
-        1.0
+fn and<T: Value>(v1: T, v2: T) -> T {
+    if !v1.is_zero() && !v2.is_zero() {
+        T::one()
     } else {
-        0.0
+        T::zero()
     }
 }
 
-fn diff_array(v1: Vec<f32>, v2: &[f32]) -> Vec<f32> {
+fn diff_array<T: Value>(v1: Vec<T>, v2: &[T]) -> Vec<T> {
     two_arrays_fun(diff, v1, v2)
 }
-fn diff(v1: f32, v2: f32) -> f32 {
-    (1.0 - v1 / v2).abs()
+fn diff<T: Value>(v1: T, v2: T) -> T {
+    (T::one() - v1 / v2).abs()
+}
+
+fn if_missing_array<T: Value>(mut v1: Vec<T>, v2: &[T]) -> Vec<T> {
+    for (value1, value2) in v1.iter_mut().zip(v2) {
+        *value1 = if_missing(*value1, *value2);
+    }
+    v1
+}
+
+/// Returns the first value unless it's missing (NaN), the second one otherwise
+fn if_missing<T: Value>(v1: T, v2: T) -> T {
+    if v1.is_nan() {
+        v2
+    } else {
+        v1
+    }
+}
+
+/// Functions written as infix operators in formulas, see [`Tree::to_formula`].
+///
+/// [`Tree::to_formula`]: crate::exec::tree::Tree::to_formula
+pub const INFIX_OPERATORS: [(&str, &str); 4] =
+    [("add", "+"), ("sub", "-"), ("mul", "*"), ("div", "/")];
+
+/// Returns infix operator for a two argument function, if it has one.
+pub fn infix_operator(name: &str) -> Option<&'static str> {
+    INFIX_OPERATORS.iter().find(|(n, _)| *n == name).map(|(_, op)| *op)
 }
 
 #[derive(Copy, Clone)]
 pub struct DoubleArgFunction {
     pub name: &'static str,
     pub fun: fn(Vec<f32>, &[f32]) -> Vec<f32>,
+    pub fun64: fn(Vec<f64>, &[f64]) -> Vec<f64>,
+    /// Function is about missing values, so it gets them as they are
+    pub missing_aware: bool,
+}
+
+impl DoubleArgFunction {
+    /// Applies function to every pair of values, treating missing ones according to
+    /// `missing`
+    pub(crate) fn apply<T: Value>(
+        &self,
+        v1: Vec<T>,
+        v2: &[T],
+        missing: MissingSemantics,
+    ) -> Vec<T> {
+        let f = T::double_arg_fun(self);
+        if missing == MissingSemantics::Legacy
+            || self.missing_aware
+            || !v1.iter().chain(v2).any(|v| v.is_nan())
+        {
+            return f(v1, v2);
+        }
+        let was_missing =
+            v1.iter().zip(v2).map(|(v1, v2)| v1.is_nan() || v2.is_nan()).collect::<Vec<_>>();
+        let mut v1 = f(v1, v2);
+        restore_missing(&mut v1, &was_missing);
+        v1
+    }
 }
 
 impl Debug for DoubleArgFunction {
@@ -434,51 +614,100 @@ impl Deserializable for &DoubleArgFunction {
     }
 }
 
-pub static TWO_ARG_FUNCTIONS: [DoubleArgFunction; 17] = [
-    DoubleArgFunction { name: "abs_higher", fun: abs_higher_array },
-    DoubleArgFunction { name: "abs_lower", fun: abs_lower_array },
-    DoubleArgFunction { name: "add", fun: add_array },
-    DoubleArgFunction { name: "and", fun: and_array },
-    DoubleArgFunction { name: "diff", fun: diff_array },
-    DoubleArgFunction { name: "div", fun: div_array },
-    DoubleArgFunction { name: "equal", fun: equal_array },
-    DoubleArgFunction { name: "first_is_higher", fun: first_is_higher_array },
-    DoubleArgFunction { name: "higher", fun: higher_array },
-    DoubleArgFunction { name: "lower", fun: lower_array },
-    DoubleArgFunction { name: "mid", fun: mid_array },
-    DoubleArgFunction { name: "mul", fun: mul_array },
-    DoubleArgFunction { name: "or", fun: or_array },
-    DoubleArgFunction { name: "sub", fun: sub_array },
-    DoubleArgFunction { name: "sum_of_squares", fun: sum_of_squares_array },
-    DoubleArgFunction { name: "xor", fun: xor_array },
-    DoubleArgFunction { name: "round_equal_array", fun: round_equal_array },
+pub static TWO_ARG_FUNCTIONS: [DoubleArgFunction; 18] = [
+    DoubleArgFunction {
+        name: "abs_higher",
+        fun: abs_higher_array,
+        fun64: abs_higher_array,
+        missing_aware: false,
+    },
+    DoubleArgFunction {
+        name: "abs_lower",
+        fun: abs_lower_array,
+        fun64: abs_lower_array,
+        missing_aware: false,
+    },
+    DoubleArgFunction { name: "add", fun: add_array, fun64: add_array, missing_aware: false },
+    DoubleArgFunction { name: "and", fun: and_array, fun64: and_array, missing_aware: false },
+    DoubleArgFunction { name: "diff", fun: diff_array, fun64: diff_array, missing_aware: false },
+    DoubleArgFunction { name: "div", fun: div_array, fun64: div_array, missing_aware: false },
+    DoubleArgFunction {
+        name: "equal",
+        fun: equal_array,
+        fun64: equal_array,
+        missing_aware: false,
+    },
+    DoubleArgFunction {
+        name: "first_is_higher",
+        fun: first_is_higher_array,
+        fun64: first_is_higher_array,
+        missing_aware: false,
+    },
+    DoubleArgFunction {
+        name: "higher",
+        fun: higher_array,
+        fun64: higher_array,
+        missing_aware: false,
+    },
+    DoubleArgFunction {
+        name: "if_missing",
+        fun: if_missing_array,
+        fun64: if_missing_array,
+        missing_aware: true,
+    },
+    DoubleArgFunction {
+        name: "lower",
+        fun: lower_array,
+        fun64: lower_array,
+        missing_aware: false,
+    },
+    DoubleArgFunction { name: "mid", fun: mid_array, fun64: mid_array, missing_aware: false },
+    DoubleArgFunction { name: "mul", fun: mul_array, fun64: mul_array, missing_aware: false },
+    DoubleArgFunction { name: "or", fun: or_array, fun64: or_array, missing_aware: false },
+    DoubleArgFunction { name: "sub", fun: sub_array, fun64: sub_array, missing_aware: false },
+    DoubleArgFunction {
+        name: "sum_of_squares",
+        fun: sum_of_squares_array,
+        fun64: sum_of_squares_array,
+        missing_aware: false,
+    },
+    DoubleArgFunction { name: "xor", fun: xor_array, fun64: xor_array, missing_aware: false },
+    DoubleArgFunction {
+        name: "round_equal_array",
+        fun: round_equal_array,
+        fun64: round_equal_array,
+        missing_aware: false,
+    },
 ];
 
 #[cfg(test)]
 mod test {
-    use crate::exec::functions::{equal, relu};
+    use crate::exec::functions::MissingSemantics::{Legacy, Propagate};
+    use crate::exec::functions::{equal, relu, MATH_CONSTANTS, ONE_ARG_FUNCTIONS};
+    use crate::exec::functions::{if_missing_array, is_missing_array, TWO_ARG_FUNCTIONS};
+    use std::f32::consts::{E, PI};
 
     #[test]
     fn test_equal() {
         let v1 = 1.0;
         let v2 = v1;
-        assert_eq!(1.0, equal(v1, v2));
+        assert_eq!(1.0, equal::<f32>(v1, v2));
 
         let v1 = -1.0;
         let v2 = 1.0;
-        assert_eq!(0.0, equal(v1, v2));
+        assert_eq!(0.0, equal::<f32>(v1, v2));
 
         let v1 = 1010.0;
         let v2 = 1000.0;
-        assert_eq!(1.0, equal(v1, v2));
+        assert_eq!(1.0, equal::<f32>(v1, v2));
 
         let v1 = 100.0;
         let v2 = 1000.0;
-        assert_eq!(0.0, equal(v1, v2));
+        assert_eq!(0.0, equal::<f32>(v1, v2));
 
         let v1 = 1020.0;
         let v2 = 1000.0;
-        assert_eq!(0.0, equal(v1, v2));
+        assert_eq!(0.0, equal::<f32>(v1, v2));
     }
 
     #[test]
@@ -487,4 +716,57 @@ mod test {
         assert_eq!(relu(-15.0), 0.0);
         assert_eq!(relu(15.0), 15.0);
     }
+
+    #[test]
+    fn single_precision_constants() {
+        let values = MATH_CONSTANTS.iter().map(|c| c.value::<f32>()).collect::<Vec<_>>();
+        assert_eq!(values, vec![0.0, 1.0, 2.0, E, PI, 2.0 * PI]);
+    }
+
+    #[test]
+    fn double_precision_functions() {
+        for f in &ONE_ARG_FUNCTIONS {
+            let single = (f.fun)(vec![0.5, 2.0, 3.0]);
+            let double = (f.fun64)(vec![0.5, 2.0, 3.0]);
+            for (s, d) in single.iter().zip(&double) {
+                assert!((f64::from(*s) - d).abs() <= d.abs() * 1e-6, "{}", f.name);
+            }
+        }
+        let large = 123_456_789.0_f64;
+        assert_eq!((ONE_ARG_FUNCTIONS[4].fun64)(vec![large]), vec![large + 1.0]);
+    }
+
+    #[test]
+    fn missing_values_propagate() {
+        for f in &ONE_ARG_FUNCTIONS {
+            let result = f.apply(vec![f32::NAN, 2.0], Propagate);
+            assert_eq!(result[0].is_nan(), f.name != "is_missing", "{}", f.name);
+            assert_eq!(result[1].to_bits(), (f.fun)(vec![2.0])[0].to_bits(), "{}", f.name);
+        }
+        for f in &TWO_ARG_FUNCTIONS {
+            let result = f.apply(vec![f32::NAN, 1.0], &[1.0, f32::NAN], Propagate);
+            if f.name == "if_missing" {
+                assert_eq!(result, vec![1.0, 1.0]);
+            } else {
+                assert!(result.iter().all(|v| v.is_nan()), "{}", f.name);
+            }
+        }
+    }
+
+    #[test]
+    fn legacy_missing_values() {
+        let relu = ONE_ARG_FUNCTIONS.iter().find(|f| f.name == "relu").unwrap();
+        assert_eq!(relu.apply(vec![f32::NAN], Legacy), vec![0.0]);
+        assert!(relu.apply(vec![f32::NAN], Propagate)[0].is_nan());
+        let higher = TWO_ARG_FUNCTIONS.iter().find(|f| f.name == "higher").unwrap();
+        assert_eq!(higher.apply(vec![f32::NAN], &[1.0], Legacy), vec![1.0]);
+        assert!(higher.apply(vec![f32::NAN], &[1.0], Propagate)[0].is_nan());
+    }
+
+    #[test]
+    fn test_missing_functions() {
+        assert_eq!(is_missing_array(vec![f32::NAN, 0.0, 2.0]), vec![1.0, 0.0, 0.0]);
+        let filled = if_missing_array(vec![f64::NAN, 3.0], &[2.0, 5.0]);
+        assert_eq!(filled, vec![2.0, 3.0]);
+    }
 }
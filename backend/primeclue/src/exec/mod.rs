@@ -19,6 +19,8 @@
 
 pub mod class_training;
 pub mod classifier;
+pub mod compiled_tree;
+pub mod formula;
 pub mod functions;
 pub mod node;
 pub mod score;
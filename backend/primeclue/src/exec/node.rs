@@ -18,14 +18,20 @@
 */
 
 use crate::data::{Data, InputShape};
-use crate::exec::functions::{DoubleArgFunction, MathConst, SingleArgFunction};
-use crate::exec::functions::{MATH_CONSTANTS, ONE_ARG_FUNCTIONS, TWO_ARG_FUNCTIONS};
+use crate::exec::compiled_tree::Instruction;
+use crate::exec::formula;
+use crate::exec::functions::{infix_operator, DoubleArgFunction, MathConst, SingleArgFunction};
+use crate::exec::functions::{
+    MissingSemantics, MATH_CONSTANTS, ONE_ARG_FUNCTIONS, TWO_ARG_FUNCTIONS,
+};
 use crate::math::std_dev;
 use crate::rand::GET_RNG;
 use crate::serialization::deserializable::Deserializable;
 use crate::serialization::serializator::Serializator;
 use crate::serialization::Serializable;
+use crate::value::Value;
 use rand::{prelude::SliceRandom, Rng};
+use serde::ser::SerializeStruct;
 use std::collections::HashSet;
 use std::{borrow::BorrowMut, ops::Deref, ops::Mul};
 
@@ -58,11 +64,11 @@ impl Weight {
     }
 }
 
-impl Mul<f32> for &Weight {
-    type Output = f32;
+impl<T: Value> Mul<T> for &Weight {
+    type Output = T;
 
-    fn mul(self, rhs: f32) -> Self::Output {
-        self.0 * rhs
+    fn mul(self, rhs: T) -> Self::Output {
+        T::from_f32(self.0) * rhs
     }
 }
 
@@ -78,6 +84,16 @@ impl Weighted {
         Weighted { w: Weight(1.0), n: Box::new(n) }
     }
 
+    #[must_use]
+    pub fn weight(&self) -> f32 {
+        self.w.0
+    }
+
+    #[must_use]
+    pub fn node(&self) -> &Node {
+        &self.n
+    }
+
     pub fn change_weight(&mut self, rate: f32) {
         let new = self.w.0 * rate;
         if !new.is_nan() {
@@ -100,13 +116,15 @@ impl Weighted {
     }
 
     #[must_use]
-    pub fn execute(&self, data: &Data<Vec<f32>>) -> Vec<f32> {
+    pub fn execute<T: Value>(&self, data: &Data<Vec<T>>, missing: MissingSemantics) -> Vec<T> {
         let mut v = match self.n.deref() {
             Node::MathConstant(v) => vec![v.value(); data.get(0, 0).len()],
             Node::DataValue(r, c) => data.get(*r, *c).clone(),
             Node::StdDev(r, c) => std_dev(data.get(*r, *c)),
-            Node::SingleArgFunction(f, n) => (f.fun)(n.execute(data)),
-            Node::DoubleArgFunction(f, n1, n2) => (f.fun)(n1.execute(data), &n2.execute(data)),
+            Node::SingleArgFunction(f, n) => f.apply(n.execute(data, missing), missing),
+            Node::DoubleArgFunction(f, n1, n2) => {
+                f.apply(n1.execute(data, missing), &n2.execute(data, missing), missing)
+            }
         };
         v.iter_mut().for_each(|v| {
             *v = &self.w * *v;
@@ -114,6 +132,61 @@ impl Weighted {
         v
     }
 
+    /// Renders this node and its children as an infix formula. Weights other than 1
+    /// are written as multipliers, always with a decimal point or an exponent so they
+    /// can't be mistaken for constants. Columns are named after `header` when it's given.
+    pub(crate) fn to_formula(
+        &self,
+        input_shape: &InputShape,
+        header: Option<&[String]>,
+    ) -> String {
+        let formula = match self.n.deref() {
+            Node::MathConstant(c) => c.name().to_owned(),
+            Node::DataValue(r, c) => formula_column_name(*r, *c, input_shape, header),
+            Node::StdDev(r, c) => {
+                format!("std_dev({})", formula_column_name(*r, *c, input_shape, header))
+            }
+            Node::SingleArgFunction(f, n) => {
+                format!("{}({})", f.name, n.to_formula(input_shape, header))
+            }
+            Node::DoubleArgFunction(f, n1, n2) => {
+                let n1 = n1.to_formula(input_shape, header);
+                let n2 = n2.to_formula(input_shape, header);
+                match infix_operator(f.name) {
+                    Some(op) => format!("({} {} {})", n1, op, n2),
+                    None => format!("{}({}, {})", f.name, n1, n2),
+                }
+            }
+        };
+        if self.w.0 == 1.0 {
+            formula
+        } else {
+            format!("{:?} * {}", self.w.0, formula)
+        }
+    }
+
+    /// Appends instructions for this node and its children in post-order, i.e. every
+    /// node's arguments are placed before the node itself.
+    pub(crate) fn compile(&self, program: &mut Vec<Instruction>) {
+        let weight = self.w.0;
+        match self.n.deref() {
+            Node::MathConstant(c) => {
+                program.push(Instruction::Constant(c.value::<f64>(), weight))
+            }
+            Node::DataValue(r, c) => program.push(Instruction::DataValue(*r, *c, weight)),
+            Node::StdDev(r, c) => program.push(Instruction::StdDev(*r, *c, weight)),
+            Node::SingleArgFunction(f, n) => {
+                n.compile(program);
+                program.push(Instruction::SingleArgFunction(f, weight));
+            }
+            Node::DoubleArgFunction(f, n1, n2) => {
+                n1.compile(program);
+                n2.compile(program);
+                program.push(Instruction::DoubleArgFunction(f, weight));
+            }
+        }
+    }
+
     pub fn new(
         current_depth: usize,
         input_shape: &InputShape,
@@ -293,6 +366,54 @@ impl Deserializable for Weighted {
     }
 }
 
+/// Nested JSON structure: `{"weight": 1.0, "node": {...}}`.
+impl serde::Serialize for Weighted {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Weighted", 2)?;
+        s.serialize_field("weight", &self.w.0)?;
+        s.serialize_field("node", self.n.deref())?;
+        s.end()
+    }
+}
+
+/// Column's name used in formulas: a header entry if there's a non empty one, `col`
+/// with column index otherwise. Row is added only for multi row inputs.
+pub(crate) fn column_name(
+    row: usize,
+    column: usize,
+    input_shape: &InputShape,
+    header: Option<&[String]>,
+) -> String {
+    let name = match header.and_then(|h| h.get(column)).filter(|n| !n.trim().is_empty()) {
+        Some(name) => name.trim().to_owned(),
+        None => format!("col{}", column),
+    };
+    with_row(name, row, input_shape)
+}
+
+/// Same as [`column_name`] but header names are quoted when [`formula::parse`] would
+/// read them as something else
+fn formula_column_name(
+    row: usize,
+    column: usize,
+    input_shape: &InputShape,
+    header: Option<&[String]>,
+) -> String {
+    let name = match header.and_then(|h| h.get(column)).filter(|n| !n.trim().is_empty()) {
+        Some(name) => formula::quote_name(name.trim()),
+        None => format!("col{}", column),
+    };
+    with_row(name, row, input_shape)
+}
+
+fn with_row(name: String, row: usize, input_shape: &InputShape) -> String {
+    if input_shape.rows() > 1 {
+        format!("{}[{}]", name, row)
+    } else {
+        name
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Node {
     DataValue(usize, usize),
@@ -320,6 +441,42 @@ impl Serializable for Node {
     }
 }
 
+/// JSON structure with node's `type` and its fields: `row` and `column` for data
+/// values, `name` and `value` for constants, `function` and `args` for functions.
+impl serde::Serialize for Node {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("Node", 3)?;
+        match self {
+            Node::DataValue(row, column) => {
+                s.serialize_field("type", "DataValue")?;
+                s.serialize_field("row", row)?;
+                s.serialize_field("column", column)?;
+            }
+            Node::StdDev(row, column) => {
+                s.serialize_field("type", "StdDev")?;
+                s.serialize_field("row", row)?;
+                s.serialize_field("column", column)?;
+            }
+            Node::MathConstant(c) => {
+                s.serialize_field("type", "MathConstant")?;
+                s.serialize_field("name", c.name())?;
+                s.serialize_field("value", &c.value::<f64>())?;
+            }
+            Node::SingleArgFunction(f, n) => {
+                s.serialize_field("type", "SingleArgFunction")?;
+                s.serialize_field("function", f.name)?;
+                s.serialize_field("args", &[n])?;
+            }
+            Node::DoubleArgFunction(f, n1, n2) => {
+                s.serialize_field("type", "DoubleArgFunction")?;
+                s.serialize_field("function", f.name)?;
+                s.serialize_field("args", &[n1, n2])?;
+            }
+        }
+        s.end()
+    }
+}
+
 impl Deserializable for Node {
     fn deserialize(s: &mut Serializator) -> Result<Node, String> {
         let node_type = s.next_token()?;
@@ -405,9 +562,27 @@ impl Node {
 
 #[cfg(test)]
 mod test {
-    use crate::exec::node::Weight;
+    use crate::exec::functions::{MATH_CONSTANTS, ONE_ARG_FUNCTIONS};
+    use crate::exec::node::{Node, Weight, Weighted};
     use crate::serialization::serializator::test::test_serialization;
 
+    #[test]
+    fn node_json() {
+        let sine = ONE_ARG_FUNCTIONS.iter().find(|f| f.name == "sine").unwrap();
+        let mut node =
+            Weighted::from(Node::one_arg_node(sine, Weighted::from(Node::DataValue(1, 2))));
+        node.change_weight(0.5);
+        assert_eq!(
+            serde_json::to_string(&node).unwrap(),
+            r#"{"weight":0.5,"node":{"type":"SingleArgFunction","function":"sine","args":[{"weight":1.0,"node":{"type":"DataValue","row":1,"column":2}}]}}"#
+        );
+        let node = Node::MathConstant(&MATH_CONSTANTS[1]);
+        assert_eq!(
+            serde_json::to_string(&node).unwrap(),
+            r#"{"type":"MathConstant","name":"1","value":1.0}"#
+        );
+    }
+
     #[test]
     fn serialize_weight() {
         for _ in 0..1_000 {
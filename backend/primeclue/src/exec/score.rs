@@ -20,6 +20,7 @@
 use crate::data::outcome::Class;
 use crate::data::Outcome;
 use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::Value;
 use core::fmt;
 use std::cmp::Ordering;
 use std::cmp::Ordering::Equal;
@@ -37,7 +38,7 @@ pub enum Objective {
 }
 
 impl Objective {
-    pub fn threshold(&self, outcomes: &[(f32, Outcome)], class: Class) -> Threshold {
+    pub fn threshold<T: Value>(&self, outcomes: &[(T, Outcome)], class: Class) -> Threshold {
         match self {
             Objective::Cost => cost_threshold(outcomes, class),
             Objective::Auc => auc_threshold(outcomes, class),
@@ -82,25 +83,31 @@ impl fmt::Display for Objective {
 
 #[derive(Debug, Clone, Copy, serde::Serialize)]
 pub struct Threshold {
-    value: f32,
+    value: f64,
 }
 
 impl Threshold {
-    pub fn bool(self, f: f32) -> Option<bool> {
+    pub fn bool<T: Value>(self, f: T) -> Option<bool> {
         if !f.is_finite() {
             None
         } else {
-            Some(f >= self.value)
+            Some(f.as_f64() >= self.value)
         }
     }
 
-    pub fn new(f: f32) -> Self {
-        Threshold { value: f }
+    pub fn new<T: Value>(f: T) -> Self {
+        Threshold { value: f.as_f64() }
     }
 
-    pub fn value(&self) -> f32 {
+    pub fn value(&self) -> f64 {
         self.value
     }
+
+    /// Threshold rounded to `f32`, as classifiers saved before thresholds were kept in
+    /// double precision had it
+    pub(crate) fn to_single(self) -> Self {
+        Threshold { value: self.value as f32 as f64 }
+    }
 }
 
 impl Serializable for Threshold {
@@ -111,7 +118,7 @@ impl Serializable for Threshold {
 
 impl Deserializable for Threshold {
     fn deserialize(s: &mut Serializator) -> Result<Self, String> {
-        Ok(Threshold { value: f32::deserialize(s)? })
+        Ok(Threshold { value: f64::deserialize(s)? })
     }
 }
 
@@ -153,6 +160,20 @@ impl Score {
     pub fn from(objective: Objective, class: Class, value: f32, threshold: Threshold) -> Self {
         Score { objective, class, value, threshold }
     }
+
+    /// Adjusts score calculated on known guesses only, where `coverage` is the fraction
+    /// of guesses that were known. AUC and accuracy are scaled down so that answering
+    /// only easy data points doesn't pay off. Cost is left as is, unknown guesses
+    /// already cost nothing.
+    #[must_use]
+    pub fn with_coverage(self, coverage: f32) -> Self {
+        match self.objective {
+            Objective::Auc | Objective::Accuracy => {
+                Score { value: self.value * coverage, ..self }
+            }
+            Objective::Cost => self,
+        }
+    }
 }
 
 impl fmt::Display for Score {
@@ -201,9 +222,15 @@ impl Deserializable for Score {
     }
 }
 
+/// Leaves only finite guesses with their outcomes. Non-finite guesses are unknown.
+#[must_use]
+pub fn known_guesses<T: Value>(guesses: &[T], outcomes: &[Outcome]) -> (Vec<T>, Vec<Outcome>) {
+    guesses.iter().copied().zip(outcomes.iter().copied()).filter(|(g, _)| g.is_finite()).unzip()
+}
+
 #[must_use]
-pub fn calc_score(
-    outcomes: &[(f32, Outcome)],
+pub fn calc_score<T: Value>(
+    outcomes: &[(T, Outcome)],
     threshold: Threshold,
     class: Class,
     objective: Objective,
@@ -217,7 +244,7 @@ pub fn calc_score(
 }
 
 #[must_use]
-pub fn calculate_auc(outcomes: &[(f32, Outcome)], class: Class) -> f32 {
+pub fn calculate_auc<T: Value>(outcomes: &[(T, Outcome)], class: Class) -> f32 {
     let mut incorrect_count = 0_usize;
     let mut correct_count = 0_usize;
     let mut total_incorrect = 0_usize;
@@ -233,7 +260,11 @@ pub fn calculate_auc(outcomes: &[(f32, Outcome)], class: Class) -> f32 {
 }
 
 #[must_use]
-fn calculate_accuracy(threshold: Threshold, outcomes: &[(f32, Outcome)], class: Class) -> f32 {
+fn calculate_accuracy<T: Value>(
+    threshold: Threshold,
+    outcomes: &[(T, Outcome)],
+    class: Class,
+) -> f32 {
     let mut correct = 0;
     let mut total = 0;
     for (guess, outcome) in outcomes.iter() {
@@ -250,7 +281,11 @@ fn calculate_accuracy(threshold: Threshold, outcomes: &[(f32, Outcome)], class:
 }
 
 #[must_use]
-fn calculate_cost(threshold: Threshold, outcomes: &[(f32, Outcome)], class: Class) -> f32 {
+fn calculate_cost<T: Value>(
+    threshold: Threshold,
+    outcomes: &[(T, Outcome)],
+    class: Class,
+) -> f32 {
     let mut cost = 0.0;
     for (guess, outcome) in outcomes.iter() {
         if let Some(b) = threshold.bool(*guess) {
@@ -261,20 +296,20 @@ fn calculate_cost(threshold: Threshold, outcomes: &[(f32, Outcome)], class: Clas
 }
 
 #[must_use]
-pub fn auc_threshold(outcomes: &[(f32, Outcome)], class: Class) -> Threshold {
+pub fn auc_threshold<T: Value>(outcomes: &[(T, Outcome)], class: Class) -> Threshold {
     let none_class_count =
         outcomes.iter().map(|(_, o)| o.class()).filter(|c| c != &class).count();
     if none_class_count == 0 {
         Threshold::new(outcomes[0].0)
     } else if none_class_count == outcomes.len() {
-        Threshold::new(2.0 * outcomes[outcomes.len() - 1].0.abs())
+        Threshold::new(T::from_f64(2.0) * outcomes[outcomes.len() - 1].0.abs())
     } else {
         Threshold::new(outcomes[none_class_count].0)
     }
 }
 
 #[must_use]
-fn cost_threshold(outcomes: &[(f32, Outcome)], class: Class) -> Threshold {
+fn cost_threshold<T: Value>(outcomes: &[(T, Outcome)], class: Class) -> Threshold {
     let mut false_list = Vec::with_capacity(outcomes.len());
     let mut false_cost = 0.0;
     for (guess, outcome) in outcomes {
@@ -295,7 +330,7 @@ fn cost_threshold(outcomes: &[(f32, Outcome)], class: Class) -> Threshold {
 }
 
 #[must_use]
-fn accuracy_threshold(outcomes: &[(f32, Outcome)], class: Class) -> Threshold {
+fn accuracy_threshold<T: Value>(outcomes: &[(T, Outcome)], class: Class) -> Threshold {
     let mut incorrect_list = Vec::with_capacity(outcomes.len());
     let mut incorrect_count = 0;
     for (guess, outcome) in outcomes {
@@ -17,12 +17,16 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::data::chunked::ChunkedView;
 use crate::data::data_set::DataView;
 use crate::data::InputShape;
+use crate::error::PrimeclueErr;
+use crate::exec::functions::MissingSemantics;
 use crate::exec::node::Weighted;
 use crate::exec::score::Score;
 use crate::exec::tree::Tree;
 use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::Value;
 use std::cmp::Ordering;
 use std::cmp::Ordering::Greater;
 
@@ -61,18 +65,36 @@ impl ScoredTree {
         &self.tree
     }
 
+    pub fn into_tree(self) -> Tree {
+        self.tree
+    }
+
+    pub(crate) fn set_missing_semantics(&mut self, missing: MissingSemantics) {
+        self.tree.set_missing_semantics(missing);
+    }
+
     pub fn node_count(&self) -> usize {
         self.tree.node_count()
     }
 
-    pub fn execute(&self, data: &DataView) -> Vec<f32> {
+    pub fn execute<T: Value>(&self, data: &DataView<T>) -> Vec<T> {
         self.tree.execute(data)
     }
 
-    pub fn guess(&self, value: f32) -> Option<bool> {
+    pub fn guess<T: Value>(&self, value: T) -> Option<bool> {
         self.score.threshold().bool(value)
     }
 
+    /// Total order for sorting: by exact score value, then shorter first. Unlike
+    /// [`PartialOrd`] it doesn't treat close scores as equal, which isn't transitive and
+    /// can make sorting panic.
+    pub fn sort_order(&self, other: &Self) -> Ordering {
+        self.score
+            .value()
+            .total_cmp(&other.score.value())
+            .then_with(|| other.tree.node_count().cmp(&self.tree.node_count()))
+    }
+
     pub fn best_tree(trees: &[ScoredTree]) -> Option<&ScoredTree> {
         trees.iter().fold(None, |last, next| match last {
             None => Some(next),
@@ -109,10 +131,17 @@ impl ScoredTree {
         ScoredTree { score, tree }
     }
 
-    pub fn execute_for_score(&self, data: &DataView) -> Option<Score> {
+    pub fn execute_for_score<T: Value>(&self, data: &DataView<T>) -> Option<Score> {
         self.tree.execute_for_score(data, self.score.class(), self.score.objective())
     }
 
+    pub fn execute_for_score_streamed<T: Value>(
+        &self,
+        data: &ChunkedView<T>,
+    ) -> Result<Option<Score>, PrimeclueErr> {
+        self.tree.execute_for_score_streamed(data, self.score.class(), self.score.objective())
+    }
+
     pub fn get_start_node(&self) -> &Weighted {
         self.tree.get_start_node()
     }
@@ -215,6 +244,26 @@ mod test {
         assert_eq!(st1.partial_cmp(&st2), Some(Ordering::Equal))
     }
 
+    #[test]
+    fn sort_order() {
+        let class = Class::new(0);
+        let close = ScoredTree::new(
+            create_long_tree(),
+            Score::new(Auc, class, 0.9995, Threshold::new(0.0)),
+        );
+        let best = ScoredTree::new(
+            create_long_tree(),
+            Score::new(Auc, class, 1.0, Threshold::new(0.0)),
+        );
+        let short = ScoredTree::new(
+            create_short_tree(),
+            Score::new(Auc, class, 1.0, Threshold::new(0.0)),
+        );
+        assert_eq!(close.partial_cmp(&best), Some(Ordering::Equal));
+        assert_eq!(close.sort_order(&best), Ordering::Less);
+        assert_eq!(short.sort_order(&best), Ordering::Greater);
+    }
+
     #[test]
     fn serialize() {
         let tree = create_long_tree();
@@ -17,28 +17,43 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::data::chunked::ChunkedView;
 use crate::data::data_set::DataView;
 use crate::data::outcome::Class;
 use crate::error::PrimeclueErr;
-use crate::exec::class_training::ClassTraining;
+use crate::exec::class_training::{ClassTraining, Sampling, ScoringData};
 use crate::exec::classifier::Classifier;
 use crate::exec::score::{Objective, Score};
 use crate::exec::scored_tree::ScoredTree;
+use crate::exec::tree::Tree;
+use crate::rand::GET_RNG;
+use crate::value::Value;
+use rand::seq::SliceRandom;
 use rayon::{ThreadPool, ThreadPoolBuilder};
 use serde::Serialize;
 use std::mem::replace;
 
+/// Number of generations a sample of streamed training data is reused for, since
+/// drawing a sample reads all chunks from disk, see [`ChunkedView::sample`]
+const RESAMPLE_GENERATIONS: u32 = 10;
+
 #[derive(Debug)]
-pub struct TrainingGroup {
+pub struct TrainingGroup<T: Value = f32> {
     generation: u32,
-    training_data: DataView,
-    verification_data: DataView,
-    classes: Vec<ClassTraining>,
+    training_data: DataView<T>,
+    verification_data: DataView<T>,
+    classes: Vec<ClassTraining<T>>,
     objective: Objective,
     thread_pool: ThreadPool,
+    sampling: Sampling,
+    sample_order: Vec<usize>,
+    sample_offset: usize,
+    /// Training data streamed from disk and size of its samples, see
+    /// [`TrainingGroup::from_chunks`]. `training_data` is then the current sample.
+    streamed: Option<(ChunkedView<T>, usize)>,
 }
 
-impl TrainingGroup {
+impl<T: Value> TrainingGroup<T> {
     /// Creates a new [`TrainingGroup`] that can be used to train a classifier through
     /// its [`next_generation`] method.
     ///
@@ -49,8 +64,8 @@ impl TrainingGroup {
     /// * `size` - size of a training group. Determines amount of RAM needed
     /// * `forbidden_cols` - indexes of data columns that should not be used as input
     pub fn new(
-        training_data: DataView,
-        verification_data: DataView,
+        training_data: DataView<T>,
+        verification_data: DataView<T>,
         objective: Objective,
         size: usize,
         forbidden_cols: &[usize],
@@ -78,12 +93,92 @@ impl TrainingGroup {
             verification_data,
             classes,
             thread_pool,
+            sampling: Sampling::Full,
+            sample_order: vec![],
+            sample_offset: 0,
+            streamed: None,
         })
     }
 
+    /// Creates a new [`TrainingGroup`] for training data that doesn't fit in memory.
+    /// Fresh trees are scored on a random sample of `sample_size` data points, drawn
+    /// anew every [`RESAMPLE_GENERATIONS`] generations, and the best ones are rescored
+    /// on all chunks streamed from disk, see [`ScoringData::Streamed`]. Verification
+    /// data must fit in memory, e.g. it can be a [`ChunkedView::split_sampled`].
+    pub fn from_chunks(
+        training_data: ChunkedView<T>,
+        verification_data: DataView<T>,
+        objective: Objective,
+        size: usize,
+        forbidden_cols: &[usize],
+        sample_size: usize,
+    ) -> Result<Self, PrimeclueErr> {
+        if sample_size < 2 {
+            return PrimeclueErr::result(format!(
+                "Sample size must be at least 2, is: {}",
+                sample_size
+            ));
+        }
+        let sample = training_data.sample(sample_size)?;
+        if sample.class_count() != training_data.class_count() {
+            return PrimeclueErr::result(format!(
+                "Sample of {} data points doesn't contain all classes",
+                sample_size
+            ));
+        }
+        let mut group =
+            TrainingGroup::new(sample, verification_data, objective, size, forbidden_cols)?;
+        group.streamed = Some((training_data, sample_size));
+        Ok(group)
+    }
+
+    /// Sets how training data is used to score fresh trees. Subsamples rotate through
+    /// all training data points in random order, so that every point is used
+    /// equally often.
+    pub fn set_sampling(&mut self, sampling: Sampling) -> Result<(), PrimeclueErr> {
+        if self.streamed.is_some() {
+            return PrimeclueErr::result(
+                "Streamed training data is always sampled, see sample size".to_owned(),
+            );
+        }
+        match sampling {
+            Sampling::Subsample(size) | Sampling::Racing(size) if size < 2 => {
+                return PrimeclueErr::result(format!(
+                    "Sample size must be at least 2, is: {}",
+                    size
+                ));
+            }
+            _ => {}
+        }
+        self.sampling = sampling;
+        self.sample_order = (0..self.training_data.len()).collect();
+        self.sample_order.shuffle(&mut GET_RNG());
+        self.sample_offset = 0;
+        Ok(())
+    }
+
+    /// Seeds training of `class` with `tree`, e.g. a rule parsed by
+    /// [`Tree::from_formula`].
+    pub fn seed(&mut self, class: &Class, tree: Tree) -> Result<(), PrimeclueErr> {
+        if tree.input_shape() != self.training_data.input_shape() {
+            return PrimeclueErr::result(format!(
+                "Seed tree's input shape {} doesn't match data's {}",
+                tree.input_shape(),
+                self.training_data.input_shape()
+            ));
+        }
+        match self.classes.iter_mut().find(|c| c.class() == class) {
+            Some(class_training) => {
+                class_training.seed(tree);
+                Ok(())
+            }
+            None => PrimeclueErr::result(format!("No class {:?} to seed", class)),
+        }
+    }
+
     fn validate(
-        training_data: &DataView,
-        verification_data: &DataView,
+        training_data: &DataView<T>,
+        verification_data: &DataView<T>,
     ) -> Result<(), PrimeclueErr> {
         if training_data.cells().is_empty() {
             PrimeclueErr::result("Data training set is empty".to_string())
@@ -106,20 +201,64 @@ impl TrainingGroup {
         }
     }
 
-    /// Performs training for one generation
-    pub fn next_generation(&mut self) {
+    /// Performs training for one generation. Fails only if streamed training data can't
+    /// be read.
+    pub fn next_generation(&mut self) -> Result<(), PrimeclueErr> {
         self.generation += 1;
+        if let Some((chunks, sample_size)) = &self.streamed {
+            if self.generation % RESAMPLE_GENERATIONS == 0 {
+                self.training_data = chunks.sample(*sample_size)?;
+            }
+        }
+        let samples = self.next_samples();
         let training_data = &self.training_data;
+        let scoring = match (&self.streamed, self.sampling, samples.first()) {
+            (Some((full, _)), _, _) => ScoringData::Streamed { full, sample: training_data },
+            (None, Sampling::Subsample(_), Some(sample)) => {
+                ScoringData::Subsample { full: training_data, sample }
+            }
+            (None, Sampling::Racing(_), Some(_)) => {
+                ScoringData::Racing { full: training_data, samples: &samples }
+            }
+            _ => ScoringData::Full(training_data),
+        };
         let verification_data = &self.verification_data;
         let mut classes = replace(&mut self.classes, vec![]);
+        let mut results = classes.iter().map(|_| Ok(())).collect::<Vec<_>>();
         self.thread_pool.scope(|s| {
-            for class in &mut classes {
+            for (class, result) in classes.iter_mut().zip(&mut results) {
                 s.spawn(move |_| {
-                    class.next_generation(training_data, verification_data);
+                    *result = class.next_generation_scored_on(scoring, verification_data);
                 })
             }
         });
         self.classes = classes;
+        results.into_iter().collect()
+    }
+
+    /// Subsamples of training data for the next generation, nested one in another
+    fn next_samples(&mut self) -> Vec<DataView<T>> {
+        let sizes = self.sampling.sample_sizes(self.training_data.len());
+        match sizes.last() {
+            None => vec![],
+            Some(&largest) => {
+                let points = self.next_sample_points(largest);
+                sizes.iter().map(|&size| self.training_data.select(&points[..size])).collect()
+            }
+        }
+    }
+
+    fn next_sample_points(&mut self, count: usize) -> Vec<usize> {
+        let mut points = Vec::with_capacity(count);
+        while points.len() < count {
+            if self.sample_offset == self.sample_order.len() {
+                self.sample_order.shuffle(&mut GET_RNG());
+                self.sample_offset = 0;
+            }
+            points.push(self.sample_order[self.sample_offset]);
+            self.sample_offset += 1;
+        }
+        points
     }
 
     pub fn generation(&self) -> u32 {
@@ -129,15 +268,18 @@ impl TrainingGroup {
     pub fn stats(&self) -> Option<Stats> {
         let mut node_count = 0;
         let mut training_score = 0.0;
+        let mut cache_hit_rate = 0.0;
         for class in &self.classes {
             let best_tree = class.best_tree()?;
             node_count += best_tree.node_count();
             training_score += class.training_score()?;
+            cache_hit_rate += class.cache_hit_rate();
         }
         if self.objective != Objective::Cost {
             training_score /= self.classes.len() as f32;
         }
-        Some(Stats { generation: self.generation, node_count, training_score })
+        cache_hit_rate /= self.classes.len() as f32;
+        Some(Stats { generation: self.generation, node_count, training_score, cache_hit_rate })
     }
 
     pub fn get_tree(&self, class: &Class) -> Option<&ScoredTree> {
@@ -160,7 +302,7 @@ impl TrainingGroup {
             }
         }
         let classes = self.training_data.class_map().clone();
-        Classifier::new(classes, trees).map_err(|e| {
+        Classifier::new(classes, trees, T::PRECISION).map_err(|e| {
             PrimeclueErr::from(format!("Unable to create a classifier: {}", e.to_string()))
         })
     }
@@ -171,6 +313,7 @@ pub struct Stats {
     pub generation: u32,
     pub training_score: f32,
     pub node_count: usize,
+    pub cache_hit_rate: f32,
 }
 
 #[derive(Serialize, Debug)]
@@ -181,10 +324,16 @@ pub struct ClassScore {
 
 #[cfg(test)]
 mod test {
-    use crate::data::data_set::test::create_simple_data;
+    use crate::data::chunked::test::write_chunks;
+    use crate::data::data_set::test::{create_simple_data, create_simple_data_as};
     use crate::data::outcome::Class;
+    use crate::data::InputShape;
+    use crate::exec::class_training::Sampling;
     use crate::exec::score::Objective::Auc;
     use crate::exec::training_group::TrainingGroup;
+    use crate::exec::tree::Tree;
+    use crate::value::Precision;
+    use std::fs;
 
     #[test]
     fn test_generation() {
@@ -192,12 +341,28 @@ mod test {
         let (training_data, verification_data, _) = data.shuffle().into_3_views_split();
         let mut training_group =
             TrainingGroup::new(training_data, verification_data, Auc, 3, &Vec::new()).unwrap();
-        training_group.next_generation();
-        training_group.next_generation();
-        training_group.next_generation();
+        training_group.next_generation().unwrap();
+        training_group.next_generation().unwrap();
+        training_group.next_generation().unwrap();
         assert_eq!(training_group.generation(), 3)
     }
 
+    #[test]
+    fn test_double_precision() {
+        let (training_data, verification_data) =
+            create_simple_data_as::<f64>(1_000).into_2_views_split();
+        let mut training_group =
+            TrainingGroup::new(training_data, verification_data, Auc, 10, &Vec::new()).unwrap();
+        for _ in 0..1_000 {
+            training_group.next_generation().unwrap();
+            if let Ok(classifier) = training_group.classifier() {
+                assert_eq!(classifier.precision(), Precision::Double);
+                return;
+            }
+        }
+        panic!("Unable to get classifier")
+    }
+
     #[test]
     fn test_get_tree() {
         let (training_data, verification_data) = create_simple_data(1_000).into_2_views_split();
@@ -205,11 +370,119 @@ mod test {
             TrainingGroup::new(training_data, verification_data, Auc, 10, &Vec::new()).unwrap();
 
         for _ in 0..1_000 {
-            training_group.next_generation();
+            training_group.next_generation().unwrap();
             if let Some(_) = training_group.get_tree(&Class::new(1)) {
                 return;
             }
         }
         panic!("get_tree(class) failed")
     }
+
+    #[test]
+    fn test_cache_hit_rate() {
+        let (training_data, verification_data) = create_simple_data(1_000).into_2_views_split();
+        let mut training_group =
+            TrainingGroup::new(training_data, verification_data, Auc, 10, &Vec::new()).unwrap();
+        for _ in 0..1_000 {
+            training_group.next_generation().unwrap();
+            if let Some(stats) = training_group.stats() {
+                if stats.cache_hit_rate > 0.0 {
+                    assert!(stats.cache_hit_rate <= 1.0);
+                    return;
+                }
+            }
+        }
+        panic!("No subtree cache hits")
+    }
+
+    #[test]
+    fn test_sampling() {
+        for sampling in
+            &[Sampling::Subsample(50), Sampling::Racing(20), Sampling::Subsample(5_000)]
+        {
+            let (training_data, verification_data) =
+                create_simple_data(1_000).into_2_views_split();
+            let mut training_group =
+                TrainingGroup::new(training_data, verification_data, Auc, 10, &Vec::new())
+                    .unwrap();
+            training_group.set_sampling(*sampling).unwrap();
+            for _ in 0..1_000 {
+                training_group.next_generation().unwrap();
+                if training_group.classifier().is_ok() {
+                    break;
+                }
+            }
+            assert!(training_group.classifier().is_ok());
+        }
+    }
+
+    #[test]
+    fn streamed() {
+        let chunks = write_chunks(&create_simple_data(1_000), 100);
+        let verification_data = create_simple_data(500).into_view();
+        let mut training_group =
+            TrainingGroup::from_chunks(chunks, verification_data, Auc, 10, &[], 50).unwrap();
+        assert!(training_group.set_sampling(Sampling::Full).is_err());
+        for _ in 0..1_000 {
+            training_group.next_generation().unwrap();
+            if training_group.classifier().is_ok() {
+                break;
+            }
+        }
+        let (chunks, _) = training_group.streamed.as_ref().unwrap();
+        fs::remove_dir_all(chunks.path()).unwrap();
+        assert!(training_group.classifier().is_ok());
+        assert!(training_group.next_generation().is_err());
+    }
+
+    #[test]
+    fn seed() {
+        let (training_data, verification_data) = create_simple_data(1_000).into_2_views_split();
+        let mut training_group =
+            TrainingGroup::new(training_data, verification_data, Auc, 10, &Vec::new()).unwrap();
+        let rule = Tree::from_formula("col1 - col0", &InputShape::new(1, 2), None).unwrap();
+        training_group.seed(&Class::new(1), rule).unwrap();
+        // seeded rule is perfect, but a random group may tie with it on verification
+        // data in a single generation
+        for _ in 0..5 {
+            training_group.next_generation().unwrap();
+        }
+        let tree = training_group.get_tree(&Class::new(1)).unwrap();
+        assert!(tree.score().value() > 0.98);
+
+        let rule = Tree::from_formula("col0", &InputShape::new(1, 1), None).unwrap();
+        assert!(training_group.seed(&Class::new(1), rule).is_err());
+        let rule = Tree::from_formula("col0", &InputShape::new(1, 2), None).unwrap();
+        assert!(training_group.seed(&Class::new(2), rule).is_err());
+    }
+
+    #[test]
+    fn invalid_sample_size() {
+        let (training_data, verification_data) = create_simple_data(100).into_2_views_split();
+        let mut training_group =
+            TrainingGroup::new(training_data, verification_data, Auc, 10, &Vec::new()).unwrap();
+        assert!(training_group.set_sampling(Sampling::Racing(1)).is_err());
+    }
+
+    #[test]
+    fn sample_sizes() {
+        assert!(Sampling::Full.sample_sizes(100).is_empty());
+        assert_eq!(Sampling::Subsample(10).sample_sizes(100), vec![10]);
+        assert!(Sampling::Subsample(100).sample_sizes(100).is_empty());
+        assert_eq!(Sampling::Racing(10).sample_sizes(100), vec![10, 20, 40, 80]);
+    }
+
+    #[test]
+    fn rotating_samples() {
+        let (training_data, verification_data) = create_simple_data(100).into_2_views_split();
+        let mut training_group =
+            TrainingGroup::new(training_data, verification_data, Auc, 10, &Vec::new()).unwrap();
+        training_group.set_sampling(Sampling::Subsample(10)).unwrap();
+        let mut points = vec![];
+        for _ in 0..5 {
+            points.append(&mut training_group.next_sample_points(10));
+        }
+        points.sort_unstable();
+        assert_eq!(points, (0..50).collect::<Vec<_>>());
+    }
 }
@@ -17,23 +17,29 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::data::chunked::ChunkedView;
 use crate::data::data_set::DataView;
 use crate::data::outcome::{sort_guesses, Class};
-use crate::data::{Input, InputShape};
-use crate::exec::functions::DoubleArgFunction;
+use crate::data::{Input, InputShape, Outcome};
+use crate::error::PrimeclueErr;
+use crate::exec::formula;
+use crate::exec::functions::{DoubleArgFunction, MissingSemantics};
 use crate::exec::node::{Node, Weighted};
-use crate::exec::score::{calc_score, Objective, Score};
+use crate::exec::score::{calc_score, known_guesses, Objective, Score};
 use crate::math::valid;
 use crate::rand::GET_RNG;
 use crate::serialization::{Deserializable, Serializable, Serializator};
+use crate::value::Value;
 use rand::Rng;
 use std::collections::HashSet;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, serde::Serialize)]
 pub struct Tree {
     node: Weighted,
     input_shape: InputShape,
     node_count: usize,
+    #[serde(skip)]
+    missing: MissingSemantics,
 }
 
 impl Serializable for Tree {
@@ -47,7 +53,7 @@ impl Deserializable for Tree {
         let node = Weighted::deserialize(s)?;
         let input_shape = InputShape::deserialize(s)?;
         let node_count = usize::deserialize(s)?;
-        Ok(Tree { node, input_shape, node_count })
+        Ok(Tree { node, input_shape, node_count, missing: MissingSemantics::default() })
     }
 }
 
@@ -60,7 +66,12 @@ impl Tree {
     ) -> Self {
         let node = Node::two_arg_node(fun, w1, w2);
         let node_count = node.node_count();
-        Tree { node: Weighted::from(node), input_shape, node_count }
+        Tree {
+            node: Weighted::from(node),
+            input_shape,
+            node_count,
+            missing: MissingSemantics::default(),
+        }
     }
 
     pub fn new(
@@ -73,7 +84,12 @@ impl Tree {
         let node =
             Weighted::new(1, input_shape, branch_prob, max_depth, forbidden_cols, data_prob);
         let node_count = node.node_count();
-        Tree { node, input_shape: *input_shape, node_count }
+        Tree {
+            node,
+            input_shape: *input_shape,
+            node_count,
+            missing: MissingSemantics::default(),
+        }
     }
 
     pub fn change_weights(&mut self) {
@@ -125,44 +141,151 @@ impl Tree {
         self.node_count
     }
 
+    /// How functions of the tree treat missing values. It's not serialized with the
+    /// tree, [`Classifier`] keeps it for all of its trees.
+    ///
+    /// [`Classifier`]: crate::exec::classifier::Classifier
     #[must_use]
-    pub fn execute_for_score(
+    pub fn missing_semantics(&self) -> MissingSemantics {
+        self.missing
+    }
+
+    pub(crate) fn set_missing_semantics(&mut self, missing: MissingSemantics) {
+        self.missing = missing;
+    }
+
+    #[must_use]
+    pub fn execute_for_score<T: Value>(
         &self,
-        data: &DataView,
+        data: &DataView<T>,
         class: Class,
         objective: Objective,
     ) -> Option<Score> {
         if data.cells().get(0, 0).len() < 2 {
             None
         } else {
-            let guesses = self.execute(data);
-            if !valid(&guesses) {
-                return None;
-            }
-            let outcomes = sort_guesses(guesses, data.outcomes());
-            let threshold = objective.threshold(&outcomes, class);
-            Some(calc_score(&outcomes, threshold, class, objective))
+            score_guesses(&self.execute(data), data, class, objective)
+        }
+    }
+
+    /// Same as [`Tree::execute_for_score`] but on data streamed from disk chunk by
+    /// chunk. Only guesses and outcomes of all data points are kept in memory, the
+    /// score is calculated once all chunks are executed.
+    pub fn execute_for_score_streamed<T: Value>(
+        &self,
+        data: &ChunkedView<T>,
+        class: Class,
+        objective: Objective,
+    ) -> Result<Option<Score>, PrimeclueErr> {
+        if data.len() < 2 {
+            return Ok(None);
+        }
+        let mut guesses = Vec::with_capacity(data.len());
+        let mut outcomes = Vec::with_capacity(data.len());
+        for chunk in data.chunks() {
+            let chunk = chunk?;
+            guesses.append(&mut self.execute(&chunk));
+            outcomes.extend_from_slice(chunk.outcomes());
         }
+        Ok(score_outcomes(&guesses, &outcomes, class, objective))
+    }
+
+    /// Renders the tree as a human readable infix formula, e.g.
+    /// `0.5 * (col0 + sine(col2))`. Column names are taken from `header` when it's
+    /// available. [`Tree::from_formula`] parses it back.
+    #[must_use]
+    pub fn to_formula(&self, header: Option<&[String]>) -> String {
+        self.node.to_formula(&self.input_shape, header)
+    }
+
+    /// Parses a tree from an infix formula, e.g. a hand written rule. See
+    /// [`formula::parse`] for syntax.
+    pub fn from_formula(
+        formula: &str,
+        input_shape: &InputShape,
+        header: Option<&[String]>,
+    ) -> Result<Self, PrimeclueErr> {
+        let node = formula::parse(formula, input_shape, header)?;
+        let node_count = node.node_count();
+        Ok(Tree {
+            node,
+            input_shape: *input_shape,
+            node_count,
+            missing: MissingSemantics::default(),
+        })
+    }
+
+    pub(crate) fn execute<T: Value>(&self, data: &DataView<T>) -> Vec<T> {
+        self.node.execute(data.cells(), self.missing)
     }
 
-    pub(crate) fn execute(&self, data: &DataView) -> Vec<f32> {
-        self.node.execute(data.cells())
+    pub fn execute_input<T: Value>(&self, input: &Input<T>) -> T {
+        self.node.execute(&input.to_view(), self.missing)[0]
     }
+}
+
+/// Scores guesses against data outcomes. Non-finite guesses are unknown: they are
+/// left out and the score is scaled by the fraction of known guesses, see
+/// [`Score::with_coverage`].
+pub(crate) fn score_guesses<T: Value>(
+    guesses: &[T],
+    data: &DataView<T>,
+    class: Class,
+    objective: Objective,
+) -> Option<Score> {
+    score_outcomes(guesses, data.outcomes(), class, objective)
+}
 
-    pub fn execute_input(&self, input: &Input) -> f32 {
-        self.node.execute(&input.to_view())[0]
+fn score_outcomes<T: Value>(
+    guesses: &[T],
+    outcomes: &[Outcome],
+    class: Class,
+    objective: Objective,
+) -> Option<Score> {
+    if guesses.iter().all(|g| g.is_finite()) {
+        score_known_guesses(guesses, outcomes, class, objective)
+    } else {
+        let (known, outcomes) = known_guesses(guesses, outcomes);
+        let coverage = known.len() as f32 / guesses.len() as f32;
+        // known guesses may miss one of the classes, leaving score undefined
+        score_known_guesses(&known, &outcomes, class, objective)
+            .filter(|s| s.value().is_finite())
+            .map(|s| s.with_coverage(coverage))
     }
 }
 
+fn score_known_guesses<T: Value>(
+    guesses: &[T],
+    outcomes: &[Outcome],
+    class: Class,
+    objective: Objective,
+) -> Option<Score> {
+    if !valid(guesses) {
+        return None;
+    }
+    let outcomes = sort_guesses(guesses, outcomes);
+    let threshold = objective.threshold(&outcomes, class);
+    Some(calc_score(&outcomes, threshold, class, objective))
+}
+
 #[cfg(test)]
 pub(crate) mod test {
-    use crate::data::InputShape;
-    use crate::exec::functions::{MATH_CONSTANTS, ONE_ARG_FUNCTIONS, TWO_ARG_FUNCTIONS};
+    use crate::data::chunked::test::write_chunks;
+    use crate::data::data_set::test::create_simple_data;
+    use crate::data::data_set::{DataSet, Point};
+    use crate::data::outcome::Class;
+    use crate::data::{Input, InputShape, Outcome};
+    use crate::exec::functions::{
+        MissingSemantics, MATH_CONSTANTS, ONE_ARG_FUNCTIONS, TWO_ARG_FUNCTIONS,
+    };
     use crate::exec::node::{Node, Weighted};
-    use crate::exec::tree::Tree;
+    use crate::exec::score::Objective::Auc;
+    use crate::exec::tree::{score_guesses, Tree};
     use crate::rand::GET_RNG;
     use crate::serialization::serializator::test::test_serialization;
     use rand::Rng;
+    use std::collections::HashMap;
+    use std::fs;
 
     #[test]
     fn serialize_tree() {
@@ -187,7 +310,12 @@ pub(crate) mod test {
         let w5 = Weighted::from(n5);
         let n6 = Node::DoubleArgFunction(&TWO_ARG_FUNCTIONS[3], w4, w5);
         let node_count = n6.node_count();
-        Tree { node: Weighted::from(n6), input_shape: InputShape::new(1, 1), node_count }
+        Tree {
+            node: Weighted::from(n6),
+            input_shape: InputShape::new(1, 1),
+            node_count,
+            missing: MissingSemantics::default(),
+        }
     }
 
     #[test]
@@ -196,11 +324,71 @@ pub(crate) mod test {
         assert_eq!(tree.node_count(), 6);
     }
 
+    #[test]
+    fn score_with_missing_guesses() {
+        let mut classes = HashMap::new();
+        classes.insert(Class::new(0), "false".to_owned());
+        classes.insert(Class::new(1), "true".to_owned());
+        let mut data = DataSet::new(classes);
+        for &class in &[0, 0, 1, 1] {
+            data.add_data_point(Point::new(
+                Input::from_vector(vec![vec![0.0]]).unwrap(),
+                Outcome::new(Class::new(class), 1.0, -1.0),
+            ))
+            .unwrap();
+        }
+        let data = data.into_view();
+        let class = Class::new(1);
+
+        let score = score_guesses(&[0.1, 0.2, 0.8, 0.9], &data, class, Auc).unwrap();
+        assert_eq!(score.value(), 1.0);
+        let score = score_guesses(&[0.1, f32::NAN, 0.8, 0.9], &data, class, Auc).unwrap();
+        assert_eq!(score.value(), 0.75);
+        assert!(score_guesses(&[f32::NAN, f32::NAN, f32::NAN, 0.9], &data, class, Auc).is_none());
+        assert!(score_guesses(&[0.1, 0.2, f32::NAN, f32::NAN], &data, class, Auc).is_none());
+    }
+
+    #[test]
+    fn score_streamed() {
+        let data = create_simple_data(100);
+        let chunks = write_chunks(&data, 30);
+        let tree = Tree::from_formula("col1 - col0", &InputShape::new(1, 2), None).unwrap();
+        let class = Class::new(1);
+        let streamed = tree.execute_for_score_streamed(&chunks, class, Auc);
+        fs::remove_dir_all(chunks.path()).unwrap();
+        let expected = tree.execute_for_score(&data.into_view(), class, Auc).unwrap();
+        let streamed = streamed.unwrap().unwrap();
+        assert_eq!(streamed.value(), expected.value());
+        assert_eq!(streamed.threshold().value(), expected.threshold().value());
+    }
+
+    #[test]
+    fn to_formula() {
+        assert_eq!(create_short_tree().to_formula(None), "col0");
+        assert_eq!(create_long_tree().to_formula(None), "abs(col0)");
+
+        let add = TWO_ARG_FUNCTIONS.iter().find(|f| f.name == "add").unwrap();
+        let sine = ONE_ARG_FUNCTIONS.iter().find(|f| f.name == "sine").unwrap();
+        let mut w1 = Weighted::from(Node::DataValue(1, 0));
+        w1.change_weight(0.5);
+        let w2 =
+            Weighted::from(Node::SingleArgFunction(sine, Weighted::from(Node::StdDev(0, 1))));
+        let tree = Tree::from_two(add, w1, w2, InputShape::new(2, 2));
+        assert_eq!(tree.to_formula(None), "(0.5 * col0[1] + sine(std_dev(col1[0])))");
+        let header = vec!["age".to_owned(), "".to_owned()];
+        assert_eq!(tree.to_formula(Some(&header)), "(0.5 * age[1] + sine(std_dev(col1[0])))");
+    }
+
     pub(crate) fn create_short_tree() -> Tree {
         let n1 = Node::DataValue(0, 0);
         let w1 = Weighted::from(n1);
         let node_count = w1.node_count();
-        Tree { node: w1, input_shape: InputShape::new(1, 1), node_count }
+        Tree {
+            node: w1,
+            input_shape: InputShape::new(1, 1),
+            node_count,
+            missing: MissingSemantics::default(),
+        }
     }
 
     pub(crate) fn create_long_tree() -> Tree {
@@ -208,6 +396,11 @@ pub(crate) mod test {
         let w1 = Weighted::from(n1);
         let w = Weighted::from(Node::SingleArgFunction(&ONE_ARG_FUNCTIONS[0], w1));
         let node_count = w.node_count();
-        Tree { node: w, input_shape: InputShape::new(1, 1), node_count }
+        Tree {
+            node: w,
+            input_shape: InputShape::new(1, 1),
+            node_count,
+            missing: MissingSemantics::default(),
+        }
     }
 }
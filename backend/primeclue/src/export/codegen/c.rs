@@ -0,0 +1,283 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::PrimeclueErr;
+use crate::export::codegen::{escape, missing_function, Model, Syntax};
+use crate::value::Precision;
+
+struct C {
+    float: &'static str,
+    /// Suffix of `math.h` functions and float literals
+    suffix: &'static str,
+}
+
+impl Syntax for C {
+    fn literal(&self, value: f64) -> String {
+        let literal = if value.is_nan() {
+            "NAN".to_owned()
+        } else if value.is_infinite() {
+            "INFINITY".to_owned()
+        } else if self.float == "float" {
+            format!("{:?}f", value as f32)
+        } else {
+            format!("{:?}", value)
+        };
+        if value.is_sign_negative() {
+            format!("(-{})", literal.trim_start_matches('-'))
+        } else {
+            literal
+        }
+    }
+
+    fn input(&self, index: usize) -> String {
+        format!("x[{}]", index)
+    }
+
+    fn standardized(&self, index: usize, stats: usize) -> String {
+        format!("((x[{}] - stats[{}]) / stats[{}])", index, 2 * stats, 2 * stats + 1)
+    }
+
+    fn call(&self, function: &str, args: &[String]) -> String {
+        format!("p_{}({})", function, args.join(", "))
+    }
+
+    fn weighted(&self, weight: &str, value: &str) -> String {
+        format!("({} * {})", weight, value)
+    }
+}
+
+pub(super) fn generate(model: &Model<'_>) -> Result<String, PrimeclueErr> {
+    let syntax = match model.precision {
+        Precision::Single => C { float: "float", suffix: "f" },
+        Precision::Double => C { float: "double", suffix: "" },
+    };
+    let t = syntax.float;
+    let mut code = format!(
+        "/* Classifier generated by Primeclue.\n\
+         \x20* A point is {} row(s) of {} column(s), flattened row by row. Missing values are NAN.\n\
+         \x20* Compile with -DPRIMECLUE_TEST to get a main() checking sample points. */\n\n\
+         #include <math.h>\n\
+         #include <stddef.h>\n\n\
+         #define POINT_SIZE {}\n\
+         #define STATS_SIZE {}\n\n",
+        model.rows,
+        model.columns,
+        model.point_size(),
+        model.standardized.len().max(1)
+    );
+    for name in &model.single_arg {
+        let body = single_arg_body(name).ok_or_else(|| missing_function(name))?;
+        let guard =
+            if model.propagates_missing(name) { "    if (isnan(v)) return v;\n" } else { "" };
+        code.push_str(&format!(
+            "static {t} p_{}({t} v) {{\n{}    return {};\n}}\n\n",
+            name,
+            guard,
+            replace(body, &syntax),
+            t = t
+        ));
+    }
+    for name in &model.double_arg {
+        let body = double_arg_body(name).ok_or_else(|| missing_function(name))?;
+        let guard = if model.propagates_missing(name) {
+            "    if (isnan(b)) return b;\n    if (isnan(a)) return a;\n"
+        } else {
+            ""
+        };
+        code.push_str(&format!(
+            "static {t} p_{}({t} a, {t} b) {{\n{}    return {};\n}}\n\n",
+            name,
+            guard,
+            replace(body, &syntax),
+            t = t
+        ));
+    }
+    if !model.standardized.is_empty() {
+        code.push_str(&replace(
+            &format!(
+                "static void standardization(const {t} points[][POINT_SIZE], size_t count, size_t index, {t} *stats) {{\n\
+                 \x20   {t} sum = 0.0$L, squares = 0.0$L;\n\
+                 \x20   size_t known = 0;\n\
+                 \x20   for (size_t i = 0; i < count; i++) {{\n\
+                 \x20       if (!isnan(points[i][index])) {{\n\
+                 \x20           sum += points[i][index];\n\
+                 \x20           known++;\n\
+                 \x20       }}\n\
+                 \x20   }}\n\
+                 \x20   stats[0] = sum / ({t})known;\n\
+                 \x20   for (size_t i = 0; i < count; i++) {{\n\
+                 \x20       if (!isnan(points[i][index])) {{\n\
+                 \x20           squares += pow$F(points[i][index] - stats[0], 2.0$L);\n\
+                 \x20       }}\n\
+                 \x20   }}\n\
+                 \x20   stats[1] = sqrt$F(squares / ({t})(known > 0 ? known - 1 : 0));\n\
+                 }}\n\n",
+                t = t
+            ),
+            &syntax,
+        ));
+    }
+    for (i, tree) in model.trees.iter().enumerate() {
+        code.push_str(&format!(
+            "static {t} tree_{}(const {t} *x, const {t} *stats) {{\n    (void)x;\n    (void)stats;\n    return {};\n}}\n\n",
+            i,
+            model.expression(tree.node, &syntax),
+            t = t
+        ));
+    }
+    code.push_str(&format!(
+        "static void guess({t} value, double threshold, const char *class_name, const char **response, int *unknown) {{\n\
+         \x20   if (!isfinite(value)) {{\n\
+         \x20       *unknown = 1;\n\
+         \x20   }} else if ((double)value >= threshold) {{\n\
+         \x20       *response = class_name;\n\
+         \x20   }}\n\
+         }}\n\n\
+         /* Classifies a batch of points, writes class name, \"\" or \"unknown\" for each into responses. */\n\
+         void classify(const {t} points[][POINT_SIZE], size_t count, const char **responses) {{\n\
+         \x20   {t} stats[2 * STATS_SIZE] = {{0}};\n",
+        t = t
+    ));
+    for (stats, index) in model.standardized.iter().enumerate() {
+        code.push_str(&format!(
+            "    standardization(points, count, {}, &stats[{}]);\n",
+            index,
+            2 * stats
+        ));
+    }
+    code.push_str(
+        "    for (size_t i = 0; i < count; i++) {\n\
+         \x20       const char *response = \"\";\n\
+         \x20       int unknown = 0;\n",
+    );
+    for (i, tree) in model.trees.iter().enumerate() {
+        code.push_str(&format!(
+            "        guess(tree_{}(points[i], stats), {:?}, {}, &response, &unknown);\n",
+            i,
+            tree.threshold,
+            escape(&tree.class)
+        ));
+    }
+    code.push_str(
+        "        responses[i] = response[0] == '\\0' && unknown ? \"unknown\" : response;\n\
+         \x20   }\n\
+         }\n\n",
+    );
+    code.push_str(&tests(model, &syntax));
+    Ok(code)
+}
+
+fn tests(model: &Model<'_>, syntax: &C) -> String {
+    let count = model.samples.len();
+    let points = model
+        .samples
+        .iter()
+        .map(|p| {
+            let values = p.iter().map(|v| syntax.literal(*v)).collect::<Vec<_>>();
+            format!("        {{{}}},\n", values.join(", "))
+        })
+        .collect::<String>();
+    let expected = model.expected.iter().map(|e| escape(e)).collect::<Vec<_>>();
+    format!(
+        "#ifdef PRIMECLUE_TEST\n\
+         #include <stdio.h>\n\
+         #include <string.h>\n\n\
+         int main(void) {{\n\
+         \x20   static const {t} points[{c}][POINT_SIZE] = {{\n{}    }};\n\
+         \x20   static const char *expected[{c}] = {{{}}};\n\
+         \x20   const char *responses[{c}];\n\
+         \x20   size_t count = {c}, failures = 0;\n\
+         \x20   classify(points, count, responses);\n\
+         \x20   for (size_t i = 0; i < count; i++) {{\n\
+         \x20       if (strcmp(responses[i], expected[i]) != 0) {{\n\
+         \x20           printf(\"Point %zu: expected '%s', got '%s'\\n\", i, expected[i], responses[i]);\n\
+         \x20           failures++;\n\
+         \x20       }}\n\
+         \x20   }}\n\
+         \x20   printf(\"%zu of %zu points failed\\n\", failures, count);\n\
+         \x20   return failures != 0;\n\
+         }}\n\
+         #endif\n",
+        points,
+        expected.join(", "),
+        t = syntax.float,
+        c = count
+    )
+}
+
+fn replace(body: &str, syntax: &C) -> String {
+    let (e, pi) = if syntax.float == "float" {
+        (format!("{:?}f", std::f32::consts::E), format!("{:?}f", std::f32::consts::PI))
+    } else {
+        (format!("{:?}", std::f64::consts::E), format!("{:?}", std::f64::consts::PI))
+    };
+    body.replace("$F", syntax.suffix)
+        .replace("$L", syntax.suffix)
+        .replace("$E", &e)
+        .replace("$PI", &pi)
+}
+
+pub(super) fn single_arg_body(name: &str) -> Option<&'static str> {
+    let body = match name {
+        "abs" => "fabs$F(v)",
+        "ceil" => "ceil$F(v)",
+        "dec" => "v - 1.0$L",
+        "floor" => "floor$F(v)",
+        "inc" => "v + 1.0$L",
+        "is_missing" => "isnan(v) ? 1.0$L : 0.0$L",
+        "log" => "log$F(v) / log$F($E)",
+        "neg" => "-v",
+        "normalize" => "v > 0.0$L ? 1.0$L : (v < 0.0$L ? -1.0$L : 0.0$L)",
+        "reciprocal" => "1.0$L / v",
+        "relu" => "fmax$F(v, 0.0$L)",
+        "round" => "round$F(v)",
+        "sine" => "sin$F(v)",
+        "sqrt" => "sqrt$F(v)",
+        "square" => "v * v",
+        "tau_sigmoid" => "pow$F(2.0$L * $PI, v)",
+        "tang_hyper" => "(pow$F($E, v) - pow$F($E, -v)) / (pow$F($E, v) + pow$F($E, -v))",
+        _ => return None,
+    };
+    Some(body)
+}
+
+pub(super) fn double_arg_body(name: &str) -> Option<&'static str> {
+    let body = match name {
+        "abs_higher" => "fmax$F(fabs$F(a), fabs$F(b))",
+        "abs_lower" => "fmin$F(fabs$F(a), fabs$F(b))",
+        "add" => "a + b",
+        "and" => "a != 0.0$L && b != 0.0$L ? 1.0$L : 0.0$L",
+        "diff" => "fabs$F(1.0$L - a / b)",
+        "div" => "a / b",
+        "equal" => "fabs$F(1.0$L - a / b) < 0.01$L ? 1.0$L : 0.0$L",
+        "first_is_higher" => "a > b ? 1.0$L : 0.0$L",
+        "higher" => "fmax$F(a, b)",
+        "if_missing" => "isnan(a) ? b : a",
+        "lower" => "fmin$F(a, b)",
+        "mid" => "(a + b) / 2.0$L",
+        "mul" => "a * b",
+        "or" => "a != 0.0$L || b != 0.0$L ? 1.0$L : 0.0$L",
+        "sub" => "a - b",
+        "sum_of_squares" => "a * a + b * b",
+        "xor" => "(a == 0.0$L) != (b == 0.0$L) ? 1.0$L : 0.0$L",
+        "round_equal_array" => "round$F(a) == round$F(b) ? 1.0$L : 0.0$L",
+        _ => return None,
+    };
+    Some(body)
+}
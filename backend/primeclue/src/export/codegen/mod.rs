@@ -0,0 +1,514 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::data::data_set::DataView;
+use crate::error::PrimeclueErr;
+use crate::exec::classifier::Classifier;
+use crate::exec::functions::MissingSemantics;
+use crate::exec::node::{Node, Weighted};
+use crate::value::{Precision, Value};
+use std::collections::BTreeSet;
+
+mod c;
+mod python;
+mod rust;
+mod sql;
+
+/// Target of [`generate`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Language {
+    Rust,
+    C,
+    Python,
+    /// PostgreSQL `CASE` expression
+    Sql,
+}
+
+/// Generates standalone code classifying data points the same way as `classifier`.
+///
+/// Generated code takes a batch of points, each one flattened row by row, with missing
+/// values as NaN (NULL in SQL). Points are classified as a batch because standardized
+/// values (`StdDev` nodes) depend on all of them, just like in [`Classifier::classify`].
+///
+/// `samples` are embedded into generated tests, together with responses of
+/// [`Classifier::classify`] for them. Rust and C reproduce classifier's arithmetic
+/// exactly, Python emulates single precision by rounding and SQL uses double
+/// precision with NULL for NaN and infinities, so their responses may differ for
+/// values very close to a threshold.
+///
+/// Classifiers with [`MissingSemantics::Legacy`] apply functions to NaN, which only
+/// Rust and C can do the same way. Classifiers with derived columns or scaling are
+/// rejected: generated code takes values trees use, not content they're computed from.
+pub fn generate<T: Value>(
+    classifier: &Classifier,
+    language: Language,
+    samples: &DataView<T>,
+) -> Result<String, PrimeclueErr> {
+    if classifier.features().is_some_and(|f| !f.is_empty()) {
+        return PrimeclueErr::result(
+            "Classifier with derived columns can't be generated".to_owned(),
+        );
+    }
+    if classifier.scaling().is_some() {
+        return PrimeclueErr::result(
+            "Classifier trained on scaled data can't be generated".to_owned(),
+        );
+    }
+    let model = Model::new(classifier, samples)?;
+    if model.missing == MissingSemantics::Legacy
+        && (language == Language::Python || language == Language::Sql)
+    {
+        return PrimeclueErr::result(format!(
+            "Classifier saved before missing values were supported can't be generated in {:?}",
+            language
+        ));
+    }
+    match language {
+        Language::Rust => rust::generate(&model),
+        Language::C => c::generate(&model),
+        Language::Python => python::generate(&model),
+        Language::Sql => sql::generate(&model),
+    }
+}
+
+struct ModelTree<'a> {
+    node: &'a Weighted,
+    threshold: f64,
+    class: String,
+}
+
+/// Everything needed to generate code, in a language independent form.
+struct Model<'a> {
+    precision: Precision,
+    rows: usize,
+    columns: usize,
+    /// Trees in order they are applied, the last one guessing true wins
+    trees: Vec<ModelTree<'a>>,
+    /// Flattened indexes of standardized values, position is index of its statistics
+    standardized: Vec<usize>,
+    single_arg: BTreeSet<&'static str>,
+    double_arg: BTreeSet<&'static str>,
+    samples: Vec<Vec<f64>>,
+    expected: Vec<String>,
+    missing: MissingSemantics,
+}
+
+impl<'a> Model<'a> {
+    fn new<T: Value>(
+        classifier: &'a Classifier,
+        samples: &DataView<T>,
+    ) -> Result<Self, PrimeclueErr> {
+        if classifier.precision() != T::PRECISION {
+            return PrimeclueErr::result(format!(
+                "Samples precision {:?} doesn't match classifier's {:?}",
+                T::PRECISION,
+                classifier.precision()
+            ));
+        }
+        if samples.is_empty() {
+            return PrimeclueErr::result("Samples for generated tests are empty".to_owned());
+        }
+        let shape = classifier.input_shape();
+        if samples.input_shape() != shape {
+            return PrimeclueErr::result(format!(
+                "Samples shape ({}) doesn't match classifier's ({})",
+                samples.input_shape(),
+                shape
+            ));
+        }
+        let mut model = Model {
+            precision: classifier.precision(),
+            rows: shape.rows(),
+            columns: shape.columns(),
+            trees: vec![],
+            standardized: vec![],
+            single_arg: BTreeSet::new(),
+            double_arg: BTreeSet::new(),
+            samples: vec![],
+            expected: vec![],
+            missing: classifier.missing_semantics(),
+        };
+        for tree in classifier.sorted_trees() {
+            let node = tree.get_start_node();
+            model.collect(node);
+            let class =
+                classifier.get_classes().get(&tree.score().class()).ok_or_else(|| {
+                    PrimeclueErr::from(format!("No class for {:?}", tree.score().class()))
+                })?;
+            model.trees.push(ModelTree {
+                node,
+                threshold: tree.score().threshold().value(),
+                class: class.to_owned(),
+            });
+        }
+        let cells = samples.cells();
+        for point in 0..samples.len() {
+            let mut values = Vec::with_capacity(model.point_size());
+            for row in 0..model.rows {
+                for column in 0..model.columns {
+                    values.push(cells.get(row, column)[point].as_f64());
+                }
+            }
+            model.samples.push(values);
+        }
+        model.expected = classifier.classify(samples).into_iter().map(str::to_owned).collect();
+        Ok(model)
+    }
+
+    fn collect(&mut self, node: &Weighted) {
+        match node.node() {
+            Node::MathConstant(_) | Node::DataValue(..) => {}
+            Node::StdDev(row, column) => {
+                let index = self.index(*row, *column);
+                if !self.standardized.contains(&index) {
+                    self.standardized.push(index);
+                }
+            }
+            Node::SingleArgFunction(f, n) => {
+                self.single_arg.insert(f.name);
+                self.collect(n);
+            }
+            Node::DoubleArgFunction(f, n1, n2) => {
+                self.double_arg.insert(f.name);
+                self.collect(n1);
+                self.collect(n2);
+            }
+        }
+    }
+
+    /// Functions other than those about missing values return missing values for
+    /// missing arguments
+    fn propagates_missing(&self, function: &str) -> bool {
+        self.missing == MissingSemantics::Propagate
+            && function != "is_missing"
+            && function != "if_missing"
+    }
+
+    fn point_size(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    fn index(&self, row: usize, column: usize) -> usize {
+        row * self.columns + column
+    }
+
+    /// Rounds `value` to model's precision.
+    fn round(&self, value: f64) -> f64 {
+        match self.precision {
+            Precision::Single => f64::from(value as f32),
+            Precision::Double => value,
+        }
+    }
+
+    /// Writes `node` as an expression of generated code.
+    fn expression(&self, node: &Weighted, syntax: &dyn Syntax) -> String {
+        let value = match node.node() {
+            Node::MathConstant(c) => syntax.literal(self.round(c.value())),
+            Node::DataValue(row, column) => syntax.input(self.index(*row, *column)),
+            Node::StdDev(row, column) => {
+                let index = self.index(*row, *column);
+                let stats = self.standardized.iter().position(|&i| i == index).unwrap();
+                syntax.standardized(index, stats)
+            }
+            Node::SingleArgFunction(f, n) => syntax.call(f.name, &[self.expression(n, syntax)]),
+            Node::DoubleArgFunction(f, n1, n2) => {
+                syntax.call(f.name, &[self.expression(n1, syntax), self.expression(n2, syntax)])
+            }
+        };
+        if node.weight() == 1.0 {
+            value
+        } else {
+            syntax.weighted(&syntax.literal(f64::from(node.weight())), &value)
+        }
+    }
+
+    fn uses_input(&self, node: &Weighted) -> bool {
+        match node.node() {
+            Node::MathConstant(_) => false,
+            Node::DataValue(..) | Node::StdDev(..) => true,
+            Node::SingleArgFunction(_, n) => self.uses_input(n),
+            Node::DoubleArgFunction(_, n1, n2) => self.uses_input(n1) || self.uses_input(n2),
+        }
+    }
+
+    fn uses_standardized(&self, node: &Weighted) -> bool {
+        match node.node() {
+            Node::MathConstant(_) | Node::DataValue(..) => false,
+            Node::StdDev(..) => true,
+            Node::SingleArgFunction(_, n) => self.uses_standardized(n),
+            Node::DoubleArgFunction(_, n1, n2) => {
+                self.uses_standardized(n1) || self.uses_standardized(n2)
+            }
+        }
+    }
+}
+
+/// Language specific parts of expressions.
+trait Syntax {
+    fn literal(&self, value: f64) -> String;
+    fn input(&self, index: usize) -> String;
+    fn standardized(&self, index: usize, stats: usize) -> String;
+    fn call(&self, function: &str, args: &[String]) -> String;
+    fn weighted(&self, weight: &str, value: &str) -> String;
+}
+
+fn missing_function(name: &str) -> PrimeclueErr {
+    PrimeclueErr::from(format!("No code for function {}", name))
+}
+
+/// Quotes and escapes a string the same way for Rust, C and Python.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::data_set::{DataSet, DataView, Point};
+    use crate::data::features::{Feature, Features};
+    use crate::data::outcome::Class;
+    use crate::data::scaling::{Scaler, Scaling};
+    use crate::data::test::temp_path;
+    use crate::data::{Input, InputShape, Outcome};
+    use crate::exec::classifier::Classifier;
+    use crate::exec::functions::{MissingSemantics, ONE_ARG_FUNCTIONS, TWO_ARG_FUNCTIONS};
+    use crate::exec::score::Objective::Auc;
+    use crate::exec::score::{Score, Threshold};
+    use crate::exec::scored_tree::ScoredTree;
+    use crate::exec::tree::test::create_short_tree;
+    use crate::exec::tree::Tree;
+    use crate::export::codegen::{c, generate, python, rust, sql, Language};
+    use crate::rand::GET_RNG;
+    use crate::value::{Precision, Value};
+    use rand::Rng;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+    use std::process::{Command, Output};
+
+    fn create_classifier() -> Classifier {
+        let mut classes = HashMap::new();
+        classes.insert(Class::new(0), "false".to_string());
+        classes.insert(Class::new(1), "it's \"true\"".to_string());
+        let trees = vec![
+            ScoredTree::new(
+                create_short_tree(),
+                Score::new(Auc, Class::new(0), 0.9, Threshold::new(10.0)),
+            ),
+            ScoredTree::new(
+                create_short_tree(),
+                Score::new(Auc, Class::new(1), 0.9, Threshold::new(0.5)),
+            ),
+        ];
+        Classifier::new(classes, trees, Precision::Single).unwrap()
+    }
+
+    fn create_samples<T: Value>() -> DataSet<T> {
+        let mut classes = HashMap::new();
+        classes.insert(Class::new(0), "false".to_string());
+        classes.insert(Class::new(1), "true".to_string());
+        let mut data = DataSet::new(classes);
+        for &value in &[1.0, 0.0, f64::NAN] {
+            data.add_data_point(Point::new(
+                Input::from_vector(vec![vec![T::from_f64(value)]]).unwrap(),
+                Outcome::new(Class::new(1), 1.0, -1.0),
+            ))
+            .unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn every_function_has_code() {
+        for f in ONE_ARG_FUNCTIONS.iter() {
+            assert!(rust::single_arg_body(f.name).is_some(), "Rust {}", f.name);
+            assert!(c::single_arg_body(f.name).is_some(), "C {}", f.name);
+            assert!(python::single_arg_body(f.name).is_some(), "Python {}", f.name);
+            assert!(sql::single_arg_body(f.name).is_some(), "SQL {}", f.name);
+        }
+        for f in TWO_ARG_FUNCTIONS.iter() {
+            assert!(rust::double_arg_body(f.name).is_some(), "Rust {}", f.name);
+            assert!(c::double_arg_body(f.name).is_some(), "C {}", f.name);
+            assert!(python::double_arg_body(f.name).is_some(), "Python {}", f.name);
+            assert!(sql::double_arg_body(f.name).is_some(), "SQL {}", f.name);
+        }
+    }
+
+    #[test]
+    fn generate_with_expected_responses() {
+        let classifier = create_classifier();
+        let samples = create_samples::<f32>().into_view();
+        for &language in &[Language::Rust, Language::C, Language::Python] {
+            let code = generate(&classifier, language, &samples).unwrap();
+            assert!(code.contains(r#""it's \"true\"""#), "{:?}", language);
+            assert!(code.contains(r#""unknown""#), "{:?}", language);
+        }
+        let code = generate(&classifier, Language::Sql, &samples).unwrap();
+        assert!(code.contains(r#"'it''s "true"'"#));
+        assert!(code.contains("WHERE actual <> expected"));
+    }
+
+    /// Classifier with random trees over 2 x 3 points and random samples for it, some
+    /// of them missing. Thresholds are medians of tree outputs, so samples get various
+    /// responses.
+    fn create_random<T: Value>(
+        precision: Precision,
+        missing: MissingSemantics,
+    ) -> (Classifier, DataSet<T>) {
+        let shape = InputShape::new(2, 3);
+        let mut classes = HashMap::new();
+        for class in 0..3 {
+            classes.insert(Class::new(class), format!("class {}", class));
+        }
+        let mut rng = GET_RNG();
+        let mut samples = DataSet::new(classes.clone());
+        for _ in 0..50 {
+            let rows = (0..shape.rows())
+                .map(|_| {
+                    (0..shape.columns())
+                        .map(|_| {
+                            if rng.gen_bool(0.02) {
+                                T::nan()
+                            } else {
+                                T::from_f64(rng.gen_range(-3.0, 3.0))
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            samples
+                .add_data_point(Point::new(
+                    Input::from_vector(rows).unwrap(),
+                    Outcome::new(Class::new(0), 1.0, -1.0),
+                ))
+                .unwrap();
+        }
+        let view = samples.clone().into_view();
+        let trees = (0..3)
+            .map(|class| {
+                let mut tree = Tree::new(&shape, 4, &[], 0.7, 0.5);
+                tree.set_missing_semantics(missing);
+                let mut outputs = tree
+                    .execute(&view)
+                    .into_iter()
+                    .map(T::as_f64)
+                    .filter(|v| v.is_finite())
+                    .collect::<Vec<_>>();
+                outputs.sort_by(f64::total_cmp);
+                let threshold = outputs.get(outputs.len() / 2).copied().unwrap_or(0.0);
+                let score = Score::new(Auc, Class::new(class), 0.9, Threshold::new(threshold));
+                ScoredTree::new(tree, score)
+            })
+            .collect();
+        (Classifier::new(classes, trees, precision).unwrap(), samples)
+    }
+
+    /// Runs a program, `None` if it's not installed
+    fn run(program: &str, args: &[&str], dir: &Path) -> Option<Output> {
+        Command::new(program).args(args).current_dir(dir).output().ok()
+    }
+
+    /// Compiles and runs generated Rust and C code with its embedded samples, if their
+    /// compilers are installed. Python and SQL may differ close to thresholds.
+    #[test]
+    fn generated_code_reproduces_classifier() {
+        let dir = temp_path("codegen");
+        fs::create_dir_all(&dir).unwrap();
+        for &missing in &[MissingSemantics::Propagate, MissingSemantics::Legacy] {
+            for _ in 0..3 {
+                let (classifier, samples) = create_random::<f32>(Precision::Single, missing);
+                check_generated(&classifier, &samples.into_view(), &dir);
+                let (classifier, samples) = create_random::<f64>(Precision::Double, missing);
+                check_generated(&classifier, &samples.into_view(), &dir);
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn check_generated<T: Value>(classifier: &Classifier, samples: &DataView<T>, dir: &Path) {
+        let code = generate(classifier, Language::Rust, samples).unwrap();
+        fs::write(dir.join("classifier.rs"), &code).unwrap();
+        let args = ["--edition", "2018", "--test", "-o", "classifier_rs", "classifier.rs"];
+        if let Some(output) = run("rustc", &args, dir) {
+            assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+            let output = run("./classifier_rs", &[], dir).unwrap();
+            assert!(
+                output.status.success(),
+                "{}\n{}",
+                String::from_utf8_lossy(&output.stdout),
+                code
+            );
+        }
+
+        let code = generate(classifier, Language::C, samples).unwrap();
+        fs::write(dir.join("classifier.c"), &code).unwrap();
+        let args = ["-DPRIMECLUE_TEST", "-o", "classifier_c", "classifier.c", "-lm"];
+        if let Some(output) = run("cc", &args, dir) {
+            assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+            let output = run("./classifier_c", &[], dir).unwrap();
+            assert!(
+                output.status.success(),
+                "{}\n{}",
+                String::from_utf8_lossy(&output.stdout),
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn legacy_missing_semantics() {
+        let (classifier, samples) =
+            create_random::<f32>(Precision::Single, MissingSemantics::Legacy);
+        let samples = samples.into_view();
+        assert!(generate(&classifier, Language::Python, &samples).is_err());
+        assert!(generate(&classifier, Language::Sql, &samples).is_err());
+    }
+
+    #[test]
+    fn derived_and_scaled() {
+        let samples = create_samples::<f32>().into_view();
+        let mut classifier = create_classifier();
+        let feature = Feature { name: "d".to_owned(), expression: "column 1".to_owned() };
+        let mut rows = vec![vec!["1".to_owned()]];
+        classifier.set_features(Some(Features::fit(&[feature], &mut rows).unwrap()));
+        assert!(generate(&classifier, Language::C, &samples).is_err());
+        classifier.set_features(None);
+        classifier.set_scaling(Some(Scaling::fit(&[Scaler::MinMax], &samples).unwrap()));
+        assert!(generate(&classifier, Language::C, &samples).is_err());
+    }
+
+    #[test]
+    fn samples_precision_mismatch() {
+        let classifier = create_classifier();
+        let samples = create_samples::<f64>().into_view();
+        assert!(generate(&classifier, Language::Rust, &samples).is_err());
+    }
+}
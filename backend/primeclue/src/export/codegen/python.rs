@@ -0,0 +1,295 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::PrimeclueErr;
+use crate::export::codegen::{escape, missing_function, Model, Syntax};
+use crate::value::Precision;
+
+struct Python {
+    precision: Precision,
+}
+
+impl Syntax for Python {
+    fn literal(&self, value: f64) -> String {
+        if value.is_nan() {
+            "math.nan".to_owned()
+        } else if value.is_infinite() && value > 0.0 {
+            "math.inf".to_owned()
+        } else if value.is_infinite() {
+            "(-math.inf)".to_owned()
+        } else if value.is_sign_negative() {
+            format!("({:?})", value)
+        } else {
+            format!("{:?}", value)
+        }
+    }
+
+    fn input(&self, index: usize) -> String {
+        format!("x[{}]", index)
+    }
+
+    fn standardized(&self, index: usize, stats: usize) -> String {
+        format!("_div(_r(x[{}] - stats[{}][0]), stats[{}][1])", index, stats, stats)
+    }
+
+    fn call(&self, function: &str, args: &[String]) -> String {
+        format!("p_{}({})", function, args.join(", "))
+    }
+
+    fn weighted(&self, weight: &str, value: &str) -> String {
+        format!("_r({} * {})", weight, value)
+    }
+}
+
+/// Python floats are always double precision. For single precision classifiers every
+/// operation's result is rounded by `_r`, which reproduces basic arithmetic exactly.
+const PRELUDE: &str = r#"def _div(a, b):
+    if b == 0.0:
+        if a == 0.0 or math.isnan(a):
+            return math.nan
+        return math.copysign(math.inf, a) * math.copysign(1.0, b)
+    return _r(a / b)
+
+
+def _log(v):
+    if math.isnan(v) or v < 0.0:
+        return math.nan
+    if v == 0.0:
+        return -math.inf
+    return _r(math.log(v))
+
+
+def _sqrt(v):
+    if math.isnan(v) or v < 0.0:
+        return math.nan
+    return _r(math.sqrt(v))
+
+
+def _pow(a, b):
+    # Only used with positive base or even exponent
+    try:
+        return _r(math.pow(a, b))
+    except OverflowError:
+        return math.inf
+
+
+def _sin(v):
+    if math.isinf(v):
+        return math.nan
+    return _r(math.sin(v))
+
+
+def _integral(f, v):
+    if math.isinf(v):
+        return v
+    return math.copysign(float(f(v)), v)
+
+
+def _round(v):
+    if math.isinf(v):
+        return v
+    t = float(math.trunc(v))
+    if abs(v - t) >= 0.5:
+        t += math.copysign(1.0, v)
+    return math.copysign(t, v)
+
+
+"#;
+
+pub(super) fn generate(model: &Model<'_>) -> Result<String, PrimeclueErr> {
+    let syntax = Python { precision: model.precision };
+    let mut code = format!(
+        "# Classifier generated by Primeclue.\n\
+         # A point is {} row(s) of {} column(s), flattened row by row. Missing values are math.nan.\n\
+         # Run this file to check sample points.\n\n\
+         import math\n\
+         from array import array\n\n\
+         POINT_SIZE = {}\n\n\n",
+        model.rows,
+        model.columns,
+        model.point_size()
+    );
+    code.push_str(match model.precision {
+        Precision::Single => "def _r(v):\n    return array(\"f\", [v])[0]\n\n\n",
+        Precision::Double => "def _r(v):\n    return v\n\n\n",
+    });
+    code.push_str(PRELUDE);
+    for name in &model.single_arg {
+        let body = single_arg_body(name).ok_or_else(|| missing_function(name))?;
+        let guard = if model.propagates_missing(name) {
+            "    if math.isnan(v):\n        return v\n"
+        } else {
+            ""
+        };
+        code.push_str(&format!(
+            "def p_{}(v):\n{}    return {}\n\n\n",
+            name,
+            guard,
+            replace(body, &syntax)
+        ));
+    }
+    for name in &model.double_arg {
+        let body = double_arg_body(name).ok_or_else(|| missing_function(name))?;
+        let guard = if model.propagates_missing(name) {
+            "    if math.isnan(b):\n        return b\n    if math.isnan(a):\n        return a\n"
+        } else {
+            ""
+        };
+        code.push_str(&format!(
+            "def p_{}(a, b):\n{}    return {}\n\n\n",
+            name,
+            guard,
+            replace(body, &syntax)
+        ));
+    }
+    code.push_str(
+        "def _standardization(points, index):\n\
+         \x20   known = [p[index] for p in points if not math.isnan(p[index])]\n\
+         \x20   total = 0.0\n\
+         \x20   for v in known:\n\
+         \x20       total = _r(total + v)\n\
+         \x20   avg = _div(total, _r(float(len(known))))\n\
+         \x20   squares = 0.0\n\
+         \x20   for v in known:\n\
+         \x20       squares = _r(squares + _pow(_r(v - avg), 2.0))\n\
+         \x20   return avg, _sqrt(_div(squares, _r(float(max(len(known) - 1, 0)))))\n\n\n",
+    );
+    for (i, tree) in model.trees.iter().enumerate() {
+        code.push_str(&format!(
+            "def _tree_{}(x, stats):\n    return {}\n\n\n",
+            i,
+            model.expression(tree.node, &syntax)
+        ));
+    }
+    let standardized =
+        model.standardized.iter().map(|i| format!("_standardization(points, {})", i));
+    let trees = model.trees.iter().enumerate().map(|(i, tree)| {
+        format!(
+            "            (_tree_{}(x, stats), {:?}, {}),\n",
+            i,
+            tree.threshold,
+            escape(&tree.class)
+        )
+    });
+    code.push_str(&format!(
+        "def classify(points):\n\
+         \x20   \"\"\"Classifies a batch of points, returns class name, \"\" or \"unknown\" for each.\"\"\"\n\
+         \x20   stats = [{}]\n\
+         \x20   responses = []\n\
+         \x20   for x in points:\n\
+         \x20       response, unknown = \"\", False\n\
+         \x20       for value, threshold, class_name in (\n{}        ):\n\
+         \x20           if math.isnan(value) or math.isinf(value):\n\
+         \x20               unknown = True\n\
+         \x20           elif value >= threshold:\n\
+         \x20               response = class_name\n\
+         \x20       responses.append(\"unknown\" if response == \"\" and unknown else response)\n\
+         \x20   return responses\n\n\n",
+        standardized.collect::<Vec<_>>().join(", "),
+        trees.collect::<String>()
+    ));
+    code.push_str(&tests(model, &syntax));
+    Ok(code)
+}
+
+fn tests(model: &Model<'_>, syntax: &Python) -> String {
+    let points = model
+        .samples
+        .iter()
+        .map(|p| {
+            let values = p.iter().map(|v| syntax.literal(*v)).collect::<Vec<_>>();
+            format!("        [{}],\n", values.join(", "))
+        })
+        .collect::<String>();
+    let expected = model.expected.iter().map(|e| escape(e)).collect::<Vec<_>>();
+    format!(
+        "def test_classify():\n\
+         \x20   points = [\n{}    ]\n\
+         \x20   expected = [{}]\n\
+         \x20   assert classify(points) == expected\n\n\n\
+         if __name__ == \"__main__\":\n\
+         \x20   test_classify()\n\
+         \x20   print(\"All points classified as expected\")\n",
+        points,
+        expected.join(", ")
+    )
+}
+
+fn replace(body: &str, syntax: &Python) -> String {
+    let (e, pi, hundredth) = match syntax.precision {
+        Precision::Single => (
+            f64::from(std::f32::consts::E),
+            f64::from(std::f32::consts::PI),
+            f64::from(0.01_f32),
+        ),
+        Precision::Double => (std::f64::consts::E, std::f64::consts::PI, 0.01),
+    };
+    body.replace("$E", &format!("{:?}", e))
+        .replace("$PI", &format!("{:?}", pi))
+        .replace("$H", &format!("{:?}", hundredth))
+}
+
+pub(super) fn single_arg_body(name: &str) -> Option<&'static str> {
+    let body = match name {
+        "abs" => "abs(v)",
+        "ceil" => "_integral(math.ceil, v)",
+        "dec" => "_r(v - 1.0)",
+        "floor" => "_integral(math.floor, v)",
+        "inc" => "_r(v + 1.0)",
+        "is_missing" => "1.0 if math.isnan(v) else 0.0",
+        "log" => "_div(_log(v), _log($E))",
+        "neg" => "-v",
+        "normalize" => "1.0 if v > 0.0 else (-1.0 if v < 0.0 else 0.0)",
+        "reciprocal" => "_div(1.0, v)",
+        "relu" => "max(v, 0.0)",
+        "round" => "_round(v)",
+        "sine" => "_sin(v)",
+        "sqrt" => "_sqrt(v)",
+        "square" => "_r(v * v)",
+        "tau_sigmoid" => "_pow(_r(2.0 * $PI), v)",
+        "tang_hyper" => "_div(_r(_pow($E, v) - _pow($E, -v)), _r(_pow($E, v) + _pow($E, -v)))",
+        _ => return None,
+    };
+    Some(body)
+}
+
+pub(super) fn double_arg_body(name: &str) -> Option<&'static str> {
+    let body = match name {
+        "abs_higher" => "max(abs(a), abs(b))",
+        "abs_lower" => "min(abs(a), abs(b))",
+        "add" => "_r(a + b)",
+        "and" => "1.0 if a != 0.0 and b != 0.0 else 0.0",
+        "diff" => "abs(_r(1.0 - _div(a, b)))",
+        "div" => "_div(a, b)",
+        "equal" => "1.0 if abs(_r(1.0 - _div(a, b))) < $H else 0.0",
+        "first_is_higher" => "1.0 if a > b else 0.0",
+        "higher" => "max(a, b)",
+        "if_missing" => "b if math.isnan(a) else a",
+        "lower" => "min(a, b)",
+        "mid" => "_div(_r(a + b), 2.0)",
+        "mul" => "_r(a * b)",
+        "or" => "1.0 if a != 0.0 or b != 0.0 else 0.0",
+        "sub" => "_r(a - b)",
+        "sum_of_squares" => "_r(_r(a * a) + _r(b * b))",
+        "xor" => "1.0 if (a == 0.0) != (b == 0.0) else 0.0",
+        "round_equal_array" => "1.0 if _round(a) == _round(b) else 0.0",
+        _ => return None,
+    };
+    Some(body)
+}
@@ -0,0 +1,264 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::PrimeclueErr;
+use crate::export::codegen::{escape, missing_function, Model, Syntax};
+use crate::value::Precision;
+
+struct Rust {
+    float: &'static str,
+}
+
+impl Syntax for Rust {
+    fn literal(&self, value: f64) -> String {
+        let literal = if value.is_nan() {
+            format!("{}::NAN", self.float)
+        } else if value.is_infinite() {
+            format!("{}::INFINITY", self.float)
+        } else if self.float == "f32" {
+            format!("{:?}_f32", value as f32)
+        } else {
+            format!("{:?}_f64", value)
+        };
+        if value.is_sign_negative() {
+            format!("(-{})", literal.trim_start_matches('-'))
+        } else {
+            literal
+        }
+    }
+
+    fn input(&self, index: usize) -> String {
+        format!("x[{}]", index)
+    }
+
+    fn standardized(&self, index: usize, stats: usize) -> String {
+        format!("((x[{}] - stats[{}].0) / stats[{}].1)", index, stats, stats)
+    }
+
+    fn call(&self, function: &str, args: &[String]) -> String {
+        format!("p_{}({})", function, args.join(", "))
+    }
+
+    fn weighted(&self, weight: &str, value: &str) -> String {
+        format!("({} * {})", weight, value)
+    }
+}
+
+pub(super) fn generate(model: &Model<'_>) -> Result<String, PrimeclueErr> {
+    let float = match model.precision {
+        Precision::Single => "f32",
+        Precision::Double => "f64",
+    };
+    let syntax = Rust { float };
+    let mut code = format!(
+        "// Classifier generated by Primeclue.\n\
+         // A point is {} row(s) of {} column(s), flattened row by row. Missing values are NaN.\n\n\
+         pub const POINT_SIZE: usize = {};\n\n",
+        model.rows,
+        model.columns,
+        model.point_size()
+    );
+    code.push_str(&format!(
+        "/// Classifies a batch of points, returns class name, \"\" or \"unknown\" for each.\n\
+         pub fn classify(points: &[[{t}; POINT_SIZE]]) -> Vec<&'static str> {{\n\
+         \x20   let stats: [({t}, {t}); {}] = [{}];\n\
+         \x20   points\n\
+         \x20       .iter()\n\
+         \x20       .map(|x| {{\n\
+         \x20           let mut response = \"\";\n\
+         \x20           let mut unknown = false;\n",
+        model.standardized.len(),
+        model
+            .standardized
+            .iter()
+            .map(|i| format!("standardization(points, {})", i))
+            .collect::<Vec<_>>()
+            .join(", "),
+        t = float
+    ));
+    for (i, tree) in model.trees.iter().enumerate() {
+        code.push_str(&format!(
+            "            guess(tree_{}(x, &stats), {:?}, {}, &mut response, &mut unknown);\n",
+            i,
+            tree.threshold,
+            escape(&tree.class)
+        ));
+    }
+    code.push_str(
+        "            if response.is_empty() && unknown {\n\
+         \x20               \"unknown\"\n\
+         \x20           } else {\n\
+         \x20               response\n\
+         \x20           }\n\
+         \x20       })\n\
+         \x20       .collect()\n\
+         }\n\n",
+    );
+    code.push_str(&format!(
+        "fn guess(\n\
+         \x20   value: {t},\n\
+         \x20   threshold: f64,\n\
+         \x20   class: &'static str,\n\
+         \x20   response: &mut &'static str,\n\
+         \x20   unknown: &mut bool,\n\
+         ) {{\n\
+         \x20   if !value.is_finite() {{\n\
+         \x20       *unknown = true;\n\
+         \x20   }} else if value as f64 >= threshold {{\n\
+         \x20       *response = class;\n\
+         \x20   }}\n\
+         }}\n\n",
+        t = float
+    ));
+    if !model.standardized.is_empty() {
+        code.push_str(&format!(
+            "fn standardization(points: &[[{t}; POINT_SIZE]], index: usize) -> ({t}, {t}) {{\n\
+             \x20   let known = points.iter().map(|p| p[index]).filter(|v| !v.is_nan());\n\
+             \x20   let count = known.clone().count();\n\
+             \x20   let avg = known.clone().sum::<{t}>() / count as {t};\n\
+             \x20   let squares = known.map(|v| (v - avg).powf(2.0)).sum::<{t}>();\n\
+             \x20   (avg, (squares / count.saturating_sub(1) as {t}).sqrt())\n\
+             }}\n\n",
+            t = float
+        ));
+    }
+    for (i, tree) in model.trees.iter().enumerate() {
+        code.push_str(&format!(
+            "fn tree_{}({}x: &[{t}; POINT_SIZE], {}stats: &[({t}, {t})]) -> {t} {{\n\
+             \x20   {}\n\
+             }}\n\n",
+            i,
+            if model.uses_input(tree.node) { "" } else { "_" },
+            if model.uses_standardized(tree.node) { "" } else { "_" },
+            model.expression(tree.node, &syntax),
+            t = float
+        ));
+    }
+    for name in &model.single_arg {
+        let body = single_arg_body(name).ok_or_else(|| missing_function(name))?;
+        let body = if model.propagates_missing(name) {
+            format!("if v.is_nan() {{\n        v\n    }} else {{\n        {}\n    }}", body)
+        } else {
+            body.to_owned()
+        };
+        code.push_str(&format!(
+            "fn p_{}(v: {t}) -> {t} {{\n    {}\n}}\n\n",
+            name,
+            body.replace("$T", float),
+            t = float
+        ));
+    }
+    for name in &model.double_arg {
+        let body = double_arg_body(name).ok_or_else(|| missing_function(name))?;
+        let body = if !model.propagates_missing(name) {
+            body.to_owned()
+        } else {
+            format!(
+                "if b.is_nan() {{\n        b\n    }} else if a.is_nan() {{\n        a\n    }} else {{\n        {}\n    }}",
+                body
+            )
+        };
+        code.push_str(&format!(
+            "fn p_{}(a: {t}, b: {t}) -> {t} {{\n    {}\n}}\n\n",
+            name,
+            body.replace("$T", float),
+            t = float
+        ));
+    }
+    code.push_str(&tests(model, &syntax));
+    Ok(code)
+}
+
+fn tests(model: &Model<'_>, syntax: &Rust) -> String {
+    let points = model
+        .samples
+        .iter()
+        .map(|p| {
+            let values = p.iter().map(|v| syntax.literal(*v)).collect::<Vec<_>>();
+            format!("            [{}],\n", values.join(", "))
+        })
+        .collect::<String>();
+    let expected = model.expected.iter().map(|e| escape(e)).collect::<Vec<_>>();
+    format!(
+        "#[cfg(test)]\n\
+         mod tests {{\n\
+         \x20   use super::*;\n\n\
+         \x20   #[test]\n\
+         \x20   fn reproduces_classifier() {{\n\
+         \x20       let points: Vec<[{}; POINT_SIZE]> = vec![\n{}        ];\n\
+         \x20       let expected: Vec<&str> = vec![{}];\n\
+         \x20       assert_eq!(classify(&points), expected);\n\
+         \x20   }}\n\
+         }}\n",
+        syntax.float,
+        points,
+        expected.join(", ")
+    )
+}
+
+pub(super) fn single_arg_body(name: &str) -> Option<&'static str> {
+    let body = match name {
+        "abs" => "v.abs()",
+        "ceil" => "v.ceil()",
+        "dec" => "v - 1.0",
+        "floor" => "v.floor()",
+        "inc" => "v + 1.0",
+        "is_missing" => "if v.is_nan() {\n        1.0\n    } else {\n        0.0\n    }",
+        "log" => "v.log(std::$T::consts::E)",
+        "neg" => "-v",
+        "normalize" => "if v > 0.0 {\n            1.0\n        } else if v < 0.0 {\n            -1.0\n        } else {\n            0.0\n        }",
+        "reciprocal" => "1.0 / v",
+        "relu" => "v.max(0.0)",
+        "round" => "v.round()",
+        "sine" => "v.sin()",
+        "sqrt" => "v.sqrt()",
+        "square" => "v * v",
+        "tau_sigmoid" => "(2.0 * std::$T::consts::PI).powf(v)",
+        "tang_hyper" => {
+            "(std::$T::consts::E.powf(v) - std::$T::consts::E.powf(-v))\n            / (std::$T::consts::E.powf(v) + std::$T::consts::E.powf(-v))"
+        }
+        _ => return None,
+    };
+    Some(body)
+}
+
+pub(super) fn double_arg_body(name: &str) -> Option<&'static str> {
+    let body = match name {
+        "abs_higher" => "a.abs().max(b.abs())",
+        "abs_lower" => "a.abs().min(b.abs())",
+        "add" => "a + b",
+        "and" => "if a != 0.0 && b != 0.0 {\n            1.0\n        } else {\n            0.0\n        }",
+        "diff" => "(1.0 - a / b).abs()",
+        "div" => "a / b",
+        "equal" => "if (1.0 - a / b).abs() < 0.01 {\n            1.0\n        } else {\n            0.0\n        }",
+        "first_is_higher" => "if a > b {\n            1.0\n        } else {\n            0.0\n        }",
+        "higher" => "a.max(b)",
+        "if_missing" => "if a.is_nan() {\n        b\n    } else {\n        a\n    }",
+        "lower" => "a.min(b)",
+        "mid" => "(a + b) / 2.0",
+        "mul" => "a * b",
+        "or" => "if a != 0.0 || b != 0.0 {\n            1.0\n        } else {\n            0.0\n        }",
+        "sub" => "a - b",
+        "sum_of_squares" => "a * a + b * b",
+        "xor" => "if (a == 0.0) != (b == 0.0) {\n            1.0\n        } else {\n            0.0\n        }",
+        "round_equal_array" => "if a.round() == b.round() {\n            1.0\n        } else {\n            0.0\n        }",
+        _ => return None,
+    };
+    Some(body)
+}
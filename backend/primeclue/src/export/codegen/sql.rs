@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::error::PrimeclueErr;
+use crate::export::codegen::{Model, Syntax};
+
+struct Sql;
+
+impl Syntax for Sql {
+    fn literal(&self, value: f64) -> String {
+        if value.is_finite() {
+            format!("CAST({:?} AS DOUBLE PRECISION)", value)
+        } else {
+            "NULL".to_owned()
+        }
+    }
+
+    fn input(&self, index: usize) -> String {
+        column(index)
+    }
+
+    fn standardized(&self, index: usize, _stats: usize) -> String {
+        format!("std_{}", column(index))
+    }
+
+    fn call(&self, function: &str, args: &[String]) -> String {
+        // functions without a body are reported before expressions are built
+        match args {
+            [v] => single_arg_body(function).unwrap_or_default().replace("{v}", v),
+            [a, b] => {
+                double_arg_body(function).unwrap_or_default().replace("{b}", b).replace("{a}", a)
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn weighted(&self, weight: &str, value: &str) -> String {
+        format!("({} * {})", weight, value)
+    }
+}
+
+fn column(index: usize) -> String {
+    format!("col{}", index)
+}
+
+/// Generates a PostgreSQL `CASE` expression over columns `col0`, `col1`, ... of points
+/// flattened row by row. Standardized values are expected in `std_col0`, ... columns
+/// and are added by a subquery shown in generated usage. It's followed by a test
+/// query returning sample points the expression doesn't reproduce.
+///
+/// SQL has no infinities: division by zero and other operations that produce one
+/// give NULL, so a point is unknown wherever the classifier relied on an infinite
+/// intermediate value.
+pub(super) fn generate(model: &Model<'_>) -> Result<String, PrimeclueErr> {
+    for name in &model.single_arg {
+        single_arg_body(name).ok_or_else(|| super::missing_function(name))?;
+    }
+    for name in &model.double_arg {
+        double_arg_body(name).ok_or_else(|| super::missing_function(name))?;
+    }
+    let expression = case(model);
+    let mut code = format!(
+        "-- Classifier generated by Primeclue.\n\
+         -- A point is {} row(s) of {} column(s), flattened row by row into columns col0 .. col{}.\n\
+         -- Missing values are NULL. Infinite intermediate values are NULL too, points\n\
+         -- relying on them are unknown and listed by the test query at the end.\n\n",
+        model.rows,
+        model.columns,
+        model.point_size() - 1
+    );
+    code.push_str(&expression);
+    code.push_str("\n\n-- Usage, points are classified as a batch:\n-- SELECT <expression above> AS class\n");
+    let usage = format!("-- FROM {}\n\n", points_source(model, "points")).replace('\n', "\n-- ");
+    code.push_str(usage.trim_end_matches("-- ").trim_end());
+    code.push_str("\n\n");
+    code.push_str(&tests(model, &expression));
+    Ok(code)
+}
+
+/// Subquery adding standardized columns to points from `source`.
+fn points_source(model: &Model<'_>, source: &str) -> String {
+    if model.standardized.is_empty() {
+        return source.to_owned();
+    }
+    let standardized = model
+        .standardized
+        .iter()
+        .map(|&i| {
+            let c = column(i);
+            format!(
+                ",\n    ({} - AVG({}) OVER ()) / NULLIF(STDDEV_SAMP({}) OVER (), 0) AS std_{}",
+                c, c, c, c
+            )
+        })
+        .collect::<String>();
+    format!("(\nSELECT *{}\nFROM {}\n) AS points", standardized, source)
+}
+
+fn case(model: &Model<'_>) -> String {
+    let values = model.trees.iter().map(|t| model.expression(t.node, &Sql)).collect::<Vec<_>>();
+    let mut case = "CASE\n".to_owned();
+    for (tree, value) in model.trees.iter().zip(&values).rev() {
+        case.push_str(&format!(
+            "    WHEN {} >= {:?} THEN {}\n",
+            value,
+            tree.threshold,
+            quote(&tree.class)
+        ));
+    }
+    let unknown = values.iter().map(|v| format!("{} IS NULL", v)).collect::<Vec<_>>();
+    case.push_str(&format!("    WHEN {} THEN 'unknown'\n", unknown.join(" OR ")));
+    case.push_str("    ELSE ''\nEND");
+    case
+}
+
+fn tests(model: &Model<'_>, expression: &str) -> String {
+    let rows = model
+        .samples
+        .iter()
+        .zip(&model.expected)
+        .map(|(point, expected)| {
+            let values = point.iter().map(|v| Sql.literal(*v)).collect::<Vec<_>>();
+            format!("    ({}, {})", values.join(", "), quote(expected))
+        })
+        .collect::<Vec<_>>();
+    let columns = (0..model.point_size()).map(column).collect::<Vec<_>>();
+    let samples =
+        format!("(VALUES\n{}\n) AS samples({}, expected)", rows.join(",\n"), columns.join(", "));
+    format!(
+        "-- Test: returns sample points classified differently than by the classifier.\n\
+         SELECT * FROM (\n\
+         SELECT expected, {} AS actual\n\
+         FROM {}\n\
+         ) AS results\n\
+         WHERE actual <> expected;\n",
+        expression,
+        points_source(model, &samples)
+    )
+}
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+pub(super) fn single_arg_body(name: &str) -> Option<&'static str> {
+    let body = match name {
+        "abs" => "ABS({v})",
+        "ceil" => "CEIL({v})",
+        "dec" => "({v} - 1)",
+        "floor" => "FLOOR({v})",
+        "inc" => "({v} + 1)",
+        "is_missing" => "CASE WHEN {v} IS NULL THEN 1.0 ELSE 0.0 END",
+        "log" => "(SELECT CASE WHEN v > 0 THEN LN(v) END FROM (SELECT {v} AS v) AS arg)",
+        "neg" => "(-{v})",
+        "normalize" => "SIGN({v})",
+        "reciprocal" => "(1 / NULLIF({v}, 0))",
+        "relu" => "(SELECT CASE WHEN v > 0 THEN v WHEN v <= 0 THEN 0.0 END FROM (SELECT {v} AS v) AS arg)",
+        "round" => "(SELECT SIGN(v) * FLOOR(ABS(v) + 0.5) FROM (SELECT {v} AS v) AS arg)",
+        "sine" => "SIN({v})",
+        "sqrt" => "(SELECT CASE WHEN v >= 0 THEN SQRT(v) END FROM (SELECT {v} AS v) AS arg)",
+        "square" => "POWER({v}, 2)",
+        "tau_sigmoid" => "POWER(2 * PI(), {v})",
+        "tang_hyper" => "TANH({v})",
+        _ => return None,
+    };
+    Some(body)
+}
+
+pub(super) fn double_arg_body(name: &str) -> Option<&'static str> {
+    let body = match name {
+        "abs_higher" => "(SELECT CASE WHEN a IS NOT NULL AND b IS NOT NULL THEN GREATEST(ABS(a), ABS(b)) END FROM (SELECT {a} AS a, {b} AS b) AS args)",
+        "abs_lower" => "(SELECT CASE WHEN a IS NOT NULL AND b IS NOT NULL THEN LEAST(ABS(a), ABS(b)) END FROM (SELECT {a} AS a, {b} AS b) AS args)",
+        "add" => "({a} + {b})",
+        "and" => "(SELECT CASE WHEN a <> 0 AND b <> 0 THEN 1.0 WHEN a IS NOT NULL AND b IS NOT NULL THEN 0.0 END FROM (SELECT {a} AS a, {b} AS b) AS args)",
+        "diff" => "ABS(1 - {a} / NULLIF({b}, 0))",
+        "div" => "({a} / NULLIF({b}, 0))",
+        "equal" => "(SELECT CASE WHEN ABS(1 - a / NULLIF(b, 0)) < 0.01 THEN 1.0 WHEN a IS NOT NULL AND b IS NOT NULL THEN 0.0 END FROM (SELECT {a} AS a, {b} AS b) AS args)",
+        "first_is_higher" => "(SELECT CASE WHEN a > b THEN 1.0 WHEN a <= b THEN 0.0 END FROM (SELECT {a} AS a, {b} AS b) AS args)",
+        "higher" => "(SELECT CASE WHEN a IS NOT NULL AND b IS NOT NULL THEN GREATEST(a, b) END FROM (SELECT {a} AS a, {b} AS b) AS args)",
+        "if_missing" => "COALESCE({a}, {b})",
+        "lower" => "(SELECT CASE WHEN a IS NOT NULL AND b IS NOT NULL THEN LEAST(a, b) END FROM (SELECT {a} AS a, {b} AS b) AS args)",
+        "mid" => "(({a} + {b}) / 2)",
+        "mul" => "({a} * {b})",
+        "or" => "(SELECT CASE WHEN a IS NULL OR b IS NULL THEN NULL WHEN a <> 0 OR b <> 0 THEN 1.0 ELSE 0.0 END FROM (SELECT {a} AS a, {b} AS b) AS args)",
+        "sub" => "({a} - {b})",
+        "sum_of_squares" => "(POWER({a}, 2) + POWER({b}, 2))",
+        "xor" => "(SELECT CASE WHEN a IS NULL OR b IS NULL THEN NULL WHEN (a = 0) <> (b = 0) THEN 1.0 ELSE 0.0 END FROM (SELECT {a} AS a, {b} AS b) AS args)",
+        "round_equal_array" => "(SELECT CASE WHEN SIGN(a) * FLOOR(ABS(a) + 0.5) = SIGN(b) * FLOOR(ABS(b) + 0.5) THEN 1.0 WHEN a <= b OR a > b THEN 0.0 END FROM (SELECT {a} AS a, {b} AS b) AS args)",
+        _ => return None,
+    };
+    Some(body)
+}
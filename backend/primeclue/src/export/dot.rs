@@ -0,0 +1,187 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::exec::classifier::Classifier;
+use crate::exec::node::{column_name, Node, Weighted};
+use crate::exec::tree::Tree;
+
+/// Renders `tree` as a Graphviz DOT digraph. Nodes are labelled with function and
+/// constant names or `DataValue(row, column)` / `StdDev(row, column)` references,
+/// followed by weight if it's not 1. Column names are added from `header` when it's
+/// available.
+#[must_use]
+pub fn tree_to_dot(tree: &Tree, header: Option<&[String]>) -> String {
+    let mut dot = Dot::new(tree, header);
+    dot.push_node(tree.get_start_node(), "n", "    ");
+    format!("digraph tree {{\n    node [shape=box];\n{}}}\n", dot.lines)
+}
+
+/// Renders `classifier` as a Graphviz DOT digraph with a cluster for every tree, in
+/// order they are applied by [`Classifier::classify`]. Graph is labelled with
+/// [`Classifier::preprocessing`] if data is derived or scaled before trees use it.
+#[must_use]
+pub fn classifier_to_dot(classifier: &Classifier, header: Option<&[String]>) -> String {
+    let mut clusters = String::new();
+    let preprocessing = classifier.preprocessing();
+    if !preprocessing.is_empty() {
+        let lines = preprocessing.iter().map(|l| format!("{}\\l", escape(l)));
+        clusters.push_str(&format!(
+            "    labelloc=t;\n    label=\"{}\";\n",
+            lines.collect::<String>()
+        ));
+    }
+    for (i, tree) in classifier.structure().iter().enumerate() {
+        let mut dot = Dot::new(&tree.tree, header);
+        dot.push_node(tree.tree.get_start_node(), &format!("t{}_n", i), "        ");
+        clusters.push_str(&format!(
+            "    subgraph cluster_{} {{\n        label=\"{}\";\n{}    }}\n",
+            i,
+            escape(&format!(
+                "{} if value >= {} (score {})",
+                tree.class, tree.threshold, tree.score
+            )),
+            dot.lines
+        ));
+    }
+    format!("digraph classifier {{\n    node [shape=box];\n{}}}\n", clusters)
+}
+
+struct Dot<'a> {
+    tree: &'a Tree,
+    header: Option<&'a [String]>,
+    count: usize,
+    lines: String,
+}
+
+impl<'a> Dot<'a> {
+    fn new(tree: &'a Tree, header: Option<&'a [String]>) -> Self {
+        Dot { tree, header, count: 0, lines: String::new() }
+    }
+
+    /// Adds node with its children and returns its id.
+    fn push_node(&mut self, node: &Weighted, prefix: &str, indent: &str) -> String {
+        let id = format!("{}{}", prefix, self.count);
+        self.count += 1;
+        let mut label = match node.node() {
+            Node::DataValue(row, column) => self.reference("DataValue", *row, *column),
+            Node::StdDev(row, column) => self.reference("StdDev", *row, *column),
+            Node::MathConstant(c) => c.name().to_owned(),
+            Node::SingleArgFunction(f, _) => f.name.to_owned(),
+            Node::DoubleArgFunction(f, _, _) => f.name.to_owned(),
+        };
+        if node.weight() != 1.0 {
+            label.push_str(&format!("\nweight {}", node.weight()));
+        }
+        self.lines.push_str(&format!("{}{} [label=\"{}\"];\n", indent, id, escape(&label)));
+        let children = match node.node() {
+            Node::SingleArgFunction(_, n) => vec![n],
+            Node::DoubleArgFunction(_, n1, n2) => vec![n1, n2],
+            _ => vec![],
+        };
+        for child in children {
+            let child_id = self.push_node(child, prefix, indent);
+            self.lines.push_str(&format!("{}{} -> {};\n", indent, id, child_id));
+        }
+        id
+    }
+
+    fn reference(&self, name: &str, row: usize, column: usize) -> String {
+        let reference = format!("{}({}, {})", name, row, column);
+        match self.header {
+            Some(_) => format!(
+                "{}\n{}",
+                reference,
+                column_name(row, column, self.tree.input_shape(), self.header)
+            ),
+            None => reference,
+        }
+    }
+}
+
+/// Escapes a DOT string, new lines become centered line breaks.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::data_set::test::create_multiclass_data;
+    use crate::data::features::{Feature, Features};
+    use crate::data::outcome::Class;
+    use crate::data::scaling::{Scaler, Scaling};
+    use crate::data::InputShape;
+    use crate::exec::classifier::Classifier;
+    use crate::exec::functions::{ONE_ARG_FUNCTIONS, TWO_ARG_FUNCTIONS};
+    use crate::exec::node::{Node, Weighted};
+    use crate::exec::score::Objective::Auc;
+    use crate::exec::score::{Score, Threshold};
+    use crate::exec::scored_tree::ScoredTree;
+    use crate::exec::tree::Tree;
+    use crate::export::dot::{classifier_to_dot, tree_to_dot};
+    use crate::value::Precision;
+    use std::collections::HashMap;
+
+    #[test]
+    fn tree_dot() {
+        let add = TWO_ARG_FUNCTIONS.iter().find(|f| f.name == "add").unwrap();
+        let abs = ONE_ARG_FUNCTIONS.iter().find(|f| f.name == "abs").unwrap();
+        let mut value = Weighted::from(Node::StdDev(0, 1));
+        value.change_weight(0.5);
+        let tree = Tree::from_two(
+            add,
+            Weighted::from(Node::DataValue(0, 0)),
+            Weighted::from(Node::one_arg_node(abs, value)),
+            InputShape::new(1, 2),
+        );
+        let header = vec!["a \"b\"".to_owned(), "".to_owned()];
+        assert_eq!(
+            tree_to_dot(&tree, Some(&header)),
+            "digraph tree {\n    node [shape=box];\n    n0 [label=\"add\"];\n    \
+             n1 [label=\"DataValue(0, 0)\\na \\\"b\\\"\"];\n    n0 -> n1;\n    \
+             n2 [label=\"abs\"];\n    n3 [label=\"StdDev(0, 1)\\ncol1\\nweight 0.5\"];\n    \
+             n2 -> n3;\n    n0 -> n2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn preprocessing_label() {
+        let mut classes = HashMap::new();
+        classes.insert(Class::new(0), "x".to_owned());
+        let tree = Tree::from_formula("col0", &InputShape::new(1, 1), None).unwrap();
+        let score = Score::new(Auc, Class::new(0), 0.9, Threshold::new(0.5));
+        let mut classifier =
+            Classifier::new(classes, vec![ScoredTree::new(tree, score)], Precision::Single)
+                .unwrap();
+        assert!(!classifier_to_dot(&classifier, None).contains("label=\"derived"));
+        let feature = Feature { name: "d".to_owned(), expression: "column 1".to_owned() };
+        let mut rows = vec![vec!["1".to_owned()]];
+        classifier.set_features(Some(Features::fit(&[feature], &mut rows).unwrap()));
+        classifier.set_header(vec!["d".to_owned()]);
+        let data = create_multiclass_data().into_view();
+        classifier.set_scaling(Some(Scaling::fit(&[Scaler::Log], &data).unwrap()));
+        assert_eq!(
+            classifier.preprocessing(),
+            ["derived: d = column 1", "scaled: d = sign(d) * ln(1 + abs(d))"]
+        );
+        let dot = classifier_to_dot(&classifier, None);
+        assert!(dot.contains(
+            "label=\"derived: d = column 1\\lscaled: d = sign(d) * ln(1 + abs(d))\\l\";"
+        ));
+    }
+}
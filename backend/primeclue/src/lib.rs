@@ -40,7 +40,9 @@ extern crate lazy_static;
 pub mod data;
 pub mod error;
 pub mod exec;
+pub mod export;
 pub mod math;
 pub mod rand;
 pub mod serialization;
 pub mod user;
+pub mod value;
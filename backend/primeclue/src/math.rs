@@ -1,35 +1,49 @@
-pub fn std_dev(values: &[f32]) -> Vec<f32> {
-    let avg = values.iter().sum::<f32>() / values.len() as f32;
-    let st_dev = (values.iter().map(|v| (v - avg).powf(2.0)).sum::<f32>()
-        / (values.len() - 1) as f32)
-        .sqrt();
-    values.iter().map(|v| (v - avg) / st_dev).collect()
+use crate::value::Value;
+
+pub fn std_dev<T: Value>(values: &[T]) -> Vec<T> {
+    let mut result = Vec::with_capacity(values.len());
+    std_dev_into(values, &mut result);
+    result
+}
+
+/// Same as [`std_dev`] but writes result into given buffer, reusing its allocation.
+/// Missing (NaN) values are skipped when calculating average and deviation and stay
+/// missing in the result.
+pub fn std_dev_into<T: Value>(values: &[T], result: &mut Vec<T>) {
+    let known = || values.iter().copied().filter(|v| !v.is_nan());
+    let count = known().count();
+    let avg = known().sum::<T>() / T::from_f64(count as f64);
+    let st_dev = (known().map(|v| (v - avg).powf(T::from_f64(2.0))).sum::<T>()
+        / T::from_f64(count.saturating_sub(1) as f64))
+    .sqrt();
+    result.clear();
+    result.extend(values.iter().map(|v| (*v - avg) / st_dev));
 }
 
-pub fn median(values: &mut [f32]) -> f32 {
+pub fn median<T: Value>(values: &mut [T]) -> T {
     values.sort_by(|v1, v2| v1.partial_cmp(&v2).unwrap());
     let middle = values.len() / 2;
     if values.len() % 2 == 0 {
-        (values[middle] + values[middle - 1]) / 2.0
+        (values[middle] + values[middle - 1]) / T::from_f64(2.0)
     } else {
         values[middle]
     }
 }
 
-pub fn valid(values: &[f32]) -> bool {
+pub fn valid<T: Value>(values: &[T]) -> bool {
     let mut change = false;
     for v in values {
         if !v.is_finite() {
             return false;
         }
-        change = change || (*v - values[0]).abs() > 0.001;
+        change = change || (*v - values[0]).abs() > T::MIN_CHANGE;
     }
     change
 }
 
 #[cfg(test)]
 mod test {
-    use crate::math::{median, valid};
+    use crate::math::{median, std_dev, valid};
 
     #[test]
     fn median_test() {
@@ -49,7 +63,7 @@ mod test {
 
     #[test]
     fn test_valid() {
-        assert!(!valid(&vec![]));
+        assert!(!valid::<f32>(&[]));
         let values = vec![-1.0, 2.0, 3.0];
         assert!(valid(&values));
 
@@ -67,7 +81,21 @@ mod test {
         let values = vec![-1.0, 2.0, 3.0, f32::INFINITY];
         assert!(!valid(&values));
 
-        let values = vec![4.0, 4.0, 4.00001];
+        let values = vec![4.0_f32, 4.0, 4.00001];
         assert!(!valid(&values));
+        let values = vec![4.0_f64, 4.0, 4.00001];
+        assert!(valid(&values));
+        let values = vec![1e12_f64, 1e12, 1e12 + 1.0];
+        assert!(valid(&values));
+    }
+
+    #[test]
+    fn std_dev_skips_missing() {
+        let expected = std_dev(&[1.0, 2.0, 4.0]);
+        let actual = std_dev(&[1.0, f64::NAN, 2.0, 4.0]);
+        assert_eq!(actual[0], expected[0]);
+        assert!(actual[1].is_nan());
+        assert_eq!(actual[2..], expected[1..]);
+        assert!(std_dev(&[f32::NAN, f32::NAN]).iter().all(|v| v.is_nan()));
     }
 }
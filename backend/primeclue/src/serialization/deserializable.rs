@@ -17,7 +17,9 @@
    along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::serialization::serializator::{Serializator, PRIMECLUE_SPACE_SUBSTITUTE};
+use crate::serialization::serializator::{
+    Serializator, PRIMECLUE_EMPTY_STRING, PRIMECLUE_SPACE_SUBSTITUTE,
+};
 use std::collections::HashMap;
 use std::hash::Hash;
 
@@ -41,9 +43,17 @@ impl Deserializable for u16 {
     }
 }
 
+impl Deserializable for u64 {
+    fn deserialize(s: &mut Serializator) -> Result<u64, String> {
+        let v = s.next_token()?;
+        v.parse().map_err(|e| format!("Unable to parse '{}': {:?}", v, e))
+    }
+}
+
 impl Deserializable for String {
     fn deserialize(s: &mut Serializator) -> Result<String, String> {
         match s.next_token() {
+            Ok(s) if s == PRIMECLUE_EMPTY_STRING => Ok(String::new()),
             Ok(s) => Ok(s.replace(PRIMECLUE_SPACE_SUBSTITUTE, " ")),
             Err(e) => Err(e.to_string()), // TODO test
         }
@@ -57,6 +67,13 @@ impl Deserializable for f32 {
     }
 }
 
+impl Deserializable for f64 {
+    fn deserialize(s: &mut Serializator) -> Result<f64, String> {
+        let v = s.next_token()?;
+        v.parse().map_err(|e| format!("Unable to parse '{}': {:?}", v, e))
+    }
+}
+
 impl Deserializable for bool {
     // TODO test
     fn deserialize(s: &mut Serializator) -> Result<bool, String> {
@@ -37,6 +37,12 @@ impl Serializable for u16 {
     }
 }
 
+impl Serializable for u64 {
+    fn serialize(&self, s: &mut Serializator) {
+        s.add_string(format!("{}", self));
+    }
+}
+
 impl Serializable for String {
     fn serialize(&self, s: &mut Serializator) {
         s.add_string(self.to_owned());
@@ -21,33 +21,58 @@ use crate::error::PrimeclueErr;
 use crate::serialization::serializable::Serializable;
 use std::fs;
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufReader, Read, Write};
 use std::path::Path;
 
 pub const PRIMECLUE_SPACE_SUBSTITUTE: &str = "PRIMECLUE_SPACE_SUBSTITUTE";
+/// Empty strings would be lost when splitting serialized content on whitespace
+pub const PRIMECLUE_EMPTY_STRING: &str = "PRIMECLUE_EMPTY_STRING";
 pub const SERIALIZED_FILE_EXT: &str = ".ssd";
+const READ_BLOCK_SIZE: usize = 64 * 1024;
 
 #[derive(Default, Debug)]
 pub struct Serializator {
     strings: Vec<String>,
     next_token: usize,
+    stream: Option<TokenStream>,
+}
+
+/// File read block by block, see [`Serializator::open`]
+#[derive(Debug)]
+struct TokenStream {
+    reader: BufReader<File>,
+    /// Bytes read after the last complete token
+    pending: Vec<u8>,
+    expected_check_sum: u128,
+    check_sum: u128,
+    error: Option<String>,
+    finished: bool,
 }
 
 impl Serializator {
     #[must_use]
     pub fn new() -> Self {
-        Serializator { strings: vec![], next_token: 0 }
+        Serializator { strings: vec![], next_token: 0, stream: None }
     }
 
     pub fn next_token(&mut self) -> Result<&String, &str> {
+        self.read_ahead(2);
         if let Some(s) = self.strings.get(self.next_token) {
             self.next_token += 1;
             Ok(s)
+        } else if let Some(error) = self.stream.as_ref().and_then(|s| s.error.as_ref()) {
+            Err(error)
         } else {
             Err("Not enough tokens")
         }
     }
 
+    /// Returns next token without consuming it.
+    #[must_use]
+    pub fn peek_token(&self) -> Option<&String> {
+        self.strings.get(self.next_token)
+    }
+
     pub fn add_items(&mut self, a: &[&dyn Serializable]) {
         a.iter().for_each(|i| self.add(*i));
     }
@@ -58,7 +83,11 @@ impl Serializator {
     }
 
     pub fn add_string(&mut self, v: String) {
-        let v = v.replace(" ", PRIMECLUE_SPACE_SUBSTITUTE);
+        let v = if v.is_empty() {
+            PRIMECLUE_EMPTY_STRING.to_owned()
+        } else {
+            v.replace(" ", PRIMECLUE_SPACE_SUBSTITUTE)
+        };
         self.strings.push(v);
     }
 
@@ -78,7 +107,7 @@ impl Serializator {
             .map_err(|e| format!("Cannot convert bytes to String {}", e))?;
         let (read_check_sum, content) = Serializator::extract_check_sum(&content)?;
         let strings: Vec<String> = content.split_whitespace().map(ToOwned::to_owned).collect();
-        let serializator = Serializator { strings, next_token: 0 };
+        let serializator = Serializator { strings, next_token: 0, stream: None };
         let actual_check_sum = serializator.calc_checksum();
         if read_check_sum == actual_check_sum {
             Ok(serializator)
@@ -99,6 +128,102 @@ impl Serializator {
         Ok(bytes_written)
     }
 
+    /// Opens serialized file for reading token by token, so that it never has to be in
+    /// memory as a whole. Checksum is verified once all tokens are read, so
+    /// [`Serializator::finish`] should be called after deserializing.
+    pub fn open(path: &Path) -> Result<Serializator, String> {
+        let file = File::open(path).map_err(|e| format!("Unable to open {:?}: {}", path, e))?;
+        let mut serializator = Serializator::new();
+        serializator.stream = Some(TokenStream {
+            reader: BufReader::new(file),
+            pending: vec![],
+            expected_check_sum: 0,
+            check_sum: 1,
+            error: None,
+            finished: false,
+        });
+        serializator.read_ahead(2);
+        if serializator.strings.is_empty() {
+            return Err("Unable to find check sum".to_owned());
+        }
+        let check_sum = serializator.strings.remove(0);
+        let read_check_sum = serializator.calc_checksum();
+        let stream = serializator.stream.as_mut().unwrap();
+        stream.expected_check_sum =
+            check_sum.parse().map_err(|e| format!("Unable to parse checksum: {}", e))?;
+        stream.check_sum = read_check_sum;
+        Ok(serializator)
+    }
+
+    /// Reads the rest of a file opened with [`Serializator::open`] and verifies its
+    /// checksum. Does nothing for serializators in memory.
+    pub fn finish(&mut self) -> Result<(), String> {
+        while self.stream.as_ref().is_some_and(|s| !s.finished) {
+            self.next_token = self.strings.len();
+            self.read_ahead(1);
+        }
+        match self.stream.as_ref().and_then(|s| s.error.clone()) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Makes sure `count` unconsumed tokens are in memory, unless the stream ends.
+    /// Consumed tokens are dropped.
+    fn read_ahead(&mut self, count: usize) {
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => return,
+        };
+        if self.strings.len() - self.next_token >= count || stream.finished {
+            return;
+        }
+        self.strings.drain(..self.next_token);
+        self.next_token = 0;
+        let first_new = self.strings.len();
+        let mut block = vec![0; READ_BLOCK_SIZE];
+        while self.strings.len() < count && !stream.finished {
+            let read = match stream.reader.read(&mut block) {
+                Ok(read) => read,
+                Err(e) => {
+                    stream.error = Some(format!("Unable to read file: {}", e));
+                    stream.finished = true;
+                    break;
+                }
+            };
+            stream.pending.extend_from_slice(&block[..read]);
+            // whitespace is ASCII so it can't be a part of multi byte character
+            let end = if read == 0 {
+                stream.finished = true;
+                stream.pending.len()
+            } else {
+                match stream.pending.iter().rposition(u8::is_ascii_whitespace) {
+                    Some(end) => end,
+                    None => continue,
+                }
+            };
+            let complete = stream.pending.drain(..end).collect::<Vec<_>>();
+            match String::from_utf8(complete) {
+                Ok(content) => {
+                    self.strings.extend(content.split_whitespace().map(ToOwned::to_owned))
+                }
+                Err(e) => {
+                    stream.error = Some(format!("Cannot convert bytes to String {}", e));
+                    stream.finished = true;
+                }
+            }
+        }
+        for string in &self.strings[first_new..] {
+            stream.check_sum = add_to_check_sum(stream.check_sum, string);
+        }
+        if stream.finished
+            && stream.error.is_none()
+            && stream.check_sum != stream.expected_check_sum
+        {
+            stream.error = Some("Invalid checksum".to_string());
+        }
+    }
+
     pub fn load(path: &Path) -> Result<Serializator, String> {
         if path.exists() {
             let bytes =
@@ -128,18 +253,19 @@ impl Serializator {
     }
 
     fn calc_checksum(&self) -> u128 {
-        const LIMIT: u128 = u128::max_value() >> 7;
-        let mut cs: u128 = 1;
-        for string in &self.strings {
-            for byte in string.as_bytes() {
-                if cs > LIMIT {
-                    cs >>= 8;
-                }
-                cs *= u128::from(*byte) + 1;
-            }
+        self.strings.iter().fold(1, |cs, string| add_to_check_sum(cs, string))
+    }
+}
+
+fn add_to_check_sum(mut cs: u128, string: &str) -> u128 {
+    const LIMIT: u128 = u128::max_value() >> 7;
+    for byte in string.as_bytes() {
+        if cs > LIMIT {
+            cs >>= 8;
         }
-        cs
+        cs *= u128::from(*byte) + 1;
     }
+    cs
 }
 
 #[cfg(test)]
@@ -166,9 +292,12 @@ pub mod test {
         }
     }
 
+    use crate::rand::GET_RNG;
     use crate::serialization::{Deserializable, Serializable, Serializator};
+    use rand::Rng;
     use std::collections::HashMap;
     use std::fmt::Debug;
+    use std::fs;
 
     pub fn test_serialization<T: Serializable + Deserializable + PartialEq + Debug>(v: T) {
         let mut s = Serializator::new();
@@ -201,6 +330,11 @@ pub mod test {
         test_serialization(test);
     }
 
+    #[test]
+    fn test_empty_string() {
+        test_serialization(vec!["".to_owned(), "".to_owned(), "x".to_owned()]);
+    }
+
     #[test]
     fn test_map() {
         let mut m = HashMap::new();
@@ -242,6 +376,37 @@ pub mod test {
         assert_eq!(r.err().unwrap(), "Invalid checksum");
     }
 
+    #[test]
+    fn open() {
+        let dir = std::env::temp_dir();
+        let name = format!("primeclue_stream_{}.ssd", GET_RNG().gen::<u64>());
+        let path = dir.join(&name);
+        // long enough to be read in many blocks
+        let strings = (0..50_000).map(|i| format!("token {}", i)).collect::<Vec<_>>();
+        let mut s = Serializator::new();
+        s.add_items(&[&strings, &"last".to_owned()]);
+        s.save(&dir, &name).unwrap();
+
+        let mut opened = Serializator::open(&path).unwrap();
+        assert_eq!(Vec::<String>::deserialize(&mut opened).unwrap(), strings);
+        assert_eq!(opened.peek_token().unwrap(), "last");
+        assert!(opened.finish().is_ok());
+        let mut opened = Serializator::open(&path).unwrap();
+        assert_eq!(usize::deserialize(&mut opened).unwrap(), strings.len());
+        assert!(opened.finish().is_ok());
+
+        let mut bytes = fs::read(&path).unwrap();
+        let index = bytes.len() - 3;
+        bytes[index] = b'x';
+        fs::write(&path, bytes).unwrap();
+        let mut opened = Serializator::open(&path).unwrap();
+        assert!(Vec::<String>::deserialize(&mut opened).is_ok());
+        assert_eq!(opened.finish().unwrap_err(), "Invalid checksum");
+        assert!(opened.next_token().is_err());
+        fs::remove_file(&path).unwrap();
+        assert!(Serializator::open(&path).is_err());
+    }
+
     #[test]
     fn test_option_invalid_token() {
         let mut s = Serializator::new();
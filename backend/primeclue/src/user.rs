@@ -23,12 +23,19 @@ use std::fs::{DirEntry, ReadDir};
 use std::path::{Path, PathBuf};
 
 pub const DELETE_IN_PROGRESS: &str = "delete_in_progress";
+/// Prefix of directory re-imported data is written to before it replaces data set
+pub const REIMPORT_IN_PROGRESS: &str = "reimport_in_progress";
 pub const DATA_DIR: &str = "data";
 pub const CLASSIFIERS_DIR: &str = "classifiers";
+pub const IMPORT_DIR: &str = "import";
+/// Environment variable with a directory to import files from, instead of
+/// [`IMPORT_DIR`] in base directory
+pub const IMPORT_DIR_VARIABLE: &str = "PRIMECLUE_IMPORT_DIR";
 
 #[derive(Clone, Debug)]
 pub struct Settings {
     base_dir: PathBuf,
+    import_dir: PathBuf,
 }
 
 impl Settings {
@@ -42,7 +49,12 @@ impl Settings {
         create_dir(&data)?;
         let projects = base_dir.join(CLASSIFIERS_DIR);
         create_dir(&projects)?;
-        Ok(Settings { base_dir })
+        let import_dir = match std::env::var_os(IMPORT_DIR_VARIABLE) {
+            Some(dir) => PathBuf::from(dir),
+            None => base_dir.join(IMPORT_DIR),
+        };
+        create_dir(&import_dir)?;
+        Ok(Settings { base_dir, import_dir })
     }
 
     pub fn base_dir(&self) -> &PathBuf {
@@ -56,6 +68,12 @@ impl Settings {
     pub fn classifier_dir(&self) -> PathBuf {
         self.base_dir.clone().join(CLASSIFIERS_DIR)
     }
+
+    /// Directory with files that can be imported on the server, see
+    /// [`crate::data::file_import`]
+    pub fn import_dir(&self) -> &PathBuf {
+        &self.import_dir
+    }
 }
 
 fn create_dir(dir: &Path) -> Result<(), String> {
@@ -81,7 +99,7 @@ pub fn read_files(list: ReadDir) -> Result<Vec<String>, PrimeclueErr> {
             .to_str()
             .ok_or_else(|| PrimeclueErr::from(format!("Unable to read file: {:?}", file)))?
             .to_owned();
-        if !utf_name.contains(DELETE_IN_PROGRESS) {
+        if !utf_name.contains(DELETE_IN_PROGRESS) && !utf_name.contains(REIMPORT_IN_PROGRESS) {
             projects.push(utf_name);
         }
     }
@@ -0,0 +1,193 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+/*
+   Primeclue: Machine Learning and Data Mining
+   Copyright (C) 2020 Łukasz Wojtów
+
+   This program is free software: you can redistribute it and/or modify
+   it under the terms of the GNU Affero General Public License as
+   published by the Free Software Foundation, either version 3 of the
+   License, or (at your option) any later version.
+
+   This program is distributed in the hope that it will be useful,
+   but WITHOUT ANY WARRANTY; without even the implied warranty of
+   MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+   GNU Affero General Public License for more details.
+
+   You should have received a copy of the GNU Affero General Public License
+   along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::exec::functions::{DoubleArgFunction, SingleArgFunction};
+use crate::serialization::{Deserializable, Serializable, Serializator};
+use num_traits::{Float, FloatConst};
+use std::fmt::{Debug, Display};
+use std::iter::Sum;
+use std::num::ParseFloatError;
+use std::str::FromStr;
+
+/// Floating point precision used to store data and execute trees.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub enum Precision {
+    #[default]
+    Single,
+    Double,
+}
+
+impl Precision {
+    /// Returns precision recorded at the current position, without consuming it.
+    /// Anything serialized before precision was recorded is `Single`.
+    #[must_use]
+    pub fn peek(s: &Serializator) -> Precision {
+        match s.peek_token().map(String::as_str) {
+            Some("Double") => Precision::Double,
+            _ => Precision::Single,
+        }
+    }
+
+    /// Consumes precision token if present, otherwise assumes `Single`.
+    pub fn deserialize_or_single(s: &mut Serializator) -> Result<Precision, String> {
+        match s.peek_token().map(String::as_str) {
+            Some("Single") | Some("Double") => Precision::deserialize(s),
+            _ => Ok(Precision::Single),
+        }
+    }
+
+    /// Like `deserialize_or_single` but fails if precision doesn't match `T`.
+    pub fn deserialize_expecting<T: Value>(s: &mut Serializator) -> Result<(), String> {
+        let precision = Precision::deserialize_or_single(s)?;
+        if precision == T::PRECISION {
+            Ok(())
+        } else {
+            Err(format!("Invalid precision: {:?}, expected: {:?}", precision, T::PRECISION))
+        }
+    }
+}
+
+impl Serializable for Precision {
+    fn serialize(&self, s: &mut Serializator) {
+        match self {
+            Precision::Single => s.add_str("Single"),
+            Precision::Double => s.add_str("Double"),
+        }
+    }
+}
+
+impl Deserializable for Precision {
+    fn deserialize(s: &mut Serializator) -> Result<Precision, String> {
+        let v = s.next_token()?;
+        match v.as_str() {
+            "Single" => Ok(Precision::Single),
+            "Double" => Ok(Precision::Double),
+            _ => Err(format!("Invalid precision: {}", v)),
+        }
+    }
+}
+
+/// Floating point type that data can be stored in and trees executed with.
+pub trait Value:
+    Float
+    + FloatConst
+    + Default
+    + Debug
+    + Display
+    + Sum
+    + FromStr<Err = ParseFloatError>
+    + Serializable
+    + Deserializable
+    + Send
+    + Sync
+    + 'static
+{
+    const PRECISION: Precision;
+
+    /// Values closer to each other than this are considered the same
+    /// when checking if tree output is usable.
+    const MIN_CHANGE: Self;
+
+    fn from_f32(v: f32) -> Self;
+
+    fn from_f64(v: f64) -> Self;
+
+    fn as_f64(self) -> f64;
+
+    fn single_arg_fun(f: &SingleArgFunction) -> fn(Vec<Self>) -> Vec<Self>;
+
+    fn double_arg_fun(f: &DoubleArgFunction) -> fn(Vec<Self>, &[Self]) -> Vec<Self>;
+}
+
+impl Value for f32 {
+    const PRECISION: Precision = Precision::Single;
+    const MIN_CHANGE: f32 = 0.001;
+
+    fn from_f32(v: f32) -> f32 {
+        v
+    }
+
+    fn from_f64(v: f64) -> f32 {
+        v as f32
+    }
+
+    fn as_f64(self) -> f64 {
+        f64::from(self)
+    }
+
+    fn single_arg_fun(f: &SingleArgFunction) -> fn(Vec<f32>) -> Vec<f32> {
+        f.fun
+    }
+
+    fn double_arg_fun(f: &DoubleArgFunction) -> fn(Vec<f32>, &[f32]) -> Vec<f32> {
+        f.fun
+    }
+}
+
+impl Value for f64 {
+    const PRECISION: Precision = Precision::Double;
+    const MIN_CHANGE: f64 = 1e-9;
+
+    fn from_f32(v: f32) -> f64 {
+        f64::from(v)
+    }
+
+    fn from_f64(v: f64) -> f64 {
+        v
+    }
+
+    fn as_f64(self) -> f64 {
+        self
+    }
+
+    fn single_arg_fun(f: &SingleArgFunction) -> fn(Vec<f64>) -> Vec<f64> {
+        f.fun64
+    }
+
+    fn double_arg_fun(f: &DoubleArgFunction) -> fn(Vec<f64>, &[f64]) -> Vec<f64> {
+        f.fun64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::serialization::serializator::test::test_serialization;
+    use crate::serialization::{Serializable, Serializator};
+    use crate::value::Precision;
+
+    #[test]
+    fn serialize_precision() {
+        test_serialization(Precision::Single);
+        test_serialization(Precision::Double);
+    }
+
+    #[test]
+    fn missing_precision_is_single() {
+        let mut s = Serializator::new();
+        s.add(&12_usize);
+        assert_eq!(Precision::peek(&s), Precision::Single);
+        assert_eq!(Precision::deserialize_or_single(&mut s), Ok(Precision::Single));
+        assert_eq!(s.next_token(), Ok(&"12".to_owned()));
+
+        let mut s = Serializator::new();
+        Precision::Double.serialize(&mut s);
+        assert_eq!(Precision::peek(&s), Precision::Double);
+        assert!(Precision::deserialize_expecting::<f32>(&mut s).is_err());
+    }
+}